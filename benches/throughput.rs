@@ -0,0 +1,93 @@
+//! Throughput benchmarks of the document tools and checksums on generated
+//! XML corpora, as a baseline for performance work:
+//!
+//! ```text
+//! cargo bench --bench throughput
+//! BENCH_SIZES=1,100,500 cargo bench --bench throughput
+//! ```
+//!
+//! `BENCH_SIZES` lists the corpus sizes in MB, `1,16` by default. Each
+//! tool runs for about `BENCH_SECONDS` (2 by default), at least once.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crabyknife::{canonicalize_xml, convert, humanize, ping, prettify_xml};
+use sha2::{Digest, Sha256};
+
+/// Generates a minified XML document of about `bytes` bytes, with the
+/// usual mix of attributes, text, escapes and comments of data exports.
+fn corpus(bytes: usize) -> String {
+    let mut xml = String::with_capacity(bytes + 256);
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?><records>");
+    let mut id = 0;
+    while xml.len() < bytes {
+        xml.push_str(&format!(
+            "<record id=\"{id}\" kind=\"{}\"><name>Record &amp; {id}</name><value unit=\"ms\">{}</value><!-- note {id} --><tags><tag>a</tag><tag>b</tag></tags></record>",
+            if id % 3 == 0 { "even" } else { "odd" },
+            id * 7 % 1000
+        ));
+        id += 1;
+    }
+    xml.push_str("</records>");
+    xml
+}
+
+/// Runs `tool` repeatedly for about `budget`, and prints its average time
+/// and throughput over the `bytes` of input.
+fn bench(name: &str, bytes: usize, budget: Duration, mut tool: impl FnMut()) {
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while iterations == 0 || start.elapsed() < budget {
+        tool();
+        iterations += 1;
+    }
+    let average = start.elapsed() / iterations;
+    let throughput = bytes as f64 / 1_000_000.0 / average.as_secs_f64();
+    println!(
+        "{name:<20} {:>10} {:>12.3} ms {throughput:>10.1} MB/s  ({iterations} runs)",
+        humanize::bytes(bytes as u64),
+        average.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    let sizes = std::env::var("BENCH_SIZES").unwrap_or_else(|_| "1,16".to_string());
+    let budget = std::env::var("BENCH_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map_or(Duration::from_secs(2), Duration::from_secs_f64);
+
+    for size in sizes.split(',') {
+        let megabytes: usize = size
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid size in BENCH_SIZES ({size})"));
+        let xml = corpus(megabytes * 1_000_000);
+        let pretty = prettify_xml::prettify_xml(&xml).unwrap();
+        let bytes = xml.len();
+
+        bench("prettify_xml", bytes, budget, || {
+            black_box(prettify_xml::prettify_xml(black_box(&xml)).unwrap());
+        });
+        bench("minify_xml", pretty.len(), budget, || {
+            black_box(prettify_xml::minify_xml(black_box(&pretty), false).unwrap());
+        });
+        bench("validate", bytes, budget, || {
+            prettify_xml::validate(black_box(&xml)).unwrap();
+        });
+        bench("canonicalize_xml", bytes, budget, || {
+            black_box(canonicalize_xml::canonicalize_xml(black_box(&xml), false).unwrap());
+        });
+        bench("xml_to_json", bytes, budget, || {
+            let options = convert::ConvertOptions::new();
+            black_box(convert::xml_to_json(black_box(&xml), &options).unwrap());
+        });
+        bench("sha256", bytes, budget, || {
+            black_box(Sha256::digest(black_box(xml.as_bytes())));
+        });
+        bench("icmp checksum", bytes, budget, || {
+            black_box(ping::checksum(black_box(xml.as_bytes())));
+        });
+    }
+}
@@ -0,0 +1,18 @@
+//! Converts arbitrary input between XML, JSON and YAML: the converters
+//! must fail with errors rather than panic, and their XML be well-formed.
+
+#![no_main]
+
+use crabyknife::convert::{xml_to_json, xml_to_yaml, yaml_to_xml, ConvertOptions};
+use crabyknife::prettify_xml::validate;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let options = ConvertOptions::new();
+    let _ = xml_to_json(text, &options);
+    let _ = xml_to_yaml(text, &options);
+
+    if let Ok(xml) = yaml_to_xml(text, &options) {
+        assert!(validate(&xml).is_ok(), "{xml}");
+    }
+});
@@ -0,0 +1,18 @@
+//! Prettifies arbitrary input: documents that parse must come out
+//! well-formed, and prettify the same again and through minifying.
+
+#![no_main]
+
+use crabyknife::prettify_xml::{minify_xml, prettify_xml, validate};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|xml: &str| {
+    let Ok(pretty) = prettify_xml(xml) else {
+        return;
+    };
+    assert!(validate(&pretty).is_ok(), "{pretty}");
+    assert_eq!(prettify_xml(&pretty).unwrap(), pretty);
+
+    let minified = minify_xml(&pretty, false).unwrap();
+    assert_eq!(prettify_xml(&minified).unwrap(), pretty);
+});
@@ -0,0 +1,45 @@
+/* C bindings of crabyknife, declared in src/ffi.rs; regenerate with cbindgen. */
+
+#ifndef CRABYKNIFE_H
+#define CRABYKNIFE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Prettifies the XML document `xml` with the default layout.
+ */
+char *crabyknife_prettify_xml(const char *xml, char **error);
+
+/**
+ * Formats the JSON document `json` with two-space indentation.
+ */
+char *crabyknife_prettify_json(const char *json, char **error);
+
+/**
+ * Computes the SHA-256 of the `len` bytes at `data`, as lowercase hex.
+ */
+char *crabyknife_sha256_hex(const uint8_t *data, size_t len);
+
+/**
+ * Generates a random (version 4) UUID in its hyphenated form.
+ */
+char *crabyknife_new_uuid(void);
+
+/**
+ * Releases a string returned by the library. Does nothing for NULL.
+ */
+void crabyknife_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CRABYKNIFE_H */
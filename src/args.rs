@@ -0,0 +1,108 @@
+//! The arguments of a subcommand, handed out one at a time to its parser.
+//!
+//! Every subcommand parses its own arguments from [`Args`] and describes
+//! them in a usage message starting with `Usage:`, given back in an error
+//! exiting with status 2 when the arguments are invalid, and printed
+//! instead when they ask for it with `--help` or `-h`, through
+//! [`Args::help`].
+//!
+//! Long flags also take their value after `=`, e.g. `--count=3` for
+//! `--count 3`.
+
+/// The arguments of a subcommand, after its name.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    args: std::collections::VecDeque<String>,
+}
+
+impl Args {
+    /// Collects `args`, splitting the long flags written with their value,
+    /// such as `--count=3`, into the flag and the value.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::args::Args;
+    ///
+    /// let args = Args::new(["--count=3", "-i", "0.5", "host"].map(String::from));
+    /// assert_eq!(args.collect::<Vec<_>>(), ["--count", "3", "-i", "0.5", "host"]);
+    /// ```
+    pub fn new(args: impl IntoIterator<Item = String>) -> Self {
+        let mut split = std::collections::VecDeque::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((flag, value)) if is_long_flag(flag) => {
+                    split.push_back(flag.to_string());
+                    split.push_back(value.to_string());
+                }
+                _ => split.push_back(arg),
+            }
+        }
+        Self { args: split }
+    }
+
+    /// Fails with [`Help`] holding `usage` when the arguments ask for help
+    /// with `--help` or `-h`, for subcommands to check before parsing.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`Help`] to print when help was asked for.
+    pub fn help(&self, usage: &str) -> Result<(), Help> {
+        if self.args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            return Err(Help {
+                usage: usage.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.pop_front()
+    }
+}
+
+/// Whether `flag` is the name of a long flag, like `--count`, rather than
+/// e.g. `--` or a negative number.
+fn is_long_flag(flag: &str) -> bool {
+    flag.strip_prefix("--")
+        .and_then(|name| name.chars().next())
+        .is_some_and(|first| first.is_ascii_alphabetic())
+}
+
+/// The usage of a subcommand asked for with `--help`, printed on stdout
+/// rather than as an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Help {
+    pub usage: String,
+}
+
+impl std::fmt::Display for Help {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.usage)
+    }
+}
+
+impl std::error::Error for Help {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::new(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_args() {
+        let split: Vec<_> = args(&["--format={a}={b}", "--", "--=x", "-c=3", "a=b"]).collect();
+        assert_eq!(split, ["--format", "{a}={b}", "--", "--=x", "-c=3", "a=b"]);
+
+        assert!(args(&["-c", "3"]).help("Usage: x").is_ok());
+        let help = args(&["-c", "3", "--help"]).help("Usage: x").unwrap_err();
+        assert_eq!(help.to_string(), "Usage: x");
+        assert!(args(&["-h"]).help("Usage: x").is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! ARP pings: who-has requests are broadcast on the local segment and the
+//! MAC address answering for an IPv4 address is reported with its latency.
+//!
+//! Hosts answer ARP requests even when their firewall drops ICMP, as they
+//! could not take part in the network otherwise, but only hosts of a
+//! network the local host is attached to can be reached. Requests are sent
+//! through a Linux packet socket, which needs root or `CAP_NET_RAW`. Only
+//! built on Linux with the `arping` feature (on by default).
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+use crate::ping::PingReport;
+
+/// The ARP operation of requests and replies.
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+/// The size of an ARP packet for IPv4 over Ethernet.
+const ARP_PACKET_LEN: usize = 28;
+
+/// A MAC (Ethernet hardware) address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    /// The broadcast address, ff:ff:ff:ff:ff:ff.
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+}
+
+impl std::fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+/// How [`arping_with_progress`] probes a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArpingConfig {
+    count: usize,
+    interval: Duration,
+    timeout: Duration,
+    interface: Option<String>,
+}
+
+impl Default for ArpingConfig {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            interface: None,
+        }
+    }
+}
+
+impl ArpingConfig {
+    /// Creates the default config: 5 requests one second apart, each
+    /// waited for one second, on the interface of the target's network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of requests sent.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Sets the time between the starts of consecutive requests.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long the reply to a request is waited for.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the interface the requests are sent on, instead of the one
+    /// attached to the network of the target.
+    pub fn interface(mut self, interface: Option<String>) -> Self {
+        self.interface = interface;
+        self
+    }
+}
+
+/// The interface ARP requests are sent on, see [`ArpingEvent::Interface`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    /// The MAC address of the interface, the sender of the requests.
+    pub mac: MacAddr,
+    /// The IPv4 address of the interface on the network of the target.
+    pub address: Ipv4Addr,
+}
+
+/// Progress reported while [`arping_with_progress`] runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArpingEvent {
+    /// The requests are sent on `interface`.
+    Interface(Interface),
+    /// The host with the MAC address `mac` answered the request `seq` after `rtt`.
+    Reply {
+        seq: u16,
+        mac: MacAddr,
+        rtt: Duration,
+    },
+    /// Another host answered the request `seq`, after the first one did,
+    /// which hints at an address conflict. Not counted as received.
+    Duplicate { seq: u16, mac: MacAddr },
+    /// No reply arrived within the timeout.
+    Timeout { seq: u16 },
+}
+
+/// Sends ARP requests for `target` on the local segment and measures how
+/// long the replies take.
+///
+/// `on_event` is called with the interface used, then with what became of
+/// each request, e.g. to print progress.
+///
+/// # Errors
+///
+/// Returns an error if the config has a zero timeout, the target is on the
+/// network of no interface (or not of the one in the config), no packet
+/// socket can be opened, or sending a request fails.
+pub fn arping_with_progress(
+    target: Ipv4Addr,
+    config: &ArpingConfig,
+    mut on_event: impl FnMut(ArpingEvent),
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the reply timeout must be greater than zero".into());
+    }
+
+    let socket = ArpSocket::open(target, config.interface.as_deref())?;
+    on_event(ArpingEvent::Interface(socket.interface.clone()));
+    let request = build_request(socket.interface.mac, socket.interface.address, target);
+
+    let mut rtts = Vec::new();
+    let mut start = Instant::now();
+    for probe in 0..config.count {
+        if probe > 0 {
+            std::thread::sleep(config.interval.saturating_sub(start.elapsed()));
+        }
+
+        // ARP has no sequence numbers, so replies answer the latest request.
+        let seq = probe as u16;
+        start = Instant::now();
+        socket.send(&request)?;
+
+        let mut answered = None;
+        let deadline = start + config.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let Ok(packet) = socket.recv(remaining) else {
+                break;
+            };
+            let rtt = start.elapsed();
+            let Some((mac, sender)) = parse_reply(&packet) else {
+                continue;
+            };
+            if sender != target {
+                continue;
+            }
+            match answered {
+                None => {
+                    answered = Some((mac, rtt));
+                    on_event(ArpingEvent::Reply { seq, mac, rtt });
+                }
+                Some((first, _)) if first != mac => on_event(ArpingEvent::Duplicate { seq, mac }),
+                // The same host answering twice, e.g. a gratuitous reply.
+                Some(_) => {}
+            }
+        }
+
+        if answered.is_none() {
+            on_event(ArpingEvent::Timeout { seq });
+        }
+        rtts.push(answered.map(|(_, rtt)| rtt));
+    }
+
+    Ok(PingReport::new(IpAddr::V4(target), rtts))
+}
+
+/// Builds the ARP request asking which host has `target`, from the
+/// interface with the addresses `sender_mac` and `sender`.
+fn build_request(sender_mac: MacAddr, sender: Ipv4Addr, target: Ipv4Addr) -> [u8; ARP_PACKET_LEN] {
+    let mut packet = [0u8; ARP_PACKET_LEN];
+    // Ethernet hardware addresses and IPv4 protocol addresses.
+    packet[0..2].copy_from_slice(&1u16.to_be_bytes());
+    packet[2..4].copy_from_slice(&0x0800u16.to_be_bytes());
+    packet[4] = 6;
+    packet[5] = 4;
+    packet[6..8].copy_from_slice(&ARP_REQUEST.to_be_bytes());
+    packet[8..14].copy_from_slice(&sender_mac.0);
+    packet[14..18].copy_from_slice(&sender.octets());
+    // The target hardware address is what the request asks for, so zero.
+    packet[24..28].copy_from_slice(&target.octets());
+    packet
+}
+
+/// Parses an ARP packet, returning the MAC and IPv4 addresses of the
+/// sender of a reply, or `None` for requests, truncated packets and other
+/// address types.
+fn parse_reply(packet: &[u8]) -> Option<(MacAddr, Ipv4Addr)> {
+    let packet = packet.get(..ARP_PACKET_LEN)?;
+    let ethernet_ipv4 = packet[0..6] == [0, 1, 0x08, 0x00, 6, 4];
+    if !ethernet_ipv4 || packet[6..8] != ARP_REPLY.to_be_bytes() {
+        return None;
+    }
+    let mac = MacAddr(packet[8..14].try_into().ok()?);
+    let sender: [u8; 4] = packet[14..18].try_into().ok()?;
+    Some((mac, Ipv4Addr::from(sender)))
+}
+
+/// A packet socket sending and receiving ARP packets on one interface.
+struct ArpSocket {
+    socket: socket2::Socket,
+    interface: Interface,
+    index: libc::c_uint,
+}
+
+impl ArpSocket {
+    /// Opens a packet socket on the interface named `interface`, or else on
+    /// the one attached to the network of `target`.
+    fn open(target: Ipv4Addr, interface: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let interface = find_interface(target, interface)?;
+        let name = std::ffi::CString::new(interface.name.as_str())?;
+        // SAFETY: `name` is a valid NUL-terminated string.
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(format!("failed to find the interface {}: {err}", interface.name).into());
+        }
+
+        // Datagram packet sockets leave the Ethernet header to the kernel.
+        let protocol = socket2::Protocol::from(i32::from((libc::ETH_P_ARP as u16).to_be()));
+        let socket = socket2::Socket::new(
+            socket2::Domain::PACKET,
+            socket2::Type::DGRAM,
+            Some(protocol),
+        )
+        .map_err(|err| {
+            format!("failed to create a packet socket: {err}, run as root or with CAP_NET_RAW")
+        })?;
+        let socket = Self {
+            socket,
+            interface,
+            index,
+        };
+        // Only receive the ARP packets of the interface.
+        let address = socket.link_address(&[0; 6]);
+        // SAFETY: `address` is a sockaddr_ll living for the whole call.
+        let result = unsafe {
+            libc::bind(
+                std::os::fd::AsRawFd::as_raw_fd(&socket.socket),
+                (&address as *const libc::sockaddr_ll).cast(),
+                std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(format!("failed to bind to {}: {err}", socket.interface.name).into());
+        }
+        Ok(socket)
+    }
+
+    /// The link-layer address of the ARP packets to or from `mac` on the interface.
+    fn link_address(&self, mac: &[u8; 6]) -> libc::sockaddr_ll {
+        // SAFETY: sockaddr_ll is plain data, valid when zeroed.
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as libc::c_ushort;
+        address.sll_protocol = (libc::ETH_P_ARP as u16).to_be();
+        address.sll_ifindex = self.index as libc::c_int;
+        address.sll_halen = 6;
+        address.sll_addr[..6].copy_from_slice(mac);
+        address
+    }
+
+    /// Broadcasts the ARP `packet` on the interface.
+    fn send(&self, packet: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let address = self.link_address(&MacAddr::BROADCAST.0);
+        // SAFETY: `packet` and `address` live for the whole call.
+        let sent = unsafe {
+            libc::sendto(
+                std::os::fd::AsRawFd::as_raw_fd(&self.socket),
+                packet.as_ptr().cast(),
+                packet.len(),
+                0,
+                (&address as *const libc::sockaddr_ll).cast(),
+                std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(format!("failed to send an ARP request: {err}").into());
+        }
+        Ok(())
+    }
+
+    /// Receives an ARP packet, waiting at most `timeout`.
+    fn recv(&self, timeout: Duration) -> std::io::Result<Vec<u8>> {
+        use std::io::Read;
+
+        self.socket.set_read_timeout(Some(timeout))?;
+        let mut packet = vec![0u8; 64];
+        let n = (&self.socket).read(&mut packet)?;
+        packet.truncate(n);
+        Ok(packet)
+    }
+}
+
+/// Finds the interface named `name`, or else the first one that is up and
+/// has an IPv4 network containing `target`, with its address on that network.
+fn find_interface(
+    target: Ipv4Addr,
+    name: Option<&str>,
+) -> Result<Interface, Box<dyn std::error::Error>> {
+    let mut addresses: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills `addresses` with a list freed below.
+    if unsafe { libc::getifaddrs(&mut addresses) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return Err(format!("failed to list the network interfaces: {err}").into());
+    }
+
+    // The matching interface name and address, and the MAC address of each interface.
+    let mut found: Option<(String, Ipv4Addr)> = None;
+    let mut macs: Vec<(String, MacAddr)> = Vec::new();
+    let mut entry = addresses;
+    while !entry.is_null() {
+        // SAFETY: the entries of the list stay valid until freeifaddrs, and
+        // their addresses have the layout of their family.
+        let (interface, address, netmask, flags) = unsafe {
+            let ifa = &*entry;
+            entry = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() {
+                continue;
+            }
+            let interface = std::ffi::CStr::from_ptr(ifa.ifa_name)
+                .to_string_lossy()
+                .into_owned();
+            match i32::from((*ifa.ifa_addr).sa_family) {
+                libc::AF_PACKET => {
+                    let link = &*(ifa.ifa_addr as *const libc::sockaddr_ll);
+                    if link.sll_halen == 6 {
+                        let mac: [u8; 6] = link.sll_addr[..6].try_into().unwrap_or_default();
+                        macs.push((interface, MacAddr(mac)));
+                    }
+                    continue;
+                }
+                libc::AF_INET if !ifa.ifa_netmask.is_null() => {
+                    let address = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    let netmask = &*(ifa.ifa_netmask as *const libc::sockaddr_in);
+                    (
+                        interface,
+                        Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)),
+                        u32::from_be(netmask.sin_addr.s_addr),
+                        ifa.ifa_flags,
+                    )
+                }
+                _ => continue,
+            }
+        };
+        let usable = flags & libc::IFF_UP as libc::c_uint != 0
+            && flags & libc::IFF_LOOPBACK as libc::c_uint == 0;
+        let on_network = u32::from(address) & netmask == u32::from(target) & netmask;
+        let wanted = match name {
+            Some(name) => interface == name,
+            None => usable && on_network,
+        };
+        if wanted && found.is_none() {
+            found = Some((interface, address));
+        }
+    }
+    // SAFETY: `addresses` came from getifaddrs and is no longer used.
+    unsafe { libc::freeifaddrs(addresses) };
+
+    let Some((name, address)) = found else {
+        return Err(match name {
+            Some(name) => format!("the interface {name} does not exist or has no IPv4 address"),
+            None => format!("{target} is not on the network of any interface, ARP only reaches the local segment"),
+        }
+        .into());
+    };
+    let mac = macs
+        .into_iter()
+        .find(|(interface, _)| *interface == name)
+        .map(|(_, mac)| mac)
+        .ok_or_else(|| format!("the interface {name} has no Ethernet address"))?;
+    Ok(Interface { name, mac, address })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arp_packets() {
+        let mac = MacAddr([0x02, 0xfc, 0, 0, 0, 0x01]);
+        assert_eq!(mac.to_string(), "02:fc:00:00:00:01");
+        let sender: Ipv4Addr = "192.0.2.2".parse().unwrap();
+        let target: Ipv4Addr = "192.0.2.1".parse().unwrap();
+
+        let request = build_request(mac, sender, target);
+        assert_eq!(request[..8], [0, 1, 0x08, 0x00, 6, 4, 0, 1]);
+        assert_eq!(request[24..], target.octets());
+        // Requests are not replies, even from the target.
+        assert_eq!(parse_reply(&request), None);
+
+        let answer = MacAddr([0x02, 0xfc, 0, 0, 0, 0x05]);
+        let mut reply = build_request(answer, target, sender);
+        reply[7] = 2;
+        assert_eq!(parse_reply(&reply), Some((answer, target)));
+        // Ethernet frames are padded to 60 bytes.
+        let mut padded = reply.to_vec();
+        padded.resize(46, 0);
+        assert_eq!(parse_reply(&padded), Some((answer, target)));
+        assert_eq!(parse_reply(&reply[..27]), None);
+        reply[4] = 8;
+        assert_eq!(parse_reply(&reply), None);
+    }
+
+    #[test]
+    fn test_arping_config() {
+        let target = "192.0.2.1".parse().unwrap();
+        let arping = |config| arping_with_progress(target, &config, |_| {});
+        assert!(arping(ArpingConfig::new().timeout(Duration::ZERO)).is_err());
+        let config = ArpingConfig::new().interface(Some("no-such-interface0".to_string()));
+        assert!(arping(config).is_err());
+    }
+}
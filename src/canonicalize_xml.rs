@@ -0,0 +1,363 @@
+//! Exclusive XML Canonicalization
+//! ([Exclusive XML Canonicalization 1.0](https://www.w3.org/TR/xml-exc-c14n/)).
+//!
+//! Canonical output is byte for byte identical for logically equivalent
+//! documents, which makes it suitable for signature verification and
+//! reproducible hashing. In short:
+//!
+//! 1. The XML declaration, DOCTYPE and whitespace outside the root element are dropped.
+//! 2. Line endings are normalized to `\n`, references are expanded and
+//!    CDATA sections are replaced by their (escaped) content.
+//! 3. Empty elements are written as start and end tag pairs.
+//! 4. Attribute values are normalized and always use double quotes.
+//! 5. Namespace declarations are written, sorted by prefix, only on the
+//!    elements that visibly use them and are not already in effect.
+//! 6. Attributes are sorted by namespace uri, then local name.
+//! 7. Comments are removed unless requested.
+
+use quick_xml::{
+    escape::unescape,
+    events::{BytesStart, BytesText, Event},
+    Reader,
+};
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Canonicalizes `xml` with Exclusive XML Canonicalization,
+/// keeping comments when `with_comments` is `true`.
+///
+/// # Example
+/// ```
+/// use crabyknife::canonicalize_xml::canonicalize_xml;
+///
+/// let xml = r#"<?xml version="1.0"?><a b='2' a="1"><c/></a>"#;
+/// assert_eq!(canonicalize_xml(xml, false).unwrap(), r#"<a a="1" b="2"><c></c></a>"#);
+/// ```
+pub fn canonicalize_xml(
+    xml: &str,
+    with_comments: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_str(xml);
+
+    let mut output = String::new();
+    // Namespace declarations of the input, one scope per open element,
+    // as `(prefix, uri)`. The default namespace has an empty prefix.
+    let mut declared: Vec<Vec<(String, String)>> = Vec::new();
+    // Namespace declarations written to the output, one scope per open element.
+    let mut rendered: Vec<Vec<(String, String)>> = Vec::new();
+    let mut root_seen = false;
+
+    loop {
+        let depth = declared.len();
+        match reader.read_event()? {
+            Event::Start(e) => {
+                write_start_tag(&e, &mut declared, &mut rendered, &mut output)?;
+                root_seen = true;
+            }
+            Event::Empty(e) => {
+                write_start_tag(&e, &mut declared, &mut rendered, &mut output)?;
+                output.push_str("</");
+                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                output.push('>');
+                declared.pop();
+                rendered.pop();
+                root_seen = true;
+            }
+            Event::End(e) => {
+                output.push_str("</");
+                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                output.push('>');
+                declared.pop();
+                rendered.pop();
+            }
+            // Text outside of the root element is insignificant.
+            Event::Text(e) if depth > 0 => output.push_str(&escape_text(&text_content(&e)?)),
+            Event::CData(e) if depth > 0 => {
+                output.push_str(&escape_text(&normalize_newlines(&e.decode()?)))
+            }
+            Event::Comment(e) if with_comments => {
+                let comment = normalize_newlines(&String::from_utf8_lossy(&e));
+                push_document_level(depth, root_seen, &format!("<!--{comment}-->"), &mut output);
+            }
+            Event::PI(e) => {
+                let target = String::from_utf8_lossy(e.target());
+                let content = String::from_utf8_lossy(e.content());
+                let content = content.trim_start();
+                let pi = if content.is_empty() {
+                    format!("<?{target}?>")
+                } else {
+                    format!("<?{target} {}?>", normalize_newlines(content))
+                };
+                push_document_level(depth, root_seen, &pi, &mut output);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !root_seen {
+        return Err("the document has no root element".into());
+    }
+
+    Ok(output)
+}
+
+/// Writes a comment or processing instruction. Outside of the root element,
+/// they are separated from it by a newline.
+fn push_document_level(depth: usize, root_seen: bool, node: &str, output: &mut String) {
+    match (depth, root_seen) {
+        (0, false) => {
+            output.push_str(node);
+            output.push('\n');
+        }
+        (0, true) => {
+            output.push('\n');
+            output.push_str(node);
+        }
+        _ => output.push_str(node),
+    }
+}
+
+/// Writes a start tag with its namespace declarations and attributes
+/// in canonical order, opening new namespace scopes for the element.
+fn write_start_tag(
+    e: &BytesStart,
+    declared: &mut Vec<Vec<(String, String)>>,
+    rendered: &mut Vec<Vec<(String, String)>>,
+    output: &mut String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+
+    let mut declarations = Vec::new();
+    let mut attributes = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+        let value = attribute_value(&String::from_utf8_lossy(&attr.value))?;
+        if key == "xmlns" {
+            declarations.push((String::new(), value));
+        } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+            declarations.push((prefix.to_string(), value));
+        } else {
+            attributes.push((key, value));
+        }
+    }
+    declared.push(declarations);
+
+    // The prefixes visibly utilized by the element and its attributes.
+    // Unprefixed attributes are in no namespace, so they never use the default one.
+    let mut utilized = vec![prefix_of(&name).unwrap_or_default().to_string()];
+    for (key, _) in &attributes {
+        if let Some(prefix) = prefix_of(key) {
+            if !utilized.iter().any(|p| p == prefix) {
+                utilized.push(prefix.to_string());
+            }
+        }
+    }
+
+    let mut new_declarations = Vec::new();
+    for prefix in utilized {
+        if prefix == "xml" {
+            continue;
+        }
+
+        let uri = match lookup(declared, &prefix) {
+            Some(uri) => uri.to_string(),
+            // An unprefixed name without a default namespace is in no namespace.
+            None if prefix.is_empty() => String::new(),
+            None => {
+                return Err(format!("undeclared namespace prefix `{prefix}` in <{name}>").into())
+            }
+        };
+
+        let in_effect = lookup(rendered, &prefix).unwrap_or_default();
+        if in_effect != uri {
+            new_declarations.push((prefix, uri));
+        }
+    }
+    new_declarations.sort();
+
+    // Attributes are sorted by namespace uri first, then by local name.
+    let mut sorted = Vec::new();
+    for (key, value) in attributes {
+        let (uri, local) = match key.split_once(':') {
+            Some(("xml", local)) => (XML_NAMESPACE.to_string(), local.to_string()),
+            Some((prefix, local)) => {
+                let uri = lookup(declared, prefix)
+                    .ok_or_else(|| format!("undeclared namespace prefix `{prefix}` in <{name}>"))?;
+                (uri.to_string(), local.to_string())
+            }
+            None => (String::new(), key.clone()),
+        };
+        sorted.push((uri, local, key, value));
+    }
+    sorted.sort();
+
+    output.push('<');
+    output.push_str(&name);
+    for (prefix, uri) in &new_declarations {
+        if prefix.is_empty() {
+            output.push_str(" xmlns=\"");
+        } else {
+            output.push_str(" xmlns:");
+            output.push_str(prefix);
+            output.push_str("=\"");
+        }
+        output.push_str(&escape_attribute(uri));
+        output.push('"');
+    }
+    for (_, _, key, value) in &sorted {
+        output.push(' ');
+        output.push_str(key);
+        output.push_str("=\"");
+        output.push_str(&escape_attribute(value));
+        output.push('"');
+    }
+    output.push('>');
+
+    rendered.push(new_declarations);
+    Ok(())
+}
+
+fn prefix_of(name: &str) -> Option<&str> {
+    name.split_once(':').map(|(prefix, _)| prefix)
+}
+
+/// Finds the innermost binding of `prefix` in `scopes`.
+fn lookup<'a>(scopes: &'a [Vec<(String, String)>], prefix: &str) -> Option<&'a str> {
+    scopes
+        .iter()
+        .rev()
+        .flat_map(|scope| scope.iter())
+        .find(|(declared, _)| declared == prefix)
+        .map(|(_, uri)| uri.as_str())
+}
+
+/// Normalizes `\r\n` and `\r` line endings to `\n`, as an XML processor does.
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn text_content(e: &BytesText) -> Result<String, Box<dyn std::error::Error>> {
+    let raw = normalize_newlines(&String::from_utf8_lossy(e));
+    Ok(unescape(&raw)?.into_owned())
+}
+
+/// Normalizes an attribute value: literal whitespace characters become
+/// spaces, then references are expanded.
+fn attribute_value(raw: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let normalized = normalize_newlines(raw).replace(['\n', '\t'], " ");
+    Ok(unescape(&normalized)?.into_owned())
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\r' => escaped.push_str("&#xD;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' => escaped.push_str("&#x9;"),
+            '\n' => escaped.push_str("&#xA;"),
+            '\r' => escaped.push_str("&#xD;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_level_nodes() {
+        let input = "<?xml version=\"1.0\"?>\n<!DOCTYPE doc>\n<?pi data?>\n<!--c1-->\n<doc>x</doc>\n<!--c2-->\n";
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            "<?pi data?>\n<doc>x</doc>"
+        );
+        assert_eq!(
+            canonicalize_xml(input, true).unwrap(),
+            "<?pi data?>\n<!--c1-->\n<doc>x</doc>\n<!--c2-->"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_inside_root_is_kept() {
+        let input = "<doc>\r\n   <a>  text </a>\n</doc>";
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            "<doc>\n   <a>  text </a>\n</doc>"
+        );
+    }
+
+    #[test]
+    fn test_references_and_cdata() {
+        let input =
+            "<doc a='&quot;x&#9;y&#xA;' b=\"1\n2\">&#65;&amp;<![CDATA[<raw> & ]]>&gt;</doc>";
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            "<doc a=\"&quot;x&#x9;y&#xA;\" b=\"1 2\">A&amp;&lt;raw&gt; &amp; &gt;</doc>"
+        );
+    }
+
+    #[test]
+    fn test_attributes_sorted_by_namespace_then_name() {
+        let input =
+            r#"<doc xmlns:z="urn:a" xmlns:a="urn:b" a:attr="1" z:attr="2" b="3" xml:lang="en"/>"#;
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            concat!(
+                r#"<doc xmlns:a="urn:b" xmlns:z="urn:a" b="3" "#,
+                r#"xml:lang="en" z:attr="2" a:attr="1"></doc>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_exclusive_namespace_propagation() {
+        // Unused declarations are dropped and used ones are written where
+        // they are first needed, once.
+        let input = concat!(
+            r#"<a:root xmlns:a="urn:a" xmlns:unused="urn:u" xmlns="urn:d">"#,
+            r#"<a:child><b:leaf xmlns:b="urn:b"/><plain/></a:child></a:root>"#
+        );
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            concat!(
+                r#"<a:root xmlns:a="urn:a"><a:child>"#,
+                r#"<b:leaf xmlns:b="urn:b"></b:leaf><plain xmlns="urn:d"></plain>"#,
+                r#"</a:child></a:root>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_default_namespace_undeclared() {
+        let input = r#"<root xmlns="urn:d"><inner xmlns=""/></root>"#;
+        assert_eq!(
+            canonicalize_xml(input, false).unwrap(),
+            r#"<root xmlns="urn:d"><inner xmlns=""></inner></root>"#
+        );
+    }
+
+    #[test]
+    fn test_undeclared_prefix() {
+        assert!(canonicalize_xml("<a:root/>", false).is_err());
+    }
+}
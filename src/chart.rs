@@ -0,0 +1,273 @@
+//! Small terminal charts (sparklines, braille plots, bar charts and meters)
+//! shared by subcommands that want to visualise a series of numbers.
+//!
+//! Subcommands draw them through [`Charts`], so the global `--plain` flag
+//! can turn every one of them into plain text for screen readers and dumb
+//! terminals.
+
+// Eight block levels used by sparklines, from lowest to highest.
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// The first codepoint of the Unicode braille block (an empty cell).
+const BRAILLE_BASE: u32 = 0x2800;
+
+// Bit for each dot of a braille cell, indexed by `[row][column]`
+// where row 0 is the top of the cell.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Renders a series as a single-line sparkline.
+///
+/// Each value becomes one block character scaled between the minimum and
+/// maximum of the series. Missing values (`None`) are rendered as a space,
+/// which makes gaps such as lost packets visible.
+///
+/// # Example
+/// ```
+/// use crabyknife::chart::sparkline;
+///
+/// assert_eq!(sparkline(&[Some(1.0), Some(2.0), None, Some(8.0)]), "▁▂ █");
+/// ```
+pub fn sparkline(values: &[Option<f64>]) -> String {
+    let Some((min, max)) = bounds(values.iter().flatten().copied()) else {
+        return " ".repeat(values.len());
+    };
+
+    values
+        .iter()
+        .map(|value| match value {
+            Some(value) => SPARK_LEVELS[scale(*value, min, max, SPARK_LEVELS.len())],
+            None => ' ',
+        })
+        .collect()
+}
+
+/// Renders a series as a braille dot plot `height` characters tall.
+///
+/// Every braille character holds two values side by side and four
+/// vertical steps, so the plot is `values.len() / 2` characters wide
+/// and has `height * 4` levels of resolution.
+pub fn braille(values: &[f64], height: usize) -> String {
+    let height = height.max(1);
+    let Some((min, max)) = bounds(values.iter().copied()) else {
+        return String::new();
+    };
+
+    let width = values.len().div_ceil(2);
+    let mut cells = vec![vec![0u32; width]; height];
+    let levels = height * 4;
+
+    for (i, value) in values.iter().enumerate() {
+        // level 0 is the bottom of the plot, but row 0 is the top line.
+        let level = scale(*value, min, max, levels);
+        let row_from_top = levels - 1 - level;
+        cells[row_from_top / 4][i / 2] |= BRAILLE_DOTS[row_from_top % 4][i % 2];
+    }
+
+    cells
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|bits| char::from_u32(BRAILLE_BASE + bits).unwrap_or(' '))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders labelled values as a horizontal bar chart, one line per entry,
+/// with the longest bar `width` characters wide. Useful for histograms.
+///
+/// # Example
+/// ```
+/// use crabyknife::chart::bar_chart;
+///
+/// let chart = bar_chart(&[("a", 2), ("bb", 4)], 4);
+/// assert_eq!(chart, "a  | ██   2\nbb | ████ 4");
+/// ```
+pub fn bar_chart<L: AsRef<str>>(entries: &[(L, u64)], width: usize) -> String {
+    let label_width = entries
+        .iter()
+        .map(|(label, _)| label.as_ref().chars().count())
+        .max()
+        .unwrap_or(0);
+    let max = entries.iter().map(|(_, value)| *value).max().unwrap_or(0);
+
+    entries
+        .iter()
+        .map(|(label, value)| {
+            // Widened, as `value * width` overflows for large values.
+            let bar_len = if max == 0 {
+                0
+            } else {
+                (u128::from(*value) * width as u128).div_ceil(u128::from(max)) as usize
+            };
+            format!(
+                "{:<label_width$} | {:<width$} {value}",
+                label.as_ref(),
+                "█".repeat(bar_len),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders a ratio between 0 and 1 as a meter `width` characters wide,
+/// e.g. the usage of a disk. Ratios outside that range are clamped.
+///
+/// # Example
+/// ```
+/// use crabyknife::chart::meter;
+///
+/// assert_eq!(meter(0.5, 4), "██░░");
+/// ```
+pub fn meter(ratio: f64, width: usize) -> String {
+    let filled = ((ratio.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+/// Draws charts with block characters, or as plain text when `plain`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Charts {
+    plain: bool,
+}
+
+impl Charts {
+    /// Creates charts drawn with block characters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether charts are plain text instead.
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// Renders a series as a [`sparkline`], or as its values separated by
+    /// spaces when plain, `-` standing for missing ones.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::chart::Charts;
+    ///
+    /// let values = [Some(1.0), None, Some(2.25)];
+    /// assert_eq!(Charts::new().sparkline(&values), "▁ █");
+    /// assert_eq!(Charts::new().plain(true).sparkline(&values), "1 - 2.25");
+    /// ```
+    pub fn sparkline(&self, values: &[Option<f64>]) -> String {
+        if !self.plain {
+            return sparkline(values);
+        }
+        values
+            .iter()
+            .map(|value| match value {
+                // At most 3 decimals, without trailing zeros.
+                Some(value) => {
+                    let value = format!("{value:.3}");
+                    value
+                        .trim_end_matches('0')
+                        .trim_end_matches('.')
+                        .to_string()
+                }
+                None => "-".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Renders labeled values as a [`bar_chart`], or as `label: value`
+    /// lines when plain.
+    pub fn bar_chart<L: AsRef<str>>(&self, entries: &[(L, u64)], width: usize) -> String {
+        if !self.plain {
+            return bar_chart(entries, width);
+        }
+        entries
+            .iter()
+            .map(|(label, value)| format!("{}: {value}", label.as_ref()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Renders a ratio as a [`meter`], or as nothing when plain, as meters
+    /// stand next to their ratio written as a number.
+    pub fn meter(&self, ratio: f64, width: usize) -> String {
+        if self.plain {
+            String::new()
+        } else {
+            meter(ratio, width)
+        }
+    }
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    values.fold(None, |acc, value| match acc {
+        None => Some((value, value)),
+        Some((min, max)) => Some((min.min(value), max.max(value))),
+    })
+}
+
+/// Maps `value` within `min..=max` onto one of `levels` buckets.
+fn scale(value: f64, min: f64, max: f64, levels: usize) -> usize {
+    if max <= min {
+        return 0;
+    }
+
+    let ratio = (value - min) / (max - min);
+    ((ratio * (levels - 1) as f64).round() as usize).min(levels - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline_flat_series() {
+        assert_eq!(sparkline(&[Some(3.0), Some(3.0)]), "▁▁");
+    }
+
+    #[test]
+    fn test_sparkline_all_missing() {
+        assert_eq!(sparkline(&[None, None]), "  ");
+    }
+
+    #[test]
+    fn test_braille_single_row() {
+        // lowest value sits on the bottom dot, highest on the top dot.
+        assert_eq!(braille(&[0.0, 3.0], 1), "⡈");
+    }
+
+    #[test]
+    fn test_braille_multiple_rows() {
+        assert_eq!(braille(&[0.0, 7.0], 2), "⠈\n⡀");
+    }
+
+    #[test]
+    fn test_meter_bounds() {
+        assert_eq!(meter(0.0, 3), "░░░");
+        assert_eq!(meter(1.5, 3), "███");
+        assert_eq!(meter(f64::NAN, 2), "░░");
+    }
+
+    #[test]
+    fn test_plain_charts() {
+        let charts = Charts::new().plain(true);
+        assert_eq!(charts.sparkline(&[Some(0.5), Some(10.0), None]), "0.5 10 -");
+        assert_eq!(charts.bar_chart(&[("a", 2), ("bb", 4)], 4), "a: 2\nbb: 4");
+        assert_eq!(charts.meter(0.5, 4), "");
+        assert_eq!(Charts::new().meter(0.5, 4), "██░░");
+    }
+
+    #[test]
+    fn test_bar_chart_zero_values() {
+        assert_eq!(bar_chart(&[("x", 0)], 3), "x |     0");
+    }
+
+    #[test]
+    fn test_bar_chart_huge_values() {
+        let chart = bar_chart(&[("a", u64::MAX), ("b", u64::MAX / 2)], 4);
+        assert_eq!(
+            chart,
+            format!("a | ████ {}\nb | ██   {}", u64::MAX, u64::MAX / 2)
+        );
+    }
+}
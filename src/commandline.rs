@@ -1,9 +1,86 @@
-use crate::{ping, prettify_xml};
+use std::io::{IsTerminal, Write};
+
+use crate::{
+    args, canonicalize_xml, chart, convert, curlconv, diff_xml, disks, fastcopy, fileinfo, har,
+    highlight, hosts, httping, humanize, i18n, janitor, links, monitor, mtr, pager, ping, pmtu,
+    prettify_html, prettify_xml, prompt, report, resolver, server, shape, shred, stats_xml,
+    strip_xml, sweep, template, webhook, xsd,
+};
+
+#[cfg(all(target_os = "linux", feature = "arping"))]
+use crate::arping;
 
 pub enum Subcommands {
     PrettifyXml,
+    PrettifyHtml,
+    MinifyXml,
+    ValidateXml,
+    XmlToJson,
+    XmlToYaml,
+    YamlToXml,
+    DiffXml,
+    CanonicalizeXml,
+    StatsXml,
+    StripXml,
     NewUuid,
     Ping,
+    Webhook,
+    Har,
+    Curl2Http,
+    File,
+    Xattr,
+    Links,
+    CpSparse,
+    Shred,
+    Disks,
+    Httping,
+    CleanTmp,
+    DevClean,
+    PingSweep,
+    LspLite,
+    Mtr,
+    Pmtu,
+    Arping,
+    Shape,
+    Hosts,
+}
+
+impl Subcommands {
+    /// The names of the subcommands, as typed on the command line.
+    pub const NAMES: &'static [&'static str] = &[
+        "prettify-xml",
+        "prettify-html",
+        "minify-xml",
+        "validate-xml",
+        "xml-to-json",
+        "xml-to-yaml",
+        "yaml-to-xml",
+        "diff-xml",
+        "canonicalize-xml",
+        "stats-xml",
+        "strip-xml",
+        "new-uuid",
+        "ping",
+        "webhook",
+        "har",
+        "curl2http",
+        "file",
+        "xattr",
+        "links",
+        "cp-sparse",
+        "shred",
+        "disks",
+        "httping",
+        "clean-tmp",
+        "dev-clean",
+        "ping-sweep",
+        "lsp-lite",
+        "mtr",
+        "pmtu",
+        "arping",
+        "shape",
+        "hosts",
+    ];
 }
 
 impl std::str::FromStr for Subcommands {
@@ -12,48 +89,3255 @@ impl std::str::FromStr for Subcommands {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "prettify-xml" => Ok(Self::PrettifyXml),
+            "prettify-html" => Ok(Self::PrettifyHtml),
+            "minify-xml" => Ok(Self::MinifyXml),
+            "validate-xml" => Ok(Self::ValidateXml),
+            "xml-to-json" => Ok(Self::XmlToJson),
+            "xml-to-yaml" => Ok(Self::XmlToYaml),
+            "yaml-to-xml" => Ok(Self::YamlToXml),
+            "diff-xml" => Ok(Self::DiffXml),
+            "canonicalize-xml" => Ok(Self::CanonicalizeXml),
+            "stats-xml" => Ok(Self::StatsXml),
+            "strip-xml" => Ok(Self::StripXml),
             "new-uuid" => Ok(Self::NewUuid),
             "ping" => Ok(Self::Ping),
+            "webhook" => Ok(Self::Webhook),
+            "har" => Ok(Self::Har),
+            "curl2http" => Ok(Self::Curl2Http),
+            "file" => Ok(Self::File),
+            "xattr" => Ok(Self::Xattr),
+            "links" => Ok(Self::Links),
+            "cp-sparse" => Ok(Self::CpSparse),
+            "shred" => Ok(Self::Shred),
+            "disks" => Ok(Self::Disks),
+            "httping" => Ok(Self::Httping),
+            "clean-tmp" => Ok(Self::CleanTmp),
+            "dev-clean" => Ok(Self::DevClean),
+            "ping-sweep" => Ok(Self::PingSweep),
+            "lsp-lite" => Ok(Self::LspLite),
+            "mtr" => Ok(Self::Mtr),
+            "pmtu" => Ok(Self::Pmtu),
+            "arping" => Ok(Self::Arping),
+            "shape" => Ok(Self::Shape),
+            "hosts" => Ok(Self::Hosts),
             _ => Err("support subcommands"),
         }
     }
 }
 
+/// Options given before the subcommand, shared by all subcommands.
+#[derive(Debug, Clone, Default)]
+pub struct GlobalOptions {
+    /// `--raw`: write exact numbers instead of humanized ones.
+    pub raw: bool,
+    /// `--locale <tag>`: conventions of humanized numbers and dates.
+    pub locale: humanize::Locale,
+    /// `--lang <tag>`: language of the messages in the catalog.
+    pub lang: i18n::Lang,
+    /// `--plain` (or `TERM=dumb`): no colors, redrawn screens or charts of
+    /// block characters, for screen readers and dumb terminals.
+    pub plain: bool,
+    /// `--no-pager`: never page long outputs through `$PAGER`.
+    pub no_pager: bool,
+    /// `--yes`: confirm every question without asking, for scripts.
+    pub yes: bool,
+    /// `--no-secrets-guard`: print secrets to the terminal too.
+    pub no_secrets_guard: bool,
+    /// `--deterministic` (or `CRABYKNIFE_DETERMINISTIC=1`): byte-reproducible
+    /// outputs, with seeded generators, masked ping times and no times
+    /// relative to now.
+    pub deterministic: bool,
+}
+
+impl GlobalOptions {
+    fn humanizer(&self) -> humanize::Humanizer {
+        humanize::Humanizer::new().raw(self.raw).locale(self.locale)
+    }
+
+    fn charts(&self) -> chart::Charts {
+        chart::Charts::new().plain(self.plain)
+    }
+
+    /// The pager of long outputs, never run in plain mode as it takes the
+    /// whole screen.
+    fn pager(&self) -> pager::Pager {
+        pager::Pager::new()
+            .enabled(!self.no_pager && !self.plain)
+            .secrets_guard(!self.no_secrets_guard)
+    }
+
+    fn prompter(&self) -> prompt::Prompter<std::io::StdinLock<'static>, std::io::Stderr> {
+        prompt::Prompter::stdin().assume_yes(self.yes)
+    }
+}
+
+/// An error ending the program with a specific exit code, for subcommands
+/// whose exit code tells more than success or failure. An empty message
+/// is not printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl ExitError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The `usage` of a subcommand given back for invalid arguments,
+    /// exiting with 2 like most command line tools.
+    fn usage(usage: &str) -> Self {
+        Self::new(2, usage)
+    }
+}
+
+impl std::fmt::Display for ExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
 pub fn run(
     subcommand: &str,
-    remaining_args: std::env::Args,
+    remaining_args: args::Args,
+    global: &GlobalOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let subcommand: Subcommands = subcommand.parse()?;
+    let subcommand: Subcommands = subcommand.parse().map_err(|_| {
+        let message = global
+            .lang
+            .message("unknown-subcommand", &[("subcommand", &subcommand)]);
+        ExitError::new(2, message)
+    })?;
 
     match subcommand {
-        Subcommands::PrettifyXml => handle_prettify_xml(remaining_args),
-        Subcommands::NewUuid => handle_new_uuid(),
-        Subcommands::Ping => handle_ping(remaining_args),
+        Subcommands::PrettifyXml => handle_prettify_xml(remaining_args, global),
+        Subcommands::PrettifyHtml => handle_prettify_html(remaining_args, global),
+        Subcommands::MinifyXml => handle_minify_xml(remaining_args, global),
+        Subcommands::ValidateXml => handle_validate_xml(remaining_args),
+        Subcommands::XmlToJson => handle_xml_to_json(remaining_args, global),
+        Subcommands::XmlToYaml => handle_xml_to_yaml(remaining_args, global),
+        Subcommands::YamlToXml => handle_yaml_to_xml(remaining_args, global),
+        Subcommands::DiffXml => handle_diff_xml(remaining_args, global),
+        Subcommands::CanonicalizeXml => handle_canonicalize_xml(remaining_args, global),
+        Subcommands::StatsXml => handle_stats_xml(remaining_args, global),
+        Subcommands::StripXml => handle_strip_xml(remaining_args, global),
+        Subcommands::NewUuid => handle_new_uuid(remaining_args, global),
+        Subcommands::Ping => handle_ping(remaining_args, global),
+        Subcommands::Webhook => handle_webhook(remaining_args),
+        Subcommands::Har => handle_har(remaining_args, global),
+        Subcommands::Curl2Http => handle_curl2http(remaining_args, global),
+        Subcommands::File => handle_file(remaining_args, global),
+        Subcommands::Xattr => handle_xattr(remaining_args),
+        Subcommands::Links => handle_links(remaining_args),
+        Subcommands::CpSparse => handle_cp_sparse(remaining_args, global),
+        Subcommands::Shred => handle_shred(remaining_args),
+        Subcommands::Disks => handle_disks(remaining_args, global),
+        Subcommands::Httping => handle_httping(remaining_args),
+        Subcommands::CleanTmp => handle_clean_tmp(remaining_args, global),
+        Subcommands::DevClean => handle_dev_clean(remaining_args, global),
+        Subcommands::PingSweep => handle_ping_sweep(remaining_args),
+        Subcommands::LspLite => handle_lsp_lite(remaining_args),
+        Subcommands::Mtr => handle_mtr(remaining_args, global),
+        Subcommands::Pmtu => handle_pmtu(remaining_args),
+        Subcommands::Arping => handle_arping(remaining_args),
+        Subcommands::Shape => handle_shape(remaining_args),
+        Subcommands::Hosts => handle_hosts(remaining_args, global),
     }
 }
 
 fn handle_prettify_xml(
-    mut remaining_args: std::env::Args,
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let xml = remaining_args
-        .next()
-        .expect("Usage: crabyknif prettify-xml <unprettified xml>");
+    const USAGE: &str = "Usage: crabyknife prettify-xml [--indent <width>] [--tabs] [--max-width <columns>] [--collapse-empty | --expand-empty] [--sort-attrs] [--namespaces] [--hoist-ns] [--ns-prefix <prefix=uri>] [--preserve-whitespace-in <tag,...>] [--max-depth <n>] [--max-size <bytes>] [--color] [--file <path> | <path> | - | --text <xml> | --write <pattern>... | --check <pattern>...] [-0 | --print0]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = prettify_xml::PrettifyOptions::new();
+    let mut input = Input::Stdin;
+    let mut in_place = None;
+    let mut patterns = Vec::new();
+    let mut color = false;
+    let mut print0 = false;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--indent" => {
+                let width = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let width = width
+                    .parse()
+                    .map_err(|err| format!("invalid indent width ({width}): {err}"))?;
+                options = options.indent_width(width);
+            }
+            "--tabs" => options = options.use_tabs(true),
+            "--max-width" => {
+                let width = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let width = width
+                    .parse()
+                    .map_err(|err| format!("invalid max width ({width}): {err}"))?;
+                options = options.max_width(Some(width));
+            }
+            "--sort-attrs" => options = options.sort_attributes(true),
+            "--namespaces" => options = options.namespace_aware(true),
+            "--hoist-ns" => options = options.hoist_namespaces(true),
+            "--ns-prefix" => {
+                let mapping = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let (prefix, uri) = mapping.split_once('=').ok_or_else(|| {
+                    format!("invalid --ns-prefix ({mapping}), expected prefix=uri")
+                })?;
+                options = options.namespace_prefix(uri, prefix);
+            }
+            "--preserve-whitespace-in" => {
+                let tags = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                for tag in tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                    options = options.preserve_whitespace_in(tag);
+                }
+            }
+            "--collapse-empty" => {
+                options = options.empty_elements(prettify_xml::EmptyElements::Collapse)
+            }
+            "--expand-empty" => {
+                options = options.empty_elements(prettify_xml::EmptyElements::Expand)
+            }
+            "--max-depth" => {
+                let depth = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let depth = depth
+                    .parse()
+                    .map_err(|err| format!("invalid max depth ({depth}): {err}"))?;
+                options = options.max_depth(Some(depth));
+            }
+            "--max-size" => {
+                let size = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                options = options.max_size(Some(humanize::parse_bytes(&size)?));
+            }
+            "--color" => color = true,
+            "--write" => in_place = Some(InPlace::Write),
+            "--check" => in_place = Some(InPlace::Check),
+            "-0" | "--print0" => print0 = true,
+            _ => {
+                input = Input::parse(arg, &mut remaining_args, USAGE)?;
+                if let Input::File(path) = &input {
+                    patterns.push(path.clone());
+                }
+            }
+        }
+    }
 
-    let prettified = prettify_xml::prettify_xml(&xml)?;
-    println!("{prettified}");
+    if let Some(in_place) = in_place {
+        if patterns.is_empty() || !matches!(input, Input::File(_)) {
+            return Err(ExitError::usage(USAGE).into());
+        }
+        return prettify_in_place(&patterns, &options, in_place, print0);
+    }
+    if print0 {
+        // Only the file lists of `--write` and `--check` are delimited.
+        return Err(ExitError::usage(USAGE).into());
+    }
+
+    let mut output = global.pager().stdout();
+    let color = color && !global.plain && highlight::should_color(&std::io::stdout());
+    let mut writer: Box<dyn Write> = if color {
+        Box::new(highlight::Highlighter::new(std::io::BufWriter::new(
+            &mut output,
+        )))
+    } else {
+        Box::new(std::io::BufWriter::new(&mut output))
+    };
+
+    // Files and stdin are streamed through the prettifier so documents
+    // larger than memory can be formatted.
+    prettify_xml::prettify_xml_stream(input.reader()?, &mut writer, &options)
+        .map_err(|err| with_excerpt(err, &input))?;
+
+    writeln!(writer)?;
+    writer.flush()?;
+    drop(writer);
+    output.finish()?;
+    Ok(())
+}
+
+/// How `prettify-xml --write` and `--check` treat files that are not pretty yet.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InPlace {
+    Write,
+    Check,
+}
+
+/// Prettifies the files matching `patterns` in place, or only lists
+/// the files that would change when checking. With `print0` the listed
+/// paths end with a NUL byte rather than a newline, for `xargs -0`.
+fn prettify_in_place(
+    patterns: &[String],
+    options: &prettify_xml::PrettifyOptions,
+    in_place: InPlace,
+    print0: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let matches =
+            glob::glob(pattern).map_err(|err| format!("invalid pattern ({pattern}): {err}"))?;
+        let before = paths.len();
+        for path in matches {
+            let path = path?;
+            if path.is_file() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        if paths.len() == before {
+            return Err(format!("no files match {pattern}").into());
+        }
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let mut changed = 0;
+    for path in &paths {
+        let original = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let mut pretty =
+            prettify_xml::prettify_xml_with_options(&original, options).map_err(|err| {
+                let err = with_excerpt(err, &Input::Text(original.clone()));
+                format!("failed to prettify {}: {err}", path.display())
+            })?;
+        pretty.push('\n');
+        if pretty == original {
+            continue;
+        }
+
+        changed += 1;
+        if print0 {
+            // The raw bytes, as paths may not be valid UTF-8.
+            stdout.write_all(path.as_os_str().as_encoded_bytes())?;
+            stdout.write_all(b"\0")?;
+        } else {
+            writeln!(stdout, "{}", path.display())?;
+        }
+        if in_place == InPlace::Write {
+            fileinfo::write_atomically(path, &pretty)
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        }
+    }
+
+    stdout.flush()?;
+    if in_place == InPlace::Check && changed > 0 {
+        return Err(format!("{changed} file(s) would be reformatted").into());
+    }
     Ok(())
 }
 
-fn handle_new_uuid() -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", uuid::Uuid::new_v4());
+fn handle_prettify_html(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife prettify-html [--indent <width>] [--tabs] [--file <path> | <path> | - | --text <html>]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = prettify_xml::PrettifyOptions::new();
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--indent" => {
+                let width = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let width = width
+                    .parse()
+                    .map_err(|err| format!("invalid indent width ({width}): {err}"))?;
+                options = options.indent_width(width);
+            }
+            "--tabs" => options = options.use_tabs(true),
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let html = input.read()?;
+
+    let prettified = prettify_html::prettify_html_with_options(&html, &options)?;
+    let mut output = global.pager().stdout();
+    writeln!(output, "{prettified}")?;
+    output.finish()?;
     Ok(())
 }
 
-fn handle_ping(mut remaining_args: std::env::Args) -> Result<(), Box<dyn std::error::Error>> {
-    let target = remaining_args
-        .next()
-        .expect("Usage: crabbyknife ping <host>");
+fn handle_minify_xml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife minify-xml [--strip-comments] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut strip_comments = false;
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--strip-comments" => strip_comments = true,
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let xml = input.read()?;
+
+    let minified = prettify_xml::minify_xml(&xml, strip_comments)
+        .map_err(|err| with_excerpt(err, &Input::Text(xml.clone())))?;
+    let mut output = global.pager().stdout();
+    writeln!(output, "{minified}")?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_validate_xml(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife validate-xml [--schema <schema.xsd>] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut schema_path = None;
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--schema" => {
+                schema_path = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let xml = input.read()?;
+
+    prettify_xml::validate(&xml)?;
+
+    if let Some(schema_path) = schema_path {
+        let schema = std::fs::read_to_string(&schema_path)
+            .map_err(|err| format!("failed to read {schema_path}: {err}"))?;
+        let schema = xsd::Schema::parse(&schema)
+            .map_err(|err| format!("invalid schema {schema_path}: {err}"))?;
+
+        let violations = schema.validate(&xml)?;
+        if !violations.is_empty() {
+            for violation in &violations {
+                println!("{violation}");
+            }
+            return Err(format!("{} violation(s) found", violations.len()).into());
+        }
+    }
+
+    println!("valid");
+    Ok(())
+}
+
+fn handle_xml_to_json(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife xml-to-json [--attribute-prefix <prefix>] [--text-key <key>] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = convert::ConvertOptions::new();
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--attribute-prefix" => {
+                options = options.attribute_prefix(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--text-key" => {
+                options = options.text_key(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let xml = input.read()?;
+
+    let json = convert::xml_to_json(&xml, &options)?;
+    let mut output = global.pager().stdout();
+    writeln!(output, "{json}")?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_xml_to_yaml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife xml-to-yaml [--attribute-prefix <prefix>] [--text-key <key>] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = convert::ConvertOptions::new();
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--attribute-prefix" => {
+                options = options.attribute_prefix(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--text-key" => {
+                options = options.text_key(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let xml = input.read()?;
+
+    let yaml = convert::xml_to_yaml(&xml, &options)?;
+    let mut output = global.pager().stdout();
+    write!(output, "{yaml}")?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_yaml_to_xml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife yaml-to-xml [--attribute-prefix <prefix>] [--text-key <key>] [--file <path> | <path> | - | --text <yaml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = convert::ConvertOptions::new();
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--attribute-prefix" => {
+                options = options.attribute_prefix(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--text-key" => {
+                options = options.text_key(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let yaml = input.read()?;
+
+    let xml = convert::yaml_to_xml(&yaml, &options)?;
+    let mut output = global.pager().stdout();
+    writeln!(output, "{}", prettify_xml::prettify_xml(&xml)?)?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_diff_xml(
+    remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Like `diff`, the exit code is 0 for identical documents, 1 when they
+    // differ and 2 when they could not be compared, for CI use.
+    let differences = diff_documents(remaining_args, global).map_err(|err| {
+        if err.is::<args::Help>() {
+            err
+        } else {
+            ExitError::new(2, err.to_string()).into()
+        }
+    })?;
+    if differences > 0 {
+        return Err(ExitError::new(1, "").into());
+    }
+    Ok(())
+}
+
+/// Prints the differences between the documents of `diff-xml` and returns
+/// how many there are.
+fn diff_documents(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife diff-xml <a.xml | -> <b.xml | ->";
+    remaining_args.help(USAGE)?;
+
+    let a = Input::parse(
+        remaining_args
+            .next()
+            .ok_or_else(|| ExitError::usage(USAGE))?,
+        &mut remaining_args,
+        USAGE,
+    )?;
+    let b = Input::parse(
+        remaining_args
+            .next()
+            .ok_or_else(|| ExitError::usage(USAGE))?,
+        &mut remaining_args,
+        USAGE,
+    )?;
+    if remaining_args.next().is_some() || matches!((&a, &b), (Input::Stdin, Input::Stdin)) {
+        return Err(ExitError::usage(USAGE).into());
+    }
+
+    let a = a.read()?;
+    let b = b.read()?;
+
+    let differences = diff_xml::diff_xml(&a, &b)?;
+    let mut output = global.pager().stdout();
+    for difference in &differences {
+        writeln!(output, "{difference}")?;
+    }
+    output.finish()?;
 
-    ping::ping(&target)?;
+    Ok(differences.len())
+}
+
+fn handle_har(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife har summary [--top <n>] [<file.har> | - | --text <har>]";
+    remaining_args.help(USAGE)?;
+
+    if remaining_args.next().as_deref() != Some("summary") {
+        return Err(ExitError::usage(USAGE).into());
+    }
+
+    let mut top = 10;
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--top" => {
+                let n = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                top = n
+                    .parse()
+                    .map_err(|err| format!("invalid --top ({n}): {err}"))?;
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let summary = har::summarize(&har::parse(&input.read()?)?, top);
+
+    let humanizer = global.humanizer();
+    println!(
+        "{} requests, {}, {} cached ({})",
+        humanizer.count(summary.requests as u64),
+        humanizer.bytes(summary.bytes),
+        humanizer.count(summary.cached as u64),
+        humanizer.percent(summary.cache_hit_ratio())
+    );
+
+    println!();
+    println!("Slowest requests:");
+    for entry in &summary.slowest {
+        let time = match std::time::Duration::try_from_secs_f64(entry.time_ms / 1000.0) {
+            Ok(time) => humanizer.duration(time),
+            // Too long for a duration, in a broken or crafted file.
+            Err(_) => format!("{:e} ms", entry.time_ms),
+        };
+        println!(
+            "{time:>12}  {}  {} {}",
+            entry.status, entry.method, entry.url
+        );
+    }
+
+    println!();
+    println!("Bytes by domain:");
+    let bars: Vec<(String, u64)> = summary
+        .domains
+        .iter()
+        .map(|size| {
+            (
+                format!("{} ({} request(s))", size.domain, size.requests),
+                size.bytes,
+            )
+        })
+        .collect();
+    println!("{}", global.charts().bar_chart(&bars, 30));
+    Ok(())
+}
+
+fn handle_curl2http(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife curl2http [<curl command>]";
+    remaining_args.help(USAGE)?;
+
+    // The command is read from stdin when not given, which avoids quoting
+    // a pasted command a second time.
+    let command = match remaining_args.next() {
+        Some(command) => command,
+        None => std::io::read_to_string(std::io::stdin())
+            .map_err(|err| format!("failed to read from stdin: {err}"))?,
+    };
+
+    let request = curlconv::parse(&command)?;
+    let mut output = global.pager().stdout();
+    writeln!(output, "{}", curlconv::to_reqwest(&request))?;
+    output.finish()?;
+    Ok(())
+}
+
+/// Where a text-processing subcommand takes the text it processes from.
+///
+/// They all follow the same convention, parsed by [`Input::parse`]: a
+/// path, or `--file <path>`, names a file to read, `-` reads stdin, and
+/// `--text <text>` gives the text inline. A bare argument starting with
+/// `<`, leading whitespace aside, is inline markup too, e.g.
+/// `prettify-xml '<a><b/></a>'`. Stdin is read when none is given.
+enum Input {
+    Text(String),
+    File(String),
+    Stdin,
+}
+
+impl Input {
+    /// Parses the input given by `arg`, taking the value of `--file` and
+    /// `--text` from `remaining_args`. Any other flag is a usage error.
+    fn parse(
+        arg: String,
+        remaining_args: &mut args::Args,
+        usage: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match arg.as_str() {
+            "--file" => Self::File(
+                remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(usage))?,
+            ),
+            "--text" => Self::Text(
+                remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(usage))?,
+            ),
+            "-" => Self::Stdin,
+            // No path starts with markup.
+            _ if arg.trim_start().starts_with('<') => Self::Text(arg),
+            _ if arg.starts_with('-') => return Err(ExitError::usage(usage).into()),
+            _ => Self::File(arg),
+        })
+    }
+
+    /// Reads the whole text.
+    fn read(self) -> Result<String, Box<dyn std::error::Error>> {
+        let text = match self {
+            Self::Text(text) => text,
+            Self::File(path) => std::fs::read_to_string(&path)
+                .map_err(|err| format!("failed to read {path}: {err}"))?,
+            Self::Stdin => std::io::read_to_string(std::io::stdin())
+                .map_err(|err| format!("failed to read from stdin: {err}"))?,
+        };
+        Ok(text)
+    }
+
+    /// Opens the text to be streamed, for subcommands that handle
+    /// documents larger than memory.
+    fn reader(&self) -> Result<Box<dyn std::io::BufRead + '_>, Box<dyn std::error::Error>> {
+        let reader: Box<dyn std::io::BufRead> = match self {
+            Self::Text(text) => Box::new(text.as_bytes()),
+            Self::File(path) => {
+                let file = std::fs::File::open(path)
+                    .map_err(|err| format!("failed to read {path}: {err}"))?;
+                Box::new(std::io::BufReader::new(file))
+            }
+            Self::Stdin => Box::new(std::io::stdin().lock()),
+        };
+        Ok(reader)
+    }
+
+    /// The text of line `number` (1-based), to show where a problem is.
+    /// Stdin is gone once read, so it has none.
+    fn line(&self, number: usize) -> Option<String> {
+        let index = number.checked_sub(1)?;
+        match self {
+            Self::Text(text) => text.lines().nth(index).map(str::to_string),
+            Self::File(path) => {
+                let file = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+                std::io::BufRead::lines(file).nth(index)?.ok()
+            }
+            Self::Stdin => None,
+        }
+    }
+}
+
+/// Adds the line of `input` where `err` was found to its message, with a
+/// caret under the problem.
+fn with_excerpt(err: prettify_xml::XmlError, input: &Input) -> Box<dyn std::error::Error> {
+    let excerpt = err
+        .location()
+        .and_then(|location| Some(location.excerpt(&input.line(location.line)?)));
+    match excerpt {
+        Some(excerpt) => format!("{err}\n{excerpt}").into(),
+        None => err.into(),
+    }
+}
+
+fn handle_canonicalize_xml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife canonicalize-xml [--with-comments] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut with_comments = false;
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--with-comments" => with_comments = true,
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    let xml = input.read()?;
+
+    // No trailing newline, so the output can be hashed or signed as is.
+    let mut output = global.pager().stdout();
+    output.write_all(canonicalize_xml::canonicalize_xml(&xml, with_comments)?.as_bytes())?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_stats_xml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife stats-xml [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        input = Input::parse(arg, &mut remaining_args, USAGE)?;
+    }
+
+    // Streamed rather than read whole, so large feeds are never held in
+    // memory.
+    let stats = stats_xml::stats_xml(input.reader()?)?;
+
+    let humanizer = global.humanizer();
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    writeln!(
+        stdout,
+        "{} element(s), max depth {}",
+        humanizer.count(stats.elements as u64),
+        humanizer.integer(stats.max_depth as u64)
+    )?;
+    writeln!(
+        stdout,
+        "{} attribute(s)",
+        humanizer.count(stats.attributes as u64)
+    )?;
+    writeln!(stdout, "{} of text", humanizer.bytes(stats.text_bytes))?;
+
+    // Most frequent names first.
+    let mut element_counts: Vec<_> = stats.element_counts.iter().collect();
+    element_counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    writeln!(stdout, "\nElements:")?;
+    for (name, count) in element_counts {
+        writeln!(stdout, "{:>10}  {name}", humanizer.count(*count as u64))?;
+    }
+
+    if !stats.attribute_counts.is_empty() {
+        let mut attribute_counts: Vec<_> = stats.attribute_counts.iter().collect();
+        attribute_counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        writeln!(stdout, "\nAttributes:")?;
+        for (name, count) in attribute_counts {
+            writeln!(stdout, "{:>10}  {name}", humanizer.count(*count as u64))?;
+        }
+    }
+
+    if !stats.namespaces.is_empty() {
+        writeln!(stdout, "\nNamespaces:")?;
+        for (uri, usage) in &stats.namespaces {
+            let prefixes: Vec<&str> = usage
+                .prefixes
+                .iter()
+                .map(|prefix| {
+                    if prefix.is_empty() {
+                        "(default)"
+                    } else {
+                        prefix
+                    }
+                })
+                .collect();
+            writeln!(
+                stdout,
+                "{uri}  prefixes: {}, {} element(s), {} attribute(s)",
+                prefixes.join(", "),
+                usage.elements,
+                usage.attributes
+            )?;
+        }
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
+
+fn handle_strip_xml(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife strip-xml [--path <filter>] [--file <path> | <path> | - | --text <xml>]";
+    remaining_args.help(USAGE)?;
+
+    let mut filter = None;
+    let mut input = Input::Stdin;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--path" => {
+                filter = Some(strip_xml::PathFilter::parse(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            _ => input = Input::parse(arg, &mut remaining_args, USAGE)?,
+        }
+    }
+
+    // Streamed like `stats-xml`, large exports are never held in memory.
+    let mut output = global.pager().stdout();
+    let stdout = std::io::BufWriter::new(&mut output);
+    strip_xml::strip_xml(input.reader()?, filter.as_ref(), stdout)?;
+    output.finish()?;
+    Ok(())
+}
+
+fn handle_new_uuid(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife new-uuid [-n <count>] [--seed <n>]";
+    remaining_args.help(USAGE)?;
+
+    let mut count = 1;
+    // Deterministic runs use a fixed seed unless given one.
+    let mut seed = global.deterministic.then_some(0);
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-n" => {
+                let n = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                count = n
+                    .parse()
+                    .map_err(|err| format!("invalid count ({n}): {err}"))?;
+            }
+            "--seed" => {
+                let n = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                seed = Some(
+                    n.parse()
+                        .map_err(|err| format!("invalid seed ({n}): {err}"))?,
+                );
+            }
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+
+    let mut state = seed;
+    for _ in 0..count {
+        let uuid = match state.as_mut() {
+            Some(state) => seeded_uuid(state),
+            None => uuid::Uuid::new_v4(),
+        };
+        println!("{uuid}");
+    }
     Ok(())
 }
+
+/// Generates a version 4 UUID from the splitmix64 generator `state`,
+/// advancing it, so a seed always gives the same UUIDs.
+fn seeded_uuid(state: &mut u64) -> uuid::Uuid {
+    let mut next = || {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&next().to_le_bytes());
+    bytes[8..].copy_from_slice(&next().to_le_bytes());
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
+/// Exits with 0 when every probe was answered, 1 when some were lost, 2
+/// when none were, and 3 when the host could not be pinged at all, so
+/// scripts can branch on reachability.
+fn handle_ping(
+    remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (sent, received) = ping_hosts(remaining_args, global).map_err(probe_failed)?;
+    reachability(sent, received)
+}
+
+/// Makes `ping` and `arping` exit with 3 when they could not probe. Help
+/// and usage errors are still printed and exit as for other subcommands.
+fn probe_failed(err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+    if err.is::<args::Help>() || err.is::<ExitError>() {
+        err
+    } else {
+        ExitError::new(3, err.to_string()).into()
+    }
+}
+
+/// The exit status of `ping` and `arping` after `sent` probes of which
+/// `received` were answered.
+fn reachability(sent: usize, received: usize) -> Result<(), Box<dyn std::error::Error>> {
+    if received == sent {
+        Ok(())
+    } else if received == 0 {
+        Err(ExitError::new(2, "").into())
+    } else {
+        Err(ExitError::new(1, "").into())
+    }
+}
+
+/// Runs `ping` and returns the number of probes sent and answered, over
+/// every address pinged.
+fn ping_hosts(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife ping [-4 | -6 | --prefer-ipv6] [--all] [-c <count>] [-i <seconds>] [-W <seconds>] [-s | --size <bytes>] [--pattern <hex>] [--ttl <n>] [--graph] [--histogram] [--json] [--export <path>] [--format <template>] [--monitor [--down-after <n>] [--up-after <n>] [--on-change <command>]] [--resolve <host:ip>] [--no-dns] [-v | --verbose] (<host>... | --tcp <host:port> | --listen)";
+    remaining_args.help(USAGE)?;
+
+    let mut resolver = resolver::Resolver::new();
+    let mut graph = false;
+    let mut histogram = false;
+    let mut json = false;
+    let mut export = None;
+    let mut format = None;
+    let mut config = ping::PingConfig::new();
+    let mut targets = Vec::new();
+    let mut family = None;
+    let mut listen = false;
+    let mut tcp = false;
+    let mut all = false;
+    let mut monitor = false;
+    let mut down_after = 3;
+    let mut up_after = 2;
+    let mut on_change = None;
+    let mut size = None;
+    let mut verbose = false;
+    // -s, --pattern and --ttl only apply to ICMP probes.
+    let mut icmp_only = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-4" | "-6" => {
+                family = Some(if arg == "-4" {
+                    ping::IpFamily::V4
+                } else {
+                    ping::IpFamily::V6
+                });
+                config = config.family(family);
+            }
+            "--prefer-ipv6" => config = config.preferred_family(ping::IpFamily::V6),
+            "--all" => all = true,
+            "--monitor" => monitor = true,
+            "--listen" => listen = true,
+            "--down-after" | "--up-after" => {
+                let probes = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let probes = probes
+                    .parse()
+                    .ok()
+                    .filter(|probes| *probes > 0)
+                    .ok_or_else(|| format!("invalid number of probes for {arg} ({probes})"))?;
+                if arg == "--down-after" {
+                    down_after = probes;
+                } else {
+                    up_after = probes;
+                }
+            }
+            "--on-change" => {
+                on_change = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "-c" => {
+                let count = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let count = count
+                    .parse()
+                    .map_err(|err| format!("invalid count ({count}): {err}"))?;
+                config = config.count(count);
+            }
+            "-i" => {
+                config = config.interval(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-s" | "--size" => {
+                let bytes = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let bytes = bytes
+                    .parse()
+                    .map_err(|err| format!("invalid packet size ({bytes}): {err}"))?;
+                size = Some(bytes);
+                config = config.payload_size(bytes);
+                icmp_only = Some(if arg == "-s" { "-s" } else { "--size" });
+            }
+            "--pattern" => {
+                let pattern = parse_pattern(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?;
+                // Without a size, the payload is the pattern once.
+                if size.is_none() {
+                    config = config.payload_size(pattern.len());
+                }
+                config = config.pattern(pattern);
+                icmp_only = Some("--pattern");
+            }
+            "--ttl" => {
+                let ttl = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let ttl = ttl
+                    .parse()
+                    .ok()
+                    .filter(|ttl| (1..=255).contains(ttl))
+                    .ok_or_else(|| format!("invalid TTL ({ttl}), expected 1 to 255"))?;
+                config = config.ttl(Some(ttl));
+                icmp_only = Some("--ttl");
+            }
+            "--tcp" => {
+                tcp = true;
+                targets.push(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                );
+            }
+            "--graph" => graph = true,
+            "--histogram" => histogram = true,
+            "--json" => json = true,
+            "--export" => {
+                export = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--format" => {
+                format = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--resolve" => resolver.add_override(
+                &remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?,
+            )?,
+            "--no-dns" => resolver = resolver.literal_only(true),
+            "-v" | "--verbose" => verbose = true,
+            _ if arg.starts_with('-') => return Err(ExitError::usage(USAGE).into()),
+            _ => targets.push(arg),
+        }
+    }
+
+    if listen {
+        if !targets.is_empty() {
+            return Err("--listen takes no host".into());
+        }
+        for (option, used) in [
+            ("--monitor", monitor),
+            ("--graph", graph),
+            ("--histogram", histogram),
+            ("--json", json),
+            ("--export", export.is_some()),
+            ("--format", format.is_some()),
+        ] {
+            if used {
+                return Err(format!("{option} cannot be used with --listen").into());
+            }
+        }
+        match listen_for_echo(family.unwrap_or(ping::IpFamily::V4), global.deterministic)? {}
+    }
+
+    // Several hosts are pinged at once and compared at the end.
+    let several = targets.len() > 1;
+    if several {
+        for (option, used) in [("--all", all), ("--monitor", monitor), ("--tcp", tcp)] {
+            if used {
+                return Err(format!("{option} cannot be used with several hosts").into());
+            }
+        }
+    }
+    let target = targets
+        .first()
+        .cloned()
+        .ok_or_else(|| ExitError::usage(USAGE))?;
+    if json {
+        for (option, used) in [
+            ("--graph", graph),
+            ("--histogram", histogram),
+            ("--format", format.is_some()),
+        ] {
+            if used {
+                return Err(format!("{option} cannot be used with --json").into());
+            }
+        }
+    }
+    let tcp_port = if tcp {
+        if let Some(option) = icmp_only {
+            return Err(format!("{option} cannot be used with --tcp").into());
+        }
+        let (host, port) = target
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected <host:port> after --tcp, got {target}"))?;
+        let port: u16 = port
+            .parse()
+            .map_err(|err| format!("invalid port ({port}): {err}"))?;
+        // IPv6 addresses are written in brackets, e.g. [::1]:443.
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        Some((host.to_string(), port))
+    } else {
+        None
+    };
+
+    if monitor {
+        for (option, used) in [
+            ("--all", all),
+            ("--graph", graph),
+            ("--histogram", histogram),
+            ("--json", json),
+            ("--export", export.is_some()),
+            ("--format", format.is_some()),
+        ] {
+            if used {
+                return Err(format!("{option} cannot be used with --monitor").into());
+            }
+        }
+        let host = tcp_port.as_ref().map_or(target.as_str(), |(host, _)| host);
+        match monitor_host(
+            &target,
+            host,
+            tcp_port.as_ref().map(|(_, port)| *port),
+            &resolver,
+            &config,
+            monitor::Monitor::new(down_after, up_after),
+            on_change.as_deref(),
+            global.deterministic,
+        )? {}
+    }
+
+    // Every probe is a row of the report, for `--export` and `--format`.
+    let columns = ["target", "seq", "rtt_ms"];
+    // The template is checked before pinging, so typos fail fast.
+    let format = format
+        .map(|format| template::Template::parse(&format, &columns.map(str::to_string)))
+        .transpose()?;
+
+    // With a template or JSON, only the rendered probes or the summaries are printed.
+    let quiet = format.is_some() || json;
+    let lang = global.lang;
+
+    // With --all, every address of the host is pinged in turn.
+    let host = tcp_port.as_ref().map_or(target.as_str(), |(host, _)| host);
+    // How long the system resolver took, reported apart from the probes.
+    let dns_time = |source: resolver::Source, elapsed: std::time::Duration| {
+        (source == resolver::Source::System).then_some(elapsed)
+    };
+    // Times change with every run, so deterministic runs mask them.
+    let deterministic = global.deterministic;
+    let probe_time = |rtt: std::time::Duration| {
+        if deterministic {
+            "?".to_string()
+        } else {
+            rtt.as_millis().to_string()
+        }
+    };
+    let dns_suffix = |time: Option<std::time::Duration>| {
+        time.map(|time| {
+            let time = if deterministic {
+                "?".to_string()
+            } else {
+                format!("{:.3}", time.as_secs_f64() * 1000.0)
+            };
+            lang.message("ping.dns-time", &[("time", &time)])
+        })
+        .unwrap_or_default()
+    };
+    let mut all_dns = None;
+    let hosts = if all {
+        let resolution = ping::resolve_addresses(host, &resolver, &config)?;
+        all_dns = dns_time(resolution.source, resolution.elapsed);
+        if !quiet {
+            let addresses: Vec<String> = resolution
+                .addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let id = if addresses.len() == 1 {
+                "ping.resolved-all.one"
+            } else {
+                "ping.resolved-all.other"
+            };
+            println!(
+                "{}{}",
+                lang.message(
+                    id,
+                    &[
+                        ("host", &host),
+                        ("count", &addresses.len()),
+                        ("source", &resolution.source),
+                        ("addresses", &addresses.join(", ")),
+                    ]
+                ),
+                dns_suffix(all_dns)
+            );
+        }
+        resolution
+            .addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    } else {
+        vec![host.to_string()]
+    };
+
+    let mut probes = report::Report::new("ping", &columns);
+    let mut summaries = Vec::new();
+    let (mut sent, mut received) = (0, 0);
+    let print_event = |target: &str,
+                       dns: &std::cell::Cell<Option<std::time::Duration>>,
+                       event: ping::PingEvent| {
+        if let ping::PingEvent::Resolved {
+            source, elapsed, ..
+        } = event
+        {
+            if !all {
+                dns.set(dns_time(source, elapsed));
+            }
+        }
+        if quiet {
+            return;
+        }
+        match event {
+            // The addresses were listed already.
+            ping::PingEvent::Resolved { .. } if all => {}
+            ping::PingEvent::Resolved {
+                address,
+                source,
+                addresses,
+                ..
+            } => {
+                let others = if addresses > 1 {
+                    lang.message("ping.other-addresses", &[("count", &addresses)])
+                } else {
+                    String::new()
+                };
+                let resolved = lang.message(
+                    "ping.resolved",
+                    &[
+                        ("target", &target),
+                        ("address", &address),
+                        ("source", &source),
+                    ],
+                );
+                println!("{resolved}{others}{}", dns_suffix(dns.get()))
+            }
+            ping::PingEvent::Timestamps(timestamps) if verbose => {
+                let id = match timestamps {
+                    ping::Timestamps::Kernel => "ping.timestamps.kernel",
+                    ping::Timestamps::Userspace => "ping.timestamps.userspace",
+                };
+                println!("{}", lang.message(id, &[]))
+            }
+            ping::PingEvent::Timestamps(_) => {}
+            ping::PingEvent::Reply { seq, rtt, .. } if tcp => {
+                let time = probe_time(rtt);
+                let args: [(&str, &dyn std::fmt::Display); 3] =
+                    [("target", &target), ("seq", &seq), ("time", &time)];
+                println!("{}", lang.message("ping.connected", &args))
+            }
+            ping::PingEvent::Reply { seq, rtt, ttl } => {
+                let ttl = ttl.map(|ttl| format!(" ttl={ttl}")).unwrap_or_default();
+                let time = probe_time(rtt);
+                let args: [(&str, &dyn std::fmt::Display); 4] = [
+                    ("target", &target),
+                    ("seq", &seq),
+                    ("ttl", &ttl),
+                    ("time", &time),
+                ];
+                println!("{}", lang.message("ping.reply", &args))
+            }
+            ping::PingEvent::Duplicate { seq } => {
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("target", &target), ("seq", &seq)];
+                println!("{}", lang.message("ping.duplicate", &args))
+            }
+            ping::PingEvent::Mismatched { seq } => {
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("target", &target), ("seq", &seq)];
+                println!("{}", lang.message("ping.mismatched", &args))
+            }
+            ping::PingEvent::IcmpError { seq, from, error } => {
+                // The MTU or the ICMP code, for the messages showing one.
+                let (id, detail) = match error {
+                    ping::IcmpError::TimeExceeded => ("ping.time-exceeded", None),
+                    ping::IcmpError::TooBig { mtu } => ("ping.too-big", Some(mtu)),
+                    ping::IcmpError::Unreachable(reason) => match reason {
+                        ping::Unreachable::Network => ("ping.unreachable.network", None),
+                        ping::Unreachable::Host => ("ping.unreachable.host", None),
+                        ping::Unreachable::Protocol => ("ping.unreachable.protocol", None),
+                        ping::Unreachable::Port => ("ping.unreachable.port", None),
+                        ping::Unreachable::Prohibited => ("ping.unreachable.prohibited", None),
+                        ping::Unreachable::Other(code) => {
+                            ("ping.unreachable.other", Some(u32::from(code)))
+                        }
+                    },
+                };
+                let detail = detail.unwrap_or_default();
+                let args: [(&str, &dyn std::fmt::Display); 4] = [
+                    ("from", &from),
+                    ("seq", &seq),
+                    ("mtu", &detail),
+                    ("code", &detail),
+                ];
+                println!("{}", lang.message(id, &args))
+            }
+            ping::PingEvent::Malformed => println!("{}", lang.message("ping.malformed", &[])),
+            ping::PingEvent::Corrupted { seq, rtt, offset } => {
+                let time = probe_time(rtt);
+                let args: [(&str, &dyn std::fmt::Display); 4] = [
+                    ("target", &target),
+                    ("seq", &seq),
+                    ("time", &time),
+                    ("offset", &offset),
+                ];
+                println!("{}", lang.message("ping.corrupted", &args))
+            }
+            ping::PingEvent::Timeout { seq } if several => {
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("target", &target), ("seq", &seq)];
+                println!("{}", lang.message("ping.timeout-target", &args))
+            }
+            ping::PingEvent::Timeout { seq } => {
+                println!("{}", lang.message("ping.timeout", &[("seq", &seq)]))
+            }
+            ping::PingEvent::Refused { seq, rtt } => {
+                let time = probe_time(rtt);
+                let args: [(&str, &dyn std::fmt::Display); 3] =
+                    [("target", &target), ("seq", &seq), ("time", &time)];
+                println!("{}", lang.message("ping.refused", &args))
+            }
+            ping::PingEvent::Failed { seq, kind } => {
+                let args: [(&str, &dyn std::fmt::Display); 2] = [("seq", &seq), ("kind", &kind)];
+                println!("{}", lang.message("ping.failed", &args))
+            }
+        }
+    };
+    let mut summarize =
+        |target: &str, dns: Option<std::time::Duration>, ping_report: &ping::PingReport| {
+            sent += ping_report.sent;
+            received += ping_report.received;
+            let millis: Vec<Option<f64>> = ping_report
+                .rtts
+                .iter()
+                .map(|rtt| rtt.map(|rtt| rtt.as_secs_f64() * 1000.0))
+                .collect();
+
+            if !quiet {
+                let loss = format!("{:.0}", ping_report.loss() * 100.0);
+                if tcp {
+                    let args: [(&str, &dyn std::fmt::Display); 4] = [
+                        ("sent", &ping_report.sent),
+                        ("connected", &ping_report.received),
+                        ("refused", &ping_report.refused),
+                        ("failed", &loss),
+                    ];
+                    println!("{}", lang.message("ping.summary-tcp", &args));
+                } else {
+                    let duplicates = if ping_report.duplicates > 0 {
+                        lang.message("ping.duplicates", &[("count", &ping_report.duplicates)])
+                    } else {
+                        String::new()
+                    };
+                    let corrupted = if ping_report.corrupted > 0 {
+                        lang.message("ping.corrupted-count", &[("count", &ping_report.corrupted)])
+                    } else {
+                        String::new()
+                    };
+                    let errors = if ping_report.errors > 0 {
+                        lang.message("ping.errors", &[("count", &ping_report.errors)])
+                    } else {
+                        String::new()
+                    };
+                    let args: [(&str, &dyn std::fmt::Display); 6] = [
+                        ("sent", &ping_report.sent),
+                        ("received", &ping_report.received),
+                        ("duplicates", &duplicates),
+                        ("corrupted", &corrupted),
+                        ("errors", &errors),
+                        ("loss", &loss),
+                    ];
+                    println!("{}", lang.message("ping.summary", &args));
+                }
+                print_statistics("rtt", ping_report);
+            }
+
+            if graph {
+                println!("rtt {}", global.charts().sparkline(&millis));
+            }
+            if histogram {
+                print_histogram(ping_report, global.charts());
+            }
+            if json {
+                let mut summary = ping_report.to_json();
+                summary["target"] = target.into();
+                let ms = |time: std::time::Duration| time.as_micros() as f64 / 1000.0;
+                summary["dns_ms"] = dns.map(ms).into();
+                summaries.push(summary);
+            }
+
+            for (seq, rtt) in millis.into_iter().enumerate() {
+                probes.push_row(vec![target.into(), (seq as i64).into(), rtt.into()]);
+            }
+        };
+
+    if several {
+        let hosts: Vec<&str> = targets.iter().map(String::as_str).collect();
+        let dns: Vec<_> = targets.iter().map(|_| std::cell::Cell::new(None)).collect();
+        let reports = ping::ping_many_with_progress(&hosts, &resolver, &config, |index, event| {
+            print_event(hosts[index], &dns[index], event)
+        });
+        let mut compared = Vec::new();
+        let mut failed = 0;
+        for ((target, dns), ping_report) in hosts.iter().zip(&dns).zip(reports) {
+            match ping_report {
+                Ok(ping_report) => {
+                    if !quiet {
+                        println!();
+                        println!("{}", lang.message("ping.statistics", &[("target", target)]));
+                    }
+                    summarize(target, dns.get(), &ping_report);
+                    compared.push((*target, ping_report));
+                }
+                Err(err) => {
+                    eprintln!("{target}: {err}");
+                    failed += 1;
+                }
+            }
+        }
+        if compared.is_empty() {
+            return Err("none of the hosts could be pinged".into());
+        }
+        if !quiet {
+            println!();
+            print!("{}", comparison_table(&mut compared));
+        }
+        // A host that could not be pinged counts as a lost probe, for the exit status.
+        sent += failed;
+    } else {
+        for (index, host) in hosts.iter().enumerate() {
+            // Probes are shown with the address pinged when there are several.
+            let target = match (&tcp_port, all) {
+                (_, false) => target.clone(),
+                (Some((_, port)), true) if host.contains(':') => format!("[{host}]:{port}"),
+                (Some((_, port)), true) => format!("{host}:{port}"),
+                (None, true) => host.clone(),
+            };
+            if all && !quiet {
+                if index > 0 {
+                    println!();
+                }
+                println!("{}", lang.message("ping.pinging", &[("target", &target)]));
+            }
+
+            let dns = std::cell::Cell::new(all_dns);
+            let on_event = |event| print_event(&target, &dns, event);
+            let ping_report = match &tcp_port {
+                Some((_, port)) => {
+                    ping::tcp_ping_with_progress(host, *port, &resolver, &config, on_event)?
+                }
+                None => ping::ping_with_progress(host, &resolver, &config, on_event)?,
+            };
+            summarize(&target, dns.get(), &ping_report);
+        }
+    }
+
+    if let Some(format) = format {
+        for line in format.render_all(&probes) {
+            println!("{line}");
+        }
+    }
+
+    if json {
+        // A single summary unless several hosts or addresses were pinged.
+        let summaries = if all || several {
+            serde_json::Value::Array(summaries)
+        } else {
+            summaries.remove(0)
+        };
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+    }
+
+    if let Some(path) = export {
+        report::export(&probes, &path)?;
+    }
+
+    Ok((sent, received))
+}
+
+/// Probes `host` (TCP `port` if given) once per interval of `config` until
+/// interrupted, printing a timestamped line each time `monitor` declares it
+/// UP or DOWN, and running the `on_change` shell command then. The lines
+/// are not timestamped when `deterministic`.
+#[allow(clippy::too_many_arguments)]
+fn monitor_host(
+    target: &str,
+    host: &str,
+    port: Option<u16>,
+    resolver: &resolver::Resolver,
+    config: &ping::PingConfig,
+    mut monitor: monitor::Monitor,
+    on_change: Option<&str>,
+    deterministic: bool,
+) -> Result<std::convert::Infallible, Box<dyn std::error::Error>> {
+    let probe = config.clone().count(1);
+    println!("Monitoring {target}, press Ctrl-C to stop");
+
+    loop {
+        let start = std::time::Instant::now();
+        let result = match port {
+            Some(port) => ping::tcp_ping_with_progress(host, port, resolver, &probe, |_| {}),
+            None => ping::ping_with_progress(host, resolver, &probe, |_| {}),
+        };
+        // Errors, e.g. a failed name lookup, count as failed probes.
+        let (answered, detail) = match &result {
+            Ok(report) => match report.avg {
+                Some(rtt) => (true, format!("time={} ms", rtt.as_millis())),
+                None if report.refused > 0 => (false, "connection refused".to_string()),
+                None => (false, "no reply".to_string()),
+            },
+            Err(err) => (false, err.to_string()),
+        };
+
+        if let Some(state) = monitor.record(answered) {
+            if deterministic {
+                println!("{target} is {state} ({detail})");
+            } else {
+                let now = fileinfo::format_timestamp(std::time::SystemTime::now());
+                println!("{now} {target} is {state} ({detail})");
+            }
+            std::io::stdout().flush()?;
+            if let Some(command) = on_change {
+                run_on_change(command, target, state);
+            }
+        }
+
+        std::thread::sleep(probe.probe_interval().saturating_sub(start.elapsed()));
+    }
+}
+
+/// Logs the ICMP echo requests sent to this host over `family` until
+/// interrupted, answering them when the kernel does not. The lines are not
+/// timestamped when `deterministic`.
+fn listen_for_echo(
+    family: ping::IpFamily,
+    deterministic: bool,
+) -> Result<std::convert::Infallible, Box<dyn std::error::Error>> {
+    // Answering the requests the kernel answers too would duplicate the replies.
+    let reply = !ping::kernel_answers_echo(family);
+    let version = match family {
+        ping::IpFamily::V4 => "IPv4",
+        ping::IpFamily::V6 => "IPv6",
+    };
+    let answered_by = if reply {
+        "answered here, as the kernel ignores them"
+    } else {
+        "answered by the kernel"
+    };
+    println!("Listening for echo requests over {version} ({answered_by}), press Ctrl-C to stop");
+    std::io::stdout().flush()?;
+
+    ping::listen(family, reply, |request| {
+        let ttl = request
+            .ttl
+            .map(|ttl| format!(" ttl={ttl}"))
+            .unwrap_or_default();
+        // Long payloads are cut, their start being enough to tell the sender.
+        let shown = &request.payload[..request.payload.len().min(32)];
+        let payload = match (shown.is_empty(), shown.len() < request.payload.len()) {
+            (true, _) => String::new(),
+            (false, false) => format!(" payload=\"{}\"", shown.escape_ascii()),
+            (false, true) => format!(" payload=\"{}\"...", shown.escape_ascii()),
+        };
+        let line = format!(
+            "Echo request from {}: id={} seq={}{ttl} bytes={}{payload}",
+            request.from,
+            request.id,
+            request.seq,
+            request.payload.len()
+        );
+        if deterministic {
+            println!("{line}");
+        } else {
+            let now = fileinfo::format_timestamp(std::time::SystemTime::now());
+            println!("{now} {line}");
+        }
+        // Lines show up at once when the output is piped, e.g. to tee.
+        let _ = std::io::stdout().flush();
+    })
+}
+
+/// Runs the `--on-change` shell `command`, with the target and its new
+/// state in the `CRABYKNIFE_TARGET` and `CRABYKNIFE_STATE` variables.
+/// Monitoring goes on whether it succeeds or not.
+fn run_on_change(command: &str, target: &str, state: monitor::HostState) {
+    let mut shell = if cfg!(windows) {
+        let mut shell = std::process::Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = std::process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let status = shell
+        .arg(command)
+        .env("CRABYKNIFE_TARGET", target)
+        .env("CRABYKNIFE_STATE", state.to_string())
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("--on-change command failed: {status}"),
+        Err(err) => eprintln!("failed to run the --on-change command: {err}"),
+    }
+}
+
+fn handle_httping(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife httping [-c <count>] [-i <seconds>] [-W <seconds>] [--get] [--resolve <host:ip>] <url>";
+    remaining_args.help(USAGE)?;
+
+    let mut resolver = resolver::Resolver::new();
+    let mut config = httping::HttpingConfig::new();
+    let mut url = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-c" => {
+                let count = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let count = count
+                    .parse()
+                    .map_err(|err| format!("invalid count ({count}): {err}"))?;
+                config = config.count(count);
+            }
+            "-i" => {
+                config = config.interval(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--get" => config = config.method(httping::Method::Get),
+            "--resolve" => resolver.add_override(
+                &remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?,
+            )?,
+            _ => url = Some(arg),
+        }
+    }
+
+    let url = url.ok_or_else(|| ExitError::usage(USAGE))?;
+    let ms = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    let report = httping::httping_with_progress(&url, &resolver, &config, |event| match event {
+        httping::HttpingEvent::Resolved { address, source } => {
+            println!("Resolved {url} to {address} via {source}")
+        }
+        httping::HttpingEvent::Response { seq, timings } => println!(
+            "{} from {url}: seq={seq} dns={:.1} ms connect={:.1} ms ttfb={:.1} ms total={:.1} ms",
+            timings.status,
+            ms(timings.dns),
+            ms(timings.connect),
+            ms(timings.ttfb),
+            ms(timings.total)
+        ),
+        httping::HttpingEvent::Failed { seq, error } => {
+            println!("Request failed (seq={seq}): {error}")
+        }
+    })?;
+
+    println!(
+        "{} requests sent, {} answered, {:.0}% failed",
+        report.sent,
+        report.received,
+        report.loss() * 100.0
+    );
+    print_statistics("total", &report);
+    Ok(())
+}
+
+/// Formats the reports of hosts pinged at once side by side, sorting them
+/// from the best to the worst.
+fn comparison_table(reports: &mut [(&str, ping::PingReport)]) -> String {
+    // The most reachable first, then the fastest.
+    reports.sort_by(|(_, a), (_, b)| {
+        a.loss().total_cmp(&b.loss()).then(
+            a.avg
+                .unwrap_or(std::time::Duration::MAX)
+                .cmp(&b.avg.unwrap_or(std::time::Duration::MAX)),
+        )
+    });
+    let ms = |rtt: Option<std::time::Duration>| {
+        rtt.map(|rtt| format!("{:.1}", rtt.as_secs_f64() * 1000.0))
+            .unwrap_or_default()
+    };
+    let mut table = format!(
+        "{:<30} {:<39} {:>6} {:>5} {:>7} {:>7} {:>7} {:>7}\n",
+        "Target", "Address", "Loss%", "Sent", "Avg", "Min", "Max", "StDev"
+    );
+    for (target, report) in reports.iter() {
+        let line = format!(
+            "{target:<30} {:<39} {:>5.1}% {:>5} {:>7} {:>7} {:>7} {:>7}",
+            report.address.to_string(),
+            report.loss() * 100.0,
+            report.sent,
+            ms(report.avg),
+            ms(report.min),
+            ms(report.max),
+            ms(report.stddev),
+        );
+        table += line.trim_end();
+        table.push('\n');
+    }
+    table
+}
+
+/// Prints the min/avg/max/stddev and p50/p90/p99 lines of the times in
+/// `report`, if any.
+fn print_statistics(label: &str, report: &ping::PingReport) {
+    let ms = |rtt: std::time::Duration| rtt.as_secs_f64() * 1000.0;
+    if let (Some(min), Some(avg), Some(max), Some(stddev)) =
+        (report.min, report.avg, report.max, report.stddev)
+    {
+        println!(
+            "{label} min/avg/max/stddev = {:.3}/{:.3}/{:.3}/{:.3} ms",
+            ms(min),
+            ms(avg),
+            ms(max),
+            ms(stddev)
+        );
+    }
+    if let (Some(p50), Some(p90), Some(p99)) = (
+        report.percentile(50.0),
+        report.percentile(90.0),
+        report.percentile(99.0),
+    ) {
+        println!(
+            "{label} p50/p90/p99 = {:.3}/{:.3}/{:.3} ms",
+            ms(p50),
+            ms(p90),
+            ms(p99)
+        );
+    }
+}
+
+/// Prints a bar chart of the number of replies per range of round-trip times.
+fn print_histogram(report: &ping::PingReport, charts: chart::Charts) {
+    let ms = |rtt: std::time::Duration| rtt.as_secs_f64() * 1000.0;
+    let entries: Vec<(String, u64)> = report
+        .histogram(10)
+        .into_iter()
+        .map(|(range, count)| {
+            (
+                format!("{:.3}-{:.3} ms", ms(range.start), ms(range.end)),
+                count as u64,
+            )
+        })
+        .collect();
+    if !entries.is_empty() {
+        println!("{}", charts.bar_chart(&entries, 40));
+    }
+}
+
+/// Parses a payload pattern of up to 16 bytes given in hex, e.g. `ff00`.
+fn parse_pattern(hex: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let invalid = || format!("invalid pattern ({hex}), expected 1 to 16 bytes in hex");
+    if hex.is_empty() || hex.len() > 32 || !hex.is_ascii() {
+        return Err(invalid().into());
+    }
+    // An odd number of digits is padded like a leading zero.
+    let padded = if hex.len() % 2 == 1 {
+        format!("0{hex}")
+    } else {
+        hex.to_string()
+    };
+    (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&padded[i..i + 2], 16).map_err(|_| invalid().into()))
+        .collect()
+}
+
+/// Parses a duration given in (possibly fractional) seconds, e.g. `0.2`.
+fn parse_seconds(seconds: &str) -> Result<std::time::Duration, Box<dyn std::error::Error>> {
+    let parsed: f64 = seconds
+        .parse()
+        .map_err(|err| format!("invalid number of seconds ({seconds}): {err}"))?;
+    std::time::Duration::try_from_secs_f64(parsed)
+        .map_err(|err| format!("invalid number of seconds ({seconds}): {err}").into())
+}
+
+fn handle_ping_sweep(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife ping-sweep [-c <count>] [-W <seconds>] [-j <workers>] <network/prefix>";
+    remaining_args.help(USAGE)?;
+
+    let mut config = ping::PingConfig::new().count(1);
+    let mut workers = 64;
+    let mut network = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-c" => {
+                let count = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let count = count
+                    .parse()
+                    .map_err(|err| format!("invalid count ({count}): {err}"))?;
+                config = config.count(count);
+            }
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-j" => {
+                let jobs = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                workers = jobs
+                    .parse()
+                    .ok()
+                    .filter(|jobs| *jobs > 0)
+                    .ok_or_else(|| format!("invalid number of workers ({jobs})"))?;
+            }
+            _ => network = Some(arg),
+        }
+    }
+
+    let network: sweep::Network = network.ok_or_else(|| ExitError::usage(USAGE))?.parse()?;
+    let reports = sweep::sweep_with_progress(&network, &config, workers, |_| {})?;
+
+    let mut alive = 0;
+    for report in reports.iter().filter(|report| report.received > 0) {
+        alive += 1;
+        let ms = |rtt: std::time::Duration| rtt.as_secs_f64() * 1000.0;
+        println!(
+            "{:<39}  {}/{} replies  rtt avg {:.3} ms",
+            report.address.to_string(),
+            report.received,
+            report.sent,
+            report.avg.map_or(0.0, ms)
+        );
+    }
+    println!("{alive} of {} hosts alive", reports.len());
+
+    Ok(())
+}
+
+fn handle_mtr(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife mtr [-4 | -6] [-c <rounds>] [-i <seconds>] [-W <seconds>] [-m <max hops>] [--report] [--resolve <host:ip>] <host>";
+    remaining_args.help(USAGE)?;
+
+    let mut resolver = resolver::Resolver::new();
+    let mut config = mtr::MtrConfig::new();
+    let mut report = false;
+    let mut target = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-4" => config = config.family(Some(ping::IpFamily::V4)),
+            "-6" => config = config.family(Some(ping::IpFamily::V6)),
+            "-c" => {
+                let rounds = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let rounds: usize = rounds
+                    .parse()
+                    .map_err(|err| format!("invalid number of rounds ({rounds}): {err}"))?;
+                // 0 probes until interrupted.
+                config = config.rounds((rounds > 0).then_some(rounds));
+            }
+            "-i" => {
+                config = config.interval(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-m" => {
+                let hops = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let hops = hops.parse().ok().filter(|hops| *hops > 0).ok_or_else(|| {
+                    format!("invalid maximum of hops ({hops}), expected 1 to 255")
+                })?;
+                config = config.max_hops(hops);
+            }
+            "--report" => report = true,
+            "--resolve" => resolver.add_override(
+                &remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?,
+            )?,
+            _ => target = Some(arg),
+        }
+    }
+
+    let target = target.ok_or_else(|| ExitError::usage(USAGE))?;
+    // The table is redrawn in place after each round on terminals,
+    // otherwise only printed once all rounds are done.
+    let live = !report && !global.plain && std::io::stdout().is_terminal();
+    let mut drawn_lines = 0;
+    let trace = mtr::mtr_with_progress(&target, &resolver, &config, |trace| {
+        if !live {
+            return;
+        }
+        let table = hops_table(&target, trace);
+        let mut stdout = std::io::stdout().lock();
+        if drawn_lines > 0 {
+            // Moves back to the first line of the table and clears it.
+            let _ = write!(stdout, "\x1b[{drawn_lines}A\x1b[J");
+        }
+        let _ = write!(stdout, "{table}");
+        let _ = stdout.flush();
+        drawn_lines = table.lines().count();
+    })?;
+
+    if !live {
+        print!("{}", hops_table(&target, &trace));
+    }
+    Ok(())
+}
+
+fn handle_pmtu(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife pmtu [-4 | -6] [-W <seconds>] [-a <attempts>] [--max <mtu>] [--resolve <host:ip>] <host>";
+    remaining_args.help(USAGE)?;
+
+    let mut resolver = resolver::Resolver::new();
+    let mut config = pmtu::PmtuConfig::new();
+    let mut target = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-4" => config = config.family(Some(ping::IpFamily::V4)),
+            "-6" => config = config.family(Some(ping::IpFamily::V6)),
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-a" => {
+                let attempts = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let attempts = attempts
+                    .parse()
+                    .map_err(|err| format!("invalid number of attempts ({attempts}): {err}"))?;
+                config = config.attempts(attempts);
+            }
+            "--max" => {
+                let mtu = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let mtu = mtu
+                    .parse()
+                    .map_err(|err| format!("invalid MTU ({mtu}): {err}"))?;
+                config = config.max_mtu(mtu);
+            }
+            "--resolve" => resolver.add_override(
+                &remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?,
+            )?,
+            _ => target = Some(arg),
+        }
+    }
+
+    let target = target.ok_or_else(|| ExitError::usage(USAGE))?;
+    let found = pmtu::pmtu_with_progress(&target, &resolver, &config, |size, probe| {
+        let outcome = match probe {
+            pmtu::Probe::Fits => "fits".to_string(),
+            pmtu::Probe::TooBigForInterface => "too big for the local interface".to_string(),
+            pmtu::Probe::TooBigAt { address, mtu: 0 } => format!("too big at {address}"),
+            pmtu::Probe::TooBigAt { address, mtu } => {
+                format!("too big at {address} (next-hop MTU {mtu})")
+            }
+            pmtu::Probe::Lost => "no answer".to_string(),
+        };
+        println!("{size:>5} bytes: {outcome}");
+    })?;
+
+    println!(
+        "Path MTU to {target} ({}): {} bytes",
+        found.address, found.mtu
+    );
+    match found.bottleneck {
+        None => println!("No limit found up to the largest size probed"),
+        Some(pmtu::Bottleneck::Interface) => println!("Limited by the MTU of the local interface"),
+        Some(pmtu::Bottleneck::Router { address, mtu: 0 }) => {
+            println!("Fragmentation needed at {address}")
+        }
+        Some(pmtu::Bottleneck::Router { address, mtu }) => {
+            println!("Fragmentation needed at {address}, whose next hop takes {mtu} bytes")
+        }
+        Some(pmtu::Bottleneck::Silent) => println!(
+            "Larger packets get no answer, a router may drop them without telling (ICMP filtered)"
+        ),
+    }
+    Ok(())
+}
+
+/// Exits like `ping`: with 0 when every request was answered, 1 when some
+/// were lost, 2 when none were, and 3 when the host could not be probed.
+#[cfg(all(target_os = "linux", feature = "arping"))]
+fn handle_arping(remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    let report = arping_host(remaining_args).map_err(probe_failed)?;
+    reachability(report.sent, report.received)
+}
+
+/// Runs `arping` and returns the statistics of the requests.
+#[cfg(all(target_os = "linux", feature = "arping"))]
+fn arping_host(
+    mut remaining_args: args::Args,
+) -> Result<ping::PingReport, Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife arping [-c <count>] [-i <seconds>] [-W <seconds>] [-I <interface>] <ip>";
+    remaining_args.help(USAGE)?;
+
+    let mut config = arping::ArpingConfig::new();
+    let mut target = None;
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-c" => {
+                let count = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let count = count
+                    .parse()
+                    .map_err(|err| format!("invalid count ({count}): {err}"))?;
+                config = config.count(count);
+            }
+            "-i" => {
+                config = config.interval(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-W" => {
+                config = config.timeout(parse_seconds(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "-I" => {
+                config = config.interface(Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                ))
+            }
+            _ => target = Some(arg),
+        }
+    }
+    let target = target.ok_or_else(|| ExitError::usage(USAGE))?;
+    let target: std::net::Ipv4Addr = target
+        .parse()
+        .map_err(|_| format!("invalid IPv4 address ({target})"))?;
+
+    let report = arping::arping_with_progress(target, &config, |event| match event {
+        arping::ArpingEvent::Interface(interface) => println!(
+            "ARP pinging {target} on {} from {} [{}]",
+            interface.name, interface.address, interface.mac
+        ),
+        arping::ArpingEvent::Reply { seq, mac, rtt } => println!(
+            "Reply from {target} [{mac}]: seq={seq} time={:.3} ms",
+            rtt.as_secs_f64() * 1000.0
+        ),
+        arping::ArpingEvent::Duplicate { seq, mac } => {
+            println!("Another reply from {target} [{mac}]: seq={seq}, address conflict?")
+        }
+        arping::ArpingEvent::Timeout { seq } => println!("Request timed out (seq={seq})"),
+    })?;
+    println!(
+        "{} requests sent, {} replies received, {:.0}% loss",
+        report.sent,
+        report.received,
+        report.loss() * 100.0
+    );
+    print_statistics("rtt", &report);
+    Ok(report)
+}
+
+#[cfg(not(all(target_os = "linux", feature = "arping")))]
+fn handle_arping(_remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    Err("arping is only available on Linux, in builds with the arping feature".into())
+}
+
+/// Renders the hops of `trace` as a table, one line per hop.
+fn hops_table(target: &str, trace: &mtr::Trace) -> String {
+    let ms = |rtt: std::time::Duration| format!("{:.1}", rtt.as_secs_f64() * 1000.0);
+    let mut table = format!(
+        "Route to {target} ({}), {} rounds\n{:>3}  {:<39} {:>6} {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}\n",
+        trace.address, trace.rounds, "Hop", "Host", "Loss%", "Sent", "Last", "Avg", "Best", "Worst", "StDev"
+    );
+    for hop in &trace.hops {
+        let report = hop.report();
+        let host = hop
+            .address
+            .map_or_else(|| "???".to_string(), |address| address.to_string());
+        let last = hop
+            .rtts
+            .last()
+            .copied()
+            .flatten()
+            .map(ms)
+            .unwrap_or_default();
+        let stat = |rtt: Option<std::time::Duration>| rtt.map(ms).unwrap_or_default();
+        let line = format!(
+            "{:>3}  {host:<39} {:>5.1}% {:>5} {last:>7} {:>7} {:>7} {:>7} {:>7}",
+            hop.ttl,
+            report.loss() * 100.0,
+            report.sent,
+            stat(report.avg),
+            stat(report.min),
+            stat(report.max),
+            stat(report.stddev),
+        );
+        table += line.trim_end();
+        table.push('\n');
+    }
+    table
+}
+
+fn handle_webhook(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife webhook verify --provider <stripe|github|slack> --secret <secret> --payload <path> --signature <signature> [--timestamp <timestamp>]";
+    remaining_args.help(USAGE)?;
+
+    if remaining_args.next().as_deref() != Some("verify") {
+        return Err(ExitError::usage(USAGE).into());
+    }
+
+    let mut provider = None;
+    let mut secret = None;
+    let mut payload = None;
+    let mut signature = None;
+    let mut timestamp = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--provider" => {
+                provider = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?
+                        .parse()?,
+                )
+            }
+            "--secret" => {
+                secret = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--payload" => {
+                payload = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--signature" => {
+                signature = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--timestamp" => {
+                timestamp = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+
+    let provider: webhook::Provider = provider.ok_or_else(|| ExitError::usage(USAGE))?;
+    let secret = secret.ok_or_else(|| ExitError::usage(USAGE))?;
+    let payload_path = payload.ok_or_else(|| ExitError::usage(USAGE))?;
+    let signature = signature.ok_or_else(|| ExitError::usage(USAGE))?;
+
+    let payload = std::fs::read(&payload_path)
+        .map_err(|err| format!("failed to read {payload_path}: {err}"))?;
+
+    let verification = webhook::verify(
+        provider,
+        secret.as_bytes(),
+        &payload,
+        &signature,
+        timestamp.as_deref(),
+    )?;
+
+    println!("computed: {}", verification.computed);
+    if !verification.valid {
+        return Err("invalid signature".into());
+    }
+
+    println!("valid");
+    Ok(())
+}
+
+fn handle_file(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife file stat [--json] <path> | file touch [--date <YYYY-MM-DD[THH:MM[:SS]]>] <path>... | file chmod-recursive [--dry-run] <mode> <path>";
+    remaining_args.help(USAGE)?;
+
+    let action = remaining_args
+        .next()
+        .ok_or_else(|| ExitError::usage(USAGE))?;
+    let mut json = false;
+    let mut date = None;
+    let mut dry_run = false;
+    let mut operands = Vec::new();
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--json" if action == "stat" => json = true,
+            "--date" if action == "touch" => {
+                date = Some(fileinfo::parse_date(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--dry-run" if action == "chmod-recursive" => dry_run = true,
+            _ => operands.push(arg),
+        }
+    }
+
+    match (action.as_str(), operands.as_slice()) {
+        ("stat", [path]) => {
+            let info = fileinfo::stat(std::path::Path::new(path))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info.to_json())?);
+                return Ok(());
+            }
+
+            let humanizer = global.humanizer();
+            let time = |time: Option<std::time::SystemTime>| {
+                time.map_or_else(|| "-".to_string(), |time| humanizer.timestamp(time))
+            };
+            println!("Path:     {}", info.path.display());
+            println!("Type:     {}", info.file_type);
+            if let Some(target) = &info.symlink_target {
+                println!("Target:   {}", target.display());
+            }
+            println!("Size:     {}", humanizer.bytes(info.size));
+            match info.mode {
+                Some(mode) => println!("Mode:     {mode:04o}"),
+                None => println!("Readonly: {}", info.readonly),
+            }
+            if let (Some(uid), Some(gid)) = (info.uid, info.gid) {
+                println!("Owner:    uid {uid}, gid {gid}");
+            }
+            println!("Modified: {}", time(info.modified));
+            println!("Accessed: {}", time(info.accessed));
+            println!("Created:  {}", time(info.created));
+            if let Some(xattrs) = info.xattrs.filter(|xattrs| !xattrs.is_empty()) {
+                println!("Xattrs:   {}", xattrs.join(", "));
+            }
+        }
+        ("touch", paths) if !paths.is_empty() => {
+            for path in paths {
+                fileinfo::touch(std::path::Path::new(path), date)?;
+            }
+        }
+        ("chmod-recursive", [mode, path]) => {
+            let mode = fileinfo::parse_mode(mode)?;
+            let changes = fileinfo::chmod_recursive(std::path::Path::new(path), mode, dry_run)?;
+            let verb = if dry_run { "would change" } else { "changed" };
+            for change in &changes {
+                println!(
+                    "{verb} {:04o} -> {:04o} {}",
+                    change.from,
+                    change.to,
+                    change.path.display()
+                );
+            }
+        }
+        _ => return Err(ExitError::usage(USAGE).into()),
+    }
+
+    Ok(())
+}
+
+fn handle_shape(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife shape -I | --interface <name> [--delay <time>] [--jitter <time>] [--loss <percent>] [--rate <bandwidth>] [--dry-run] | shape reset -I | --interface <name> [--dry-run] | shape show -I | --interface <name>";
+    remaining_args.help(USAGE)?;
+
+    let mut action = None;
+    let mut interface = None;
+    let mut shaping = shape::Shaping::new();
+    let mut dry_run = false;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "reset" | "show" if action.is_none() && interface.is_none() => action = Some(arg),
+            "-I" | "--interface" => {
+                interface = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--delay" => {
+                shaping = shaping.delay(shape::parse_time(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--jitter" => {
+                shaping = shaping.jitter(shape::parse_time(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--loss" => {
+                shaping = shaping.loss(shape::parse_percent(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--rate" => {
+                shaping = shaping.rate(shape::parse_rate(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--dry-run" => dry_run = true,
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+    let interface = interface.ok_or_else(|| ExitError::usage(USAGE))?;
+
+    match action.as_deref() {
+        None if dry_run => {
+            let platform =
+                shape::Platform::current().ok_or("shaping is only supported on Linux and macOS")?;
+            for step in shape::apply_steps(platform, &interface, &shaping)? {
+                println!("{step}");
+            }
+        }
+        None => {
+            shape::apply(&interface, &shaping)?;
+            println!("Shaping the traffic {interface} sends, undo with `crabyknife shape reset --interface {interface}`");
+        }
+        Some("reset") if dry_run => {
+            let platform =
+                shape::Platform::current().ok_or("shaping is only supported on Linux and macOS")?;
+            for step in shape::reset_steps(platform, &interface, shape::pf_token().as_deref()) {
+                println!("{step}");
+            }
+        }
+        Some("reset") => match shape::reset(&interface)? {
+            true => println!("Removed the shaping of {interface}"),
+            false => println!("{interface} is not shaped"),
+        },
+        Some(_) if dry_run => return Err(ExitError::usage(USAGE).into()),
+        Some(_) => match shape::show(&interface)? {
+            Some(shaping) => println!("{shaping}"),
+            None => println!("{interface} is not shaped"),
+        },
+    }
+    Ok(())
+}
+
+fn handle_hosts(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife hosts add <name> <address> | hosts remove <name> [<address>] | hosts list [--managed] [--file <path>] [--no-backup]";
+    remaining_args.help(USAGE)?;
+
+    let action = remaining_args
+        .next()
+        .ok_or_else(|| ExitError::usage(USAGE))?;
+    let mut path = hosts::HOSTS_FILE.to_string();
+    let mut managed_only = false;
+    let mut backup = true;
+    let mut operands = Vec::new();
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--file" => {
+                path = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?
+            }
+            "--managed" if action == "list" => managed_only = true,
+            "--no-backup" if action != "list" => backup = false,
+            _ if arg.starts_with('-') => return Err(ExitError::usage(USAGE).into()),
+            _ => operands.push(arg),
+        }
+    }
+
+    let parse_address = |address: &str| {
+        address
+            .parse::<std::net::IpAddr>()
+            .map_err(|_| format!("invalid address ({address})"))
+    };
+    let read =
+        || std::fs::read_to_string(&path).map_err(|err| format!("cannot read {path}: {err}"));
+    let updated = match (action.as_str(), operands.as_slice()) {
+        ("list", []) => {
+            let entries: Vec<hosts::Entry> = hosts::entries(&read()?)?
+                .into_iter()
+                .filter(|entry| entry.managed || !managed_only)
+                .collect();
+            let width = entries
+                .iter()
+                .map(|entry| entry.address.to_string().len())
+                .max()
+                .unwrap_or_default();
+            for entry in entries {
+                println!("{:<width$} {}", entry.address.to_string(), entry.name);
+            }
+            return Ok(());
+        }
+        ("add", [name, address]) => {
+            let address = parse_address(address)?;
+            let content = read()?;
+            // Resolvers use the first entry of a name, so those before the
+            // block win over it.
+            for entry in hosts::entries(&content)? {
+                if !entry.managed && entry.name.eq_ignore_ascii_case(name) {
+                    eprintln!(
+                        "warning: {path} also maps {name} to {} outside the managed block",
+                        entry.address
+                    );
+                }
+            }
+            match hosts::add(&content, name, address)? {
+                Some(updated) => (updated, format!("Added {name} -> {address} to {path}")),
+                None => {
+                    println!("{name} already points to {address}");
+                    return Ok(());
+                }
+            }
+        }
+        ("remove", [name, address @ ..]) if address.len() <= 1 => {
+            let address = address
+                .first()
+                .map(|address| parse_address(address))
+                .transpose()?;
+            match hosts::remove(&read()?, name, address)? {
+                Some(updated) => (updated, format!("Removed {name} from {path}")),
+                None => {
+                    println!("{name} is not in the managed block of {path}");
+                    return Ok(());
+                }
+            }
+        }
+        _ => return Err(ExitError::usage(USAGE).into()),
+    };
+
+    let (content, done) = updated;
+    match hosts::write(std::path::Path::new(&path), &content, backup) {
+        Ok(Some(backup)) => println!("{done} (backup in {})", backup.display()),
+        Ok(None) => println!("{done}"),
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            return run_elevated(global, &format!("cannot write {path}: {err}"))
+        }
+        Err(err) => return Err(format!("cannot write {path}: {err}").into()),
+    }
+    Ok(())
+}
+
+/// Runs the command line again with sudo, once confirmed, after `denied`
+/// was not permitted. Exits with the status of the elevated run.
+#[cfg(unix)]
+fn run_elevated(global: &GlobalOptions, denied: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut prompter = global.prompter();
+    if !(prompter.interactive() || prompter.assumes_yes())
+        || !prompter.confirm(&format!("{denied}, run again with sudo?"), true)?
+    {
+        return Err(format!("{denied}, run again as root or with sudo").into());
+    }
+    let status = std::process::Command::new("sudo")
+        .arg(std::env::current_exe()?)
+        .args(std::env::args().skip(1))
+        .status()
+        .map_err(|err| format!("cannot run sudo: {err}"))?;
+    Err(ExitError::new(status.code().unwrap_or(1), "").into())
+}
+
+#[cfg(not(unix))]
+fn run_elevated(_global: &GlobalOptions, denied: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!("{denied}, run again from an elevated prompt").into())
+}
+
+fn handle_xattr(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife xattr list <path> | xattr get <name> <path> | xattr set <name> <value> <path> | xattr strip [-r] [--name <name> | --remove-quarantine] <path>...";
+    remaining_args.help(USAGE)?;
+
+    let action = remaining_args
+        .next()
+        .ok_or_else(|| ExitError::usage(USAGE))?;
+    let mut recursive = false;
+    let mut name = None;
+    let mut operands = Vec::new();
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "-r" if action == "strip" => recursive = true,
+            "--name" if action == "strip" => {
+                name = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--remove-quarantine" if action == "strip" => {
+                name = Some(fileinfo::QUARANTINE_XATTR.to_string())
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    match (action.as_str(), operands.as_slice()) {
+        ("list", [path]) => {
+            for name in fileinfo::list_xattrs(std::path::Path::new(path))? {
+                println!("{name}");
+            }
+        }
+        ("get", [name, path]) => {
+            let value = fileinfo::get_xattr(std::path::Path::new(path), name)?
+                .ok_or_else(|| format!("{path} has no extended attribute {name}"))?;
+            // Values are arbitrary bytes, so they are written unchanged.
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&value)?;
+            stdout.flush()?;
+        }
+        ("set", [name, value, path]) => {
+            fileinfo::set_xattr(std::path::Path::new(path), name, value.as_bytes())?
+        }
+        ("strip", paths) if !paths.is_empty() => {
+            for path in paths {
+                let removed =
+                    fileinfo::strip_xattrs(std::path::Path::new(path), name.as_deref(), recursive)?;
+                for (path, name) in removed {
+                    println!("removed {name} from {}", path.display());
+                }
+            }
+        }
+        _ => return Err(ExitError::usage(USAGE).into()),
+    }
+
+    Ok(())
+}
+
+fn handle_links(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife links check <dir> | links rewrite --from <prefix> --to <prefix> [--dry-run] <dir>";
+    remaining_args.help(USAGE)?;
+
+    let action = remaining_args
+        .next()
+        .ok_or_else(|| ExitError::usage(USAGE))?;
+    let mut from = None;
+    let mut to = None;
+    let mut dry_run = false;
+    let mut dir = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--from" if action == "rewrite" => {
+                from = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--to" if action == "rewrite" => {
+                to = Some(
+                    remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )
+            }
+            "--dry-run" if action == "rewrite" => dry_run = true,
+            _ if dir.is_none() => dir = Some(arg),
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+    let dir = dir.ok_or_else(|| ExitError::usage(USAGE))?;
+
+    match action.as_str() {
+        "check" => {
+            let bad_links = links::check(std::path::Path::new(&dir))?;
+            for link in &bad_links {
+                println!(
+                    "{} {} -> {}",
+                    link.problem,
+                    link.path.display(),
+                    link.target.display()
+                );
+            }
+            if !bad_links.is_empty() {
+                return Err(format!("{} bad link(s) found", bad_links.len()).into());
+            }
+        }
+        "rewrite" => {
+            let from = from.ok_or_else(|| ExitError::usage(USAGE))?;
+            let to = to.ok_or_else(|| ExitError::usage(USAGE))?;
+            let retargets = links::rewrite(
+                std::path::Path::new(&dir),
+                std::path::Path::new(&from),
+                std::path::Path::new(&to),
+                dry_run,
+            )?;
+            let verb = if dry_run {
+                "would retarget"
+            } else {
+                "retargeted"
+            };
+            for retarget in retargets {
+                println!(
+                    "{verb} {}: {} -> {}",
+                    retarget.path.display(),
+                    retarget.from.display(),
+                    retarget.to.display()
+                );
+            }
+        }
+        _ => return Err(ExitError::usage(USAGE).into()),
+    }
+
+    Ok(())
+}
+
+fn handle_cp_sparse(
+    remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife cp-sparse [--reflink] [--no-verify] <source> <destination>";
+    remaining_args.help(USAGE)?;
+
+    let mut options = fastcopy::CopyOptions::new();
+    let mut paths = Vec::new();
+
+    for arg in remaining_args {
+        match arg.as_str() {
+            "--reflink" => options = options.reflink(true),
+            "--no-verify" => options = options.verify(false),
+            _ => paths.push(arg),
+        }
+    }
+    let [source, destination] = paths.as_slice() else {
+        return Err(ExitError::usage(USAGE).into());
+    };
+
+    let report = fastcopy::copy(
+        std::path::Path::new(source),
+        std::path::Path::new(destination),
+        &options,
+    )?;
+    let humanizer = global.humanizer();
+    match report.method {
+        fastcopy::Method::Reflink => {
+            println!("{}: {}", report.method, humanizer.bytes(report.size))
+        }
+        fastcopy::Method::Sparse => println!(
+            "{}: {}, {} of data",
+            report.method,
+            humanizer.bytes(report.size),
+            humanizer.bytes(report.copied)
+        ),
+    }
+    if let Some(sha256) = report.sha256 {
+        println!("verified sha256 {sha256}");
+    }
+
+    Ok(())
+}
+
+fn handle_shred(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife shred [--passes <n>] [-r] [--rename-then-unlink] <path>...";
+    remaining_args.help(USAGE)?;
+
+    let mut options = shred::ShredOptions::new();
+    let mut paths = Vec::new();
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--passes" => {
+                let passes = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                let passes = passes
+                    .parse()
+                    .map_err(|err| format!("invalid number of passes ({passes}): {err}"))?;
+                options = options.passes(passes);
+            }
+            "-r" => options = options.recursive(true),
+            "--rename-then-unlink" => options = options.unlink(true),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(ExitError::usage(USAGE).into());
+    }
+
+    eprintln!(
+        "warning: SSDs, copy-on-write and journaling file systems, snapshots and backups can keep copies of the data that overwriting cannot reach"
+    );
+    for path in paths {
+        for shredded in shred::shred(std::path::Path::new(&path), &options)? {
+            println!("shredded {}", shredded.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_disks(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife disks [--all] | disks smart <device>";
+    remaining_args.help(USAGE)?;
+
+    let mut all = false;
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "smart" => {
+                let device = remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?;
+                if remaining_args.next().is_some() {
+                    return Err(ExitError::usage(USAGE).into());
+                }
+                let attributes = disks::smart(std::path::Path::new(&device))?;
+                println!(
+                    "{:>3}  {:<24} {:>5} {:>5}  RAW",
+                    "ID", "ATTRIBUTE", "VALUE", "WORST"
+                );
+                for attribute in attributes {
+                    println!(
+                        "{:>3}  {:<24} {:>5} {:>5}  {}",
+                        attribute.id,
+                        attribute.name,
+                        attribute.value,
+                        attribute.worst,
+                        attribute.raw
+                    );
+                }
+                return Ok(());
+            }
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+
+    let humanizer = global.humanizer();
+    let mounts = disks::mounts(all)?;
+    let rows: Vec<[String; 5]> = mounts
+        .iter()
+        .map(|mount| {
+            [
+                mount.mount_point.display().to_string(),
+                mount.fs_type.clone(),
+                humanizer.bytes(mount.total),
+                humanizer.bytes(mount.used),
+                humanizer.bytes(mount.available),
+            ]
+        })
+        .collect();
+    let header = ["MOUNT", "TYPE", "SIZE", "USED", "AVAIL"].map(str::to_string);
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |row: &[String; 5]| {
+        format!(
+            "{:<w0$}  {:<w1$}  {:>w2$}  {:>w3$}  {:>w4$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        )
+    };
+    println!("{}  USE", line(&header));
+    let charts = global.charts();
+    for (mount, row) in mounts.iter().zip(&rows) {
+        let usage = humanizer.percent(mount.usage());
+        match charts.meter(mount.usage(), 20) {
+            meter if meter.is_empty() => println!("{}  {usage:>4}", line(row)),
+            meter => println!("{}  {meter} {usage:>4}", line(row)),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_clean_tmp(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife clean-tmp [--older-than <age>] [--pattern <glob>]... [--root <dir>]... [--delete]";
+    remaining_args.help(USAGE)?;
+
+    let mut options = janitor::CleanOptions::new();
+    let mut patterns = Vec::new();
+    let mut roots = Vec::new();
+    let mut delete = false;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--older-than" => {
+                options = options.older_than(humanize::parse_duration(
+                    &remaining_args
+                        .next()
+                        .ok_or_else(|| ExitError::usage(USAGE))?,
+                )?)
+            }
+            "--pattern" => patterns.push(
+                remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?,
+            ),
+            "--root" => roots.push(
+                remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?
+                    .into(),
+            ),
+            "--delete" => delete = true,
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+    if !roots.is_empty() {
+        options = options.roots(roots);
+    }
+    let report = janitor::clean(&options.patterns(patterns).delete(delete))?;
+
+    let humanizer = global.humanizer();
+    let now = std::time::SystemTime::now();
+    for entry in &report.entries {
+        // Ages change with every run, so deterministic runs show the
+        // modification time instead.
+        let age = if global.deterministic {
+            fileinfo::format_timestamp(entry.modified)
+        } else {
+            humanizer.duration(now.duration_since(entry.modified).unwrap_or_default())
+        };
+        println!(
+            "{:>10}  {:>10}  {}",
+            humanizer.bytes(entry.size),
+            age,
+            entry.path.display()
+        );
+    }
+    for (path, err) in &report.failed {
+        eprintln!("failed to remove {}: {err}", path.display());
+    }
+
+    let freed = humanizer.bytes(report.freed());
+    let count = report.entries.len() - report.failed.len();
+    if delete {
+        println!("Freed {freed} by removing {count} stale entries");
+    } else {
+        println!("Would free {freed} by removing {count} stale entries (dry run, remove them with --delete)");
+    }
+
+    if !report.failed.is_empty() {
+        return Err(format!("{} stale entries could not be removed", report.failed.len()).into());
+    }
+    Ok(())
+}
+
+fn handle_dev_clean(
+    mut remaining_args: args::Args,
+    global: &GlobalOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: crabyknife dev-clean [--category <cargo|node|venv|pycache|gradle|maven>]... [--yes] [<root>]";
+    remaining_args.help(USAGE)?;
+
+    let mut categories: Vec<janitor::Category> = Vec::new();
+    let mut yes = false;
+    let mut root = None;
+
+    while let Some(arg) = remaining_args.next() {
+        match arg.as_str() {
+            "--category" => categories.push(
+                remaining_args
+                    .next()
+                    .ok_or_else(|| ExitError::usage(USAGE))?
+                    .parse()?,
+            ),
+            "--yes" => yes = true,
+            _ if root.is_none() && !arg.starts_with('-') => root = Some(arg),
+            _ => return Err(ExitError::usage(USAGE).into()),
+        }
+    }
+    if categories.is_empty() {
+        categories = janitor::Category::ALL.to_vec();
+    }
+
+    let root = root.unwrap_or_else(|| ".".to_string());
+    let caches: Vec<janitor::Cache> = janitor::find_caches(std::path::Path::new(&root))?
+        .into_iter()
+        .filter(|cache| categories.contains(&cache.category))
+        .collect();
+
+    let humanizer = global.humanizer();
+    for cache in &caches {
+        println!(
+            "{:>10}  {:<8} {}",
+            humanizer.bytes(cache.size),
+            cache.category.to_string(),
+            cache.path.display()
+        );
+    }
+    if caches.is_empty() {
+        println!("No build caches found under {root}");
+        return Ok(());
+    }
+
+    // Without --yes, each category is confirmed on the terminal.
+    let mut prompter = global.prompter();
+    if yes {
+        prompter = prompter.assume_yes(true);
+    }
+    let deleting = prompter.interactive() || prompter.assumes_yes();
+    let mut freed = 0;
+    let mut failed = 0;
+    for category in janitor::Category::ALL {
+        let selected: Vec<&janitor::Cache> = caches
+            .iter()
+            .filter(|cache| cache.category == category)
+            .collect();
+        if selected.is_empty() {
+            continue;
+        }
+        let size = selected.iter().map(|cache| cache.size).sum();
+        let summary = format!(
+            "{} {category} cache(s), {}",
+            selected.len(),
+            humanizer.bytes(size)
+        );
+
+        if !deleting {
+            println!("{summary}");
+            continue;
+        }
+        if !prompter.confirm(&format!("Delete {summary}?"), false)? {
+            continue;
+        }
+
+        for cache in selected {
+            match janitor::remove_cache(cache) {
+                Ok(()) => freed += cache.size,
+                Err(err) => {
+                    eprintln!("{err}");
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if deleting {
+        println!("Freed {}", humanizer.bytes(freed));
+    } else {
+        println!(
+            "Nothing deleted: confirm on a terminal, or pass --yes to delete the listed caches"
+        );
+    }
+    if failed > 0 {
+        return Err(format!("{failed} cache(s) could not be removed").into());
+    }
+    Ok(())
+}
+
+fn handle_lsp_lite(mut remaining_args: args::Args) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: crabyknife lsp-lite";
+    remaining_args.help(USAGE)?;
+
+    if remaining_args.next().is_some() {
+        return Err(ExitError::usage(USAGE).into());
+    }
+    server::serve(std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_prettify_in_place() {
+        let dir = temp_dir("commandline-in-place");
+        let path = dir.join("a.xml");
+        std::fs::write(&path, "<a><b/></a>").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = std::fs::Permissions::from_mode(0o640);
+            std::fs::set_permissions(&path, permissions).unwrap();
+        }
+
+        let patterns = [dir.join("*.xml").to_string_lossy().into_owned()];
+        let options = prettify_xml::PrettifyOptions::new();
+        prettify_in_place(&patterns, &options, InPlace::Write, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "<a>\n  <b />\n</a>\n"
+        );
+        // The rewritten file keeps its permissions, and the temporary file
+        // it was written to is gone.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a.xml"]);
+
+        // A file that does not parse is left as it was.
+        let broken = dir.join("b.xml");
+        std::fs::write(&broken, "<a><b></a>").unwrap();
+        assert!(prettify_in_place(&patterns, &options, InPlace::Write, false).is_err());
+        assert_eq!(std::fs::read_to_string(&broken).unwrap(), "<a><b></a>");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,666 @@
+//! Conversions between XML and other data formats.
+//!
+//! A document is first mapped into a format independent [`Value`] tree:
+//!
+//! - an element becomes an object keyed by its child element names,
+//! - attributes become keys prefixed with the attribute prefix (`@` by default),
+//! - text and CDATA become a string under the text key (`#text` by default),
+//!   or the element value itself when the element has nothing else,
+//! - repeated child elements are grouped into an array,
+//! - an empty element becomes `null`.
+//!
+//! The tree is then rendered by one of the writers, e.g. [`xml_to_json`].
+//! The mapping is reversed by [`value_to_xml`], so documents converted to
+//! YAML can be turned back into XML with [`yaml_to_xml`].
+
+use quick_xml::{
+    escape::{escape, partial_escape},
+    events::Event,
+    Reader,
+};
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::report::json_string;
+
+/// A format independent representation of converted data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    String(String),
+    Array(Vec<Value>),
+    /// Keys keep document order.
+    Object(Vec<(String, Value)>),
+}
+
+/// Options controlling how XML is mapped into a [`Value`] tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertOptions {
+    attribute_prefix: String,
+    text_key: String,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            attribute_prefix: "@".to_string(),
+            text_key: "#text".to_string(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Creates the default options: `@` attribute prefix and `#text` text key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix added to attribute names to tell them apart from child elements.
+    pub fn attribute_prefix(mut self, prefix: &str) -> Self {
+        self.attribute_prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets the key holding the text of elements that also have attributes or children.
+    pub fn text_key(mut self, key: &str) -> Self {
+        self.text_key = key.to_string();
+        self
+    }
+}
+
+/// An element whose close tag has not been read yet.
+struct OpenElement {
+    name: String,
+    /// Attributes and child elements seen so far.
+    entries: Vec<(String, Value)>,
+    /// How many of the first `entries` are attributes.
+    attributes: usize,
+    text: String,
+}
+
+/// Maps an XML document into a [`Value`] tree.
+///
+/// The result is an object with a single key, the name of the root element.
+pub fn xml_to_value(
+    xml: &str,
+    options: &ConvertOptions,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    // Elements being built, innermost last.
+    let mut stack: Vec<OpenElement> = Vec::new();
+    let mut root = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                let entries = attribute_entries(&e, options)?;
+                stack.push(OpenElement {
+                    name,
+                    attributes: entries.len(),
+                    entries,
+                    text: String::new(),
+                });
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                let entries = attribute_entries(&e, options)?;
+                let value = element_value(&name, entries, String::new(), options)?;
+                add_child(&mut stack, &mut root, name, value)?;
+            }
+            Event::End(_) => {
+                let element = stack.pop().ok_or("unexpected close tag")?;
+                let value = element_value(&element.name, element.entries, element.text, options)?;
+                add_child(&mut stack, &mut root, element.name, value)?;
+            }
+            Event::Text(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.unescape()?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.decode()?);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let (name, value) = root.ok_or("the document has no root element")?;
+    Ok(Value::Object(vec![(name, value)]))
+}
+
+/// Converts an XML document into pretty-printed JSON.
+///
+/// # Example
+/// ```
+/// use crabyknife::convert::{xml_to_json, ConvertOptions};
+///
+/// let json = xml_to_json(r#"<a id="1"><b>x</b><b>y</b></a>"#, &ConvertOptions::new()).unwrap();
+/// assert_eq!(
+///     json,
+///     "{\n  \"a\": {\n    \"@id\": \"1\",\n    \"b\": [\n      \"x\",\n      \"y\"\n    ]\n  }\n}"
+/// );
+/// ```
+pub fn xml_to_json(
+    xml: &str,
+    options: &ConvertOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let value = xml_to_value(xml, options)?;
+    let mut output = String::new();
+    write_json(&value, 0, &mut output);
+    Ok(output)
+}
+
+/// Converts an XML document into YAML.
+///
+/// # Example
+/// ```
+/// use crabyknife::convert::{xml_to_yaml, ConvertOptions};
+///
+/// let yaml = xml_to_yaml(r#"<a id="1"><b>one</b><b>two</b></a>"#, &ConvertOptions::new()).unwrap();
+/// assert_eq!(yaml, "a:\n  \"@id\": \"1\"\n  b:\n    - one\n    - two\n");
+/// ```
+pub fn xml_to_yaml(
+    xml: &str,
+    options: &ConvertOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let value = xml_to_value(xml, options)?;
+    let mut output = String::new();
+    write_yaml(&value, 0, &mut output);
+    Ok(output)
+}
+
+/// Converts a YAML document, as written by [`xml_to_yaml`], back into XML.
+///
+/// # Example
+/// ```
+/// use crabyknife::convert::{yaml_to_xml, ConvertOptions};
+///
+/// let xml = yaml_to_xml("a:\n  '@id': 1\n  b: [x, y]\n", &ConvertOptions::new()).unwrap();
+/// assert_eq!(xml, r#"<a id="1"><b>x</b><b>y</b></a>"#);
+/// ```
+pub fn yaml_to_xml(
+    yaml: &str,
+    options: &ConvertOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let documents =
+        YamlLoader::load_from_str(yaml).map_err(|err| format!("invalid YAML: {err}"))?;
+    let document = documents.first().ok_or("the YAML input has no document")?;
+    value_to_xml(&yaml_to_value(document)?, options)
+}
+
+/// Maps a [`Value`] tree back into an XML document, the reverse of [`xml_to_value`].
+///
+/// # Errors
+///
+/// Returns an error if the tree is not an object with a single root
+/// element, uses arrays or objects where only text is possible, or has
+/// keys that are not valid XML names.
+pub fn value_to_xml(
+    value: &Value,
+    options: &ConvertOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let Value::Object(entries) = value else {
+        return Err("the document must be an object with a single root element".into());
+    };
+    let [(name, value)] = entries.as_slice() else {
+        return Err("the document must be an object with a single root element".into());
+    };
+    if matches!(value, Value::Array(_)) {
+        return Err(format!("the root element ({name}) cannot be repeated").into());
+    }
+
+    let mut output = String::new();
+    write_element(name, value, options, &mut output)?;
+    Ok(output)
+}
+
+fn write_element(
+    name: &str,
+    value: &Value,
+    options: &ConvertOptions,
+    output: &mut String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_name(name)?;
+    match value {
+        Value::Null => output.push_str(&format!("<{name}/>")),
+        Value::String(text) => {
+            output.push_str(&format!("<{name}>{}</{name}>", partial_escape(text)))
+        }
+        Value::Array(items) => {
+            for item in items {
+                if matches!(item, Value::Array(_)) {
+                    return Err(format!("nested arrays in {name} cannot be converted").into());
+                }
+                write_element(name, item, options, output)?;
+            }
+        }
+        Value::Object(entries) => {
+            output.push_str(&format!("<{name}"));
+            for (key, value) in entries {
+                let Some(attribute) = key.strip_prefix(options.attribute_prefix.as_str()) else {
+                    continue;
+                };
+                check_name(attribute)?;
+                let value = match value {
+                    Value::String(text) => escape(text).into_owned(),
+                    Value::Null => String::new(),
+                    _ => return Err(format!("attribute {attribute} of {name} must be text").into()),
+                };
+                output.push_str(&format!(" {attribute}=\"{value}\""));
+            }
+
+            let mut content = String::new();
+            for (key, value) in entries {
+                if key.starts_with(options.attribute_prefix.as_str()) {
+                    continue;
+                }
+                if *key == options.text_key {
+                    match value {
+                        Value::String(text) => content.push_str(&partial_escape(text)),
+                        Value::Null => {}
+                        _ => return Err(format!("the text of {name} must be a string").into()),
+                    }
+                } else {
+                    write_element(key, value, options, &mut content)?;
+                }
+            }
+
+            if content.is_empty() {
+                output.push_str("/>");
+            } else {
+                output.push_str(&format!(">{content}</{name}>"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `name` can be written as an element or attribute name. Like
+/// the XML `Name` production, except that any non-ASCII character is allowed.
+fn check_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|first| {
+        first.is_alphabetic() || first == '_' || first == ':' || !first.is_ascii()
+    }) && chars.all(|c| c.is_alphanumeric() || "-._:".contains(c) || !c.is_ascii());
+    if !valid {
+        return Err(format!("invalid XML name ({name})").into());
+    }
+    Ok(())
+}
+
+/// Maps a parsed YAML document into a [`Value`] tree; scalars become strings.
+fn yaml_to_value(yaml: &Yaml) -> Result<Value, Box<dyn std::error::Error>> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::String(text) | Yaml::Real(text) => Value::String(text.clone()),
+        Yaml::Integer(n) => Value::String(n.to_string()),
+        Yaml::Boolean(b) => Value::String(b.to_string()),
+        Yaml::Array(items) => {
+            Value::Array(items.iter().map(yaml_to_value).collect::<Result<_, _>>()?)
+        }
+        Yaml::Hash(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match yaml_to_value(key)? {
+                        Value::String(key) => key,
+                        _ => return Err("YAML keys must be scalars".into()),
+                    };
+                    Ok((key, yaml_to_value(value)?))
+                })
+                .collect::<Result<_, Box<dyn std::error::Error>>>()?,
+        ),
+        Yaml::Alias(_) | Yaml::BadValue => return Err("unsupported YAML value".into()),
+    })
+}
+
+fn attribute_entries(
+    e: &quick_xml::events::BytesStart,
+    options: &ConvertOptions,
+) -> Result<Vec<(String, Value)>, Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        let key = format!(
+            "{}{}",
+            options.attribute_prefix,
+            String::from_utf8_lossy(attr.key.as_ref())
+        );
+        entries.push((key, Value::String(attr.unescape_value()?.into_owned())));
+    }
+    Ok(entries)
+}
+
+/// Builds the value of the finished element `name` from its entries and text.
+///
+/// # Errors
+///
+/// Returns an error when the element has both text and an attribute whose
+/// key is the text key, which an empty attribute prefix allows.
+fn element_value(
+    name: &str,
+    mut entries: Vec<(String, Value)>,
+    text: String,
+    options: &ConvertOptions,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    Ok(match (entries.is_empty(), text.is_empty()) {
+        (true, true) => Value::Null,
+        (true, false) => Value::String(text),
+        (false, true) => Value::Object(entries),
+        (false, false) => {
+            if entries.iter().any(|(key, _)| *key == options.text_key) {
+                return Err(clash(name, &options.text_key, "its text").into());
+            }
+            entries.push((options.text_key.clone(), Value::String(text)));
+            Value::Object(entries)
+        }
+    })
+}
+
+/// Adds a finished element to its parent, grouping repeated names into an array.
+///
+/// # Errors
+///
+/// Returns an error when the parent has an attribute whose key is `name`,
+/// which an empty attribute prefix allows.
+fn add_child(
+    stack: &mut [OpenElement],
+    root: &mut Option<(String, Value)>,
+    name: String,
+    value: Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(parent) = stack.last_mut() else {
+        *root = Some((name, value));
+        return Ok(());
+    };
+
+    match parent.entries.iter_mut().position(|(key, _)| *key == name) {
+        Some(index) if index < parent.attributes => {
+            return Err(clash(&parent.name, &name, &format!("its child element {name}")).into());
+        }
+        Some(index) => match &mut parent.entries[index].1 {
+            Value::Array(items) => items.push(value),
+            existing => {
+                let first = std::mem::replace(existing, Value::Null);
+                *existing = Value::Array(vec![first, value]);
+            }
+        },
+        None => parent.entries.push((name, value)),
+    }
+    Ok(())
+}
+
+/// The error for an attribute of element `name` whose `key` is also that
+/// of `other`.
+fn clash(name: &str, key: &str, other: &str) -> String {
+    format!(
+        "the attribute {key} of {name} has the same key as {other}; use a non-empty attribute prefix"
+    )
+}
+
+fn write_json(value: &Value, indent: usize, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null"),
+        Value::String(text) => output.push_str(&json_string(text)),
+        Value::Array(items) if items.is_empty() => output.push_str("[]"),
+        Value::Array(items) => {
+            output.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                push_json_indent(indent + 1, output);
+                write_json(item, indent + 1, output);
+            }
+            push_json_indent(indent, output);
+            output.push(']');
+        }
+        Value::Object(entries) if entries.is_empty() => output.push_str("{}"),
+        Value::Object(entries) => {
+            output.push('{');
+            for (i, (key, item)) in entries.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                push_json_indent(indent + 1, output);
+                output.push_str(&json_string(key));
+                output.push_str(": ");
+                write_json(item, indent + 1, output);
+            }
+            push_json_indent(indent, output);
+            output.push('}');
+        }
+    }
+}
+
+fn push_json_indent(indent: usize, output: &mut String) {
+    output.push('\n');
+    output.push_str(&"  ".repeat(indent));
+}
+
+/// Writes `value` as the block YAML following a key or `- ` at `indent`.
+fn write_yaml(value: &Value, indent: usize, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null\n"),
+        Value::String(text) => {
+            output.push_str(&yaml_string(text));
+            output.push('\n');
+        }
+        Value::Array(items) if items.is_empty() => output.push_str("[]\n"),
+        Value::Object(entries) if entries.is_empty() => output.push_str("{}\n"),
+        Value::Array(items) => {
+            for item in items {
+                output.push_str(&"  ".repeat(indent));
+                output.push_str("- ");
+                write_yaml_item(item, indent + 1, output);
+            }
+        }
+        Value::Object(entries) => {
+            for (key, item) in entries {
+                output.push_str(&"  ".repeat(indent));
+                write_yaml_entry(key, item, indent, output);
+            }
+        }
+    }
+}
+
+/// Writes an array item, whose first line follows the `- ` already written.
+fn write_yaml_item(value: &Value, indent: usize, output: &mut String) {
+    match value {
+        Value::Object(entries) if !entries.is_empty() => {
+            for (i, (key, item)) in entries.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(&"  ".repeat(indent));
+                }
+                write_yaml_entry(key, item, indent, output);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            output.push('\n');
+            write_yaml(value, indent, output);
+        }
+        _ => write_yaml(value, indent, output),
+    }
+}
+
+fn write_yaml_entry(key: &str, value: &Value, indent: usize, output: &mut String) {
+    output.push_str(&yaml_string(key));
+    output.push(':');
+    match value {
+        Value::Object(entries) if !entries.is_empty() => {
+            output.push('\n');
+            write_yaml(value, indent + 1, output);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            output.push('\n');
+            write_yaml(value, indent + 1, output);
+        }
+        _ => {
+            output.push(' ');
+            write_yaml(value, indent, output);
+        }
+    }
+}
+
+/// Writes `text` as a plain YAML scalar when it cannot be mistaken for
+/// anything but a string, and double-quoted otherwise.
+fn yaml_string(text: &str) -> String {
+    const RESERVED: [&str; 9] = ["true", "false", "yes", "no", "on", "off", "null", "y", "n"];
+
+    let plain = text.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '/')
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || " _./-".contains(c))
+        && !text.ends_with(' ')
+        && !RESERVED.contains(&text.to_ascii_lowercase().as_str());
+    if plain {
+        text.to_string()
+    } else {
+        // JSON strings are valid double-quoted YAML scalars.
+        json_string(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_value(xml: &str) -> Value {
+        xml_to_value(xml, &ConvertOptions::new()).unwrap()
+    }
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn test_text_only_element() {
+        assert_eq!(
+            to_value("<a>hi &amp; bye</a>"),
+            Value::Object(vec![("a".to_string(), string("hi & bye"))])
+        );
+    }
+
+    #[test]
+    fn test_empty_element() {
+        assert_eq!(
+            to_value("<a><b/></a>"),
+            Value::Object(vec![(
+                "a".to_string(),
+                Value::Object(vec![("b".to_string(), Value::Null)])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_attributes_with_text_and_cdata() {
+        assert_eq!(
+            to_value(r#"<a x="1">t<![CDATA[<raw>]]></a>"#),
+            Value::Object(vec![(
+                "a".to_string(),
+                Value::Object(vec![
+                    ("@x".to_string(), string("1")),
+                    ("#text".to_string(), string("t<raw>")),
+                ])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_custom_prefix_and_text_key() {
+        let options = ConvertOptions::new()
+            .attribute_prefix("_")
+            .text_key("value");
+        let json = xml_to_json(r#"<a x="1">t</a>"#, &options).unwrap();
+        assert_eq!(
+            json,
+            "{\n  \"a\": {\n    \"_x\": \"1\",\n    \"value\": \"t\"\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn test_empty_prefix_clashes() {
+        let options = ConvertOptions::new().attribute_prefix("");
+        assert_eq!(
+            xml_to_value(r#"<a b="1"><b>2</b></a>"#, &options)
+                .unwrap_err()
+                .to_string(),
+            "the attribute b of a has the same key as its child element b; use a non-empty attribute prefix"
+        );
+        let options = options.text_key("b");
+        assert!(xml_to_value(r#"<a b="1">2</a>"#, &options).is_err());
+        // Without a clash the empty prefix is fine.
+        assert!(xml_to_value(r#"<a b="1"><c>2</c></a>"#, &options).is_ok());
+    }
+
+    #[test]
+    fn test_repeated_children_become_array() {
+        assert_eq!(
+            to_value("<a><b>1</b><c/><b>2</b><b>3</b></a>"),
+            Value::Object(vec![(
+                "a".to_string(),
+                Value::Object(vec![
+                    (
+                        "b".to_string(),
+                        Value::Array(vec![string("1"), string("2"), string("3")])
+                    ),
+                    ("c".to_string(), Value::Null),
+                ])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let xml = concat!(
+            r#"<config version="2"><name>app: "demo"</name><enabled>true</enabled>"#,
+            r#"<server host="a"><port>80</port></server><server host="b"/>"#,
+            "<empty/><note lang=\"en\">multi\nline</note></config>"
+        );
+        let options = ConvertOptions::new();
+        let yaml = xml_to_yaml(xml, &options).unwrap();
+        assert_eq!(
+            yaml,
+            concat!(
+                "config:\n",
+                "  \"@version\": \"2\"\n",
+                "  name: \"app: \\\"demo\\\"\"\n",
+                "  enabled: \"true\"\n",
+                "  server:\n",
+                "    - \"@host\": a\n",
+                "      port: \"80\"\n",
+                "    - \"@host\": b\n",
+                "  empty: null\n",
+                "  note:\n",
+                "    \"@lang\": en\n",
+                "    \"#text\": \"multi\\nline\"\n",
+            )
+        );
+        assert_eq!(yaml_to_xml(&yaml, &options).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_yaml_to_xml_errors() {
+        let options = ConvertOptions::new();
+        assert!(yaml_to_xml("a: 1\nb: 2\n", &options).is_err());
+        assert!(yaml_to_xml("- a\n", &options).is_err());
+        assert!(yaml_to_xml("a:\n  '@x': [1]\n", &options).is_err());
+        assert!(yaml_to_xml("a: [[1]]\n", &options).is_err());
+        assert!(yaml_to_xml("a: [unclosed\n", &options).is_err());
+        assert!(yaml_to_xml("a b: 1\n", &options).is_err());
+        assert!(yaml_to_xml("'': 1\n", &options).is_err());
+        assert!(yaml_to_xml("a:\n  '@1d': x\n", &options).is_err());
+        assert!(yaml_to_xml("a:\n  '<b': x\n", &options).is_err());
+    }
+
+    #[test]
+    fn test_no_root_element() {
+        assert!(xml_to_value("<!--only a comment-->", &ConvertOptions::new()).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Conversion of pasted `curl` commands into Rust code.
+//!
+//! The command line is split like a POSIX shell would (quotes, backslash
+//! escapes and line continuations) and the common request options of curl
+//! are read into a [`CurlRequest`], which can be rendered as a `reqwest`
+//! snippet with [`to_reqwest`].
+
+/// A request described by a `curl` command.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CurlRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// The request body, `@path` when read from a file.
+    pub body: Option<String>,
+    /// The user name and optional password of `-u`.
+    pub basic_auth: Option<(String, Option<String>)>,
+    /// `-k`: accept invalid TLS certificates.
+    pub insecure: bool,
+    /// `-L`: follow redirects, which curl does not do by default.
+    pub follow_redirects: bool,
+}
+
+/// Parses a `curl` command line.
+///
+/// # Example
+/// ```
+/// use crabyknife::curlconv::parse;
+///
+/// let request = parse("curl -X POST https://example.com/api -H 'Accept: */*' -d 'a=1'").unwrap();
+/// assert_eq!(request.method, "POST");
+/// assert_eq!(request.headers[0], ("Accept".to_string(), "*/*".to_string()));
+/// assert_eq!(request.body.as_deref(), Some("a=1"));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error for unbalanced quotes, options curl would not accept
+/// or that are not supported, and commands without a url.
+pub fn parse(command: &str) -> Result<CurlRequest, Box<dyn std::error::Error>> {
+    let words = split_words(command)?;
+    let mut words = words.into_iter().peekable();
+    if words.peek().map(String::as_str) == Some("curl") {
+        words.next();
+    }
+
+    let mut request = CurlRequest::default();
+    let mut method = None;
+    let mut data: Vec<String> = Vec::new();
+    let mut get = false;
+
+    while let Some(word) = words.next() {
+        // Long options may carry their value after `=`, short ones right after the letter.
+        let (option, attached) = match word.split_once('=') {
+            Some((option, value)) if word.starts_with("--") => {
+                (option.to_string(), Some(value.to_string()))
+            }
+            _ if word.starts_with('-') && !word.starts_with("--") && word.len() > 2 => {
+                let flags = &word[1..];
+                if flags.chars().all(|c| "sSLkivG".contains(c)) {
+                    // Combined boolean flags, e.g. `-sSL`.
+                    for flag in flags.chars() {
+                        apply_flag(&format!("-{flag}"), &mut request, &mut get);
+                    }
+                    continue;
+                }
+                let (letter, value) =
+                    flags.split_at(flags.chars().next().map_or(0, char::len_utf8));
+                (format!("-{letter}"), Some(value.to_string()))
+            }
+            _ => (word.clone(), None),
+        };
+
+        let mut value = || {
+            attached
+                .clone()
+                .or_else(|| words.next())
+                .ok_or_else(|| format!("curl option {option} expects a value"))
+        };
+
+        match option.as_str() {
+            "-X" | "--request" => method = Some(value()?),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid header ({header}), expected `Name: value`"))?;
+                request
+                    .headers
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" => {
+                data.push(value()?)
+            }
+            "--data-urlencode" => data.push(url_encoded_data(&value()?)?),
+            "--json" => {
+                data.push(value()?);
+                for (name, value) in [
+                    ("Content-Type", "application/json"),
+                    ("Accept", "application/json"),
+                ] {
+                    if !request
+                        .headers
+                        .iter()
+                        .any(|(existing, _)| existing.eq_ignore_ascii_case(name))
+                    {
+                        request.headers.push((name.to_string(), value.to_string()));
+                    }
+                }
+            }
+            "-u" | "--user" => {
+                let user = value()?;
+                request.basic_auth = Some(match user.split_once(':') {
+                    Some((name, password)) => (name.to_string(), Some(password.to_string())),
+                    None => (user, None),
+                });
+            }
+            "-A" | "--user-agent" => request.headers.push(("User-Agent".to_string(), value()?)),
+            "-e" | "--referer" => request.headers.push(("Referer".to_string(), value()?)),
+            "-b" | "--cookie" => request.headers.push(("Cookie".to_string(), value()?)),
+            "--url" => request.url = value()?,
+            "-s" | "--silent" | "-S" | "--show-error" | "-L" | "--location" | "-k"
+            | "--insecure" | "-i" | "--include" | "-v" | "--verbose" | "-G" | "--get"
+            | "--compressed" => apply_flag(&option, &mut request, &mut get),
+            _ if option.starts_with('-') => {
+                return Err(format!("unsupported curl option {option}").into())
+            }
+            _ => request.url = word,
+        }
+    }
+
+    if request.url.is_empty() {
+        return Err("the curl command has no url".into());
+    }
+
+    if !data.is_empty() {
+        let data = data.join("&");
+        if get {
+            let separator = if request.url.contains('?') { '&' } else { '?' };
+            request.url = format!("{}{separator}{data}", request.url);
+        } else {
+            if !request
+                .headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+            {
+                // What curl sends for `-d`.
+                request.headers.push((
+                    "Content-Type".to_string(),
+                    "application/x-www-form-urlencoded".to_string(),
+                ));
+            }
+            request.body = Some(data);
+        }
+    }
+
+    request.method = method.unwrap_or_else(|| {
+        if request.body.is_some() {
+            "POST"
+        } else {
+            "GET"
+        }
+        .to_string()
+    });
+    Ok(request)
+}
+
+/// The data of `--data-urlencode`, whose content curl percent-encodes:
+/// all of it, or what follows `=` in `=content` and `name=content`.
+fn url_encoded_data(data: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match data.find(['=', '@']) {
+        Some(at) if data[at..].starts_with('@') => {
+            Err(format!("--data-urlencode {data} reads a file, which is not supported").into())
+        }
+        Some(0) => Ok(percent_encode(&data[1..])),
+        Some(at) => Ok(format!(
+            "{}={}",
+            &data[..at],
+            percent_encode(&data[at + 1..])
+        )),
+        None => Ok(percent_encode(data)),
+    }
+}
+
+/// Percent-encodes every byte of `text` but the unreserved characters of
+/// RFC 3986, like curl does.
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Applies a curl option without value.
+fn apply_flag(flag: &str, request: &mut CurlRequest, get: &mut bool) {
+    match flag {
+        "-L" | "--location" => request.follow_redirects = true,
+        "-k" | "--insecure" => request.insecure = true,
+        "-G" | "--get" => *get = true,
+        // Output options, irrelevant to the request.
+        _ => {}
+    }
+}
+
+/// Renders `request` as a blocking `reqwest` snippet.
+///
+/// # Example
+/// ```
+/// use crabyknife::curlconv::{parse, to_reqwest};
+///
+/// let snippet = to_reqwest(&parse("curl -L https://example.com").unwrap());
+/// assert!(snippet.contains(".get(\"https://example.com\")"));
+/// ```
+pub fn to_reqwest(request: &CurlRequest) -> String {
+    let mut snippet = String::from("let client = reqwest::blocking::Client::builder()\n");
+    if !request.follow_redirects {
+        snippet.push_str("    .redirect(reqwest::redirect::Policy::none())\n");
+    }
+    if request.insecure {
+        snippet.push_str("    .danger_accept_invalid_certs(true)\n");
+    }
+    snippet.push_str("    .build()?;\n");
+
+    snippet.push_str("let response = client\n");
+    match request.method.as_str() {
+        "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" => snippet.push_str(&format!(
+            "    .{}({:?})\n",
+            request.method.to_ascii_lowercase(),
+            request.url
+        )),
+        method => snippet.push_str(&format!(
+            "    .request(reqwest::Method::from_bytes(b{method:?})?, {:?})\n",
+            request.url
+        )),
+    }
+    for (name, value) in &request.headers {
+        snippet.push_str(&format!("    .header({name:?}, {value:?})\n"));
+    }
+    if let Some((user, password)) = &request.basic_auth {
+        match password {
+            Some(password) => {
+                snippet.push_str(&format!("    .basic_auth({user:?}, Some({password:?}))\n"))
+            }
+            None => snippet.push_str(&format!("    .basic_auth({user:?}, None::<&str>)\n")),
+        }
+    }
+    if let Some(body) = request.body.as_deref() {
+        match body.strip_prefix('@') {
+            Some(path) => snippet.push_str(&format!("    .body(std::fs::read({path:?})?)\n")),
+            None => snippet.push_str(&format!("    .body({body:?})\n")),
+        }
+    }
+    snippet.push_str("    .send()?;\n");
+    snippet.push_str("println!(\"{}\", response.text()?);");
+    snippet
+}
+
+/// Splits a command line into words like a POSIX shell, without expansions.
+fn split_words(command: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated ' quote in the curl command".into()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated \" quote in the curl command".into()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated \" quote in the curl command".into()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // A line continuation.
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => {}
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        let command = "curl 'https://x.test/a b' \\\n  -H \"X-Quote: \\\"q\\\"\" -d a\\ b";
+        assert_eq!(
+            split_words(command).unwrap(),
+            vec![
+                "curl",
+                "https://x.test/a b",
+                "-H",
+                "X-Quote: \"q\"",
+                "-d",
+                "a b"
+            ]
+        );
+        assert!(split_words("curl 'open").is_err());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let request = parse(
+            "curl -sSL -k -XPUT --url=https://x.test -u admin:secret -A agent \
+             --json '{\"a\":1}' -H 'Content-Type: application/vnd+json'",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            CurlRequest {
+                method: "PUT".to_string(),
+                url: "https://x.test".to_string(),
+                headers: vec![
+                    ("User-Agent".to_string(), "agent".to_string()),
+                    ("Content-Type".to_string(), "application/json".to_string()),
+                    ("Accept".to_string(), "application/json".to_string()),
+                    (
+                        "Content-Type".to_string(),
+                        "application/vnd+json".to_string()
+                    ),
+                ],
+                body: Some("{\"a\":1}".to_string()),
+                basic_auth: Some(("admin".to_string(), Some("secret".to_string()))),
+                insecure: true,
+                follow_redirects: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_data_defaults() {
+        let request = parse("curl https://x.test -d a=1 -d b=2").unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.body.as_deref(), Some("a=1&b=2"));
+        assert_eq!(
+            request.headers,
+            vec![(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string()
+            )]
+        );
+
+        let request = parse("curl -G https://x.test?q=1 -d b=2").unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.url, "https://x.test?q=1&b=2");
+        assert_eq!(request.body, None);
+    }
+
+    #[test]
+    fn test_data_urlencode() {
+        let request = parse(
+            "curl https://x.test --data-urlencode 'q=a b&c' --data-urlencode =ü \\
+             --data-urlencode 'x/y'",
+        )
+        .unwrap();
+        assert_eq!(request.body.as_deref(), Some("q=a%20b%26c&%C3%BC&x%2Fy"));
+        assert!(parse("curl https://x.test --data-urlencode name@file.txt").is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("curl -H").is_err());
+        assert!(parse("curl --proxy x https://x.test").is_err());
+        assert!(parse("curl -s").is_err());
+        assert_eq!(
+            parse("curl -éx https://a").unwrap_err().to_string(),
+            "unsupported curl option -é"
+        );
+    }
+
+    #[test]
+    fn test_to_reqwest() {
+        let request = parse("curl -X PURGE https://x.test -u me -d @body.json").unwrap();
+        assert_eq!(
+            to_reqwest(&request),
+            concat!(
+                "let client = reqwest::blocking::Client::builder()\n",
+                "    .redirect(reqwest::redirect::Policy::none())\n",
+                "    .build()?;\n",
+                "let response = client\n",
+                "    .request(reqwest::Method::from_bytes(b\"PURGE\")?, \"https://x.test\")\n",
+                "    .header(\"Content-Type\", \"application/x-www-form-urlencoded\")\n",
+                "    .basic_auth(\"me\", None::<&str>)\n",
+                "    .body(std::fs::read(\"body.json\")?)\n",
+                "    .send()?;\n",
+                "println!(\"{}\", response.text()?);"
+            )
+        );
+    }
+}
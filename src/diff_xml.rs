@@ -0,0 +1,265 @@
+//! Structural comparison of two XML documents.
+//!
+//! Documents are compared as element trees rather than text, so attribute
+//! order and whitespace between tags do not matter. Child elements are
+//! paired up by name and position among siblings of the same name.
+
+use std::collections::BTreeMap;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+/// A single difference between two documents.
+///
+/// Paths use an XPath like syntax, e.g. `/root/item[2]/@id`, where the
+/// index is only given when several siblings share the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// The element or attribute only exists in the second document.
+    Added { path: String },
+    /// The element or attribute only exists in the first document.
+    Removed { path: String },
+    /// The attribute value or text differs between the documents.
+    Changed {
+        path: String,
+        before: String,
+        after: String,
+    },
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Added { path } => write!(f, "+ {path}"),
+            Difference::Removed { path } => write!(f, "- {path}"),
+            Difference::Changed {
+                path,
+                before,
+                after,
+            } => write!(f, "~ {path}: {before:?} -> {after:?}"),
+        }
+    }
+}
+
+/// Compares two documents and lists their structural differences,
+/// in document order. An empty list means the documents are equivalent.
+///
+/// # Example
+/// ```
+/// use crabyknife::diff_xml::{diff_xml, Difference};
+///
+/// let a = r#"<root><item id="1" kind="a"/></root>"#;
+/// let b = "<root>\n  <item kind=\"a\" id=\"2\"/>\n</root>";
+/// assert_eq!(
+///     diff_xml(a, b).unwrap(),
+///     vec![Difference::Changed {
+///         path: "/root/item/@id".to_string(),
+///         before: "1".to_string(),
+///         after: "2".to_string(),
+///     }]
+/// );
+/// ```
+pub fn diff_xml(a: &str, b: &str) -> Result<Vec<Difference>, Box<dyn std::error::Error>> {
+    let a = parse(a)?;
+    let b = parse(b)?;
+
+    let mut differences = Vec::new();
+    if a.name == b.name {
+        diff_elements(&a, &b, &format!("/{}", a.name), &mut differences);
+    } else {
+        differences.push(Difference::Removed {
+            path: format!("/{}", a.name),
+        });
+        differences.push(Difference::Added {
+            path: format!("/{}", b.name),
+        });
+    }
+
+    Ok(differences)
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: BTreeMap<String, String>,
+    text: String,
+    children: Vec<Element>,
+}
+
+fn parse(xml: &str) -> Result<Element, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<Element> = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => stack.push(new_element(&e)?),
+            Event::Empty(e) => {
+                let element = new_element(&e)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let element = stack.pop().ok_or("unexpected close tag")?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.unescape()?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.decode()?);
+                }
+            }
+            Event::Eof => return Err("the document has no root element".into()),
+            _ => {}
+        }
+    }
+}
+
+fn new_element(e: &BytesStart) -> Result<Element, Box<dyn std::error::Error>> {
+    let mut attributes = BTreeMap::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        attributes.insert(
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            attr.unescape_value()?.into_owned(),
+        );
+    }
+
+    Ok(Element {
+        name: String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+        attributes,
+        ..Element::default()
+    })
+}
+
+fn diff_elements(a: &Element, b: &Element, path: &str, differences: &mut Vec<Difference>) {
+    for (key, before) in &a.attributes {
+        let attr_path = format!("{path}/@{key}");
+        match b.attributes.get(key) {
+            Some(after) if after != before => differences.push(Difference::Changed {
+                path: attr_path,
+                before: before.clone(),
+                after: after.clone(),
+            }),
+            Some(_) => {}
+            None => differences.push(Difference::Removed { path: attr_path }),
+        }
+    }
+    for key in b.attributes.keys() {
+        if !a.attributes.contains_key(key) {
+            differences.push(Difference::Added {
+                path: format!("{path}/@{key}"),
+            });
+        }
+    }
+
+    if a.text != b.text {
+        differences.push(Difference::Changed {
+            path: format!("{path}/text()"),
+            before: a.text.clone(),
+            after: b.text.clone(),
+        });
+    }
+
+    // Child names in order of first appearance across both documents.
+    let mut names: Vec<&str> = Vec::new();
+    for child in a.children.iter().chain(&b.children) {
+        if !names.contains(&child.name.as_str()) {
+            names.push(&child.name);
+        }
+    }
+
+    for name in names {
+        let a_children: Vec<&Element> = a.children.iter().filter(|c| c.name == name).collect();
+        let b_children: Vec<&Element> = b.children.iter().filter(|c| c.name == name).collect();
+        let indexed = a_children.len() > 1 || b_children.len() > 1;
+
+        for i in 0..a_children.len().max(b_children.len()) {
+            let child_path = if indexed {
+                format!("{path}/{name}[{}]", i + 1)
+            } else {
+                format!("{path}/{name}")
+            };
+
+            match (a_children.get(i), b_children.get(i)) {
+                (Some(a_child), Some(b_child)) => {
+                    diff_elements(a_child, b_child, &child_path, differences)
+                }
+                (Some(_), None) => differences.push(Difference::Removed { path: child_path }),
+                (None, Some(_)) => differences.push(Difference::Added { path: child_path }),
+                (None, None) => unreachable!("index is below the longer list length"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent_documents() {
+        let a = r#"<root><a x="1" y="2">text</a><b/></root>"#;
+        let b = "<root>\n  <a y=\"2\" x=\"1\">text</a>\n  <b></b>\n</root>";
+        assert_eq!(diff_xml(a, b).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_added_and_removed_elements() {
+        let a = "<root><item/><item/><old/></root>";
+        let b = "<root><item/><new/></root>";
+        assert_eq!(
+            diff_xml(a, b).unwrap(),
+            vec![
+                Difference::Removed {
+                    path: "/root/item[2]".to_string()
+                },
+                Difference::Removed {
+                    path: "/root/old".to_string()
+                },
+                Difference::Added {
+                    path: "/root/new".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_text_and_attributes() {
+        let a = r#"<root><a gone="1">one</a></root>"#;
+        let b = r#"<root><a added="1">two</a></root>"#;
+        assert_eq!(
+            diff_xml(a, b).unwrap(),
+            vec![
+                Difference::Removed {
+                    path: "/root/a/@gone".to_string()
+                },
+                Difference::Added {
+                    path: "/root/a/@added".to_string()
+                },
+                Difference::Changed {
+                    path: "/root/a/text()".to_string(),
+                    before: "one".to_string(),
+                    after: "two".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_different_roots() {
+        assert_eq!(diff_xml("<a/>", "<b/>").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,479 @@
+//! Mounted file systems with their capacity, and the SMART health
+//! attributes of ATA disks.
+//!
+//! Mounts are read from `/proc/self/mounts` on Linux and `getmntinfo` on
+//! macOS. SMART attributes are read through the Linux SCSI generic driver
+//! with an ATA pass-through command, which SATA disks and most USB bridges
+//! support; NVMe and virtual disks have no ATA SMART data.
+
+use std::path::{Path, PathBuf};
+
+/// File system types that describe kernel state rather than storage, and
+/// are hidden from [`mounts`] unless all mounts are asked for.
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "securityfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// A mounted file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// The mounted device or source, e.g. `/dev/sda1` or `tmpfs`.
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// The capacity in bytes.
+    pub total: u64,
+    pub used: u64,
+    /// The bytes available to unprivileged users, less than `total - used`
+    /// when blocks are reserved for root.
+    pub available: u64,
+}
+
+impl Mount {
+    /// The used fraction of the space available to users, like `df` computes it.
+    pub fn usage(&self) -> f64 {
+        let usable = self.used + self.available;
+        if usable == 0 {
+            return 0.0;
+        }
+        self.used as f64 / usable as f64
+    }
+
+    /// Whether the file system describes kernel state rather than storage,
+    /// or has no capacity.
+    pub fn is_pseudo(&self) -> bool {
+        self.total == 0 || PSEUDO_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+/// Lists the mounted file systems in mount order, without pseudo file
+/// systems (see [`Mount::is_pseudo`]) unless `all`.
+///
+/// # Errors
+///
+/// Returns an error if the mount table cannot be read, and on platforms
+/// other than Linux and macOS.
+pub fn mounts(all: bool) -> Result<Vec<Mount>, Box<dyn std::error::Error>> {
+    let mounts = read_mounts()?;
+    Ok(mounts
+        .into_iter()
+        .filter(|mount| all || !mount.is_pseudo())
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn read_mounts() -> Result<Vec<Mount>, Box<dyn std::error::Error>> {
+    let table = std::fs::read_to_string("/proc/self/mounts")
+        .map_err(|err| format!("failed to read the mount table: {err}"))?;
+
+    Ok(parse_mount_table(&table)
+        .into_iter()
+        .map(|(device, mount_point, fs_type)| {
+            // Mounts that cannot be queried, e.g. without permission, show no capacity.
+            let (total, used, available) = capacity(&mount_point).unwrap_or_default();
+            Mount {
+                device,
+                mount_point,
+                fs_type,
+                total,
+                used,
+                available,
+            }
+        })
+        .collect())
+}
+
+/// Returns the total, used and available bytes of the file system at `path`.
+#[cfg(target_os = "linux")]
+// The statvfs fields are only 64-bit wide on 64-bit targets.
+#[allow(clippy::useless_conversion)]
+fn capacity(path: &Path) -> std::io::Result<(u64, u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read once statvfs succeeded.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+
+    let block = u64::from(stat.f_frsize);
+    let total = u64::from(stat.f_blocks) * block;
+    let free = u64::from(stat.f_bfree) * block;
+    Ok((total, total - free, u64::from(stat.f_bavail) * block))
+}
+
+#[cfg(target_os = "macos")]
+fn read_mounts() -> Result<Vec<Mount>, Box<dyn std::error::Error>> {
+    let text = |chars: &[libc::c_char]| {
+        // SAFETY: the names in statfs are NUL-terminated.
+        unsafe { std::ffi::CStr::from_ptr(chars.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let mut entries: *mut libc::statfs = std::ptr::null_mut();
+    // SAFETY: getmntinfo points `entries` to `count` statfs it keeps allocated.
+    let count = unsafe { libc::getmntinfo(&mut entries, libc::MNT_NOWAIT) };
+    if count <= 0 {
+        return Err(format!(
+            "failed to read the mount table: {}",
+            std::io::Error::last_os_error()
+        )
+        .into());
+    }
+    let entries = unsafe { std::slice::from_raw_parts(entries, count as usize) };
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let block = u64::from(entry.f_bsize);
+            let total = entry.f_blocks * block;
+            Mount {
+                device: text(&entry.f_mntfromname),
+                mount_point: PathBuf::from(text(&entry.f_mntonname)),
+                fs_type: text(&entry.f_fstypename),
+                total,
+                used: total - entry.f_bfree * block,
+                available: entry.f_bavail * block,
+            }
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_mounts() -> Result<Vec<Mount>, Box<dyn std::error::Error>> {
+    Err("listing mounts is only supported on Linux and macOS".into())
+}
+
+/// Parses the device, mount point and type of each line of a Linux mount
+/// table (`/proc/self/mounts` or `/etc/fstab` format).
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mount_table(table: &str) -> Vec<(String, PathBuf, String)> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape_mount_field(fields.next()?);
+            let mount_point = unescape_mount_field(fields.next()?);
+            let fs_type = unescape_mount_field(fields.next()?);
+            Some((device, PathBuf::from(mount_point), fs_type))
+        })
+        .collect()
+}
+
+/// Decodes the octal escapes (`\040` for a space) of a mount table field.
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+        match escape
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok())
+        {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A SMART attribute of an ATA disk.
+///
+/// `value` and `worst` are normalized by the vendor, usually from 100 or
+/// 200 down to 1, where lower is worse; `raw` has vendor-specific units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    /// The common name of the attribute, `Unknown_Attribute` for
+    /// vendor-specific ones.
+    pub name: &'static str,
+    pub value: u8,
+    pub worst: u8,
+    pub raw: u64,
+}
+
+/// Reads the SMART attributes of the ATA disk `device`, e.g. `/dev/sda`.
+///
+/// Needs read access to the device, usually root.
+///
+/// # Errors
+///
+/// Returns an error if the device cannot be opened or does not answer the
+/// SMART READ DATA command, and on platforms other than Linux.
+pub fn smart(device: &Path) -> Result<Vec<SmartAttribute>, Box<dyn std::error::Error>> {
+    let data = read_smart_data(device).map_err(|err| {
+        format!(
+            "SMART data is not available for {}: {err}",
+            device.display()
+        )
+    })?;
+    Ok(parse_smart_data(&data))
+}
+
+/// The `SG_IO` ioctl of the Linux SCSI generic driver.
+#[cfg(target_os = "linux")]
+const SG_IO: libc::c_ulong = 0x2285;
+
+/// `sg_io_hdr` from `<scsi/sg.h>`.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SgIoHeader {
+    interface_id: libc::c_int,
+    dxfer_direction: libc::c_int,
+    cmd_len: libc::c_uchar,
+    mx_sb_len: libc::c_uchar,
+    iovec_count: libc::c_ushort,
+    dxfer_len: libc::c_uint,
+    dxferp: *mut libc::c_void,
+    cmdp: *mut libc::c_uchar,
+    sbp: *mut libc::c_uchar,
+    timeout: libc::c_uint,
+    flags: libc::c_uint,
+    pack_id: libc::c_int,
+    usr_ptr: *mut libc::c_void,
+    status: libc::c_uchar,
+    masked_status: libc::c_uchar,
+    msg_status: libc::c_uchar,
+    sb_len_wr: libc::c_uchar,
+    host_status: libc::c_ushort,
+    driver_status: libc::c_ushort,
+    resid: libc::c_int,
+    duration: libc::c_uint,
+    info: libc::c_uint,
+}
+
+/// Sends SMART READ DATA through an ATA PASS-THROUGH (16) command and
+/// returns the 512-byte data page.
+#[cfg(target_os = "linux")]
+fn read_smart_data(device: &Path) -> std::io::Result<[u8; 512]> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::File::open(device)?;
+    let mut data = [0u8; 512];
+    let mut sense = [0u8; 32];
+    let mut command = [0u8; 16];
+    command[0] = 0x85; // ATA PASS-THROUGH (16)
+    command[1] = 4 << 1; // PIO data-in
+    command[2] = 0x0e; // Transfer from the device, in blocks, count in the sector count field
+    command[4] = 0xd0; // Feature: SMART READ DATA
+    command[6] = 1; // Sector count
+    command[10] = 0x4f; // LBA mid and high: the SMART signature
+    command[12] = 0xc2;
+    command[14] = 0xb0; // Command: SMART
+
+    let mut header = SgIoHeader {
+        interface_id: b'S' as libc::c_int,
+        dxfer_direction: -3, // SG_DXFER_FROM_DEV
+        cmd_len: command.len() as libc::c_uchar,
+        mx_sb_len: sense.len() as libc::c_uchar,
+        iovec_count: 0,
+        dxfer_len: data.len() as libc::c_uint,
+        dxferp: data.as_mut_ptr().cast(),
+        cmdp: command.as_mut_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: 5000,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+
+    // SAFETY: the header points to buffers that outlive the call, with their lengths.
+    if unsafe { libc::ioctl(file.as_raw_fd(), SG_IO as _, &mut header) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    if header.status != 0 || header.host_status != 0 || header.driver_status != 0 {
+        return Err(std::io::Error::other(
+            "the device rejected the SMART command",
+        ));
+    }
+
+    Ok(data)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_smart_data(_device: &Path) -> std::io::Result<[u8; 512]> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reading SMART data is only supported on Linux",
+    ))
+}
+
+/// Parses the attribute table of a SMART READ DATA page: 30 entries of 12
+/// bytes from offset 2, each an id (0 for unused entries), 2 flag bytes,
+/// the normalized and worst values and 6 little-endian raw bytes.
+fn parse_smart_data(data: &[u8]) -> Vec<SmartAttribute> {
+    data.get(2..362)
+        .unwrap_or_default()
+        .chunks_exact(12)
+        .filter(|entry| entry[0] != 0)
+        .map(|entry| {
+            let mut raw = [0u8; 8];
+            raw[..6].copy_from_slice(&entry[5..11]);
+            SmartAttribute {
+                id: entry[0],
+                name: smart_attribute_name(entry[0]),
+                value: entry[3],
+                worst: entry[4],
+                raw: u64::from_le_bytes(raw),
+            }
+        })
+        .collect()
+}
+
+/// The names smartmontools uses for the common attributes.
+fn smart_attribute_name(id: u8) -> &'static str {
+    match id {
+        1 => "Raw_Read_Error_Rate",
+        3 => "Spin_Up_Time",
+        4 => "Start_Stop_Count",
+        5 => "Reallocated_Sector_Ct",
+        7 => "Seek_Error_Rate",
+        9 => "Power_On_Hours",
+        10 => "Spin_Retry_Count",
+        12 => "Power_Cycle_Count",
+        177 => "Wear_Leveling_Count",
+        187 => "Reported_Uncorrect",
+        188 => "Command_Timeout",
+        190 => "Airflow_Temperature_Cel",
+        194 => "Temperature_Celsius",
+        196 => "Reallocated_Event_Count",
+        197 => "Current_Pending_Sector",
+        198 => "Offline_Uncorrectable",
+        199 => "UDMA_CRC_Error_Count",
+        231 => "SSD_Life_Left",
+        241 => "Total_LBAs_Written",
+        242 => "Total_LBAs_Read",
+        _ => "Unknown_Attribute",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mount_table() {
+        let table = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                     /dev/sdb1 /mnt/My\\040Disk vfat rw 0 0\n\
+                     proc /proc proc rw 0 0\n";
+        assert_eq!(
+            parse_mount_table(table),
+            [
+                (
+                    "/dev/sda1".to_string(),
+                    PathBuf::from("/"),
+                    "ext4".to_string()
+                ),
+                (
+                    "/dev/sdb1".to_string(),
+                    PathBuf::from("/mnt/My Disk"),
+                    "vfat".to_string()
+                ),
+                (
+                    "proc".to_string(),
+                    PathBuf::from("/proc"),
+                    "proc".to_string()
+                ),
+            ]
+        );
+        assert_eq!(unescape_mount_field("a\\134b\\01"), "a\\b\\01");
+    }
+
+    #[test]
+    fn test_mount_usage() {
+        let mount = Mount {
+            device: "/dev/sda1".to_string(),
+            mount_point: PathBuf::from("/"),
+            fs_type: "ext4".to_string(),
+            total: 100,
+            used: 45,
+            available: 45,
+        };
+        assert_eq!(mount.usage(), 0.5);
+        assert!(!mount.is_pseudo());
+        assert!(Mount {
+            fs_type: "proc".to_string(),
+            ..mount
+        }
+        .is_pseudo());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mounts_include_root() {
+        let all = mounts(true).unwrap();
+        assert!(all.iter().any(|mount| mount.mount_point == Path::new("/")));
+        assert!(mounts(false)
+            .unwrap()
+            .iter()
+            .all(|mount| !mount.is_pseudo()));
+    }
+
+    #[test]
+    fn test_parse_smart_data() {
+        let mut data = [0u8; 512];
+        // Power_On_Hours: value 98, worst 98, raw 12345.
+        data[2..14].copy_from_slice(&[9, 0x32, 0, 98, 98, 0x39, 0x30, 0, 0, 0, 0, 0]);
+        // An unused entry, then Temperature_Celsius.
+        data[26..38].copy_from_slice(&[194, 0x22, 0, 36, 52, 36, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(
+            parse_smart_data(&data),
+            [
+                SmartAttribute {
+                    id: 9,
+                    name: "Power_On_Hours",
+                    value: 98,
+                    worst: 98,
+                    raw: 12345
+                },
+                SmartAttribute {
+                    id: 194,
+                    name: "Temperature_Celsius",
+                    value: 36,
+                    worst: 52,
+                    raw: 36
+                },
+            ]
+        );
+        assert!(parse_smart_data(&[]).is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+//! Copies of large, mostly empty files such as VM images and databases.
+//!
+//! Only the data regions of the source are copied, so holes stay holes in
+//! the copy instead of being filled with zeros. Data regions are found
+//! with `SEEK_DATA`/`SEEK_HOLE` where the platform has them, and by
+//! skipping zero blocks otherwise. On file systems with copy-on-write
+//! clones (btrfs, XFS, APFS) the copy can instead share the source's
+//! blocks ("reflink"), which is instant whatever the size.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// The size of the blocks copied, and checked for zeros, at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// How [`copy`] made the copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// The copy shares the blocks of the source until either is modified.
+    Reflink,
+    /// The data regions were copied and the holes recreated.
+    Sparse,
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method = match self {
+            Method::Reflink => "reflink",
+            Method::Sparse => "sparse copy",
+        };
+        write!(f, "{method}")
+    }
+}
+
+/// Options of [`copy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOptions {
+    reflink: bool,
+    verify: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            reflink: false,
+            verify: true,
+        }
+    }
+}
+
+impl CopyOptions {
+    /// Creates the default options: a sparse copy, verified afterwards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to try a reflink first, falling back to a sparse copy
+    /// where the file system does not support them.
+    pub fn reflink(mut self, reflink: bool) -> Self {
+        self.reflink = reflink;
+        self
+    }
+
+    /// Sets whether to compare the SHA-256 of the copy with the source's.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+}
+
+/// The outcome of [`copy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyReport {
+    pub method: Method,
+    /// The size of the file, holes included.
+    pub size: u64,
+    /// The bytes read from the data regions of the source, 0 for reflinks.
+    pub copied: u64,
+    /// The lowercase hex SHA-256 of both files, when verified.
+    pub sha256: Option<String>,
+}
+
+/// Copies `source` to `destination`, preserving holes, and replacing the
+/// destination if it exists.
+///
+/// # Example
+/// ```
+/// use crabyknife::fastcopy::{copy, CopyOptions, Method};
+///
+/// let dir = std::env::temp_dir().join(format!("crabyknife-fastcopy-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("a"), "data").unwrap();
+///
+/// let report = copy(&dir.join("a"), &dir.join("b"), &CopyOptions::new()).unwrap();
+/// assert_eq!(report.method, Method::Sparse);
+/// assert_eq!(std::fs::read(dir.join("b")).unwrap(), b"data");
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or written, if both paths are
+/// the same file, or if the verification finds that the files differ.
+pub fn copy(
+    source: &Path,
+    destination: &Path,
+    options: &CopyOptions,
+) -> Result<CopyReport, Box<dyn std::error::Error>> {
+    let mut source_file =
+        File::open(source).map_err(|err| format!("failed to open {}: {err}", source.display()))?;
+    let size = source_file.metadata()?.len();
+    if destination.canonicalize().is_ok_and(|destination| {
+        source
+            .canonicalize()
+            .is_ok_and(|source| source == destination)
+    }) {
+        return Err(format!(
+            "{} and {} are the same file",
+            source.display(),
+            destination.display()
+        )
+        .into());
+    }
+
+    let (method, copied) = if options.reflink && reflink(source, destination).is_ok() {
+        (Method::Reflink, 0)
+    } else {
+        let mut destination_file = File::create(destination)
+            .map_err(|err| format!("failed to create {}: {err}", destination.display()))?;
+        let copied = copy_sparse(&mut source_file, &mut destination_file, size).map_err(|err| {
+            format!(
+                "failed to copy {} to {}: {err}",
+                source.display(),
+                destination.display()
+            )
+        })?;
+        destination_file.sync_all()?;
+        (Method::Sparse, copied)
+    };
+
+    let sha256 = if options.verify {
+        let expected = sha256_hex(source)?;
+        let actual = sha256_hex(destination)?;
+        if expected != actual {
+            return Err(format!(
+                "verification failed: {} has SHA-256 {expected}, but the copy {} has {actual}",
+                source.display(),
+                destination.display()
+            )
+            .into());
+        }
+        Some(actual)
+    } else {
+        None
+    };
+
+    Ok(CopyReport {
+        method,
+        size,
+        copied,
+        sha256,
+    })
+}
+
+/// Copies the data regions of `source`, `size` bytes long, to the empty
+/// `destination` and returns the bytes copied.
+fn copy_sparse(source: &mut File, destination: &mut File, size: u64) -> std::io::Result<u64> {
+    let mut copied = 0;
+    let mut offset = 0;
+    let mut buf = vec![0u8; BLOCK_SIZE];
+
+    while offset < size {
+        let Some((start, end)) = next_data(source, offset, size)? else {
+            break;
+        };
+
+        source.seek(SeekFrom::Start(start))?;
+        let mut position = start;
+        while position < end {
+            let len = (end - position).min(BLOCK_SIZE as u64) as usize;
+            source.read_exact(&mut buf[..len])?;
+            // Zero blocks inside data regions become holes too.
+            if buf[..len].iter().any(|byte| *byte != 0) {
+                destination.seek(SeekFrom::Start(position))?;
+                destination.write_all(&buf[..len])?;
+            }
+            position += len as u64;
+            copied += len as u64;
+        }
+        offset = end;
+    }
+
+    // Extends the copy to the full size, making trailing holes.
+    destination.set_len(size)?;
+    Ok(copied)
+}
+
+/// Finds the first data region at or after `offset`, `None` past the last one.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn next_data(file: &File, offset: u64, size: u64) -> std::io::Result<Option<(u64, u64)>> {
+    use std::os::fd::AsRawFd;
+
+    let seek = |offset: u64, whence| {
+        // SAFETY: lseek only reads the descriptor, which `file` keeps open.
+        let position = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if position < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(position as u64)
+        }
+    };
+
+    match seek(offset, libc::SEEK_DATA) {
+        Ok(start) => Ok(Some((start, seek(start, libc::SEEK_HOLE)?.min(size)))),
+        // No data after `offset`.
+        Err(err) if err.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+        // The file system cannot tell holes apart, so everything is data.
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => Ok(Some((offset, size))),
+        Err(err) => Err(err),
+    }
+}
+
+/// Without `SEEK_DATA`, the whole file is a data region, and holes are
+/// only recreated from zero blocks.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn next_data(_file: &File, offset: u64, size: u64) -> std::io::Result<Option<(u64, u64)>> {
+    Ok(Some((offset, size)))
+}
+
+/// Makes `destination` a clone sharing the blocks of `source`.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, destination: &Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = File::open(source)?;
+    let destination = File::create(destination)?;
+    // SAFETY: FICLONE takes the source descriptor, and both files stay open.
+    let result = unsafe { libc::ioctl(destination.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Makes `destination` a clone sharing the blocks of `source`.
+#[cfg(target_os = "macos")]
+fn reflink(source: &Path, destination: &Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = |path: &Path| {
+        std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))
+    };
+    let (source, destination_path) = (path(source)?, path(destination)?);
+
+    // clonefile only creates new files.
+    match std::fs::remove_file(destination) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    // SAFETY: both paths are NUL-terminated strings that outlive the call.
+    if unsafe { libc::clonefile(source.as_ptr(), destination_path.as_ptr(), 0) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_source: &Path, _destination: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Computes the lowercase hex SHA-256 of the file at `path`.
+fn sha256_hex(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut file =
+        File::open(path).map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BLOCK_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_sparse_copy() {
+        let dir = temp_dir("fastcopy-sparse");
+        let source = dir.join("disk.img");
+        let destination = dir.join("copy.img");
+
+        // 8 MiB with data in the middle and holes around it.
+        let mut file = File::create(&source).unwrap();
+        file.set_len(8 << 20).unwrap();
+        file.seek(SeekFrom::Start(3 << 20)).unwrap();
+        file.write_all(b"data in the middle").unwrap();
+        drop(file);
+
+        let report = copy(&source, &destination, &CopyOptions::new()).unwrap();
+        assert_eq!(report.method, Method::Sparse);
+        assert_eq!(report.size, 8 << 20);
+        assert_eq!(
+            std::fs::read(&source).unwrap(),
+            std::fs::read(&destination).unwrap()
+        );
+        assert_eq!(report.sha256.unwrap().len(), 64);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Only meaningful where the source itself is stored sparsely.
+            let allocated = |path: &Path| std::fs::metadata(path).unwrap().blocks() * 512;
+            if allocated(&source) < 1 << 20 {
+                assert!(report.copied < 8 << 20);
+                assert!(allocated(&destination) < 1 << 20);
+            }
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_errors() {
+        let dir = temp_dir("fastcopy-errors");
+        let source = dir.join("a");
+        std::fs::write(&source, "a").unwrap();
+
+        assert!(copy(&source, &source, &CopyOptions::new()).is_err());
+        assert!(copy(&dir.join("missing"), &dir.join("b"), &CopyOptions::new()).is_err());
+        // Without reflink support, --reflink falls back to a sparse copy.
+        let report = copy(
+            &source,
+            &dir.join("b"),
+            &CopyOptions::new().reflink(true).verify(false),
+        )
+        .unwrap();
+        assert_eq!(report.sha256, None);
+        assert_eq!(std::fs::read(dir.join("b")).unwrap(), b"a");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+//! A C ABI over some of the tools, so applications in other languages can
+//! embed them from a shared library. Only built with the `ffi` feature:
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! The declarations are in `include/crabyknife.h`, which can be generated
+//! again with `cbindgen --config cbindgen.toml --output include/crabyknife.h`.
+//!
+//! Strings are NUL-terminated UTF-8 both ways. Every string returned by a
+//! function is allocated by the library and must be released with
+//! [`crabyknife_string_free`]. Functions that can fail return NULL and, if
+//! `error` is not NULL, store a message in `*error`, to free the same way.
+
+use std::ffi::{c_char, CStr, CString};
+
+use sha2::{Digest, Sha256};
+
+use crate::prettify_xml;
+
+/// Prettifies the XML document `xml` with the default layout.
+///
+/// # Safety
+///
+/// `xml` must be a valid NUL-terminated string, and `error` NULL or a
+/// valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn crabyknife_prettify_xml(
+    xml: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    with_input(xml, error, |xml| Ok(prettify_xml::prettify_xml(xml)?))
+}
+
+/// Formats the JSON document `json` with two-space indentation.
+///
+/// # Safety
+///
+/// `json` must be a valid NUL-terminated string, and `error` NULL or a
+/// valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn crabyknife_prettify_json(
+    json: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    with_input(json, error, |json| {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|err| format!("invalid JSON: {err}"))?;
+        Ok(serde_json::to_string_pretty(&value)?)
+    })
+}
+
+/// Computes the SHA-256 of the `len` bytes at `data`, as lowercase hex.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or be NULL when `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn crabyknife_sha256_hex(data: *const u8, len: usize) -> *mut c_char {
+    let data = if len == 0 {
+        &[]
+    } else {
+        // SAFETY: the caller guarantees `len` readable bytes at `data`.
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    let hex: String = Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    into_c_string(hex)
+}
+
+/// Generates a random (version 4) UUID in its hyphenated form.
+#[no_mangle]
+pub extern "C" fn crabyknife_new_uuid() -> *mut c_char {
+    into_c_string(uuid::Uuid::new_v4().to_string())
+}
+
+/// Releases a string returned by the library. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `string` must be NULL or a string returned by the library, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn crabyknife_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: the string was created by `CString::into_raw`.
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Runs `tool` on the string `input`, returning its output or NULL with
+/// the error stored in `*error`.
+///
+/// # Safety
+///
+/// See the functions calling it.
+unsafe fn with_input(
+    input: *const c_char,
+    error: *mut *mut c_char,
+    tool: impl FnOnce(&str) -> Result<String, Box<dyn std::error::Error>>,
+) -> *mut c_char {
+    let result = if input.is_null() {
+        Err("the input is NULL".into())
+    } else {
+        // SAFETY: the caller guarantees a valid NUL-terminated string.
+        match unsafe { CStr::from_ptr(input) }.to_str() {
+            Ok(input) => tool(input),
+            Err(err) => Err(format!("the input is not UTF-8: {err}").into()),
+        }
+    };
+
+    match result {
+        Ok(output) => into_c_string(output),
+        Err(err) => {
+            if !error.is_null() {
+                // SAFETY: the caller guarantees `error` can be written to.
+                unsafe { *error = into_c_string(err.to_string()) };
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Hands `string` over to the caller. Strings cannot hold NUL bytes in C,
+/// so the output is cut at the first one.
+fn into_c_string(string: String) -> *mut c_char {
+    let mut bytes = string.into_bytes();
+    if let Some(nul) = bytes.iter().position(|byte| *byte == 0) {
+        bytes.truncate(nul);
+    }
+    CString::new(bytes).unwrap_or_default().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Takes back a string returned by the library.
+    fn take(string: *mut c_char) -> String {
+        assert!(!string.is_null());
+        let owned = unsafe { CStr::from_ptr(string) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { crabyknife_string_free(string) };
+        owned
+    }
+
+    #[test]
+    fn test_prettify() {
+        let mut error = std::ptr::null_mut();
+        let output = unsafe { crabyknife_prettify_xml(c"<a><b/></a>".as_ptr(), &mut error) };
+        assert_eq!(take(output), "<a>\n  <b />\n</a>");
+        assert!(error.is_null());
+
+        let output = unsafe { crabyknife_prettify_json(c"{\"a\":[1]}".as_ptr(), &mut error) };
+        assert_eq!(take(output), "{\n  \"a\": [\n    1\n  ]\n}");
+
+        let output = unsafe { crabyknife_prettify_json(c"{".as_ptr(), &mut error) };
+        assert!(output.is_null());
+        assert!(take(error).starts_with("invalid JSON"));
+
+        let output = unsafe { crabyknife_prettify_xml(std::ptr::null(), std::ptr::null_mut()) };
+        assert!(output.is_null());
+    }
+
+    #[test]
+    fn test_sha256_and_uuid() {
+        let data = b"abc";
+        assert_eq!(
+            take(unsafe { crabyknife_sha256_hex(data.as_ptr(), data.len()) }),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            take(unsafe { crabyknife_sha256_hex(std::ptr::null(), 0) }),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(take(crabyknife_new_uuid()).len(), 36);
+        unsafe { crabyknife_string_free(std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,657 @@
+//! File metadata for scripting: `stat`, `touch`, recursive permission
+//! changes and extended attributes.
+//!
+//! These behave the same on every platform instead of depending on which
+//! flavour of coreutils is installed. Timestamps are read and written as
+//! RFC 3339 dates in UTC, e.g. `2024-01-01T12:00:00Z`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The extended attribute macOS sets on downloaded files, which makes
+/// Gatekeeper ask before opening them.
+pub const QUARANTINE_XATTR: &str = "com.apple.quarantine";
+
+/// The kind of a file system entry, see [`FileInfo::file_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+impl std::fmt::Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file_type = match self {
+            FileType::File => "file",
+            FileType::Directory => "directory",
+            FileType::Symlink => "symlink",
+            FileType::Other => "other",
+        };
+        write!(f, "{file_type}")
+    }
+}
+
+/// The metadata of a file, see [`stat`].
+///
+/// Fields the platform or file system does not provide are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub file_type: FileType,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    /// The unix permission bits, e.g. `0o644`.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub readonly: bool,
+    /// Where a symlink points to, as stored in the link.
+    pub symlink_target: Option<PathBuf>,
+    /// The names of the extended attributes, `None` where they are not supported.
+    pub xattrs: Option<Vec<String>>,
+}
+
+impl FileInfo {
+    /// Converts the metadata to JSON, with timestamps as RFC 3339 strings.
+    pub fn to_json(&self) -> serde_json::Value {
+        let time = |time: Option<SystemTime>| time.map(format_timestamp);
+        serde_json::json!({
+            "path": self.path.to_string_lossy(),
+            "type": self.file_type.to_string(),
+            "size": self.size,
+            "modified": time(self.modified),
+            "accessed": time(self.accessed),
+            "created": time(self.created),
+            "mode": self.mode.map(|mode| format!("{mode:04o}")),
+            "uid": self.uid,
+            "gid": self.gid,
+            "readonly": self.readonly,
+            "symlink_target": self.symlink_target.as_ref().map(|target| target.to_string_lossy()),
+            "xattrs": self.xattrs,
+        })
+    }
+}
+
+/// Reads the metadata of `path`, without following a final symlink.
+///
+/// # Errors
+///
+/// Returns an error if the metadata cannot be read.
+pub fn stat(path: &Path) -> Result<FileInfo, Box<dyn std::error::Error>> {
+    let metadata = std::fs::symlink_metadata(path)
+        .map_err(|err| format!("failed to read metadata of {}: {err}", path.display()))?;
+
+    let file_type = if metadata.is_symlink() {
+        FileType::Symlink
+    } else if metadata.is_dir() {
+        FileType::Directory
+    } else if metadata.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    };
+    let symlink_target = if metadata.is_symlink() {
+        Some(std::fs::read_link(path)?)
+    } else {
+        None
+    };
+
+    #[cfg(unix)]
+    let (mode, uid, gid) = {
+        use std::os::unix::fs::MetadataExt;
+        (
+            Some(metadata.mode() & 0o7777),
+            Some(metadata.uid()),
+            Some(metadata.gid()),
+        )
+    };
+    #[cfg(not(unix))]
+    let (mode, uid, gid) = (None, None, None);
+
+    Ok(FileInfo {
+        path: path.to_path_buf(),
+        file_type,
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        created: metadata.created().ok(),
+        mode,
+        uid,
+        gid,
+        readonly: metadata.permissions().readonly(),
+        symlink_target,
+        xattrs: list_xattrs(path).ok(),
+    })
+}
+
+/// Sets the access and modification times of `path` to `time`, or to now
+/// without one, creating an empty file if it does not exist.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be created or its times cannot be set.
+pub fn touch(path: &Path, time: Option<SystemTime>) -> Result<(), Box<dyn std::error::Error>> {
+    let file = if path.exists() {
+        std::fs::File::open(path)
+    } else {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+    }
+    .map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+
+    let time = time.unwrap_or_else(SystemTime::now);
+    file.set_times(
+        std::fs::FileTimes::new()
+            .set_accessed(time)
+            .set_modified(time),
+    )
+    .map_err(|err| format!("failed to set the times of {}: {err}", path.display()))?;
+
+    Ok(())
+}
+
+/// Replaces the content of `path` through a temporary file in the same
+/// directory, so an interrupted write never leaves a truncated file behind.
+/// The file keeps its permissions, and is created if it does not exist.
+///
+/// # Errors
+///
+/// Returns the error of the system when the temporary file cannot be
+/// written or renamed over `path`, after removing it.
+pub fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.crabyknife-tmp"));
+
+    let result = std::fs::write(&temp_path, content)
+        .and_then(|()| match std::fs::metadata(path) {
+            Ok(metadata) => std::fs::set_permissions(&temp_path, metadata.permissions()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        })
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Lists the names of the extended attributes of `path`, without following
+/// a final symlink.
+///
+/// # Errors
+///
+/// Returns an error if the attributes cannot be read, e.g. on platforms or
+/// file systems without extended attributes.
+pub fn list_xattrs(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut names: Vec<String> = xattr::list(path)
+        .map_err(|err| {
+            format!(
+                "failed to list extended attributes of {}: {err}",
+                path.display()
+            )
+        })?
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Reads the extended attribute `name` of `path`, `None` if it is not set.
+///
+/// # Errors
+///
+/// Returns an error if the attribute cannot be read.
+pub fn get_xattr(path: &Path, name: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    xattr::get(path, name).map_err(|err| {
+        format!(
+            "failed to read extended attribute {name} of {}: {err}",
+            path.display()
+        )
+        .into()
+    })
+}
+
+/// Sets the extended attribute `name` of `path` to `value`.
+///
+/// # Errors
+///
+/// Returns an error if the attribute cannot be set, e.g. a name outside the
+/// `user.` namespace on Linux.
+pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    xattr::set(path, name, value).map_err(|err| {
+        format!(
+            "failed to set extended attribute {name} of {}: {err}",
+            path.display()
+        )
+        .into()
+    })
+}
+
+/// Removes the extended attributes of `path`, all of them or only `name`,
+/// and of everything below it with `recursive`. Symlinks are not followed.
+///
+/// # Returns
+///
+/// The paths and names of the removed attributes.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be read or an attribute cannot be removed.
+pub fn strip_xattrs(
+    path: &Path,
+    name: Option<&str>,
+    recursive: bool,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let mut removed = Vec::new();
+    strip_entry(path, name, recursive, &mut removed)?;
+    Ok(removed)
+}
+
+fn strip_entry(
+    path: &Path,
+    name: Option<&str>,
+    recursive: bool,
+    removed: &mut Vec<(PathBuf, String)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for attribute in list_xattrs(path)? {
+        if name.is_some_and(|name| name != attribute) {
+            continue;
+        }
+        xattr::remove(path, &attribute).map_err(|err| {
+            format!(
+                "failed to remove extended attribute {attribute} of {}: {err}",
+                path.display()
+            )
+        })?;
+        removed.push((path.to_path_buf(), attribute));
+    }
+
+    if recursive && std::fs::symlink_metadata(path)?.is_dir() {
+        let mut entries = std::fs::read_dir(path)
+            .map_err(|err| format!("failed to read directory {}: {err}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            strip_entry(&entry, name, recursive, removed)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A permission change made, or planned, by [`chmod_recursive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeChange {
+    pub path: PathBuf,
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Sets the permission bits of `path` and everything below it to `mode`.
+///
+/// Symlinks are skipped, so their targets are never changed, and entries
+/// that already have `mode` are left alone. With `dry_run`, nothing is
+/// changed but the changes are still returned.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be read or a mode cannot be set,
+/// and on platforms without unix permissions.
+pub fn chmod_recursive(
+    path: &Path,
+    mode: u32,
+    dry_run: bool,
+) -> Result<Vec<ModeChange>, Box<dyn std::error::Error>> {
+    let mut changes = Vec::new();
+    chmod_entry(path, mode, dry_run, &mut changes)?;
+    Ok(changes)
+}
+
+#[cfg(unix)]
+fn chmod_entry(
+    path: &Path,
+    mode: u32,
+    dry_run: bool,
+    changes: &mut Vec<ModeChange>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let metadata = std::fs::symlink_metadata(path)
+        .map_err(|err| format!("failed to read metadata of {}: {err}", path.display()))?;
+    if metadata.is_symlink() {
+        return Ok(());
+    }
+
+    let from = metadata.mode() & 0o7777;
+    if from != mode {
+        if !dry_run {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .map_err(|err| format!("failed to change mode of {}: {err}", path.display()))?;
+        }
+        changes.push(ModeChange {
+            path: path.to_path_buf(),
+            from,
+            to: mode,
+        });
+    }
+
+    if metadata.is_dir() {
+        let mut entries = std::fs::read_dir(path)
+            .map_err(|err| format!("failed to read directory {}: {err}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            chmod_entry(&entry, mode, dry_run, changes)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn chmod_entry(
+    _path: &Path,
+    _mode: u32,
+    _dry_run: bool,
+    _changes: &mut Vec<ModeChange>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err("unix permissions are not supported on this platform".into())
+}
+
+/// Parses an octal permission mode, e.g. `755` or `0644`.
+///
+/// # Errors
+///
+/// Returns an error for non-octal modes and modes above `7777`.
+pub fn parse_mode(mode: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|parsed| *parsed <= 0o7777)
+        .ok_or_else(|| format!("invalid mode ({mode}), expected octal digits like 644").into())
+}
+
+/// Formats a timestamp as an RFC 3339 date in UTC, e.g. `2024-01-01T00:00:00Z`.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use crabyknife::fileinfo::format_timestamp;
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+/// assert_eq!(format_timestamp(time), "2024-01-01T00:00:00Z");
+/// ```
+pub fn format_timestamp(time: SystemTime) -> String {
+    let ((year, month, day), seconds) = utc_date_time(time);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// The UTC date of `time`, as year, month and day, and its time of day in
+/// seconds.
+pub(crate) fn utc_date_time(time: SystemTime) -> ((i64, u32, u32), i64) {
+    let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs_f64().ceil() as i64),
+    };
+    let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    (civil_from_days(days), seconds)
+}
+
+/// Parses a UTC date, `YYYY-MM-DD`, optionally followed by a time,
+/// `THH:MM` or `THH:MM:SS` (a space also separates them), and a final `Z`.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use crabyknife::fileinfo::parse_date;
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+/// assert_eq!(parse_date("2024-01-01").unwrap(), time);
+/// assert_eq!(parse_date("2024-01-01 00:01").unwrap(), time + Duration::from_secs(60));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error for dates in other formats and out-of-range fields.
+pub fn parse_date(date: &str) -> Result<SystemTime, Box<dyn std::error::Error>> {
+    let invalid = || format!("invalid date ({date}), expected YYYY-MM-DD[THH:MM[:SS]]");
+
+    let trimmed = date.strip_suffix('Z').unwrap_or(date);
+    let (day_part, time_part) = match trimmed.split_once(['T', ' ']) {
+        Some((day_part, time_part)) => (day_part, Some(time_part)),
+        None => (trimmed, None),
+    };
+
+    let fields: Vec<&str> = day_part.split('-').collect();
+    let [year, month, day] = fields[..] else {
+        return Err(invalid().into());
+    };
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: u32 = month.parse().map_err(|_| invalid())?;
+    let day: u32 = day.parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return Err(invalid().into());
+    }
+
+    let mut seconds = days_from_civil(year, month, day) * 86_400;
+    if let Some(time_part) = time_part {
+        let fields: Vec<u32> = time_part
+            .split(':')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let (hours, minutes, secs) = match fields[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, secs] => (hours, minutes, secs),
+            _ => return Err(invalid().into()),
+        };
+        if hours > 23 || minutes > 59 || secs > 59 {
+            return Err(invalid().into());
+        }
+        seconds += i64::from(hours * 3600 + minutes * 60 + secs);
+    }
+
+    let time = if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    };
+    time.ok_or_else(|| format!("date out of range ({date})").into())
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+///
+/// This is Howard Hinnant's `days_from_civil` algorithm, which counts in
+/// 400-year eras starting in March so leap days fall at the end of a year.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_dates() {
+        for date in [
+            "1970-01-01T00:00:00Z",
+            "2000-02-29T23:59:59Z",
+            "1969-12-31T23:59:59Z",
+            "1900-03-01T12:00:00Z",
+            "2400-12-31T00:00:00Z",
+        ] {
+            assert_eq!(format_timestamp(parse_date(date).unwrap()), date);
+        }
+        assert_eq!(
+            parse_date("1970-01-02").unwrap(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(86_400)
+        );
+
+        for date in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-01-01T24:00",
+            "2024/01/01",
+            "yesterday",
+        ] {
+            assert!(parse_date(date).is_err(), "{date}");
+        }
+    }
+
+    #[test]
+    fn test_touch_and_stat() {
+        let dir = temp_dir("fileinfo-touch");
+        let path = dir.join("new.txt");
+
+        let date = parse_date("2024-01-01T12:00:00Z").unwrap();
+        touch(&path, Some(date)).unwrap();
+        let info = stat(&path).unwrap();
+        assert_eq!(info.file_type, FileType::File);
+        assert_eq!(info.size, 0);
+        assert_eq!(info.modified, Some(date));
+        assert_eq!(info.to_json()["modified"], "2024-01-01T12:00:00Z");
+
+        std::fs::write(&path, "content").unwrap();
+        touch(&path, Some(date)).unwrap();
+        assert_eq!(stat(&path).unwrap().size, 7);
+
+        #[cfg(unix)]
+        {
+            let link = dir.join("link");
+            std::os::unix::fs::symlink("new.txt", &link).unwrap();
+            let info = stat(&link).unwrap();
+            assert_eq!(info.file_type, FileType::Symlink);
+            assert_eq!(info.symlink_target, Some(PathBuf::from("new.txt")));
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_xattrs() {
+        let dir = temp_dir("fileinfo-xattr");
+        let path = dir.join("download.zip");
+        std::fs::write(&path, "").unwrap();
+
+        if set_xattr(&path, "user.a", b"1").is_err() {
+            // The temp directory is on a file system without user attributes.
+            std::fs::remove_dir_all(dir).unwrap();
+            return;
+        }
+        set_xattr(&path, "user.b", b"2").unwrap();
+        set_xattr(&dir, "user.a", b"3").unwrap();
+        assert_eq!(list_xattrs(&path).unwrap(), ["user.a", "user.b"]);
+        assert_eq!(get_xattr(&path, "user.b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(get_xattr(&path, "user.c").unwrap(), None);
+        assert_eq!(
+            stat(&path).unwrap().xattrs,
+            Some(vec!["user.a".to_string(), "user.b".to_string()])
+        );
+
+        assert_eq!(
+            strip_xattrs(&dir, Some("user.a"), true).unwrap(),
+            [
+                (dir.clone(), "user.a".to_string()),
+                (path.clone(), "user.a".to_string())
+            ]
+        );
+        assert_eq!(
+            strip_xattrs(&path, None, false).unwrap(),
+            [(path.clone(), "user.b".to_string())]
+        );
+        assert!(list_xattrs(&path).unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_chmod_recursive() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("fileinfo-chmod");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a"), "").unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(dir.join("sub"), std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::fs::set_permissions(dir.join("sub/a"), std::fs::Permissions::from_mode(0o600))
+            .unwrap();
+
+        let changes = chmod_recursive(&dir, 0o755, true).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                ModeChange {
+                    path: dir.join("sub"),
+                    from: 0o700,
+                    to: 0o755
+                },
+                ModeChange {
+                    path: dir.join("sub/a"),
+                    from: 0o600,
+                    to: 0o755
+                },
+            ]
+        );
+        assert_eq!(stat(&dir.join("sub/a")).unwrap().mode, Some(0o600));
+
+        assert_eq!(chmod_recursive(&dir, 0o755, false).unwrap(), changes);
+        assert_eq!(stat(&dir.join("sub/a")).unwrap().mode, Some(0o755));
+        assert!(chmod_recursive(&dir, 0o755, false).unwrap().is_empty());
+
+        assert_eq!(parse_mode("0644").unwrap(), 0o644);
+        assert!(parse_mode("888").is_err());
+        assert!(parse_mode("17777").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
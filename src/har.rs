@@ -0,0 +1,222 @@
+//! Analysis of HAR (HTTP Archive) files exported by browser developer tools.
+//!
+//! Only the fields needed for a summary are read from each entry of
+//! `log.entries`: the request method and url, the response status and
+//! size, the total time and whether the response came from a cache.
+
+use serde_json::Value;
+
+/// A single request of a HAR file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    /// The total time of the request in milliseconds.
+    pub time_ms: f64,
+    /// The size of the response body in bytes, as decoded by the browser.
+    pub size: u64,
+    /// Whether the response was served from a cache or revalidated (`304`).
+    pub cached: bool,
+}
+
+impl Entry {
+    /// The host name of the request url, without user info nor port.
+    pub fn domain(&self) -> &str {
+        let rest = self
+            .url
+            .split_once("://")
+            .map_or(self.url.as_str(), |(_, rest)| rest);
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        if host.starts_with('[') {
+            // An IPv6 literal keeps its colons.
+            return host.split_inclusive(']').next().unwrap_or(host);
+        }
+        host.split(':').next().unwrap_or(host)
+    }
+}
+
+/// Totals per domain, see [`Summary::domains`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainSize {
+    pub domain: String,
+    pub requests: usize,
+    pub bytes: u64,
+}
+
+/// An overview of the requests of a HAR file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub requests: usize,
+    pub cached: usize,
+    pub bytes: u64,
+    /// The slowest requests, slowest first.
+    pub slowest: Vec<Entry>,
+    /// Response sizes by domain, largest first.
+    pub domains: Vec<DomainSize>,
+}
+
+impl Summary {
+    /// The share of requests served from a cache, between 0 and 1.
+    pub fn cache_hit_ratio(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.cached as f64 / self.requests as f64
+        }
+    }
+}
+
+/// Reads the entries of a HAR file.
+///
+/// # Errors
+///
+/// Returns an error if `har` is not JSON or has no `log.entries` array.
+pub fn parse(har: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let har: Value = serde_json::from_str(har).map_err(|err| format!("invalid HAR file: {err}"))?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or("invalid HAR file: no log.entries array")?;
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let request = &entry["request"];
+            let response = &entry["response"];
+            let status = response["status"].as_u64().unwrap_or_default() as u16;
+            // Sizes are -1 when unknown.
+            let size = response["content"]["size"]
+                .as_i64()
+                .or_else(|| response["bodySize"].as_i64())
+                .unwrap_or_default()
+                .max(0) as u64;
+            let from_cache = !entry["_fromCache"].is_null()
+                || entry["cache"]["afterRequest"].is_object()
+                || response["_transferSize"].as_i64() == Some(0);
+
+            Entry {
+                method: request["method"].as_str().unwrap_or_default().to_string(),
+                url: request["url"].as_str().unwrap_or_default().to_string(),
+                status,
+                time_ms: entry["time"].as_f64().unwrap_or_default().max(0.0),
+                size,
+                cached: status == 304 || from_cache,
+            }
+        })
+        .collect())
+}
+
+/// Summarizes `entries`, keeping the `top` slowest requests.
+///
+/// # Example
+/// ```
+/// use crabyknife::har::{parse, summarize};
+///
+/// let har = r#"{"log": {"entries": [
+///   {"time": 120, "request": {"method": "GET", "url": "https://example.com/"},
+///    "response": {"status": 200, "content": {"size": 1000}}},
+///   {"time": 5, "request": {"method": "GET", "url": "https://cdn.example.com/a.css"},
+///    "response": {"status": 304, "content": {"size": 0}}}
+/// ]}}"#;
+/// let summary = summarize(&parse(har).unwrap(), 1);
+/// assert_eq!(summary.requests, 2);
+/// assert_eq!(summary.cache_hit_ratio(), 0.5);
+/// assert_eq!(summary.slowest[0].url, "https://example.com/");
+/// ```
+pub fn summarize(entries: &[Entry], top: usize) -> Summary {
+    let mut slowest = entries.to_vec();
+    slowest.sort_by(|a, b| b.time_ms.total_cmp(&a.time_ms));
+    slowest.truncate(top);
+
+    let mut domains: Vec<DomainSize> = Vec::new();
+    for entry in entries {
+        let domain = entry.domain();
+        match domains.iter_mut().find(|size| size.domain == domain) {
+            Some(size) => {
+                size.requests += 1;
+                size.bytes += entry.size;
+            }
+            None => domains.push(DomainSize {
+                domain: domain.to_string(),
+                requests: 1,
+                bytes: entry.size,
+            }),
+        }
+    }
+    domains.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.domain.cmp(&b.domain)));
+
+    Summary {
+        requests: entries.len(),
+        cached: entries.iter().filter(|entry| entry.cached).count(),
+        bytes: entries.iter().map(|entry| entry.size).sum(),
+        slowest,
+        domains,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HAR: &str = r#"{"log": {"version": "1.2", "entries": [
+        {"time": 80.5, "request": {"method": "GET", "url": "https://example.com/index.html"},
+         "response": {"status": 200, "bodySize": 500, "content": {"size": 2000}}},
+        {"time": 310, "request": {"method": "POST", "url": "https://api.example.com:8443/v1/items?x=1"},
+         "response": {"status": 201, "bodySize": -1, "content": {"size": -1}}},
+        {"time": 2, "_fromCache": "memory", "request": {"method": "GET", "url": "https://example.com/app.js"},
+         "response": {"status": 200, "content": {"size": 3000}}}
+    ]}}"#;
+
+    #[test]
+    fn test_parse_entries() {
+        let entries = parse(HAR).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].method, "POST");
+        assert_eq!(entries[1].status, 201);
+        assert_eq!(entries[1].size, 0);
+        assert_eq!(entries[0].size, 2000);
+        assert!(!entries[0].cached);
+        assert!(entries[2].cached);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&parse(HAR).unwrap(), 2);
+        assert_eq!(summary.requests, 3);
+        assert_eq!(summary.cached, 1);
+        assert_eq!(summary.bytes, 5000);
+        let slowest: Vec<f64> = summary.slowest.iter().map(|e| e.time_ms).collect();
+        assert_eq!(slowest, vec![310.0, 80.5]);
+        assert_eq!(
+            summary.domains,
+            vec![
+                DomainSize {
+                    domain: "example.com".to_string(),
+                    requests: 2,
+                    bytes: 5000
+                },
+                DomainSize {
+                    domain: "api.example.com".to_string(),
+                    requests: 1,
+                    bytes: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_domain() {
+        let mut entry = parse(HAR).unwrap().remove(0);
+        entry.url = "http://user:pass@[::1]:8080/path".to_string();
+        assert_eq!(entry.domain(), "[::1]");
+    }
+
+    #[test]
+    fn test_invalid_har() {
+        assert!(parse("{}").is_err());
+        assert!(parse("not json").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! ANSI syntax highlighting of XML for terminals.
+//!
+//! [`Highlighter`] wraps a writer and colors the markup written through it,
+//! so it can sit behind the streaming prettifier. It tracks its position in
+//! the markup across writes, so tags may be split between writes.
+
+use std::io::{IsTerminal, Write};
+
+const RESET: &[u8] = b"\x1b[0m";
+const TAG: &[u8] = b"\x1b[34m";
+const ATTRIBUTE: &[u8] = b"\x1b[36m";
+const VALUE: &[u8] = b"\x1b[32m";
+const COMMENT: &[u8] = b"\x1b[90m";
+const CDATA: &[u8] = b"\x1b[35m";
+const DECLARATION: &[u8] = b"\x1b[33m";
+
+/// Whether to color output written to `stream`: only for terminals, and
+/// never when the `NO_COLOR` environment variable is set to a non-empty value.
+pub fn should_color(stream: &impl IsTerminal) -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    stream.is_terminal() && !no_color
+}
+
+/// Colors `xml` for a terminal.
+///
+/// # Example
+/// ```
+/// use crabyknife::highlight::highlight;
+///
+/// assert_eq!(
+///     highlight(r#"<a id="1">x</a>"#),
+///     "\x1b[34m<a\x1b[0m \x1b[36mid\x1b[0m=\x1b[32m\"1\"\x1b[0m\x1b[34m>\x1b[0mx\x1b[34m</a>\x1b[0m"
+/// );
+/// ```
+pub fn highlight(xml: &str) -> String {
+    let mut highlighter = Highlighter::new(Vec::new());
+    highlighter.push(xml.as_bytes());
+    String::from_utf8_lossy(&highlighter.pending).into_owned()
+}
+
+/// Where in the markup the highlighter is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After `<`, before knowing what it opens.
+    Open,
+    /// After `<!`, before knowing what it opens.
+    Bang,
+    TagName,
+    /// Between attributes.
+    InTag,
+    AttributeName,
+    AttributeValue(u8),
+    /// After the `/` of a self-closing tag.
+    TagClose,
+    Comment {
+        dashes: u8,
+    },
+    CData {
+        brackets: u8,
+    },
+    ProcessingInstruction {
+        question: bool,
+    },
+    Doctype {
+        depth: u8,
+    },
+}
+
+/// A writer coloring the XML markup written through it.
+pub struct Highlighter<W: Write> {
+    inner: W,
+    state: State,
+    /// Colored bytes not written to `inner` yet.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Highlighter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            state: State::Text,
+            pending: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, buf: &[u8]) {
+        for &byte in buf {
+            self.push_byte(byte);
+        }
+    }
+
+    fn emit(&mut self, parts: &[&[u8]]) {
+        for part in parts {
+            self.pending.extend_from_slice(part);
+        }
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.state = match self.state {
+            State::Text if byte == b'<' => State::Open,
+            State::Text => {
+                self.emit(&[&[byte]]);
+                State::Text
+            }
+            State::Open => match byte {
+                b'!' => State::Bang,
+                b'?' => {
+                    self.emit(&[DECLARATION, b"<?"]);
+                    State::ProcessingInstruction { question: false }
+                }
+                _ => {
+                    self.emit(&[TAG, b"<", &[byte]]);
+                    State::TagName
+                }
+            },
+            State::Bang => match byte {
+                b'-' => {
+                    self.emit(&[COMMENT, b"<!-"]);
+                    State::Comment { dashes: 0 }
+                }
+                b'[' => {
+                    self.emit(&[CDATA, b"<!["]);
+                    State::CData { brackets: 0 }
+                }
+                _ => {
+                    self.emit(&[DECLARATION, b"<!", &[byte]]);
+                    State::Doctype {
+                        depth: u8::from(byte == b'['),
+                    }
+                }
+            },
+            State::TagName => match byte {
+                b'>' => {
+                    self.emit(&[b">", RESET]);
+                    State::Text
+                }
+                _ if byte.is_ascii_whitespace() => {
+                    self.emit(&[RESET, &[byte]]);
+                    State::InTag
+                }
+                _ => {
+                    self.emit(&[&[byte]]);
+                    State::TagName
+                }
+            },
+            State::InTag => match byte {
+                b'>' => {
+                    self.emit(&[TAG, b">", RESET]);
+                    State::Text
+                }
+                b'/' => {
+                    self.emit(&[TAG, b"/"]);
+                    State::TagClose
+                }
+                b'"' | b'\'' => {
+                    self.emit(&[VALUE, &[byte]]);
+                    State::AttributeValue(byte)
+                }
+                b'=' => {
+                    self.emit(&[b"="]);
+                    State::InTag
+                }
+                _ if byte.is_ascii_whitespace() => {
+                    self.emit(&[&[byte]]);
+                    State::InTag
+                }
+                _ => {
+                    self.emit(&[ATTRIBUTE, &[byte]]);
+                    State::AttributeName
+                }
+            },
+            State::AttributeName => match byte {
+                b'=' => {
+                    self.emit(&[RESET, b"="]);
+                    State::InTag
+                }
+                b'>' => {
+                    self.emit(&[RESET, TAG, b">", RESET]);
+                    State::Text
+                }
+                b'/' => {
+                    self.emit(&[RESET, TAG, b"/"]);
+                    State::TagClose
+                }
+                _ if byte.is_ascii_whitespace() => {
+                    self.emit(&[RESET, &[byte]]);
+                    State::InTag
+                }
+                _ => {
+                    self.emit(&[&[byte]]);
+                    State::AttributeName
+                }
+            },
+            State::AttributeValue(quote) => {
+                self.emit(&[&[byte]]);
+                if byte == quote {
+                    self.emit(&[RESET]);
+                    State::InTag
+                } else {
+                    State::AttributeValue(quote)
+                }
+            }
+            State::TagClose => {
+                self.emit(&[&[byte]]);
+                if byte == b'>' {
+                    self.emit(&[RESET]);
+                    State::Text
+                } else {
+                    State::TagClose
+                }
+            }
+            State::Comment { dashes } => {
+                self.emit(&[&[byte]]);
+                match byte {
+                    b'>' if dashes >= 2 => {
+                        self.emit(&[RESET]);
+                        State::Text
+                    }
+                    b'-' => State::Comment {
+                        dashes: dashes.saturating_add(1),
+                    },
+                    _ => State::Comment { dashes: 0 },
+                }
+            }
+            State::CData { brackets } => {
+                self.emit(&[&[byte]]);
+                match byte {
+                    b'>' if brackets >= 2 => {
+                        self.emit(&[RESET]);
+                        State::Text
+                    }
+                    b']' => State::CData {
+                        brackets: brackets.saturating_add(1),
+                    },
+                    _ => State::CData { brackets: 0 },
+                }
+            }
+            State::ProcessingInstruction { question } => {
+                self.emit(&[&[byte]]);
+                if byte == b'>' && question {
+                    self.emit(&[RESET]);
+                    State::Text
+                } else {
+                    State::ProcessingInstruction {
+                        question: byte == b'?',
+                    }
+                }
+            }
+            State::Doctype { depth } => {
+                self.emit(&[&[byte]]);
+                match byte {
+                    b'>' if depth == 0 => {
+                        self.emit(&[RESET]);
+                        State::Text
+                    }
+                    b'[' => State::Doctype {
+                        depth: depth.saturating_add(1),
+                    },
+                    b']' => State::Doctype {
+                        depth: depth.saturating_sub(1),
+                    },
+                    _ => State::Doctype { depth },
+                }
+            }
+        };
+    }
+}
+
+impl<W: Write> Write for Highlighter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.push(buf);
+        self.inner.write_all(&self.pending)?;
+        self.pending.clear();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_cdata_and_declarations() {
+        assert_eq!(
+            highlight("<?xml version=\"1.0\"?><!DOCTYPE a [<!ENTITY e \"x\">]><!-- - -->"),
+            concat!(
+                "\x1b[33m<?xml version=\"1.0\"?>\x1b[0m",
+                "\x1b[33m<!DOCTYPE a [<!ENTITY e \"x\">]>\x1b[0m",
+                "\x1b[90m<!-- - -->\x1b[0m"
+            )
+        );
+        assert_eq!(
+            highlight("<![CDATA[a]b]]>"),
+            "\x1b[35m<![CDATA[a]b]]>\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_self_closing_tag() {
+        assert_eq!(
+            highlight("<b flag='x' />"),
+            "\x1b[34m<b\x1b[0m \x1b[36mflag\x1b[0m=\x1b[32m'x'\x1b[0m \x1b[34m/>\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_tags_split_across_writes() {
+        let mut highlighter = Highlighter::new(Vec::new());
+        for part in ["<a", " i", "d=\"1", "\">t</", "a>"] {
+            highlighter.write_all(part.as_bytes()).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(highlighter.inner).unwrap(),
+            highlight("<a id=\"1\">t</a>")
+        );
+    }
+}
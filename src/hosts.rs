@@ -0,0 +1,381 @@
+//! Edits the hosts file, keeping the entries added by crabyknife in a
+//! managed block between two marker comments:
+//!
+//! ```text
+//! # BEGIN crabyknife managed hosts
+//! 127.0.0.1 dev.local
+//! # END crabyknife managed hosts
+//! ```
+//!
+//! Only the block is ever changed, so the rest of the file stays byte for
+//! byte as it was, and every edit is idempotent: adding an entry twice or
+//! removing one that is gone changes nothing. The block is appended on the
+//! first entry added, after a line ending if the file does not end with
+//! one, and removed with the last one.
+
+use std::net::IpAddr;
+use std::path::Path;
+
+/// The hosts file of the system.
+#[cfg(windows)]
+pub const HOSTS_FILE: &str = r"C:\Windows\System32\drivers\etc\hosts";
+#[cfg(not(windows))]
+pub const HOSTS_FILE: &str = "/etc/hosts";
+
+/// The comments around the managed block.
+const BEGIN: &str = "# BEGIN crabyknife managed hosts";
+const END: &str = "# END crabyknife managed hosts";
+
+/// A hostname and one of its addresses, from a line of a hosts file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub address: IpAddr,
+    pub name: String,
+    /// Whether the line is in the managed block.
+    pub managed: bool,
+}
+
+/// The lines of a hosts file, with where the managed block is.
+struct Lines<'a> {
+    content: &'a str,
+    lines: Vec<&'a str>,
+    /// The byte offset of each line in the content, then its length.
+    offsets: Vec<usize>,
+    /// The indices of the BEGIN and END lines.
+    block: Option<(usize, usize)>,
+    /// The line ending of the file, `\r\n` on Windows.
+    newline: &'static str,
+}
+
+impl<'a> Lines<'a> {
+    fn parse(content: &'a str) -> Result<Self, Box<dyn std::error::Error>> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut offsets = vec![0];
+        offsets.extend(content.split_inclusive('\n').scan(0, |offset, line| {
+            *offset += line.len();
+            Some(*offset)
+        }));
+        let find = |marker: &str| lines.iter().position(|line| line.trim() == marker);
+        let block = match (find(BEGIN), find(END)) {
+            (None, None) => None,
+            (Some(begin), Some(end)) if begin < end => Some((begin, end)),
+            _ => {
+                return Err(format!(
+                    "the managed block of the hosts file is broken, expected \"{BEGIN}\" then \"{END}\""
+                )
+                .into())
+            }
+        };
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        Ok(Self {
+            content,
+            lines,
+            offsets,
+            block,
+            newline,
+        })
+    }
+
+    /// The entries of the managed block.
+    fn managed(&self) -> Vec<Entry> {
+        match self.block {
+            Some((begin, end)) => parse_lines(&self.lines[begin + 1..end], true),
+            None => Vec::new(),
+        }
+    }
+
+    /// The file with `entries` as the managed block, which is removed
+    /// when there are none. The bytes around the block are kept as they are.
+    fn with_managed(&self, entries: &[Entry]) -> String {
+        let (before, after) = match self.block {
+            Some((begin, end)) => (
+                &self.content[..self.offsets[begin]],
+                &self.content[self.offsets[end + 1]..],
+            ),
+            None => (self.content, ""),
+        };
+        let mut content = before.to_string();
+        if !entries.is_empty() {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push_str(self.newline);
+            }
+            for line in std::iter::once(BEGIN.to_string())
+                .chain(
+                    entries
+                        .iter()
+                        .map(|entry| format!("{}\t{}", entry.address, entry.name)),
+                )
+                .chain(std::iter::once(END.to_string()))
+            {
+                content.push_str(&line);
+                content.push_str(self.newline);
+            }
+        }
+        content.push_str(after);
+        content
+    }
+}
+
+/// Parses the entries of hosts file `lines`, skipping comments and
+/// malformed lines.
+fn parse_lines(lines: &[&str], managed: bool) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for line in lines {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(Ok(address)) = fields.next().map(str::parse::<IpAddr>) else {
+            continue;
+        };
+        entries.extend(fields.map(|name| Entry {
+            address,
+            name: name.to_string(),
+            managed,
+        }));
+    }
+    entries
+}
+
+/// The entries of the hosts file `content`, in file order.
+///
+/// # Example
+/// ```
+/// use crabyknife::hosts::{add, entries};
+///
+/// let content = add("127.0.0.1 localhost\n", "dev.local", "127.0.0.1".parse().unwrap())
+///     .unwrap()
+///     .unwrap();
+/// let managed: Vec<_> = entries(&content).unwrap().into_iter().filter(|entry| entry.managed).collect();
+/// assert_eq!(managed[0].name, "dev.local");
+/// ```
+///
+/// # Errors
+///
+/// Returns an error when only one of the markers of the managed block is
+/// found, or they are out of order.
+pub fn entries(content: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let lines = Lines::parse(content)?;
+    Ok(match lines.block {
+        Some((begin, end)) => {
+            let mut entries = parse_lines(&lines.lines[..begin], false);
+            entries.extend(lines.managed());
+            entries.extend(parse_lines(&lines.lines[end + 1..], false));
+            entries
+        }
+        None => parse_lines(&lines.lines, false),
+    })
+}
+
+/// Adds `name` pointing to `address` to the managed block of the hosts file
+/// `content`, replacing the managed address of `name` of the same IP
+/// version. Returns the new content, `None` when the entry is there already.
+///
+/// # Errors
+///
+/// Returns an error for invalid hostnames, and a broken managed block.
+pub fn add(
+    content: &str,
+    name: &str,
+    address: IpAddr,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    check_name(name)?;
+    let lines = Lines::parse(content)?;
+    let mut managed = lines.managed();
+    if managed
+        .iter()
+        .any(|entry| entry.name.eq_ignore_ascii_case(name) && entry.address == address)
+    {
+        return Ok(None);
+    }
+    managed.retain(|entry| {
+        !(entry.name.eq_ignore_ascii_case(name) && entry.address.is_ipv4() == address.is_ipv4())
+    });
+    managed.push(Entry {
+        address,
+        name: name.to_string(),
+        managed: true,
+    });
+    Ok(Some(lines.with_managed(&managed)))
+}
+
+/// Removes `name` from the managed block of the hosts file `content`, only
+/// its entry for `address` if given. Returns the new content, `None` when
+/// there was nothing to remove.
+///
+/// # Errors
+///
+/// Returns an error for a broken managed block.
+pub fn remove(
+    content: &str,
+    name: &str,
+    address: Option<IpAddr>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let lines = Lines::parse(content)?;
+    let mut managed = lines.managed();
+    let count = managed.len();
+    managed.retain(|entry| {
+        !(entry.name.eq_ignore_ascii_case(name)
+            && address.is_none_or(|address| entry.address == address))
+    });
+    if managed.len() == count {
+        return Ok(None);
+    }
+    Ok(Some(lines.with_managed(&managed)))
+}
+
+/// Writes `content` to the hosts file at `path`, first copying the file to
+/// `<path>.crabyknife.bak` when `backup`. Returns the path of the backup.
+///
+/// The file is replaced through a temporary file, see
+/// [`fileinfo::write_atomically`](crate::fileinfo::write_atomically), but
+/// written in place when it is mounted on its own, e.g. in containers, as
+/// nothing can be renamed over it then.
+///
+/// # Errors
+///
+/// Returns the error of the system when the backup or the file cannot be
+/// written, so permission errors can be told apart.
+pub fn write(
+    path: &Path,
+    content: &str,
+    backup: bool,
+) -> std::io::Result<Option<std::path::PathBuf>> {
+    let backup = if backup && path.exists() {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".crabyknife.bak");
+        let backup = std::path::PathBuf::from(backup);
+        std::fs::copy(path, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+    match crate::fileinfo::write_atomically(path, content) {
+        Err(err) if err.kind() == std::io::ErrorKind::ResourceBusy => {
+            std::fs::write(path, content)?
+        }
+        result => result?,
+    }
+    Ok(backup)
+}
+
+/// Fails for a hostname that would break the line of its entry.
+fn check_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let valid = !name.is_empty()
+        && name.len() <= 253
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'))
+        && !name.starts_with(['-', '.']);
+    if !valid {
+        return Err(format!("invalid hostname ({name})").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTS: &str = "127.0.0.1\tlocalhost\n::1\tlocalhost\n";
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let added = add(HOSTS, "dev.local", ip("127.0.0.1")).unwrap().unwrap();
+        assert_eq!(
+            added,
+            format!("{HOSTS}{BEGIN}\n127.0.0.1\tdev.local\n{END}\n")
+        );
+        // Adding again changes nothing, and another address replaces the
+        // one of the same IP version.
+        assert_eq!(add(&added, "Dev.Local", ip("127.0.0.1")).unwrap(), None);
+        let replaced = add(&added, "dev.local", ip("127.0.0.2")).unwrap().unwrap();
+        let both = add(&replaced, "dev.local", ip("::1")).unwrap().unwrap();
+        assert_eq!(
+            both,
+            format!("{HOSTS}{BEGIN}\n127.0.0.2\tdev.local\n::1\tdev.local\n{END}\n")
+        );
+
+        let one = remove(&both, "dev.local", Some(ip("::1")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(one, replaced);
+        assert_eq!(remove(&one, "api.local", None).unwrap(), None);
+        // The block goes with its last entry.
+        assert_eq!(remove(&one, "dev.local", None).unwrap().unwrap(), HOSTS);
+
+        assert!(add(HOSTS, "evil\n0.0.0.0 bank.com", ip("127.0.0.1")).is_err());
+        assert!(add(&format!("{END}\n{BEGIN}\n"), "dev.local", ip("::1")).is_err());
+    }
+
+    #[test]
+    fn test_entries() {
+        let content = format!("# hosts\r\n{BEGIN}\r\n10.0.0.1 a b\r\n{END}\r\n::1 c # comment\r\n");
+        let names: Vec<(String, bool)> = entries(&content)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.managed))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("a".to_string(), true),
+                ("b".to_string(), true),
+                ("c".to_string(), false)
+            ]
+        );
+        // The line endings of the file are kept.
+        let added = add(&content, "d", ip("10.0.0.2")).unwrap().unwrap();
+        assert!(added.contains("10.0.0.1\ta\r\n10.0.0.1\tb\r\n10.0.0.2\td\r\n"));
+    }
+
+    #[test]
+    fn test_bytes_kept_around_block() {
+        // Mixed line endings, blank lines and no final line ending.
+        let before = "127.0.0.1 localhost\r\n\n  # comment  \n";
+        let after = "\n::1 localhost";
+        let content = format!("{before}{BEGIN}\n10.0.0.1 a\n{END}\n{after}");
+        let added = add(&content, "b", ip("10.0.0.2")).unwrap().unwrap();
+        assert_eq!(
+            added,
+            format!("{before}{BEGIN}\r\n10.0.0.1\ta\r\n10.0.0.2\tb\r\n{END}\r\n{after}")
+        );
+        let removed = remove(&added, "a", None).unwrap().unwrap();
+        assert_eq!(
+            remove(&removed, "b", None).unwrap().unwrap(),
+            format!("{before}{after}")
+        );
+
+        // A new block starts on a line of its own.
+        let added = add("127.0.0.1 localhost", "a", ip("10.0.0.1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            added,
+            format!("127.0.0.1 localhost\n{BEGIN}\n10.0.0.1\ta\n{END}\n")
+        );
+    }
+    #[test]
+    fn test_write() {
+        let dir = crate::test_util::temp_dir("hosts-write");
+        let path = dir.join("hosts");
+        std::fs::write(&path, HOSTS).unwrap();
+
+        let backup = write(&path, "10.0.0.1\ta\n", true).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), HOSTS);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "10.0.0.1\ta\n");
+        // The temporary file the content went through is gone.
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["hosts", "hosts.crabyknife.bak"]);
+    }
+}
@@ -0,0 +1,393 @@
+//! HTTP latency probes: repeated requests to a URL, each timed phase by
+//! phase (name lookup, connection, first byte, whole response).
+//!
+//! Only plain `http://` URLs are supported, as no TLS implementation is
+//! built in. Requests are HTTP/1.1 with `Connection: close`, so every
+//! attempt opens a new connection and the timings include its setup.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::ping::PingReport;
+use crate::resolver::{Resolver, Source};
+
+/// The request method of the probes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Head,
+    Get,
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method = match self {
+            Method::Head => "HEAD",
+            Method::Get => "GET",
+        };
+        write!(f, "{method}")
+    }
+}
+
+/// How [`httping_with_progress`] probes a URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpingConfig {
+    count: usize,
+    interval: Duration,
+    timeout: Duration,
+    method: Method,
+}
+
+impl Default for HttpingConfig {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            method: Method::Head,
+        }
+    }
+}
+
+impl HttpingConfig {
+    /// Creates the default config: 5 HEAD requests one second apart,
+    /// waiting up to 5 seconds to connect and for each read.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many requests are sent.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Sets the time between the start of two requests.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long to wait to connect and for each read. Must not be zero.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the request method. GET also times the transfer of the body.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+}
+
+/// The phases of one request, each measured from the start of the attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    /// The status code of the response.
+    pub status: u16,
+    /// The name lookup, near zero once the resolver has cached the host.
+    pub dns: Duration,
+    /// Until the TCP connection is established.
+    pub connect: Duration,
+    /// Until the first byte of the response arrives.
+    pub ttfb: Duration,
+    /// Until the server closes the connection after the response.
+    pub total: Duration,
+}
+
+/// Progress reported while [`httping_with_progress`] runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpingEvent {
+    /// The host of the URL resolved to `address`, which is probed.
+    Resolved { address: IpAddr, source: Source },
+    /// A response arrived.
+    Response { seq: u16, timings: Timings },
+    /// The request failed, e.g. it timed out or the response is not HTTP.
+    Failed { seq: u16, error: String },
+}
+
+/// The parts of an `http://` URL needed to send a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    /// The path and query, `/` when the URL has none.
+    pub path: String,
+}
+
+impl std::str::FromStr for Url {
+    type Err = String;
+
+    /// Parses `http://host[:port][/path]`, where the scheme may be omitted
+    /// and IPv6 hosts are written in brackets.
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        if url.starts_with("https://") {
+            return Err(format!(
+                "HTTPS is not supported, only plain http:// URLs ({url})"
+            ));
+        }
+        let rest = url.strip_prefix("http://").unwrap_or(url);
+        if rest.contains("://") {
+            return Err(format!("unsupported URL scheme ({url})"));
+        }
+
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let path = if path.starts_with('?') {
+            format!("/{path}")
+        } else {
+            path.to_string()
+        };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            // A colon inside brackets belongs to an IPv6 host.
+            Some((host, port)) if !port.contains(']') => {
+                let port = port
+                    .parse()
+                    .map_err(|err| format!("invalid port in URL ({url}): {err}"))?;
+                (host, port)
+            }
+            _ => (authority, 80),
+        };
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() {
+            return Err(format!("missing host in URL ({url})"));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+/// Requests `url` repeatedly and times each attempt.
+///
+/// The returned [`PingReport`] holds the total time of each attempt, `None`
+/// for failed ones, with the same statistics as `ping`.
+///
+/// # Errors
+///
+/// Returns an error if the URL is invalid or not `http://`, the host
+/// cannot be resolved, or the timeout is zero. Failed requests are only
+/// reported to `on_event`.
+pub fn httping_with_progress(
+    url: &str,
+    resolver: &Resolver,
+    config: &HttpingConfig,
+    mut on_event: impl FnMut(HttpingEvent),
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the httping timeout must be greater than zero".into());
+    }
+    let url: Url = url.parse()?;
+
+    let mut address = None;
+    let mut totals = Vec::new();
+    let mut start = Instant::now();
+
+    for attempt in 0..config.count {
+        if attempt > 0 {
+            std::thread::sleep(config.interval.saturating_sub(start.elapsed()));
+        }
+        let seq = attempt as u16;
+
+        start = Instant::now();
+        let resolution = resolver.resolve(&url.host)?;
+        let target_ip = *resolution
+            .addresses
+            .iter()
+            .find(|ip| ip.is_ipv4())
+            .or_else(|| resolution.addresses.first())
+            .ok_or_else(|| format!("no address is found for target host ({})", url.host))?;
+        let dns = start.elapsed();
+        if address.is_none() {
+            on_event(HttpingEvent::Resolved {
+                address: target_ip,
+                source: resolution.source,
+            });
+        }
+        address = Some(target_ip);
+
+        match request(&url, target_ip, config, start, dns) {
+            Ok(timings) => {
+                on_event(HttpingEvent::Response { seq, timings });
+                totals.push(Some(timings.total));
+            }
+            Err(err) => {
+                on_event(HttpingEvent::Failed {
+                    seq,
+                    error: err.to_string(),
+                });
+                totals.push(None);
+            }
+        }
+    }
+
+    let address = address.unwrap_or(IpAddr::from([0, 0, 0, 0]));
+    Ok(PingReport::new(address, totals))
+}
+
+/// Sends one request to `ip` and reads the whole response.
+fn request(
+    url: &Url,
+    ip: IpAddr,
+    config: &HttpingConfig,
+    start: Instant,
+    dns: Duration,
+) -> Result<Timings, Box<dyn std::error::Error>> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::new(ip, url.port), config.timeout)?;
+    let connect = start.elapsed();
+    stream.set_read_timeout(Some(config.timeout))?;
+    stream.set_write_timeout(Some(config.timeout))?;
+
+    let host = if url.host.contains(':') {
+        format!("[{}]", url.host)
+    } else {
+        url.host.clone()
+    };
+    let host = if url.port == 80 {
+        host
+    } else {
+        format!("{host}:{}", url.port)
+    };
+    // Written at once, as formatting into the stream would send it in pieces.
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {host}\r\nUser-Agent: crabyknife/{}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        config.method,
+        url.path,
+        env!("CARGO_PKG_VERSION")
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut buf = [0u8; 8192];
+    let mut head = Vec::new();
+    let mut ttfb = None;
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        ttfb.get_or_insert_with(|| start.elapsed());
+        // Only the status line is kept, the rest is only drained.
+        if !head.contains(&b'\n') {
+            head.extend_from_slice(&buf[..n]);
+        }
+    }
+    let total = start.elapsed();
+
+    let ttfb = ttfb.ok_or("the server closed the connection without responding")?;
+    let status = parse_status(&head).ok_or("the response is not HTTP")?;
+    Ok(Timings {
+        status,
+        dns,
+        connect,
+        ttfb,
+        total,
+    })
+}
+
+/// Extracts the status code from the status line starting `response`.
+fn parse_status(response: &[u8]) -> Option<u16> {
+    let line = response.split(|byte| *byte == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = |host: &str, port, path: &str| Url {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        };
+        assert_eq!(
+            "http://example.com".parse::<Url>(),
+            Ok(url("example.com", 80, "/"))
+        );
+        assert_eq!(
+            "example.com:8080/health?full=1".parse::<Url>(),
+            Ok(url("example.com", 8080, "/health?full=1"))
+        );
+        assert_eq!(
+            "http://[::1]:8080/".parse::<Url>(),
+            Ok(url("::1", 8080, "/"))
+        );
+        assert_eq!("http://[::1]".parse::<Url>(), Ok(url("::1", 80, "/")));
+        assert_eq!("http://a?b".parse::<Url>(), Ok(url("a", 80, "/?b")));
+        assert!("https://example.com".parse::<Url>().is_err());
+        assert!("ftp://example.com".parse::<Url>().is_err());
+        assert!("http://:80/".parse::<Url>().is_err());
+        assert!("http://a:port/".parse::<Url>().is_err());
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_status(b"HTTP/1.1 204 No Content\r\n\r\n"), Some(204));
+        assert_eq!(parse_status(b"HTTP/1.0 404"), Some(404));
+        assert_eq!(parse_status(b"SSH-2.0-OpenSSH\r\n"), None);
+        assert_eq!(parse_status(b""), None);
+    }
+
+    #[test]
+    fn test_httping() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests.push(String::from_utf8(request).unwrap());
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+            requests
+        });
+
+        let config = HttpingConfig::new()
+            .count(2)
+            .interval(Duration::ZERO)
+            .method(Method::Get);
+        let mut statuses = Vec::new();
+        let report = httping_with_progress(
+            &format!("http://127.0.0.1:{port}/health"),
+            &Resolver::new(),
+            &config,
+            |event| {
+                if let HttpingEvent::Response { timings, .. } = event {
+                    assert!(timings.connect <= timings.ttfb && timings.ttfb <= timings.total);
+                    statuses.push(timings.status);
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!((report.sent, report.received), (2, 2));
+        assert_eq!(statuses, [200, 200]);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /health HTTP/1.1\r\n"));
+        assert!(requests[0].contains(&format!("\r\nHost: 127.0.0.1:{port}\r\n")));
+    }
+}
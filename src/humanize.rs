@@ -0,0 +1,502 @@
+//! Human friendly formatting of sizes, durations and counts.
+//!
+//! Subcommands format the numbers of their human output through a
+//! [`Humanizer`], so the global `--raw` flag can turn every one of them
+//! back into exact values for scripts. The global `--locale` option picks
+//! the [`Locale`] used for decimal points, thousands separators and dates.
+
+use std::time::{Duration, SystemTime};
+
+const BYTE_UNITS: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+const COUNT_SUFFIXES: [&str; 4] = ["k", "M", "B", "T"];
+// Units of `duration`, largest first.
+const DURATION_UNITS: [(&str, u64); 4] = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+
+/// Formats a size in bytes with SI units.
+///
+/// # Example
+/// ```
+/// use crabyknife::humanize::bytes;
+///
+/// assert_eq!(bytes(532), "532 B");
+/// assert_eq!(bytes(1_532_000), "1.5 MB");
+/// ```
+pub fn bytes(bytes: u64) -> String {
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    let mut value = bytes as f64;
+    let mut unit = BYTE_UNITS[0];
+    for candidate in BYTE_UNITS {
+        value /= 1000.0;
+        unit = candidate;
+        // Rounding may still reach 1000 of a unit, e.g. 999.96 kB.
+        if value < 999.95 {
+            break;
+        }
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Parses a size in bytes, optionally with an SI unit as written by [`bytes`].
+///
+/// # Example
+/// ```
+/// use crabyknife::humanize::parse_bytes;
+///
+/// assert_eq!(parse_bytes("512").unwrap(), 512);
+/// assert_eq!(parse_bytes("1.5MB").unwrap(), 1_500_000);
+/// assert_eq!(parse_bytes("10 k").unwrap(), 10_000);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error for negative or malformed sizes and unknown units.
+pub fn parse_bytes(size: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let unit = unit.trim();
+
+    let multiplier = if unit.is_empty() || unit.eq_ignore_ascii_case("B") {
+        1
+    } else {
+        let exponent = BYTE_UNITS
+            .iter()
+            .position(|candidate| {
+                unit.eq_ignore_ascii_case(candidate) || unit.eq_ignore_ascii_case(&candidate[..1])
+            })
+            .ok_or_else(|| format!("invalid size ({size}): unknown unit {unit}"))?;
+        1000u64.pow(exponent as u32 + 1)
+    };
+
+    let number: f64 = number
+        .parse()
+        .map_err(|err| format!("invalid size ({size}): {err}"))?;
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Formats a count with three significant digits and a suffix.
+///
+/// # Example
+/// ```
+/// use crabyknife::humanize::count;
+///
+/// assert_eq!(count(999), "999");
+/// assert_eq!(count(1_234_567), "1.23M");
+/// ```
+pub fn count(count: u64) -> String {
+    if count < 1000 {
+        return count.to_string();
+    }
+
+    let mut value = count as f64;
+    let mut suffix = COUNT_SUFFIXES[0];
+    for candidate in COUNT_SUFFIXES {
+        value /= 1000.0;
+        suffix = candidate;
+        if value < 999.5 {
+            break;
+        }
+    }
+    let decimals = if value < 9.995 {
+        2
+    } else if value < 99.95 {
+        1
+    } else {
+        0
+    };
+    format!("{value:.decimals$}{suffix}")
+}
+
+/// Formats a duration: milliseconds or seconds below a minute, otherwise
+/// the three largest units, e.g. `1d 2h 3m`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use crabyknife::humanize::duration;
+///
+/// assert_eq!(duration(Duration::from_millis(310)), "310.0 ms");
+/// assert_eq!(duration(Duration::from_secs(93_784)), "1d 2h 3m");
+/// ```
+pub fn duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        return format!("{:.1} ms", duration.as_secs_f64() * 1000.0);
+    }
+    if duration < Duration::from_secs(60) {
+        return format!("{:.1} s", duration.as_secs_f64());
+    }
+
+    let seconds = duration.as_secs();
+    let largest = DURATION_UNITS
+        .iter()
+        .position(|(_, unit)| seconds >= *unit)
+        .unwrap_or(DURATION_UNITS.len() - 1);
+    (largest..DURATION_UNITS.len())
+        .take(3)
+        .filter_map(|i| {
+            let (name, unit) = DURATION_UNITS[i];
+            let amount = match i {
+                0 => seconds / unit,
+                _ => seconds % DURATION_UNITS[i - 1].1 / unit,
+            };
+            (amount > 0).then(|| format!("{amount}{name}"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a duration written in the units of [`duration`], such as `7d`,
+/// `90m` or `1d 12h`. A number without unit is in seconds.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use crabyknife::humanize::parse_duration;
+///
+/// assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 86_400));
+/// assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(5_400));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error for negative or malformed durations and unknown units.
+pub fn parse_duration(text: &str) -> Result<Duration, Box<dyn std::error::Error>> {
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err("invalid duration (): expected e.g. 7d, 12h or 30m".into());
+    }
+
+    let mut seconds = 0;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: u64 = number
+            .parse()
+            .map_err(|err| format!("invalid duration ({text}): {err}"))?;
+        let tail = tail.trim_start();
+        let unit_len = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let multiplier = if unit.is_empty() {
+            1
+        } else {
+            DURATION_UNITS
+                .iter()
+                .find(|(name, _)| unit.eq_ignore_ascii_case(name))
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| format!("invalid duration ({text}): unknown unit {unit}"))?
+        };
+        seconds = number
+            .checked_mul(multiplier)
+            .and_then(|amount| amount.checked_add(seconds))
+            .ok_or_else(|| format!("invalid duration ({text}): too long"))?;
+        rest = tail.trim_start();
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// The order of the parts of a date, see [`Locale::date`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    YearMonthDay,
+    DayMonthYear,
+    MonthDayYear,
+}
+
+/// Conventions for writing numbers and dates.
+///
+/// The default is the `C` locale: no thousands separators, a `.`
+/// decimal point and ISO 8601 dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal_point: char,
+    pub thousands_separator: Option<char>,
+    pub date_order: DateOrder,
+    pub date_separator: char,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            decimal_point: '.',
+            thousands_separator: None,
+            date_order: DateOrder::YearMonthDay,
+            date_separator: '-',
+        }
+    }
+}
+
+impl Locale {
+    /// Parses a locale tag such as `de`, `en-GB` or `fr_FR.UTF-8`.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::humanize::Locale;
+    ///
+    /// let locale = Locale::parse("de_DE.UTF-8").unwrap();
+    /// assert_eq!(locale.integer(1_234_567), "1.234.567");
+    /// assert_eq!(locale.date(2024, 3, 9), "09.03.2024");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for languages without known conventions.
+    pub fn parse(tag: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let tag = tag.split(['.', '@']).next().unwrap_or_default();
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let region = parts.next().unwrap_or_default().to_ascii_uppercase();
+
+        let (decimal_point, thousands_separator, date_order, date_separator) =
+            match (language.as_str(), region.as_str()) {
+                ("c" | "posix", _) => return Ok(Self::default()),
+                ("en", "" | "US") => ('.', ',', DateOrder::MonthDayYear, '/'),
+                ("en", _) => ('.', ',', DateOrder::DayMonthYear, '/'),
+                ("de" | "da" | "nl" | "id" | "tr", _) => (',', '.', DateOrder::DayMonthYear, '.'),
+                ("es" | "it" | "pt", _) => (',', '.', DateOrder::DayMonthYear, '/'),
+                // Narrow no-break spaces, as in CLDR.
+                ("fr", _) => (',', '\u{202f}', DateOrder::DayMonthYear, '/'),
+                ("ru" | "uk" | "pl" | "cs" | "fi" | "nb", _) => {
+                    (',', '\u{a0}', DateOrder::DayMonthYear, '.')
+                }
+                ("sv", _) => (',', '\u{a0}', DateOrder::YearMonthDay, '-'),
+                ("ja" | "zh" | "ko", _) => ('.', ',', DateOrder::YearMonthDay, '/'),
+                _ => return Err(format!("unsupported locale ({tag})").into()),
+            };
+
+        Ok(Self {
+            decimal_point,
+            thousands_separator: Some(thousands_separator),
+            date_order,
+            date_separator,
+        })
+    }
+
+    /// Writes an integer with thousands separators.
+    pub fn integer(&self, value: u64) -> String {
+        let digits = value.to_string();
+        let Some(separator) = self.thousands_separator else {
+            return digits;
+        };
+
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// Writes a date in the order of the locale.
+    pub fn date(&self, year: i32, month: u32, day: u32) -> String {
+        let separator = self.date_separator;
+        match self.date_order {
+            DateOrder::YearMonthDay => format!("{year:04}{separator}{month:02}{separator}{day:02}"),
+            DateOrder::DayMonthYear => format!("{day:02}{separator}{month:02}{separator}{year:04}"),
+            DateOrder::MonthDayYear => format!("{month:02}{separator}{day:02}{separator}{year:04}"),
+        }
+    }
+
+    /// Replaces the `.` decimal point of a formatted number.
+    fn localize(&self, formatted: String) -> String {
+        if self.decimal_point == '.' {
+            formatted
+        } else {
+            formatted.replace('.', &self.decimal_point.to_string())
+        }
+    }
+}
+
+/// Formats numbers for human output, or exactly with [`Humanizer::raw`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Humanizer {
+    raw: bool,
+    locale: Locale,
+}
+
+impl Humanizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether numbers are written exactly: bytes and counts as
+    /// integers, durations in milliseconds.
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Sets the locale of humanized numbers; raw numbers are never localized.
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn bytes(&self, value: u64) -> String {
+        if self.raw {
+            format!("{value} B")
+        } else {
+            self.locale.localize(bytes(value))
+        }
+    }
+
+    pub fn count(&self, value: u64) -> String {
+        if self.raw {
+            value.to_string()
+        } else {
+            self.locale.localize(count(value))
+        }
+    }
+
+    pub fn duration(&self, value: Duration) -> String {
+        if self.raw {
+            format!("{:.3} ms", value.as_secs_f64() * 1000.0)
+        } else {
+            self.locale.localize(duration(value))
+        }
+    }
+
+    /// Writes a time in UTC with the date in the order of the locale, or as
+    /// RFC 3339 when raw or in the default locale, e.g.
+    /// `2024-01-01T12:00:00Z`.
+    pub fn timestamp(&self, time: SystemTime) -> String {
+        if self.raw || self.locale == Locale::default() {
+            return crate::fileinfo::format_timestamp(time);
+        }
+        let ((year, month, day), seconds) = crate::fileinfo::utc_date_time(time);
+        format!(
+            "{} {:02}:{:02}:{:02} UTC",
+            self.locale.date(year as i32, month, day),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+
+    /// Writes a ratio between 0 and 1 as a percentage.
+    pub fn percent(&self, ratio: f64) -> String {
+        let percent = format!("{:.1}%", ratio * 100.0);
+        if self.raw {
+            percent
+        } else {
+            self.locale.localize(percent)
+        }
+    }
+
+    /// Writes an exact integer, with the thousands separators of the locale.
+    pub fn integer(&self, value: u64) -> String {
+        if self.raw {
+            value.to_string()
+        } else {
+            self.locale.integer(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(999), "999 B");
+        assert_eq!(bytes(1000), "1.0 kB");
+        assert_eq!(bytes(999_960), "1.0 MB");
+        assert_eq!(bytes(u64::MAX), "18.4 EB");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("0").unwrap(), 0);
+        assert_eq!(parse_bytes("2 GB").unwrap(), 2_000_000_000);
+        assert_eq!(parse_bytes("3kb").unwrap(), 3_000);
+        assert_eq!(parse_bytes(&bytes(4_200_000)).unwrap(), 4_200_000);
+        assert!(parse_bytes("").is_err());
+        assert!(parse_bytes("-1").is_err());
+        assert!(parse_bytes("1 XB").is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(count(1000), "1.00k");
+        assert_eq!(count(12_345), "12.3k");
+        assert_eq!(count(123_456), "123k");
+        assert_eq!(count(999_999), "1.00M");
+        assert_eq!(count(u64::MAX), "18446744T");
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(Duration::from_micros(1500)), "1.5 ms");
+        assert_eq!(duration(Duration::from_millis(4200)), "4.2 s");
+        assert_eq!(duration(Duration::from_secs(60)), "1m");
+        assert_eq!(duration(Duration::from_secs(3_605)), "1h 5s");
+        assert_eq!(duration(Duration::from_secs(86_400 * 3 + 4)), "3d");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("2 h").unwrap(), Duration::from_secs(7_200));
+        assert_eq!(
+            parse_duration(&duration(Duration::from_secs(93_784))).unwrap(),
+            Duration::from_secs(93_780)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("1.5d").is_err());
+        assert!(parse_duration("3w").is_err());
+    }
+
+    #[test]
+    fn test_raw_humanizer() {
+        let humanizer = Humanizer::new().raw(true);
+        assert_eq!(humanizer.bytes(1_532_000), "1532000 B");
+        assert_eq!(humanizer.count(1_234_567), "1234567");
+        assert_eq!(humanizer.duration(Duration::from_micros(1500)), "1.500 ms");
+        assert_eq!(Humanizer::new().bytes(1_532_000), "1.5 MB");
+    }
+
+    #[test]
+    fn test_locales() {
+        let german = Locale::parse("de").unwrap();
+        let humanizer = Humanizer::new().locale(german);
+        assert_eq!(humanizer.bytes(1_532_000), "1,5 MB");
+        assert_eq!(humanizer.count(1_234_567), "1,23M");
+        assert_eq!(humanizer.integer(1_234_567), "1.234.567");
+        assert_eq!(humanizer.percent(0.5), "50,0%");
+        assert_eq!(humanizer.raw(true).integer(1_234_567), "1234567");
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_710_000_000);
+        assert_eq!(humanizer.timestamp(time), "09.03.2024 16:00:00 UTC");
+        assert_eq!(humanizer.raw(true).timestamp(time), "2024-03-09T16:00:00Z");
+        assert_eq!(Humanizer::new().timestamp(time), "2024-03-09T16:00:00Z");
+
+        assert_eq!(Locale::parse("en").unwrap().integer(999), "999");
+        assert_eq!(
+            Locale::parse("en_US").unwrap().date(2024, 3, 9),
+            "03/09/2024"
+        );
+        assert_eq!(
+            Locale::parse("en-GB").unwrap().date(2024, 3, 9),
+            "09/03/2024"
+        );
+        assert_eq!(Locale::parse("fr").unwrap().integer(1000), "1\u{202f}000");
+        assert_eq!(Locale::parse("C").unwrap(), Locale::default());
+        assert_eq!(Locale::default().date(2024, 3, 9), "2024-03-09");
+        assert!(Locale::parse("xx").is_err());
+    }
+}
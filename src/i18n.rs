@@ -0,0 +1,437 @@
+//! Translations of user-facing messages.
+//!
+//! Messages are looked up by id in a catalog embedded in the binary, with
+//! their text in every supported [`Lang`], and their `{name}` placeholders
+//! are filled in. The global `--lang` option picks the language. Messages
+//! not moved to the catalog yet are written in English.
+
+use std::fmt::Display;
+
+/// A language of the message catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Zh,
+    Es,
+}
+
+impl Lang {
+    /// Parses a language tag such as `zh`, `es-MX` or `en_US.UTF-8`.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::i18n::Lang;
+    ///
+    /// assert_eq!(Lang::parse("zh_CN.UTF-8").unwrap(), Lang::Zh);
+    /// assert!(Lang::parse("tlh").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for languages without translations.
+    pub fn parse(tag: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let language = tag
+            .split(['.', '@', '-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Ok(Lang::En),
+            "zh" => Ok(Lang::Zh),
+            "es" => Ok(Lang::Es),
+            _ => Err(format!("unsupported language ({tag}), expected en, zh or es").into()),
+        }
+    }
+
+    /// Writes the message `id` in this language, replacing each `{name}`
+    /// placeholder by the value of `name` in `args`. Unknown ids are written
+    /// as is.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::i18n::Lang;
+    ///
+    /// let message = Lang::Es.message("ping.timeout", &[("seq", &3)]);
+    /// assert_eq!(message, "Tiempo de espera agotado (seq=3)");
+    /// ```
+    pub fn message(self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let text = CATALOG
+            .iter()
+            .find(|(key, _)| *key == id)
+            .map_or(id, |(_, texts)| texts[self as usize]);
+
+        let mut message = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            message.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let value = after.find('}').and_then(|end| {
+                let (_, value) = args.iter().find(|(name, _)| *name == &after[..end])?;
+                Some((value, end))
+            });
+            match value {
+                Some((value, end)) => {
+                    message.push_str(&value.to_string());
+                    rest = &after[end + 1..];
+                }
+                // Braces that are no placeholder are kept.
+                None => {
+                    message.push('{');
+                    rest = after;
+                }
+            }
+        }
+        message.push_str(rest);
+        message
+    }
+
+    /// Translates the `Usage:` prefix of a usage message, leaving other
+    /// messages as they are.
+    pub fn usage(self, message: &str) -> String {
+        match message.strip_prefix("Usage:") {
+            Some(rest) => format!("{}{rest}", self.message("usage", &[])),
+            None => message.to_string(),
+        }
+    }
+}
+
+/// The messages by id, in English, Chinese and Spanish (the order of [`Lang`]).
+const CATALOG: &[(&str, [&str; 3])] = &[
+    ("usage", ["Usage:", "用法:", "Uso:"]),
+    ("help.subcommands", ["Subcommands:", "子命令:", "Subcomandos:"]),
+    (
+        "help.subcommand",
+        [
+            "Run `crabyknife <subcommand> --help` for the arguments of a subcommand.",
+            "运行 `crabyknife <subcommand> --help` 查看子命令的参数。",
+            "Ejecute `crabyknife <subcommand> --help` para ver los argumentos de un subcomando.",
+        ],
+    ),
+    (
+        "unknown-subcommand",
+        [
+            "unknown subcommand ({subcommand})",
+            "未知的子命令（{subcommand}）",
+            "subcomando desconocido ({subcommand})",
+        ],
+    ),
+    (
+        "ping.resolved",
+        [
+            "Resolved {target} to {address} via {source}",
+            "已将 {target} 解析为 {address}（通过 {source}）",
+            "{target} resuelto a {address} mediante {source}",
+        ],
+    ),
+    (
+        "ping.other-addresses",
+        [
+            " (1 of {count} addresses, --all pings each)",
+            "（{count} 个地址之一，--all 会逐个 ping）",
+            " (1 de {count} direcciones, --all hace ping a cada una)",
+        ],
+    ),
+    (
+        "ping.resolved-all.one",
+        [
+            "Resolved {host} to {count} address via {source}: {addresses}",
+            "已将 {host} 解析为 {count} 个地址（通过 {source}）：{addresses}",
+            "{host} resuelto a {count} dirección mediante {source}: {addresses}",
+        ],
+    ),
+    (
+        "ping.resolved-all.other",
+        [
+            "Resolved {host} to {count} addresses via {source}: {addresses}",
+            "已将 {host} 解析为 {count} 个地址（通过 {source}）：{addresses}",
+            "{host} resuelto a {count} direcciones mediante {source}: {addresses}",
+        ],
+    ),
+    (
+        "ping.dns-time",
+        [
+            " (DNS lookup {time} ms)",
+            "（DNS 查询耗时 {time} ms）",
+            " (consulta DNS {time} ms)",
+        ],
+    ),
+    (
+        "ping.timestamps.kernel",
+        [
+            "Round-trip times use kernel timestamps (SO_TIMESTAMPNS)",
+            "往返时间使用内核时间戳（SO_TIMESTAMPNS）",
+            "Los tiempos de ida y vuelta usan marcas de tiempo del kernel (SO_TIMESTAMPNS)",
+        ],
+    ),
+    (
+        "ping.timestamps.userspace",
+        [
+            "Round-trip times use userspace timestamps",
+            "往返时间使用用户空间时间戳",
+            "Los tiempos de ida y vuelta usan marcas de tiempo del espacio de usuario",
+        ],
+    ),
+    (
+        "ping.pinging",
+        ["Pinging {target}", "正在 ping {target}", "Haciendo ping a {target}"],
+    ),
+    (
+        "ping.statistics",
+        [
+            "--- {target} ping statistics ---",
+            "--- {target} ping 统计 ---",
+            "--- Estadísticas de ping de {target} ---",
+        ],
+    ),
+    (
+        "ping.connected",
+        [
+            "Connected to {target}: seq={seq} time={time} ms",
+            "已连接到 {target}：seq={seq} 时间={time} ms",
+            "Conectado a {target}: seq={seq} tiempo={time} ms",
+        ],
+    ),
+    (
+        "ping.reply",
+        [
+            "Reply from {target}: seq={seq}{ttl} time={time} ms",
+            "来自 {target} 的回复：seq={seq}{ttl} 时间={time} ms",
+            "Respuesta de {target}: seq={seq}{ttl} tiempo={time} ms",
+        ],
+    ),
+    (
+        "ping.duplicate",
+        [
+            "Duplicate reply from {target}: seq={seq}",
+            "来自 {target} 的重复回复：seq={seq}",
+            "Respuesta duplicada de {target}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.mismatched",
+        [
+            "Ignored reply from {target}: seq={seq} answers no waiting probe",
+            "已忽略来自 {target} 的回复：seq={seq} 不对应任何等待中的探测",
+            "Respuesta ignorada de {target}: seq={seq} no responde a ninguna sonda pendiente",
+        ],
+    ),
+    (
+        "ping.malformed",
+        [
+            "Received malform packet",
+            "收到格式错误的数据包",
+            "Paquete malformado recibido",
+        ],
+    ),
+    (
+        "ping.corrupted",
+        [
+            "Corrupted reply from {target}: seq={seq} time={time} ms, wrong data from byte {offset}",
+            "来自 {target} 的回复已损坏：seq={seq} 时间={time} ms，从第 {offset} 字节起数据错误",
+            "Respuesta corrupta de {target}: seq={seq} tiempo={time} ms, datos erróneos desde el byte {offset}",
+        ],
+    ),
+    (
+        "ping.time-exceeded",
+        [
+            "Time to live exceeded from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 生存时间（TTL）耗尽",
+            "Tiempo de vida excedido desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.too-big",
+        [
+            "Packet too big from {from}: seq={seq} mtu={mtu}",
+            "来自 {from}：seq={seq} 数据包过大 mtu={mtu}",
+            "Paquete demasiado grande desde {from}: seq={seq} mtu={mtu}",
+        ],
+    ),
+    (
+        "ping.unreachable.network",
+        [
+            "Destination network unreachable from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 目标网络不可达",
+            "Red de destino inalcanzable desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.unreachable.host",
+        [
+            "Destination host unreachable from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 目标主机不可达",
+            "Host de destino inalcanzable desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.unreachable.protocol",
+        [
+            "Destination protocol unreachable from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 目标协议不可达",
+            "Protocolo de destino inalcanzable desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.unreachable.port",
+        [
+            "Destination port unreachable from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 目标端口不可达",
+            "Puerto de destino inalcanzable desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.unreachable.prohibited",
+        [
+            "Destination administratively prohibited from {from}: seq={seq}",
+            "来自 {from}：seq={seq} 目标被管理策略禁止",
+            "Destino prohibido administrativamente desde {from}: seq={seq}",
+        ],
+    ),
+    (
+        "ping.unreachable.other",
+        [
+            "Destination unreachable from {from}: seq={seq} code={code}",
+            "来自 {from}：seq={seq} 目标不可达 code={code}",
+            "Destino inalcanzable desde {from}: seq={seq} code={code}",
+        ],
+    ),
+    (
+        "ping.timeout",
+        [
+            "Request timed out (seq={seq})",
+            "请求超时（seq={seq}）",
+            "Tiempo de espera agotado (seq={seq})",
+        ],
+    ),
+    (
+        "ping.timeout-target",
+        [
+            "Request to {target} timed out (seq={seq})",
+            "到 {target} 的请求超时（seq={seq}）",
+            "Tiempo de espera agotado para {target} (seq={seq})",
+        ],
+    ),
+    (
+        "ping.refused",
+        [
+            "Connection refused by {target}: seq={seq} time={time} ms",
+            "{target} 拒绝了连接：seq={seq} 时间={time} ms",
+            "Conexión rechazada por {target}: seq={seq} tiempo={time} ms",
+        ],
+    ),
+    (
+        "ping.failed",
+        [
+            "Connection failed (seq={seq}): {kind}",
+            "连接失败（seq={seq}）：{kind}",
+            "Conexión fallida (seq={seq}): {kind}",
+        ],
+    ),
+    (
+        "ping.summary",
+        [
+            "{sent} packets transmitted, {received} received{duplicates}{corrupted}{errors}, {loss}% packet loss",
+            "已发送 {sent} 个数据包，已接收 {received} 个{duplicates}{corrupted}{errors}，丢包率 {loss}%",
+            "{sent} paquetes transmitidos, {received} recibidos{duplicates}{corrupted}{errors}, {loss}% de pérdida de paquetes",
+        ],
+    ),
+    (
+        "ping.duplicates",
+        [
+            ", +{count} duplicates",
+            "，另有 {count} 个重复",
+            ", +{count} duplicados",
+        ],
+    ),
+    (
+        "ping.corrupted-count",
+        [
+            ", {count} corrupted",
+            "，{count} 个已损坏",
+            ", {count} corruptos",
+        ],
+    ),
+    (
+        "ping.errors",
+        [
+            ", +{count} errors",
+            "，另有 {count} 个错误",
+            ", +{count} errores",
+        ],
+    ),
+    (
+        "ping.summary-tcp",
+        [
+            "{sent} connections attempted, {connected} connected, {refused} refused, {failed}% failed",
+            "尝试连接 {sent} 次，成功 {connected} 次，被拒绝 {refused} 次，失败率 {failed}%",
+            "{sent} conexiones intentadas, {connected} establecidas, {refused} rechazadas, {failed}% fallidas",
+        ],
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The placeholder names of a catalog text, sorted.
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_catalog() {
+        for (i, (id, texts)) in CATALOG.iter().enumerate() {
+            assert!(
+                CATALOG[..i].iter().all(|(other, _)| other != id),
+                "{id} is in the catalog twice"
+            );
+            for text in texts {
+                assert!(!text.is_empty(), "{id} has an empty translation");
+                assert_eq!(
+                    placeholders(text),
+                    placeholders(texts[0]),
+                    "{id} has other placeholders in {text}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let args: [(&str, &dyn Display); 2] = [("target", &"example.com"), ("seq", &"{seq}")];
+        assert_eq!(
+            Lang::En.message("ping.duplicate", &args),
+            "Duplicate reply from example.com: seq={seq}"
+        );
+        assert_eq!(
+            Lang::Zh.message("ping.duplicate", &args),
+            "来自 example.com 的重复回复：seq={seq}"
+        );
+        assert_eq!(Lang::En.message("no.such.id", &args), "no.such.id");
+        assert_eq!(
+            Lang::En.message("ping.timeout", &[]),
+            "Request timed out (seq={seq})"
+        );
+
+        assert_eq!(
+            Lang::Es.usage("Usage: crabyknife ping <host>"),
+            "Uso: crabyknife ping <host>"
+        );
+        assert_eq!(Lang::Zh.usage("invalid count"), "invalid count");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Lang::parse("en-GB").unwrap(), Lang::En);
+        assert_eq!(Lang::parse("ES").unwrap(), Lang::Es);
+        assert_eq!(Lang::parse("C").unwrap(), Lang::En);
+        assert!(Lang::parse("").is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Finding and removing stale artifacts left in temp directories by
+//! builds, installers and crashed programs.
+//!
+//! Only the entries directly under each root are considered, and each is
+//! judged as a whole: a directory is stale when nothing inside it was
+//! modified recently, so a build still writing into an old directory keeps
+//! it alive. Symlinks are never followed, only removed themselves.
+//!
+//! [`find_caches`] finds the build caches of development projects instead,
+//! which can be rebuilt or downloaded again, by their well-known names.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Options of [`clean`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanOptions {
+    roots: Vec<PathBuf>,
+    older_than: Duration,
+    patterns: Vec<String>,
+    delete: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            roots: vec![std::env::temp_dir()],
+            older_than: Duration::from_secs(7 * 86_400),
+            patterns: Vec::new(),
+            delete: false,
+        }
+    }
+}
+
+impl CleanOptions {
+    /// Creates the default options: a dry run over the system temp
+    /// directory, reporting every entry untouched for 7 days.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the directories whose entries are cleaned.
+    pub fn roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    /// Sets how long an entry must have been left unmodified to be stale.
+    pub fn older_than(mut self, older_than: Duration) -> Self {
+        self.older_than = older_than;
+        self
+    }
+
+    /// Sets the glob patterns, e.g. `cargo-*`, of which the entry names
+    /// must match one. Without patterns every entry matches.
+    pub fn patterns(mut self, patterns: Vec<String>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Sets whether stale entries are removed, instead of only reported.
+    pub fn delete(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+}
+
+/// An entry found stale by [`clean`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleEntry {
+    pub path: PathBuf,
+    /// The size of the files in the entry.
+    pub size: u64,
+    /// The latest modification of the entry or anything inside it.
+    pub modified: SystemTime,
+}
+
+/// The outcome of [`clean`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanReport {
+    /// The stale entries, sorted by path, removed unless it is a dry run.
+    pub entries: Vec<StaleEntry>,
+    /// The stale entries that could not be removed, with the reason,
+    /// e.g. those owned by other users. They are also in `entries`.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl CleanReport {
+    /// The size of the stale entries that were, or would be, removed.
+    pub fn freed(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| !self.failed.iter().any(|(path, _)| *path == entry.path))
+            .map(|entry| entry.size)
+            .sum()
+    }
+}
+
+/// Finds the stale entries under the roots of `options`, and removes them
+/// unless it is a dry run.
+///
+/// # Errors
+///
+/// Returns an error for invalid patterns and unreadable roots. Entries
+/// that cannot be removed are listed in [`CleanReport::failed`] instead.
+pub fn clean(options: &CleanOptions) -> Result<CleanReport, Box<dyn std::error::Error>> {
+    let patterns = options
+        .patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).map_err(|err| format!("invalid pattern ({pattern}): {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let cutoff = SystemTime::now()
+        .checked_sub(options.older_than)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut entries = Vec::new();
+    for root in &options.roots {
+        let dir = std::fs::read_dir(root)
+            .map_err(|err| format!("failed to read directory {}: {err}", root.display()))?;
+        for entry in dir {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !patterns.is_empty() && !patterns.iter().any(|pattern| pattern.matches(&name)) {
+                continue;
+            }
+            // Entries vanishing or unreadable meanwhile are not stale artifacts to report.
+            let Ok((size, modified)) = measure(&path) else {
+                continue;
+            };
+            if modified <= cutoff {
+                entries.push(StaleEntry {
+                    path,
+                    size,
+                    modified,
+                });
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut failed = Vec::new();
+    if options.delete {
+        for entry in &entries {
+            if let Err(err) = remove(&entry.path) {
+                failed.push((entry.path.clone(), err.to_string()));
+            }
+        }
+    }
+
+    Ok(CleanReport { entries, failed })
+}
+
+/// A kind of build cache found by [`find_caches`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    /// `target/` next to a `Cargo.toml`.
+    Cargo,
+    /// `node_modules/`.
+    Node,
+    /// Python virtual environments: directories with a `pyvenv.cfg`.
+    Venv,
+    /// `__pycache__/`.
+    Pycache,
+    /// `.gradle/` (including `~/.gradle`), and `build/` next to a
+    /// `build.gradle` or `build.gradle.kts`.
+    Gradle,
+    /// `target/` next to a `pom.xml`, and `~/.m2/repository`.
+    Maven,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Cargo,
+        Category::Node,
+        Category::Venv,
+        Category::Pycache,
+        Category::Gradle,
+        Category::Maven,
+    ];
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let category = match self {
+            Category::Cargo => "cargo",
+            Category::Node => "node",
+            Category::Venv => "venv",
+            Category::Pycache => "pycache",
+            Category::Gradle => "gradle",
+            Category::Maven => "maven",
+        };
+        write!(f, "{category}")
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown cache category ({s}), expected cargo, node, venv, pycache, gradle or maven"
+                )
+            })
+    }
+}
+
+/// A build cache found by [`find_caches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    pub category: Category,
+    pub path: PathBuf,
+    /// The size of the files in the cache.
+    pub size: u64,
+}
+
+/// Finds the build caches in `root` and below, sorted by path.
+///
+/// Caches are not searched for nested caches, e.g. the `node_modules`
+/// inside a `node_modules`, and symlinks are not followed.
+///
+/// # Errors
+///
+/// Returns an error if `root` cannot be read. Unreadable directories
+/// below it are skipped.
+pub fn find_caches(root: &Path) -> Result<Vec<Cache>, Box<dyn std::error::Error>> {
+    std::fs::read_dir(root)
+        .map_err(|err| format!("failed to read directory {}: {err}", root.display()))?;
+
+    let mut caches = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            match cache_category(&path) {
+                Some(category) => caches.push(Cache {
+                    category,
+                    size: measure(&path).map_or(0, |(size, _)| size),
+                    path,
+                }),
+                None => pending.push(path),
+            }
+        }
+    }
+
+    caches.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(caches)
+}
+
+/// Removes `cache` with everything in it.
+///
+/// # Errors
+///
+/// Returns an error if anything in the cache cannot be removed.
+pub fn remove_cache(cache: &Cache) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::remove_dir_all(&cache.path)
+        .map_err(|err| format!("failed to remove {}: {err}", cache.path.display()).into())
+}
+
+/// The category of the cache that the directory at `path` is, if any.
+fn cache_category(path: &Path) -> Option<Category> {
+    let name = path.file_name()?.to_str()?;
+    let parent = path.parent()?;
+    let sibling = |file: &str| parent.join(file).is_file();
+
+    match name {
+        "target" if sibling("Cargo.toml") => Some(Category::Cargo),
+        "target" if sibling("pom.xml") => Some(Category::Maven),
+        "node_modules" => Some(Category::Node),
+        "__pycache__" => Some(Category::Pycache),
+        ".gradle" => Some(Category::Gradle),
+        "build" if sibling("build.gradle") || sibling("build.gradle.kts") => Some(Category::Gradle),
+        "repository" if parent.file_name().is_some_and(|parent| parent == ".m2") => {
+            Some(Category::Maven)
+        }
+        _ if path.join("pyvenv.cfg").is_file() => Some(Category::Venv),
+        _ => None,
+    }
+}
+
+/// Sums the size of the files at or below `path` and finds their latest
+/// modification, without following symlinks.
+fn measure(path: &Path) -> std::io::Result<(u64, SystemTime)> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let mut size = 0;
+    let mut modified = metadata.modified()?;
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let (entry_size, entry_modified) = measure(&entry?.path())?;
+            size += entry_size;
+            modified = modified.max(entry_modified);
+        }
+    } else {
+        size = metadata.len();
+    }
+
+    Ok((size, modified))
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    /// Sets the modification time of `path` to `age` ago.
+    fn age(path: &Path, age: Duration) {
+        let file = std::fs::File::options().read(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn test_clean() {
+        let root = temp_dir("janitor-clean");
+        let week = Duration::from_secs(8 * 86_400);
+
+        std::fs::create_dir_all(root.join("cargo-old/deps")).unwrap();
+        std::fs::write(root.join("cargo-old/deps/lib.rlib"), "12345").unwrap();
+        age(&root.join("cargo-old/deps/lib.rlib"), week);
+        age(&root.join("cargo-old/deps"), week);
+        age(&root.join("cargo-old"), week);
+
+        // An old directory with a recently modified file is still in use.
+        std::fs::create_dir_all(root.join("cargo-busy")).unwrap();
+        std::fs::write(root.join("cargo-busy/log"), "").unwrap();
+        age(&root.join("cargo-busy"), week);
+
+        std::fs::write(root.join("other.tmp"), "abc").unwrap();
+        age(&root.join("other.tmp"), week);
+
+        let options = CleanOptions::new()
+            .roots(vec![root.clone()])
+            .patterns(vec!["cargo-*".to_string()]);
+        let report = clean(&options).unwrap();
+        let paths: Vec<_> = report.entries.iter().map(|entry| &entry.path).collect();
+        assert_eq!(paths, [&root.join("cargo-old")]);
+        assert_eq!(report.freed(), 5);
+        assert!(root.join("cargo-old").exists());
+
+        let report = clean(&options.clone().patterns(Vec::new()).delete(true)).unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.freed(), 8);
+        assert!(report.failed.is_empty());
+        assert!(!root.join("cargo-old").exists());
+        assert!(!root.join("other.tmp").exists());
+        assert!(root.join("cargo-busy").exists());
+
+        assert!(clean(&options.clone().patterns(vec!["[".to_string()])).is_err());
+        assert!(clean(&options.roots(vec![root.join("missing")])).is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_find_caches() {
+        let root = temp_dir("janitor-caches");
+        for dir in [
+            "rust/target/debug",
+            "rust/src",
+            "web/node_modules/left-pad/node_modules",
+            "py/.venv/lib",
+            "py/pkg/__pycache__",
+            "java/target",
+            "android/build",
+            "android/.gradle",
+            "home/.m2/repository",
+            "other/target",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "rust/Cargo.toml",
+            "py/.venv/pyvenv.cfg",
+            "java/pom.xml",
+            "android/build.gradle.kts",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        std::fs::write(root.join("rust/target/debug/app"), "12345").unwrap();
+
+        let caches = find_caches(&root).unwrap();
+        let found: Vec<_> = caches
+            .iter()
+            .map(|cache| (cache.category, cache.path.strip_prefix(&root).unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Category::Gradle, Path::new("android/.gradle")),
+                (Category::Gradle, Path::new("android/build")),
+                (Category::Maven, Path::new("home/.m2/repository")),
+                (Category::Maven, Path::new("java/target")),
+                (Category::Venv, Path::new("py/.venv")),
+                (Category::Pycache, Path::new("py/pkg/__pycache__")),
+                (Category::Cargo, Path::new("rust/target")),
+                (Category::Node, Path::new("web/node_modules")),
+            ]
+        );
+        assert_eq!(caches[6].size, 5);
+
+        remove_cache(&caches[6]).unwrap();
+        assert!(!root.join("rust/target").exists());
+        assert!(root.join("rust/src").exists());
+
+        assert_eq!("Node".parse(), Ok(Category::Node));
+        assert!("npm".parse::<Category>().is_err());
+        assert!(find_caches(&root.join("missing")).is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,6 +1,58 @@
 //! All library crate thate share by all binaries crates
 //! in crabyknife package.
 
+pub mod args;
+#[cfg(all(target_os = "linux", feature = "arping"))]
+pub mod arping;
+pub mod canonicalize_xml;
+pub mod chart;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod commandline;
+pub mod convert;
+pub mod curlconv;
+pub mod diff_xml;
+pub mod disks;
+pub mod fastcopy;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fileinfo;
+pub mod har;
+pub mod highlight;
+pub mod hosts;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod httping;
+pub mod humanize;
+pub mod i18n;
+pub mod janitor;
+pub mod links;
+pub mod monitor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mtr;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pager;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod ping;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pmtu;
+pub mod prettify_html;
 pub mod prettify_xml;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prompt;
+pub mod report;
+pub mod resolver;
+pub mod secrets;
+pub mod server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shape;
+pub mod shred;
+pub mod stats_xml;
+pub mod strip_xml;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sweep;
+pub mod template;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod webhook;
+pub mod xsd;
@@ -0,0 +1,258 @@
+//! Symbolic link audits: finding broken and cyclic links in a tree, and
+//! retargeting links after the tree they point into was moved.
+//!
+//! Directories are walked without following symlinks, so a link to one of
+//! its own ancestors is checked like any other link instead of looping.
+
+use std::path::{Path, PathBuf};
+
+/// What is wrong with a symlink found by [`check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// The link, or a link it points to, points to a path that does not exist.
+    Broken,
+    /// Following the link leads back to a link already followed.
+    Cycle,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problem = match self {
+            Problem::Broken => "broken",
+            Problem::Cycle => "cycle",
+        };
+        write!(f, "{problem}")
+    }
+}
+
+/// A symlink with a [`Problem`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadLink {
+    pub path: PathBuf,
+    /// The target as stored in the link.
+    pub target: PathBuf,
+    pub problem: Problem,
+}
+
+/// A symlink retargeted, or to be retargeted, by [`rewrite`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Retarget {
+    pub path: PathBuf,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Finds the broken and cyclic symlinks in `dir` and below, sorted by path.
+///
+/// # Errors
+///
+/// Returns an error if a directory or a link cannot be read.
+pub fn check(dir: &Path) -> Result<Vec<BadLink>, Box<dyn std::error::Error>> {
+    let mut bad_links = Vec::new();
+    for path in symlinks(dir)? {
+        let target = read_link(&path)?;
+        if std::fs::metadata(&path).is_ok() {
+            continue;
+        }
+
+        let problem = if leads_to_cycle(&path) {
+            Problem::Cycle
+        } else {
+            Problem::Broken
+        };
+        bad_links.push(BadLink {
+            path,
+            target,
+            problem,
+        });
+    }
+
+    Ok(bad_links)
+}
+
+/// Retargets the symlinks in `dir` and below whose target starts with the
+/// path `from`, replacing that prefix with `to`. With `dry_run`, nothing
+/// is changed but the retargets are still returned.
+///
+/// Prefixes match whole path components, so `/old` does not match `/older`.
+/// Each link is replaced by renaming a new link over it.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be read or a link cannot be
+/// replaced, and on platforms without unix symlinks.
+pub fn rewrite(
+    dir: &Path,
+    from: &Path,
+    to: &Path,
+    dry_run: bool,
+) -> Result<Vec<Retarget>, Box<dyn std::error::Error>> {
+    let mut retargets = Vec::new();
+    for path in symlinks(dir)? {
+        let target = read_link(&path)?;
+        let Ok(rest) = target.strip_prefix(from) else {
+            continue;
+        };
+
+        let new_target = if rest.as_os_str().is_empty() {
+            to.to_path_buf()
+        } else {
+            to.join(rest)
+        };
+        if !dry_run {
+            replace_link(&path, &new_target)?;
+        }
+        retargets.push(Retarget {
+            path,
+            from: target,
+            to: new_target,
+        });
+    }
+
+    Ok(retargets)
+}
+
+/// Lists the symlinks in `dir` and below, sorted by path.
+fn symlinks(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut links = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|err| format!("failed to read directory {}: {err}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                links.push(entry.path());
+            } else if file_type.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+
+    links.sort();
+    Ok(links)
+}
+
+fn read_link(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::read_link(path)
+        .map_err(|err| format!("failed to read link {}: {err}", path.display()).into())
+}
+
+/// Whether following the chain of links starting at `path` revisits a link.
+fn leads_to_cycle(path: &Path) -> bool {
+    let mut visited = std::collections::HashSet::new();
+    let mut current = path.to_path_buf();
+
+    while std::fs::symlink_metadata(&current).is_ok_and(|metadata| metadata.is_symlink()) {
+        if !visited.insert(current.clone()) {
+            return true;
+        }
+        let Ok(target) = std::fs::read_link(&current) else {
+            return false;
+        };
+        // Relative targets are relative to the directory of the link.
+        current = match current.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+
+    false
+}
+
+#[cfg(unix)]
+fn replace_link(path: &Path, target: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.crabyknife-tmp"));
+
+    let result = std::os::unix::fs::symlink(target, &temp_path)
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(format!("failed to retarget {}: {err}", path.display()).into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn replace_link(_path: &Path, _target: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err("retargeting symlinks is only supported on unix".into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_check() {
+        let dir = temp_dir("links-check");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("file"), "").unwrap();
+        symlink("file", dir.join("ok")).unwrap();
+        symlink("..", dir.join("sub/parent")).unwrap();
+        symlink("missing", dir.join("sub/broken")).unwrap();
+        symlink("broken", dir.join("sub/to-broken")).unwrap();
+        symlink("b", dir.join("a")).unwrap();
+        symlink("a", dir.join("b")).unwrap();
+        symlink("self", dir.join("self")).unwrap();
+
+        let bad_link = |path: &str, target: &str, problem| BadLink {
+            path: dir.join(path),
+            target: PathBuf::from(target),
+            problem,
+        };
+        assert_eq!(
+            check(&dir).unwrap(),
+            [
+                bad_link("a", "b", Problem::Cycle),
+                bad_link("b", "a", Problem::Cycle),
+                bad_link("self", "self", Problem::Cycle),
+                bad_link("sub/broken", "missing", Problem::Broken),
+                bad_link("sub/to-broken", "broken", Problem::Broken),
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let dir = temp_dir("links-rewrite");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        symlink("/old/project/src", dir.join("src")).unwrap();
+        symlink("/old/project", dir.join("sub/root")).unwrap();
+        symlink("/old/projects", dir.join("other")).unwrap();
+        symlink("relative", dir.join("relative")).unwrap();
+
+        let from = Path::new("/old/project");
+        let to = Path::new("/new/project");
+        let retarget = |path: &str, from: &str, to: &str| Retarget {
+            path: dir.join(path),
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        };
+        let expected = [
+            retarget("src", "/old/project/src", "/new/project/src"),
+            retarget("sub/root", "/old/project", "/new/project"),
+        ];
+
+        assert_eq!(rewrite(&dir, from, to, true).unwrap(), expected);
+        assert_eq!(
+            std::fs::read_link(dir.join("src")).unwrap(),
+            Path::new("/old/project/src")
+        );
+
+        assert_eq!(rewrite(&dir, from, to, false).unwrap(), expected);
+        assert_eq!(
+            std::fs::read_link(dir.join("src")).unwrap(),
+            Path::new("/new/project/src")
+        );
+        assert!(rewrite(&dir, from, to, false).unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
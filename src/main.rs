@@ -1,16 +1,103 @@
-use crabyknife::commandline;
+use crabyknife::args::{self, Args};
+use crabyknife::commandline::{self, GlobalOptions};
 
-fn main() {
-    let mut args = std::env::args();
+const USAGE: &str = "Usage: crabyknife [--raw] [--locale <tag>] [--lang <tag>] [--plain] [--no-pager] [--yes] [--no-secrets-guard] [--deterministic] <subcommand> [<args>...]";
 
-    let program_name = args.next().expect("we should always has the program name");
+fn main() {
+    // Past the program name.
+    let mut args = std::env::args().skip(1);
 
-    let subcommand = args
-        .next()
-        .unwrap_or_else(|| panic!("usage: {program_name} <subcommand>"));
+    // Global options come before the subcommand.
+    let mut global = GlobalOptions {
+        deterministic: std::env::var_os("CRABYKNIFE_DETERMINISTIC")
+            .is_some_and(|value| !value.is_empty() && value != "0"),
+        plain: std::env::var_os("TERM").is_some_and(|term| term == "dumb"),
+        ..Default::default()
+    };
+    let (subcommand, args) = loop {
+        match args.next() {
+            Some(arg) if arg == "--raw" => global.raw = true,
+            Some(arg) if arg == "--deterministic" => global.deterministic = true,
+            Some(arg) if arg == "--plain" => global.plain = true,
+            Some(arg) if arg == "--no-pager" => global.no_pager = true,
+            Some(arg) if arg == "--yes" => global.yes = true,
+            Some(arg) if arg == "--no-secrets-guard" => global.no_secrets_guard = true,
+            Some(arg) if arg == "--lang" => {
+                let Some(tag) = args.next() else {
+                    usage_error(&global)
+                };
+                match crabyknife::i18n::Lang::parse(&tag) {
+                    Ok(lang) => global.lang = lang,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some(arg) if arg == "--locale" => {
+                let Some(tag) = args.next() else {
+                    usage_error(&global)
+                };
+                match crabyknife::humanize::Locale::parse(&tag) {
+                    Ok(locale) => global.locale = locale,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some(arg) if arg == "--help" || arg == "-h" => print_help(&global),
+            // `help <subcommand>` is `<subcommand> --help`.
+            Some(arg) if arg == "help" => match args.next() {
+                Some(subcommand) => break (subcommand, Args::new(["--help".to_string()])),
+                None => print_help(&global),
+            },
+            Some(arg) => break (arg, Args::new(args)),
+            None => usage_error(&global),
+        }
+    };
 
-    if let Err(err) = commandline::run(&subcommand, args) {
-        eprint!("{err}");
+    if let Err(err) = commandline::run(&subcommand, args, &global) {
+        if let Some(help) = err.downcast_ref::<args::Help>() {
+            println!("{}", global.lang.usage(&help.usage));
+            return;
+        }
+        let label = error_label(&global);
+        if let Some(exit) = err.downcast_ref::<commandline::ExitError>() {
+            if !exit.message.is_empty() {
+                eprintln!("{label}{}", global.lang.usage(&exit.message));
+            }
+            std::process::exit(exit.code);
+        }
+        eprintln!("{label}{}", global.lang.usage(&err.to_string()));
         std::process::exit(1);
     }
 }
+
+/// Plain output labels error lines, which screen readers cannot tell from
+/// others by their color or stream.
+fn error_label(global: &GlobalOptions) -> &'static str {
+    if global.plain {
+        "error: "
+    } else {
+        ""
+    }
+}
+
+/// Prints the usage on stderr and exits with 2, for invalid global options.
+fn usage_error(global: &GlobalOptions) -> ! {
+    eprintln!("{}{}", error_label(global), global.lang.usage(USAGE));
+    std::process::exit(2)
+}
+
+/// Prints the usage and the subcommands on stdout and exits, for `--help`.
+fn print_help(global: &GlobalOptions) -> ! {
+    let lang = global.lang;
+    println!("{}\n", lang.usage(USAGE));
+    println!("{}", lang.message("help.subcommands", &[]));
+    for name in commandline::Subcommands::NAMES {
+        println!("  {name}");
+    }
+    println!("\n{}", lang.message("help.subcommand", &[]));
+    std::process::exit(0)
+}
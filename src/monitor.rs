@@ -0,0 +1,117 @@
+//! Availability monitoring: the outcomes of repeated probes of a host are
+//! turned into UP and DOWN states, which only change once several
+//! consecutive probes agree, so a single lost packet does not flap them.
+
+/// Whether a monitored host is available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostState {
+    Up,
+    Down,
+}
+
+impl std::fmt::Display for HostState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            HostState::Up => "UP",
+            HostState::Down => "DOWN",
+        };
+        write!(f, "{state}")
+    }
+}
+
+/// Tracks the state of a host from the outcomes of its probes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    down_after: usize,
+    up_after: usize,
+    state: Option<HostState>,
+    /// The outcome of the latest probes, and how many in a row had it.
+    streak: Option<(bool, usize)>,
+}
+
+impl Monitor {
+    /// Creates a monitor declaring the host DOWN after `down_after`
+    /// consecutive failed probes, and UP after `up_after` answered ones.
+    /// Thresholds of 0 count as 1.
+    pub fn new(down_after: usize, up_after: usize) -> Self {
+        Self {
+            down_after: down_after.max(1),
+            up_after: up_after.max(1),
+            state: None,
+            streak: None,
+        }
+    }
+
+    /// The current state, `None` until a threshold is first reached.
+    pub fn state(&self) -> Option<HostState> {
+        self.state
+    }
+
+    /// Records whether a probe was answered, returning the new state when
+    /// it changes, including the first one.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::monitor::{HostState, Monitor};
+    ///
+    /// let mut monitor = Monitor::new(2, 1);
+    /// assert_eq!(monitor.record(true), Some(HostState::Up));
+    /// assert_eq!(monitor.record(false), None);
+    /// assert_eq!(monitor.record(false), Some(HostState::Down));
+    /// ```
+    pub fn record(&mut self, answered: bool) -> Option<HostState> {
+        let count = match self.streak {
+            Some((outcome, count)) if outcome == answered => count + 1,
+            _ => 1,
+        };
+        self.streak = Some((answered, count));
+
+        let (state, threshold) = if answered {
+            (HostState::Up, self.up_after)
+        } else {
+            (HostState::Down, self.down_after)
+        };
+        if self.state != Some(state) && count >= threshold {
+            self.state = Some(state);
+            return Some(state);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let mut monitor = Monitor::new(3, 2);
+        let outcomes = [
+            false, false, true, false, false, false, false, true, true, true,
+        ];
+        let transitions: Vec<Option<HostState>> = outcomes
+            .iter()
+            .map(|answered| monitor.record(*answered))
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(HostState::Down),
+                None,
+                None,
+                Some(HostState::Up),
+                None
+            ]
+        );
+        assert_eq!(monitor.state(), Some(HostState::Up));
+
+        let mut monitor = Monitor::new(0, 0);
+        assert_eq!(monitor.record(false), Some(HostState::Down));
+        assert_eq!(monitor.record(true), Some(HostState::Up));
+    }
+}
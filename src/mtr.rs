@@ -0,0 +1,243 @@
+//! Live traceroutes in the manner of mtr: every hop on the path to a host
+//! is probed round after round, keeping loss and latency statistics per hop.
+//!
+//! Hops are found with ICMP echo requests of increasing TTL. The router
+//! where the TTL of a request runs out answers with a Time Exceeded
+//! message, and the target with an echo reply. Only raw sockets receive
+//! Time Exceeded messages, so tracing needs root or `CAP_NET_RAW`.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::ping::{self, IcmpError, IcmpSocket, IpFamily, PingConfig, PingReport, Received};
+use crate::resolver::Resolver;
+
+/// How [`mtr_with_progress`] probes the path to a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtrConfig {
+    rounds: Option<usize>,
+    interval: Duration,
+    timeout: Duration,
+    max_hops: u8,
+    family: Option<IpFamily>,
+}
+
+impl Default for MtrConfig {
+    fn default() -> Self {
+        Self {
+            rounds: Some(10),
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            max_hops: 30,
+            family: None,
+        }
+    }
+}
+
+impl MtrConfig {
+    /// Creates the default config: 10 rounds one second apart, waiting one
+    /// second for the replies of each round, over at most 30 hops.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of rounds, or `None` to probe until interrupted.
+    pub fn rounds(mut self, rounds: Option<usize>) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Sets the time between the starts of consecutive rounds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long the replies of a round are waited for.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the largest TTL probed, when the target is not reached before.
+    pub fn max_hops(mut self, max_hops: u8) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Restricts the target to addresses of `family`, instead of preferring IPv4.
+    pub fn family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+}
+
+/// A hop on the path to the target, see [`Trace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    /// The TTL of the probes to this hop, from 1.
+    pub ttl: u8,
+    /// The address that answered the latest probe, `None` while none did.
+    pub address: Option<IpAddr>,
+    /// The round-trip time of the probe of each round, `None` when lost.
+    pub rtts: Vec<Option<Duration>>,
+}
+
+impl Hop {
+    /// Computes the loss and round-trip time statistics of the hop. Hops
+    /// that never answered are reported at the unspecified address.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use crabyknife::mtr::Hop;
+    ///
+    /// let hop = Hop {
+    ///     ttl: 1,
+    ///     address: Some("192.168.1.1".parse().unwrap()),
+    ///     rtts: vec![Some(Duration::from_millis(2)), None],
+    /// };
+    /// assert_eq!(hop.report().loss(), 0.5);
+    /// ```
+    pub fn report(&self) -> PingReport {
+        let address = self
+            .address
+            .unwrap_or(IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
+        PingReport::new(address, self.rtts.clone())
+    }
+}
+
+/// The path to a host, as probed so far by [`mtr_with_progress`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// The address of the target.
+    pub address: IpAddr,
+    /// The number of rounds done.
+    pub rounds: usize,
+    /// The hops in TTL order, up to the target once it answered.
+    pub hops: Vec<Hop>,
+}
+
+/// Traces the path to the specified host, probing every hop once per round.
+///
+/// `on_round` is called with the trace after each round, e.g. to redraw a
+/// table of the hops.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be resolved, the config has a
+/// zero timeout or maximum of hops, no raw ICMP socket can be opened, or
+/// sending a probe fails.
+pub fn mtr_with_progress(
+    target: &str,
+    resolver: &Resolver,
+    config: &MtrConfig,
+    mut on_round: impl FnMut(&Trace),
+) -> Result<Trace, Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the probe timeout must be greater than zero".into());
+    }
+    if config.max_hops == 0 {
+        return Err("the maximum number of hops must be greater than zero".into());
+    }
+
+    let ping_config = PingConfig::new().family(config.family);
+    let address = ping::resolve_target(target, resolver, &ping_config, &mut |_| {})?;
+    let icmp = IcmpSocket::open(address, None)?;
+    if !icmp.raw {
+        return Err("tracing a route needs a raw ICMP socket to receive Time Exceeded messages, run as root or with CAP_NET_RAW".into());
+    }
+
+    let mut trace = Trace {
+        address,
+        rounds: 0,
+        hops: (1..=config.max_hops)
+            .map(|ttl| Hop {
+                ttl,
+                address: None,
+                rtts: Vec::new(),
+            })
+            .collect(),
+    };
+    let mut seq: u16 = 0;
+    let mut start = Instant::now();
+
+    while config.rounds.is_none_or(|rounds| trace.rounds < rounds) {
+        if trace.rounds > 0 {
+            std::thread::sleep(config.interval.saturating_sub(start.elapsed()));
+        }
+        start = Instant::now();
+
+        // The sequence number, hop index and sending time of each probe
+        // still waiting for an answer.
+        let mut pending = Vec::new();
+        for (index, hop) in trace.hops.iter_mut().enumerate() {
+            icmp.set_ttl(u32::from(hop.ttl))?;
+            icmp.send(seq, &[])?;
+            pending.push((seq, index, Instant::now()));
+            hop.rtts.push(None);
+            seq = seq.wrapping_add(1);
+        }
+
+        // The index of the first hop answered by the target.
+        let mut reached: Option<usize> = None;
+        let deadline = start + config.timeout;
+        while !pending.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            icmp.socket
+                .set_read_timeout(Some(remaining))
+                .map_err(|err| format!("failed to set socket timeout: {err}"))?;
+            let Ok((packet, from, received)) = icmp.recv(0) else {
+                break;
+            };
+
+            let (answered, from) = match icmp.classify(&packet, from, &[]) {
+                // Only the target answers with echo replies.
+                Received::Echo { seq, .. } | Received::Corrupted { seq, .. } => (seq, address),
+                Received::Error {
+                    seq,
+                    from,
+                    error: IcmpError::TimeExceeded,
+                } => (seq, from),
+                Received::Error { .. } | Received::Malformed | Received::Ignored => continue,
+            };
+            let Some(position) = pending.iter().position(|(seq, ..)| *seq == answered) else {
+                // An answer to a probe of an earlier round.
+                continue;
+            };
+            let (_, index, sent) = pending.swap_remove(position);
+            let hop = &mut trace.hops[index];
+            hop.address = Some(from);
+            if let Some(rtt) = hop.rtts.last_mut() {
+                *rtt = Some(received.saturating_duration_since(sent));
+            }
+            if from == address {
+                reached = Some(reached.map_or(index, |reached| reached.min(index)));
+            }
+        }
+
+        // Probes with a larger TTL reach the target too, so are not hops.
+        if let Some(reached) = reached {
+            trace.hops.truncate(reached + 1);
+        }
+        trace.rounds += 1;
+        on_round(&trace);
+    }
+
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mtr_config() {
+        let mtr = |config| mtr_with_progress("127.0.0.1", &Resolver::new(), &config, |_| {});
+        assert!(mtr(MtrConfig::new().timeout(Duration::ZERO)).is_err());
+        assert!(mtr(MtrConfig::new().max_hops(0)).is_err());
+    }
+}
@@ -1,23 +1,425 @@
 use std::mem::MaybeUninit;
-use std::net::ToSocketAddrs;
+use std::net::IpAddr;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU16, Ordering};
 use std::time::{Duration, Instant};
 
+use crate::resolver::{Resolution, Resolver, Source};
+
 // ICMP ECHO request type encoding.
 const ICMP_ECHO_REQUEST: u8 = 8;
 // ICMP ECHO reply type encoding.
 const ICMP_ECHO_REPLY: u8 = 0;
+// ICMPv6 ECHO request type encoding.
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+// ICMPv6 ECHO reply type encoding.
+const ICMPV6_ECHO_REPLY: u8 = 129;
+// ICMP TIME EXCEEDED type encoding, sent by the router where the TTL runs out.
+const ICMP_TIME_EXCEEDED: u8 = 11;
+// ICMPv6 TIME EXCEEDED type encoding.
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+// ICMP DESTINATION UNREACHABLE type encoding.
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+// ICMP DESTINATION UNREACHABLE code of a packet too big for the next hop
+// and not to be fragmented.
+const ICMP_FRAGMENTATION_NEEDED: u8 = 4;
+// ICMPv6 PACKET TOO BIG type encoding.
+const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+// ICMPv6 DESTINATION UNREACHABLE type encoding.
+const ICMPV6_DEST_UNREACHABLE: u8 = 1;
+
+/// The IP version to ping over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub(crate) fn of(ip: &std::net::IpAddr) -> Self {
+        if ip.is_ipv4() {
+            IpFamily::V4
+        } else {
+            IpFamily::V6
+        }
+    }
+}
+
+/// Where the arrival times of replies are taken, see [`PingEvent::Timestamps`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    /// By the kernel as packets arrive (`SO_TIMESTAMPNS`, Linux only), so
+    /// round-trip times exclude the delay before the process is scheduled
+    /// to read them.
+    Kernel,
+    /// With [`Instant::now`] once the process has read the packet.
+    Userspace,
+}
+
+/// How [`ping`] probes a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingConfig {
+    count: usize,
+    interval: Duration,
+    timeout: Duration,
+    payload_size: usize,
+    pattern: Vec<u8>,
+    family: Option<IpFamily>,
+    preferred_family: IpFamily,
+    ttl: Option<u32>,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            payload_size: 0,
+            pattern: Vec::new(),
+            family: None,
+            preferred_family: IpFamily::V4,
+            ttl: None,
+        }
+    }
+}
+
+impl PingConfig {
+    /// Creates the default config: 5 probes one second apart, waiting one
+    /// second for each reply, without payload after the ICMP header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many probes are sent.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Sets the time between the start of two probes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long to wait for each reply. Must not be zero.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of data bytes sent after the 8-byte ICMP header.
+    pub fn payload_size(mut self, size: usize) -> Self {
+        self.payload_size = size;
+        self
+    }
+
+    /// Sets the bytes repeated to fill the payload, instead of incrementing
+    /// bytes like iputils ping. Replies must echo the payload unchanged.
+    pub fn pattern(mut self, pattern: Vec<u8>) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets the IP version to use. Without one, the preferred family is
+    /// used, or the other one for hosts without an address of it.
+    pub fn family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+
+    /// Sets the IP version used for hosts with both IPv4 and IPv6
+    /// addresses, when no family is set. IPv4 by default.
+    pub fn preferred_family(mut self, family: IpFamily) -> Self {
+        self.preferred_family = family;
+        self
+    }
+
+    /// Sets the TTL (IPv4) or hop limit (IPv6) of the probes, instead of
+    /// the system default.
+    pub fn ttl(mut self, ttl: Option<u32>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The time between the start of two probes.
+    pub(crate) fn probe_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The data bytes of the probes, filled with the pattern.
+    fn payload(&self) -> Vec<u8> {
+        if self.pattern.is_empty() {
+            (0..self.payload_size).map(|i| i as u8).collect()
+        } else {
+            self.pattern
+                .iter()
+                .copied()
+                .cycle()
+                .take(self.payload_size)
+                .collect()
+        }
+    }
+}
+
+/// Progress reported while [`ping_with_progress`] runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingEvent {
+    /// The target resolved to `address`, which is pinged, out of
+    /// `addresses` usable ones (see [`resolve_addresses`]), after looking
+    /// it up for `elapsed`, which the round-trip times do not include.
+    Resolved {
+        address: IpAddr,
+        source: Source,
+        addresses: usize,
+        elapsed: Duration,
+    },
+    /// The probes are sent, and the arrival times of their replies taken
+    /// as told.
+    Timestamps(Timestamps),
+    /// A valid echo reply arrived after `rtt`, with `ttl` left when the
+    /// IP header of the reply is received (only over IPv4 raw sockets, and
+    /// datagram sockets on macOS).
+    Reply {
+        seq: u16,
+        rtt: Duration,
+        ttl: Option<u8>,
+    },
+    /// Another reply to a probe that was already answered arrived.
+    Duplicate { seq: u16 },
+    /// A reply to this ping arrived that answers none of the waiting
+    /// probes, e.g. a late reply to a probe that timed out.
+    Mismatched { seq: u16 },
+    /// The router or host `from` answered the probe with an ICMP error
+    /// instead of the target (raw sockets only). Not counted as received.
+    IcmpError {
+        seq: u16,
+        from: IpAddr,
+        error: IcmpError,
+    },
+    /// A truncated echo reply, one with an invalid checksum, or a packet
+    /// with an invalid IP header arrived.
+    Malformed,
+    /// The reply to the probe arrived after `rtt`, but its data differs
+    /// from the payload sent from byte `offset` on. Not counted as received.
+    Corrupted {
+        seq: u16,
+        rtt: Duration,
+        offset: usize,
+    },
+    /// The TCP port refused the connection after `rtt`, so the host is
+    /// reachable but nothing listens on the port (TCP ping only).
+    Refused { seq: u16, rtt: Duration },
+    /// The TCP connection failed for another reason than a refusal or a
+    /// timeout, e.g. an unreachable network (TCP ping only).
+    Failed { seq: u16, kind: std::io::ErrorKind },
+    /// No reply arrived within the timeout.
+    Timeout { seq: u16 },
+}
+
+/// The results of pinging a host, see [`ping`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingReport {
+    /// The address that was pinged.
+    pub address: IpAddr,
+    pub sent: usize,
+    pub received: usize,
+    /// Replies to probes that were already answered, not counted as received.
+    pub duplicates: usize,
+    /// Replies whose data differs from the payload sent, not counted as received.
+    pub corrupted: usize,
+    /// TCP connections refused by the target, not counted as received.
+    pub refused: usize,
+    /// Probes answered by an ICMP error, not counted as received.
+    pub errors: usize,
+    /// The round-trip time of each probe in sending order,
+    /// `None` for probes that timed out or received a malformed reply.
+    pub rtts: Vec<Option<Duration>>,
+    /// The fastest round-trip time, `None` without replies.
+    pub min: Option<Duration>,
+    pub avg: Option<Duration>,
+    pub max: Option<Duration>,
+    /// The population standard deviation of the round-trip times.
+    pub stddev: Option<Duration>,
+}
+
+impl PingReport {
+    /// Computes the statistics of the round-trip times of the probes sent to `address`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use crabyknife::ping::PingReport;
+    ///
+    /// let rtts = vec![Some(Duration::from_millis(10)), None, Some(Duration::from_millis(30))];
+    /// let report = PingReport::new("127.0.0.1".parse().unwrap(), rtts);
+    /// assert_eq!((report.sent, report.received), (3, 2));
+    /// assert_eq!(report.avg, Some(Duration::from_millis(20)));
+    /// assert_eq!(report.stddev, Some(Duration::from_millis(10)));
+    /// ```
+    pub fn new(address: IpAddr, rtts: Vec<Option<Duration>>) -> Self {
+        let replies: Vec<f64> = rtts.iter().flatten().map(Duration::as_secs_f64).collect();
+        let (avg, stddev) = if replies.is_empty() {
+            (None, None)
+        } else {
+            let avg = replies.iter().sum::<f64>() / replies.len() as f64;
+            let variance =
+                replies.iter().map(|rtt| (rtt - avg).powi(2)).sum::<f64>() / replies.len() as f64;
+            (
+                Some(Duration::from_secs_f64(avg)),
+                Some(Duration::from_secs_f64(variance.sqrt())),
+            )
+        };
+
+        Self {
+            address,
+            sent: rtts.len(),
+            received: replies.len(),
+            duplicates: 0,
+            corrupted: 0,
+            refused: 0,
+            errors: 0,
+            min: rtts.iter().flatten().min().copied(),
+            avg,
+            max: rtts.iter().flatten().max().copied(),
+            stddev,
+            rtts,
+        }
+    }
+
+    /// The fraction of probes without a valid reply, from 0 to 1.
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        (self.sent - self.received) as f64 / self.sent as f64
+    }
+
+    /// The round-trip time within which `percentile` percent (0 to 100) of
+    /// the replies arrived, by nearest rank. `None` without replies.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use crabyknife::ping::PingReport;
+    ///
+    /// let rtts = (1..=10).map(|ms| Some(Duration::from_millis(ms))).collect();
+    /// let report = PingReport::new("127.0.0.1".parse().unwrap(), rtts);
+    /// assert_eq!(report.percentile(50.0), Some(Duration::from_millis(5)));
+    /// assert_eq!(report.percentile(99.0), Some(Duration::from_millis(10)));
+    /// ```
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut replies: Vec<Duration> = self.rtts.iter().flatten().copied().collect();
+        if replies.is_empty() {
+            return None;
+        }
+        replies.sort_unstable();
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * replies.len() as f64).ceil() as usize;
+        Some(replies[rank.saturating_sub(1)])
+    }
+
+    /// Counts the replies in `buckets` ranges of equal width from the
+    /// fastest to the slowest round-trip time, the last range including the
+    /// slowest. A single range when all replies took as long, and none
+    /// without replies.
+    pub fn histogram(&self, buckets: usize) -> Vec<(Range<Duration>, usize)> {
+        let (Some(min), Some(max)) = (self.min, self.max) else {
+            return Vec::new();
+        };
+        let buckets = buckets.max(1);
+        let width = (max - min) / buckets as u32;
+        if width.is_zero() {
+            return vec![(min..max, self.received)];
+        }
+
+        let mut counts = vec![0; buckets];
+        for rtt in self.rtts.iter().flatten() {
+            let index = ((*rtt - min).as_nanos() / width.as_nanos()) as usize;
+            counts[index.min(buckets - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let start = min + width * i as u32;
+                let end = if i + 1 == buckets { max } else { start + width };
+                (start..end, count)
+            })
+            .collect()
+    }
+
+    /// The statistics as JSON, with times in milliseconds and the
+    /// histogram over 10 ranges.
+    pub fn to_json(&self) -> serde_json::Value {
+        // Whole microseconds, so the milliseconds print without float noise.
+        let ms = |rtt: Duration| rtt.as_micros() as f64 / 1000.0;
+        let histogram: Vec<serde_json::Value> = self
+            .histogram(10)
+            .into_iter()
+            .map(|(range, count)| {
+                serde_json::json!({
+                    "from_ms": ms(range.start),
+                    "to_ms": ms(range.end),
+                    "count": count,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "address": self.address.to_string(),
+            "sent": self.sent,
+            "received": self.received,
+            "duplicates": self.duplicates,
+            "corrupted": self.corrupted,
+            "refused": self.refused,
+            "errors": self.errors,
+            "loss": self.loss(),
+            "rtt_ms": {
+                "min": self.min.map(ms),
+                "avg": self.avg.map(ms),
+                "max": self.max.map(ms),
+                "stddev": self.stddev.map(ms),
+                "p50": self.percentile(50.0).map(ms),
+                "p90": self.percentile(90.0).map(ms),
+                "p99": self.percentile(99.0).map(ms),
+            },
+            "histogram": histogram,
+        })
+    }
+}
+
+/// Pings the specified host without reporting progress, see [`ping_with_progress`].
+///
+/// # Errors
+///
+/// See [`ping_with_progress`].
+pub fn ping(
+    target: &str,
+    resolver: &Resolver,
+    config: &PingConfig,
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    ping_with_progress(target, resolver, config, |_| {})
+}
 
 /// Sends an ICMP echo request ("ping") to the specified host and waits for a reply.
 ///
 /// This function performs the following steps:
-/// 1. Resolves the target hostname to an IP address.
-/// 2. Creates a raw ICMP socket.
+/// 1. Resolves the target hostname to an IP address with `resolver`.
+/// 2. Creates a raw ICMP or ICMPv6 socket, depending on the address.
 /// 3. Builds and sends an ICMP Echo Request packet.
 /// 4. Waits for a valid Echo Reply response and measures round-trip time.
 ///
 /// # Arguments
 ///
 /// * `host` - A hostname or IP address (e.g., `"google.com"` or `"8.8.8.8"`).
+/// * `resolver` - The resolver used to look up the hostname.
+/// * `config` - The number of probes, their timing and size, see [`PingConfig`].
+/// * `on_event` - Called with the resolved address and the outcome of each
+///   probe as they happen, e.g. to print progress.
+///
+/// # Returns
+///
+/// The [`PingReport`] with the round-trip time of each probe and their statistics.
 ///
 /// # Errors
 ///
@@ -25,74 +427,1136 @@ const ICMP_ECHO_REPLY: u8 = 0;
 /// - DNS resolution fails
 /// - Raw socket creation fails (may require root/privileged access)
 /// - The packet fails to send or receive
-pub fn ping(target: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // `ToSocketAddrs`'s `to_socket_addrs` method expect the str to be parsed
-    // in the format of `hostname:port`.
-    // However we expect the user to provider only the hostname without the port.
-    // So we append a dumpy port `0` to the target hostname.
-    let target_with_port = format!("{target}:0");
-    let mut address_iter = target_with_port
-        .to_socket_addrs()
-        .map_err(|err| format!("DNS lookup failed on the target host ({target}): {err}"))?;
-    let target_socket_addr = address_iter
-        .next()
-        .ok_or("no DNS recoard is found for target host({target})")?;
-
-    let socket = socket2::Socket::new(
-        socket2::Domain::IPV4,
-        socket2::Type::RAW,
-        Some(socket2::Protocol::ICMPV4),
-    )?;
-
-    // Set the socket timeout;
-    socket
-        .set_read_timeout(Some(Duration::from_secs(1)))
-        .map_err(|err| format!("failed to set socket timeout: {err}"))?;
-
-    let addr = target_socket_addr.into();
-
-    let pid = std::process::id() as u16;
-
-    for seq in 0..5 {
-        let packet = build_packet(seq, pid);
-
-        let start = Instant::now();
-        socket
-            .send_to(&packet, &addr)
-            .map_err(|err| format!("failed to send packet to the target host: {err}"))?;
-
-        let mut buf = [MaybeUninit::<u8>::uninit(); 1024];
-
-        match socket.recv_from(&mut buf) {
-            Ok((n, _)) => {
-                let rtt = start.elapsed().as_millis();
-
-                // MaybeUninit is Rust’s way of saying: “this memory may or may not be initialized.” After reading from a socket, we know the data is valid, but Rust doesn't — so we have to safely assume that it's now initialized.
-                //
-                // By using assume_init(), you say: “Yes, this byte was written to. I know it’s safe.”
-                if n >= 20 + 8 && unsafe { buf[20].assume_init() } == ICMP_ECHO_REPLY {
-                    println!("Reply from {target}: seq={seq} time={rtt} ms");
-                } else {
-                    println!("Received malform packet");
+/// - The config has a zero timeout or a payload too large for a packet
+pub fn ping_with_progress(
+    target: &str,
+    resolver: &Resolver,
+    config: &PingConfig,
+    mut on_event: impl FnMut(PingEvent),
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    check_icmp_config(config)?;
+    let target_ip = resolve_target(target, resolver, config, &mut on_event)?;
+    let icmp = IcmpSocket::open(target_ip, config.ttl)?;
+    on_event(PingEvent::Timestamps(icmp.timestamps));
+    let payload = config.payload();
+
+    let mut probes = Probes::default();
+    let mut start = Instant::now();
+
+    for probe in 0..config.count {
+        if probe > 0 {
+            std::thread::sleep(config.interval.saturating_sub(start.elapsed()));
+        }
+
+        // Sequence numbers wrap around like in other ping implementations.
+        let seq = probe as u16;
+        start = Instant::now();
+        icmp.send(seq, &payload)?;
+
+        // Raw sockets receive every ICMP packet of the host, so packets that
+        // do not answer this probe are skipped until the timeout.
+        let deadline = start + config.timeout;
+        let answer = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break None;
+            }
+            icmp.socket
+                .set_read_timeout(Some(remaining))
+                .map_err(|err| format!("failed to set socket timeout: {err}"))?;
+
+            let Ok((reply, from, arrived)) = icmp.recv(config.payload_size) else {
+                break None;
+            };
+            let received = icmp.classify(&reply, from, &payload);
+            let rtt = arrived.saturating_duration_since(start);
+            if let Some(event) = probes.receive(received, seq, rtt, &mut on_event) {
+                break Some(event);
+            }
+        };
+        on_event(probes.finish(seq, answer));
+    }
+
+    Ok(probes.report(target_ip))
+}
+
+/// The outcomes of the probes of a ping, shared by [`ping_with_progress`]
+/// and [`ping_async`].
+#[derive(Debug, Default)]
+struct Probes {
+    rtts: Vec<Option<Duration>>,
+    duplicates: usize,
+    corrupted: usize,
+    errors: usize,
+}
+
+impl Probes {
+    /// Counts the packet `received` after `rtt` while waiting for the reply
+    /// to `seq`, and returns the outcome of the probe if it answers it.
+    /// Other packets are passed to `on_event`.
+    fn receive(
+        &mut self,
+        received: Received,
+        seq: u16,
+        rtt: Duration,
+        on_event: &mut impl FnMut(PingEvent),
+    ) -> Option<PingEvent> {
+        match received {
+            Received::Ignored => {}
+            Received::Malformed => on_event(PingEvent::Malformed),
+            Received::Error {
+                seq: error_seq,
+                from,
+                error,
+            } if error_seq == seq => {
+                self.errors += 1;
+                return Some(PingEvent::IcmpError { seq, from, error });
+            }
+            // A late error about an earlier probe, already timed out.
+            Received::Error { .. } => {}
+            Received::Echo { seq: echo_seq, ttl } if echo_seq == seq => {
+                return Some(PingEvent::Reply { seq, rtt, ttl })
+            }
+            Received::Corrupted {
+                seq: echo_seq,
+                offset,
+            } if echo_seq == seq => {
+                self.corrupted += 1;
+                return Some(PingEvent::Corrupted { seq, rtt, offset });
+            }
+            Received::Echo { seq: echo_seq, .. } | Received::Corrupted { seq: echo_seq, .. } => {
+                let event = late_reply(echo_seq, seq, &self.rtts);
+                if matches!(event, PingEvent::Duplicate { .. }) {
+                    self.duplicates += 1;
                 }
+                on_event(event);
             }
-            Err(_) => println!("Request timed out (seq={seq})"),
         }
+        None
+    }
 
-        std::thread::sleep(Duration::from_secs(1));
+    /// Records the outcome of the probe `seq`, given its `answer` if one
+    /// arrived before the timeout, and returns it.
+    fn finish(&mut self, seq: u16, answer: Option<PingEvent>) -> PingEvent {
+        let event = answer.unwrap_or(PingEvent::Timeout { seq });
+        self.rtts.push(match event {
+            PingEvent::Reply { rtt, .. } => Some(rtt),
+            _ => None,
+        });
+        event
     }
 
+    /// The report of the probes sent to `address`.
+    fn report(self, address: IpAddr) -> PingReport {
+        let mut report = PingReport::new(address, self.rtts);
+        report.duplicates = self.duplicates;
+        report.corrupted = self.corrupted;
+        report.errors = self.errors;
+        report
+    }
+}
+
+/// Pings several hosts at once like [`ping_with_progress`], their probes
+/// interleaved: each host is probed once per interval, from its own
+/// thread, the first probes of the hosts spread over the first interval.
+///
+/// `on_event` is called on the calling thread with the index of the host
+/// in `targets` and each of its events, in the order they happen.
+///
+/// Returns the report of each host, or the error it could not be pinged
+/// with, see [`ping_with_progress`].
+pub fn ping_many_with_progress(
+    targets: &[&str],
+    resolver: &Resolver,
+    config: &PingConfig,
+    mut on_event: impl FnMut(usize, PingEvent),
+) -> Vec<Result<PingReport, Box<dyn std::error::Error>>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = targets
+            .iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let sender = sender.clone();
+                let delay = config.interval * index as u32 / targets.len() as u32;
+                scope.spawn(move || {
+                    std::thread::sleep(delay);
+                    ping_with_progress(target, resolver, config, |event| {
+                        // The receiver outlives the threads.
+                        let _ = sender.send((index, event));
+                    })
+                    .map_err(|err| err.to_string())
+                })
+            })
+            .collect();
+        // The events end once every thread dropped its sender.
+        drop(sender);
+        for (index, event) in receiver {
+            on_event(index, event);
+        }
+        threads
+            .into_iter()
+            .map(|thread| match thread.join() {
+                Ok(report) => report.map_err(Into::into),
+                Err(_) => Err("the ping thread panicked".into()),
+            })
+            .collect()
+    })
+}
+
+/// Pings the specified host like [`ping`], but without blocking the thread,
+/// so many hosts can be pinged concurrently by the tasks of one thread.
+///
+/// The runtime must have its I/O and time drivers enabled, which wake the
+/// task when a packet arrives or the probe times out.
+///
+/// # Example
+/// ```no_run
+/// use crabyknife::ping::{ping_async, PingConfig};
+/// use crabyknife::resolver::Resolver;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread()
+///     .enable_all()
+///     .build()
+///     .unwrap();
+/// let report = runtime
+///     .block_on(ping_async("192.168.1.1", &Resolver::new(), &PingConfig::new()))
+///     .unwrap();
+/// println!("{} of {} replies", report.received, report.sent);
+/// ```
+///
+/// # Errors
+///
+/// See [`ping_with_progress`].
+#[cfg(all(feature = "tokio", unix))]
+pub async fn ping_async(
+    target: &str,
+    resolver: &Resolver,
+    config: &PingConfig,
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    use std::os::fd::AsRawFd;
+
+    check_icmp_config(config)?;
+    let resolution = resolver.resolve_async(target).await?;
+    let target_ip = usable_addresses(target, resolution, config)?.addresses[0];
+    let icmp = IcmpSocket::open(target_ip, config.ttl)?;
+    icmp.socket
+        .set_nonblocking(true)
+        .map_err(|err| format!("failed to make the socket non-blocking: {err}"))?;
+    // Declared after the socket, so it is deregistered before the socket closes.
+    let socket = tokio::io::unix::AsyncFd::with_interest(
+        icmp.socket.as_raw_fd(),
+        tokio::io::Interest::READABLE,
+    )
+    .map_err(|err| format!("failed to register the socket: {err}"))?;
+    let payload = config.payload();
+
+    let mut probes = Probes::default();
+    let mut start = Instant::now();
+
+    for probe in 0..config.count {
+        if probe > 0 {
+            tokio::time::sleep(config.interval.saturating_sub(start.elapsed())).await;
+        }
+
+        let seq = probe as u16;
+        start = Instant::now();
+        icmp.send(seq, &payload)?;
+
+        let deadline = start + config.timeout;
+        let answer = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break None;
+            }
+            let Ok(ready) = tokio::time::timeout(remaining, socket.readable()).await else {
+                break None;
+            };
+            let mut ready = ready.map_err(|err| format!("failed to wait for a reply: {err}"))?;
+            // A readiness the packet was already read for clears on WouldBlock.
+            let Ok(received) = ready.try_io(|_| icmp.recv(config.payload_size)) else {
+                continue;
+            };
+            let Ok((reply, from, arrived)) = received else {
+                break None;
+            };
+            let received = icmp.classify(&reply, from, &payload);
+            let rtt = arrived.saturating_duration_since(start);
+            if let Some(event) = probes.receive(received, seq, rtt, &mut |_| {}) {
+                break Some(event);
+            }
+        };
+        probes.finish(seq, answer);
+    }
+
+    Ok(probes.report(target_ip))
+}
+
+/// Checks that `config` can be used for ICMP probes.
+fn check_icmp_config(config: &PingConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the ping timeout must be greater than zero".into());
+    }
+    // The largest ICMP payload fitting in an IPv4 packet.
+    if config.payload_size > 65_507 {
+        return Err(format!(
+            "the packet size ({}) is larger than the maximum of 65507 bytes",
+            config.payload_size
+        )
+        .into());
+    }
     Ok(())
 }
 
-fn build_packet(seq: u16, pid: u16) -> Vec<u8> {
-    let mut packet = vec![0u8; 8]; // ICMP header: type(1 byte), code(1 byte), checksum(2 bytes), id(2), seq(2 byte)
-    packet[0] = ICMP_ECHO_REQUEST; // Type
+/// Measures the TCP connect latency to `port` of the specified host, for
+/// networks that filter ICMP. Each probe opens a connection and closes it
+/// as soon as it is established.
+///
+/// The `count`, `interval`, `timeout` and `family` of `config` apply as for
+/// [`ping_with_progress`]; the payload size and TTL are ignored. Refused
+/// connections are reported as [`PingEvent::Refused`] and counted in
+/// [`PingReport::refused`] rather than as received.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be resolved or the timeout is zero.
+pub fn tcp_ping_with_progress(
+    target: &str,
+    port: u16,
+    resolver: &Resolver,
+    config: &PingConfig,
+    mut on_event: impl FnMut(PingEvent),
+) -> Result<PingReport, Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the ping timeout must be greater than zero".into());
+    }
+
+    let target_ip = resolve_target(target, resolver, config, &mut on_event)?;
+    on_event(PingEvent::Timestamps(Timestamps::Userspace));
+    let addr = std::net::SocketAddr::new(target_ip, port);
+
+    let mut rtts = Vec::new();
+    let mut refused = 0;
+    let mut start = Instant::now();
+
+    for probe in 0..config.count {
+        if probe > 0 {
+            std::thread::sleep(config.interval.saturating_sub(start.elapsed()));
+        }
+        let seq = probe as u16;
+
+        start = Instant::now();
+        let rtt = match std::net::TcpStream::connect_timeout(&addr, config.timeout) {
+            Ok(_) => {
+                let rtt = start.elapsed();
+                on_event(PingEvent::Reply {
+                    seq,
+                    rtt,
+                    ttl: None,
+                });
+                Some(rtt)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                refused += 1;
+                on_event(PingEvent::Refused {
+                    seq,
+                    rtt: start.elapsed(),
+                });
+                None
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) =>
+            {
+                on_event(PingEvent::Timeout { seq });
+                None
+            }
+            Err(err) => {
+                on_event(PingEvent::Failed {
+                    seq,
+                    kind: err.kind(),
+                });
+                None
+            }
+        };
+        rtts.push(rtt);
+    }
+
+    let mut report = PingReport::new(target_ip, rtts);
+    report.refused = refused;
+    Ok(report)
+}
+
+/// Resolves `target` to the addresses it can be pinged at with `config`:
+/// those of its family if set, otherwise all of them, those of the
+/// preferred family first. The first one is the one [`ping`] uses.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be resolved, or has no address
+/// of the family set.
+pub fn resolve_addresses(
+    target: &str,
+    resolver: &Resolver,
+    config: &PingConfig,
+) -> Result<Resolution, Box<dyn std::error::Error>> {
+    usable_addresses(target, resolver.resolve(target)?, config)
+}
+
+/// Keeps the addresses of the `resolution` of `target` that can be pinged
+/// with `config`, see [`resolve_addresses`].
+fn usable_addresses(
+    target: &str,
+    resolution: Resolution,
+    config: &PingConfig,
+) -> Result<Resolution, Box<dyn std::error::Error>> {
+    let mut addresses = resolution.addresses;
+    match config.family {
+        Some(family) => {
+            addresses.retain(|ip| IpFamily::of(ip) == family);
+            if addresses.is_empty() {
+                let version = if family == IpFamily::V4 {
+                    "IPv4"
+                } else {
+                    "IPv6"
+                };
+                return Err(
+                    format!("no {version} address is found for target host ({target})").into(),
+                );
+            }
+        }
+        // A stable sort keeps the resolver's order within each family.
+        None => addresses.sort_by_key(|ip| IpFamily::of(ip) != config.preferred_family),
+    }
+    if addresses.is_empty() {
+        return Err(format!("no address is found for target host ({target})").into());
+    }
+
+    Ok(Resolution {
+        addresses,
+        ..resolution
+    })
+}
+
+/// Resolves `target` to the address to ping with `config`, see
+/// [`resolve_addresses`], and reports it to `on_event`.
+pub(crate) fn resolve_target(
+    target: &str,
+    resolver: &Resolver,
+    config: &PingConfig,
+    on_event: &mut impl FnMut(PingEvent),
+) -> Result<IpAddr, Box<dyn std::error::Error>> {
+    let resolution = resolve_addresses(target, resolver, config)?;
+    let address = resolution.addresses[0];
+    on_event(PingEvent::Resolved {
+        address,
+        source: resolution.source,
+        addresses: resolution.addresses.len(),
+        elapsed: resolution.elapsed,
+    });
+    Ok(address)
+}
+
+/// An echo request received by [`listen`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EchoRequest {
+    /// The host that sent it.
+    pub from: IpAddr,
+    pub id: u16,
+    pub seq: u16,
+    /// The TTL it arrived with, when the IP header is received (IPv4).
+    pub ttl: Option<u8>,
+    pub payload: Vec<u8>,
+    /// Whether [`listen`] answered it, rather than leaving it to the kernel.
+    pub answered: bool,
+}
+
+/// Whether the kernel answers echo requests over `family` itself, so a
+/// responder must not answer them twice. Only Linux tells when it was set
+/// not to (`net.ipv4.icmp_echo_ignore_all` and
+/// `net.ipv6.icmp.echo_ignore_all`); other systems are assumed to answer.
+pub fn kernel_answers_echo(family: IpFamily) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let path = match family {
+            IpFamily::V4 => "/proc/sys/net/ipv4/icmp_echo_ignore_all",
+            IpFamily::V6 => "/proc/sys/net/ipv6/icmp/echo_ignore_all",
+        };
+        std::fs::read_to_string(path).map_or(true, |value| value.trim() == "0")
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = family;
+        true
+    }
+}
+
+/// Listens for the ICMP echo requests sent to this host over `family`,
+/// calling `on_request` with each, and answers them when `reply`, e.g.
+/// when the kernel does not (see [`kernel_answers_echo`]).
+///
+/// # Example
+/// ```no_run
+/// use crabyknife::ping::{kernel_answers_echo, listen, IpFamily};
+///
+/// let reply = !kernel_answers_echo(IpFamily::V4);
+/// match listen(IpFamily::V4, reply, |request| {
+///     println!("echo request from {} (seq={})", request.from, request.seq)
+/// })
+/// .unwrap()
+/// {}
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if no raw ICMP socket can be opened, as receiving the
+/// echo requests of other hosts needs one, or if receiving or answering a
+/// request fails.
+pub fn listen(
+    family: IpFamily,
+    reply: bool,
+    mut on_request: impl FnMut(EchoRequest),
+) -> Result<std::convert::Infallible, Box<dyn std::error::Error>> {
+    let (domain, protocol, reply_type) = match family {
+        IpFamily::V4 => (
+            socket2::Domain::IPV4,
+            socket2::Protocol::ICMPV4,
+            ICMP_ECHO_REPLY,
+        ),
+        IpFamily::V6 => (
+            socket2::Domain::IPV6,
+            socket2::Protocol::ICMPV6,
+            ICMPV6_ECHO_REPLY,
+        ),
+    };
+    let socket =
+        socket2::Socket::new(domain, socket2::Type::RAW, Some(protocol)).map_err(|err| {
+            format!("failed to create a raw ICMP socket, which listening needs: {err}")
+        })?;
+
+    // Room for the largest IP packet.
+    let mut buf = vec![MaybeUninit::<u8>::uninit(); 65_535];
+    loop {
+        let (n, from) = socket
+            .recv_from(&mut buf)
+            .map_err(|err| format!("failed to receive a packet: {err}"))?;
+        // SAFETY: recv_from initialized the first `n` bytes.
+        let packet: Vec<u8> = buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        let Some(from) = from.as_socket().map(|from| from.ip()) else {
+            continue;
+        };
+        let Some(mut request) = parse_echo_request(family, &packet, from) else {
+            continue;
+        };
+        if reply {
+            let packet = build_packet(reply_type, request.seq, request.id, &request.payload);
+            let addr = std::net::SocketAddr::new(from, 0).into();
+            socket
+                .send_to(&packet, &addr)
+                .map_err(|err| format!("failed to answer {from}: {err}"))?;
+            request.answered = true;
+        }
+        on_request(request);
+    }
+}
+
+/// Parses the echo request in a `packet` received from `from` on a raw
+/// socket of `family`, `None` for other ICMP messages and malformed ones.
+fn parse_echo_request(family: IpFamily, packet: &[u8], from: IpAddr) -> Option<EchoRequest> {
+    let header_len = ip_header_len(family, packet)?;
+    // The TTL is the 9th byte of the IPv4 header.
+    let ttl = (header_len > 0).then(|| packet[8]);
+    let message = &packet[header_len..];
+    let request_type = match family {
+        IpFamily::V4 => ICMP_ECHO_REQUEST,
+        IpFamily::V6 => ICMPV6_ECHO_REQUEST,
+    };
+    // The kernel verifies ICMPv6 checksums itself.
+    if message.len() < 8
+        || message[0] != request_type
+        || (family == IpFamily::V4 && checksum(message) != 0)
+    {
+        return None;
+    }
+    Some(EchoRequest {
+        from,
+        id: u16::from_be_bytes([message[4], message[5]]),
+        seq: u16::from_be_bytes([message[6], message[7]]),
+        ttl,
+        payload: message[8..].to_vec(),
+        answered: false,
+    })
+}
+
+/// Offsets the identifier of the echo requests of each socket from the
+/// process ID, so that sockets pinging at once, e.g. the same host from
+/// several threads, tell their replies apart.
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+/// An ICMP socket sending echo requests to a single target.
+pub(crate) struct IcmpSocket {
+    pub(crate) socket: socket2::Socket,
+    /// Whether the socket is a raw one, see [`open_socket`].
+    pub(crate) raw: bool,
+    target: IpAddr,
+    family: IpFamily,
+    request_type: u8,
+    reply_type: u8,
+    /// The identifier of the echo requests of raw sockets, see [`NEXT_ID`].
+    pid: u16,
+    /// Where the arrival times of the packets received are taken.
+    pub(crate) timestamps: Timestamps,
+}
+
+/// What a packet received by an [`IcmpSocket`] is to its ping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Received {
+    /// An echo reply from the target, with the TTL left when the IP header
+    /// is received.
+    Echo { seq: u16, ttl: Option<u8> },
+    /// The router or host `from` dropped the echo request `seq`, telling
+    /// why with an ICMP error (raw sockets only).
+    Error {
+        seq: u16,
+        from: IpAddr,
+        error: IcmpError,
+    },
+    /// An echo reply from the target whose data differs from the payload
+    /// sent from byte `offset` on.
+    Corrupted { seq: u16, offset: usize },
+    /// A truncated echo reply, one with an invalid checksum, or a packet
+    /// with an invalid IP header.
+    Malformed,
+    /// Other ICMP traffic, or a reply to another ping.
+    Ignored,
+}
+
+impl IcmpSocket {
+    /// Opens a socket to `target`, sending packets with `ttl` if given.
+    pub(crate) fn open(
+        target: IpAddr,
+        ttl: Option<u32>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // The kernel computes the ICMPv6 checksum.
+        let family = IpFamily::of(&target);
+        let (domain, protocol, request_type, reply_type) = match family {
+            IpFamily::V4 => (
+                socket2::Domain::IPV4,
+                socket2::Protocol::ICMPV4,
+                ICMP_ECHO_REQUEST,
+                ICMP_ECHO_REPLY,
+            ),
+            IpFamily::V6 => (
+                socket2::Domain::IPV6,
+                socket2::Protocol::ICMPV6,
+                ICMPV6_ECHO_REQUEST,
+                ICMPV6_ECHO_REPLY,
+            ),
+        };
+        let (socket, raw) = open_socket(domain, protocol)?;
+        let timestamps = enable_timestamps(&socket);
+        let icmp = Self {
+            socket,
+            raw,
+            target,
+            family,
+            request_type,
+            reply_type,
+            pid: (std::process::id() as u16).wrapping_add(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+            timestamps,
+        };
+        if let Some(ttl) = ttl {
+            icmp.set_ttl(ttl)?;
+        }
+        Ok(icmp)
+    }
+
+    /// Sets the TTL (hop limit in IPv6) of the next packets sent.
+    pub(crate) fn set_ttl(&self, ttl: u32) -> Result<(), Box<dyn std::error::Error>> {
+        match self.family {
+            IpFamily::V4 => self.socket.set_ttl(ttl),
+            IpFamily::V6 => self.socket.set_unicast_hops_v6(ttl),
+        }
+        .map_err(|err| format!("failed to set the TTL to {ttl}: {err}"))?;
+        Ok(())
+    }
+
+    /// Sets the Don't Fragment bit of the next packets sent, and lets them
+    /// be larger than the path MTU known to the kernel, to probe it.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_dont_fragment(&self) -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::fd::AsRawFd;
+
+        let set = |level: libc::c_int, name: libc::c_int, value: libc::c_int| {
+            // SAFETY: the option value is a c_int living for the whole call,
+            // and `self.socket` keeps the descriptor open.
+            let result = unsafe {
+                libc::setsockopt(
+                    self.socket.as_raw_fd(),
+                    level,
+                    name,
+                    (&value as *const libc::c_int).cast(),
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if result != 0 {
+                let err = std::io::Error::last_os_error();
+                return Err(format!("failed to set the Don't Fragment bit: {err}"));
+            }
+            Ok(())
+        };
+        match self.family {
+            IpFamily::V4 => set(
+                libc::IPPROTO_IP,
+                libc::IP_MTU_DISCOVER,
+                libc::IP_PMTUDISC_PROBE,
+            )?,
+            IpFamily::V6 => {
+                set(
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_MTU_DISCOVER,
+                    libc::IPV6_PMTUDISC_PROBE,
+                )?;
+                // IPv6 packets are only fragmented by their sender, which
+                // the discovery mode does not prevent for raw sockets.
+                set(libc::IPPROTO_IPV6, libc::IPV6_DONTFRAG, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the Don't Fragment bit of the next packets sent, only
+    /// supported on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn set_dont_fragment(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Err("setting the Don't Fragment bit is only supported on Linux".into())
+    }
+
+    /// Sends the echo request `seq` with the data `payload`.
+    pub(crate) fn send(&self, seq: u16, payload: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.try_send(seq, payload)
+            .map_err(|err| format!("failed to send packet to the target host: {err}").into())
+    }
+
+    /// Sends the echo request `seq` with the data `payload`, returning the
+    /// error of the system as is, e.g. to tell packets too big apart.
+    pub(crate) fn try_send(&self, seq: u16, payload: &[u8]) -> std::io::Result<()> {
+        let packet = build_packet(self.request_type, seq, self.pid, payload);
+        let addr = std::net::SocketAddr::new(self.target, 0).into();
+        self.socket.send_to(&packet, &addr)?;
+        Ok(())
+    }
+
+    /// Receives a packet, large enough for the reply to a request with
+    /// `payload_size` bytes of data, the address it came from, and when it
+    /// arrived.
+    pub(crate) fn recv(
+        &self,
+        payload_size: usize,
+    ) -> std::io::Result<(Vec<u8>, Option<IpAddr>, Instant)> {
+        // Room for the largest IPv4 header and the echoed packet, or the
+        // headers of the request quoted by an ICMP error.
+        let mut buf = vec![MaybeUninit::<u8>::uninit(); 60 + 8 + payload_size.max(60 + 8)];
+        let (n, from, arrived) = match self.timestamps {
+            Timestamps::Kernel => recv_timestamped(&self.socket, &mut buf)?,
+            Timestamps::Userspace => {
+                let (n, from) = self.socket.recv_from(&mut buf)?;
+                (n, from, Instant::now())
+            }
+        };
+
+        // MaybeUninit is Rust’s way of saying: “this memory may or may not be initialized.” After reading from a socket, we know the data is valid, but Rust doesn't — so we have to safely assume that it's now initialized.
+        //
+        // By using assume_init(), you say: “Yes, this byte was written to. I know it’s safe.”
+        let reply: Vec<u8> = buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        Ok((reply, from.as_socket().map(|from| from.ip()), arrived))
+    }
+
+    /// Tells what the `packet` received from `from` is, to a ping whose
+    /// requests carry the data `payload`.
+    pub(crate) fn classify(&self, packet: &[u8], from: Option<IpAddr>, payload: &[u8]) -> Received {
+        let Some(header_len) = ip_header_len(self.family, packet) else {
+            return Received::Malformed;
+        };
+        // The TTL is the 9th byte of the IPv4 header.
+        let ttl = (header_len > 0).then(|| packet[8]);
+        let message = &packet[header_len..];
+
+        let echo = match parse_echo_reply(message, self.reply_type, self.family == IpFamily::V4) {
+            Ok(Some(echo)) => echo,
+            Ok(None) => return self.classify_error(message, from),
+            Err(()) => return Received::Malformed,
+        };
+        // The kernel sets the identifier of datagram sockets, and only
+        // delivers them their own replies.
+        if (self.raw && echo.id != self.pid) || from != Some(self.target) {
+            return Received::Ignored;
+        }
+        match corrupted_offset(&message[8..], payload) {
+            Some(offset) => Received::Corrupted {
+                seq: echo.seq,
+                offset,
+            },
+            None => Received::Echo { seq: echo.seq, ttl },
+        }
+    }
+
+    /// Tells what the ICMP `message` received from `from`, which is not an
+    /// echo reply, is.
+    fn classify_error(&self, message: &[u8], from: Option<IpAddr>) -> Received {
+        match (parse_error_message(message, self.family), from) {
+            (Some((error, destination, request)), Some(from))
+                if destination == self.target && (!self.raw || request.id == self.pid) =>
+            {
+                Received::Error {
+                    seq: request.seq,
+                    from,
+                    error,
+                }
+            }
+            // Other ICMP traffic, such as our own request over loopback.
+            _ => Received::Ignored,
+        }
+    }
+}
+
+/// The event of an echo reply numbered `echo_seq` arriving while waiting
+/// for the reply to `seq`, given the round-trip times of the earlier probes.
+fn late_reply(echo_seq: u16, seq: u16, rtts: &[Option<Duration>]) -> PingEvent {
+    // The latest probe sent with the reply's sequence number.
+    let back = usize::from(seq.wrapping_sub(echo_seq));
+    if back <= rtts.len() && rtts[rtts.len() - back].is_some() {
+        PingEvent::Duplicate { seq: echo_seq }
+    } else {
+        PingEvent::Mismatched { seq: echo_seq }
+    }
+}
+
+/// Opens a raw ICMP socket, or a datagram one for users not allowed raw sockets.
+///
+/// ICMP datagram sockets ("ping sockets") are supported on Linux, for the
+/// groups in `net.ipv4.ping_group_range`, and on macOS. The kernel sets
+/// the identifier of the packets sent through them.
+///
+/// Returns the socket and whether it is a raw one.
+fn open_socket(
+    domain: socket2::Domain,
+    protocol: socket2::Protocol,
+) -> Result<(socket2::Socket, bool), Box<dyn std::error::Error>> {
+    match socket2::Socket::new(domain, socket2::Type::RAW, Some(protocol)) {
+        Ok(socket) => Ok((socket, true)),
+        Err(raw_err) if raw_err.kind() == std::io::ErrorKind::PermissionDenied => {
+            let socket = socket2::Socket::new(domain, socket2::Type::DGRAM, Some(protocol));
+            socket.map(|socket| (socket, false)).map_err(|err| {
+                format!(
+                    "failed to create an ICMP socket: {raw_err} (raw socket), {err} (datagram socket)"
+                )
+                .into()
+            })
+        }
+        Err(err) => Err(format!("failed to create an ICMP socket: {err}").into()),
+    }
+}
+
+/// Asks the kernel to timestamp the packets `socket` receives, and tells
+/// whether it will.
+#[cfg(target_os = "linux")]
+fn enable_timestamps(socket: &socket2::Socket) -> Timestamps {
+    use std::os::fd::AsRawFd;
+
+    let on: libc::c_int = 1;
+    // SAFETY: the option value is a c_int living for the whole call, and
+    // `socket` keeps the descriptor open.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMPNS,
+            (&on as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Timestamps::Kernel
+    } else {
+        Timestamps::Userspace
+    }
+}
+
+/// Kernel timestamps are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+fn enable_timestamps(_socket: &socket2::Socket) -> Timestamps {
+    Timestamps::Userspace
+}
+
+/// Receives a packet into `buf` from a socket with kernel timestamps, see
+/// [`enable_timestamps`], returning its length, the address it came from,
+/// and when it arrived.
+///
+/// The kernel stamps packets with the wall clock, which is compared to the
+/// wall clock once the packet is read to tell how long it waited. That wait
+/// is taken off the monotonic time it is read at, so that a wall clock set
+/// while pinging does not skew round-trip times. Packets without a
+/// timestamp, or one from the future, count as read as they arrived.
+#[cfg(target_os = "linux")]
+fn recv_timestamped(
+    socket: &socket2::Socket,
+    buf: &mut [MaybeUninit<u8>],
+) -> std::io::Result<(usize, socket2::SockAddr, Instant)> {
+    use std::os::fd::AsRawFd;
+
+    // Room for a few control messages, the timestamp being the only one
+    // expected.
+    let mut control = [0u64; 16];
+    // SAFETY: recvmsg writes at most `msg_namelen` bytes of address into
+    // the storage, and at most the lengths of the buffers given, which
+    // outlive the call. Control messages are read within `msg_controllen`
+    // with the CMSG macros, and their data without assuming its alignment.
+    let ((n, stamp), from) = unsafe {
+        socket2::SockAddr::try_init(|storage, len| {
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_name = storage.cast();
+            msg.msg_namelen = *len;
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+            let n = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            *len = msg.msg_namelen;
+
+            let mut stamp = None;
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                    && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS
+                {
+                    let time: libc::timespec =
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast());
+                    stamp = Some(time);
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+            Ok((n as usize, stamp))
+        })?
+    };
+
+    let read = Instant::now();
+    let waited = stamp
+        .and_then(|time| {
+            let secs = u64::try_from(time.tv_sec).ok()?;
+            let nanos = u32::try_from(time.tv_nsec).ok()?;
+            let arrived = std::time::UNIX_EPOCH + Duration::new(secs, nanos);
+            std::time::SystemTime::now().duration_since(arrived).ok()
+        })
+        .unwrap_or_default();
+    Ok((n, from, read.checked_sub(waited).unwrap_or(read)))
+}
+
+/// Kernel timestamps are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+fn recv_timestamped(
+    socket: &socket2::Socket,
+    buf: &mut [MaybeUninit<u8>],
+) -> std::io::Result<(usize, socket2::SockAddr, Instant)> {
+    let (n, from) = socket.recv_from(buf)?;
+    Ok((n, from, Instant::now()))
+}
+
+/// The length of the IP header in front of the ICMP message of a received packet.
+///
+/// ICMPv4 raw sockets, and datagram sockets on macOS, receive packets with
+/// their IPv4 header, whose length is in its IHL field. ICMPv6 sockets and
+/// ICMPv4 datagram sockets on Linux receive the ICMP message alone.
+///
+/// Returns `None` for an IPv4 header shorter than 20 bytes, or longer than
+/// the packet.
+fn ip_header_len(family: IpFamily, packet: &[u8]) -> Option<usize> {
+    match packet.first() {
+        Some(first) if family == IpFamily::V4 && first >> 4 == 4 => ipv4_header_len(packet),
+        _ => Some(0),
+    }
+}
+
+/// The length of the IPv4 `header` from its IHL field, `None` when shorter
+/// than the 20 bytes of a header without options, or than `header` itself.
+fn ipv4_header_len(header: &[u8]) -> Option<usize> {
+    let len = usize::from(header.first()? & 0x0f) * 4;
+    (len >= 20 && len <= header.len()).then_some(len)
+}
+
+/// The identifier and sequence number of an echo reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EchoReply {
+    id: u16,
+    seq: u16,
+}
+
+/// Parses the ICMP `message` of a received packet.
+///
+/// Returns `None` for messages of another type than `reply_type`, and an
+/// error for truncated echo replies and, with `verify_checksum`, ones whose
+/// checksum is invalid. The kernel verifies ICMPv6 checksums itself.
+fn parse_echo_reply(
+    message: &[u8],
+    reply_type: u8,
+    verify_checksum: bool,
+) -> Result<Option<EchoReply>, ()> {
+    match message.first() {
+        Some(&message_type) if message_type == reply_type => {}
+        _ => return Ok(None),
+    }
+    // A valid checksum makes the checksum of the whole message zero.
+    if message.len() < 8 || (verify_checksum && checksum(message) != 0) {
+        return Err(());
+    }
+
+    Ok(Some(EchoReply {
+        id: u16::from_be_bytes([message[4], message[5]]),
+        seq: u16::from_be_bytes([message[6], message[7]]),
+    }))
+}
+
+/// The offset of the first byte of the echoed `data` that differs from the
+/// `payload` sent, or of the end of the shorter one, `None` if they match.
+fn corrupted_offset(data: &[u8], payload: &[u8]) -> Option<usize> {
+    if data == payload {
+        return None;
+    }
+    let offset = data
+        .iter()
+        .zip(payload)
+        .position(|(received, sent)| received != sent)
+        .unwrap_or(data.len().min(payload.len()));
+    Some(offset)
+}
+
+/// Why a router or host dropped an echo request, as told by an ICMP error
+/// message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpError {
+    /// The TTL of the request ran out (Time Exceeded).
+    TimeExceeded,
+    /// Fragmentation Needed (IPv4) or Packet Too Big (IPv6), with the MTU
+    /// of the next hop, 0 when the router does not tell it.
+    TooBig { mtu: u32 },
+    /// Destination Unreachable, for another reason than the size.
+    Unreachable(Unreachable),
+}
+
+/// Why a destination is unreachable, from the code of a Destination
+/// Unreachable message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unreachable {
+    /// No route to the network of the destination.
+    Network,
+    /// The destination host does not answer on its network.
+    Host,
+    /// The destination does not support the protocol (IPv4 only).
+    Protocol,
+    /// Nothing listens on the port of the destination.
+    Port,
+    /// A firewall or policy forbids reaching the destination.
+    Prohibited,
+    /// Another ICMP code.
+    Other(u8),
+}
+
+impl Unreachable {
+    /// Tells the reason of a Destination Unreachable message with `code`.
+    fn from_code(family: IpFamily, code: u8) -> Self {
+        match (family, code) {
+            (IpFamily::V4, 0 | 6 | 11) | (IpFamily::V6, 0) => Unreachable::Network,
+            (IpFamily::V4, 1 | 7 | 12) | (IpFamily::V6, 3) => Unreachable::Host,
+            (IpFamily::V4, 2) => Unreachable::Protocol,
+            (IpFamily::V4, 3) | (IpFamily::V6, 4) => Unreachable::Port,
+            (IpFamily::V4, 9 | 10 | 13) | (IpFamily::V6, 1 | 5 | 6) => Unreachable::Prohibited,
+            (_, code) => Unreachable::Other(code),
+        }
+    }
+}
+
+/// Parses the ICMP error `message`, which quotes the start of the dropped
+/// packet: its IP header and the ICMP header of the echo request.
+///
+/// Returns the error, the destination of the dropped packet, and the
+/// identifier and sequence number of the echo request, or `None` for other
+/// messages.
+fn parse_error_message(message: &[u8], family: IpFamily) -> Option<(IcmpError, IpAddr, EchoReply)> {
+    let header = message.get(..8)?;
+    let error = match (family, header[0], header[1]) {
+        (IpFamily::V4, ICMP_TIME_EXCEEDED, _) | (IpFamily::V6, ICMPV6_TIME_EXCEEDED, _) => {
+            IcmpError::TimeExceeded
+        }
+        // The next-hop MTU is in the low half of the unused word (RFC 1191).
+        (IpFamily::V4, ICMP_DEST_UNREACHABLE, ICMP_FRAGMENTATION_NEEDED) => IcmpError::TooBig {
+            mtu: u32::from(u16::from_be_bytes([header[6], header[7]])),
+        },
+        (IpFamily::V6, ICMPV6_PACKET_TOO_BIG, _) => IcmpError::TooBig {
+            mtu: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+        },
+        (IpFamily::V4, ICMP_DEST_UNREACHABLE, code)
+        | (IpFamily::V6, ICMPV6_DEST_UNREACHABLE, code) => {
+            IcmpError::Unreachable(Unreachable::from_code(family, code))
+        }
+        _ => return None,
+    };
+    let request_type = match family {
+        IpFamily::V4 => ICMP_ECHO_REQUEST,
+        IpFamily::V6 => ICMPV6_ECHO_REQUEST,
+    };
+
+    let quoted = message.get(8..)?;
+    let (destination, header_len) = match family {
+        IpFamily::V4 => {
+            let header_len = ipv4_header_len(quoted)?;
+            let destination: [u8; 4] = quoted.get(16..20)?.try_into().ok()?;
+            (IpAddr::from(destination), header_len)
+        }
+        IpFamily::V6 => {
+            let destination: [u8; 16] = quoted.get(24..40)?.try_into().ok()?;
+            (IpAddr::from(destination), 40)
+        }
+    };
+    let request = quoted.get(header_len..header_len + 8)?;
+    if request[0] != request_type {
+        return None;
+    }
+
+    Some((
+        error,
+        destination,
+        EchoReply {
+            id: u16::from_be_bytes([request[4], request[5]]),
+            seq: u16::from_be_bytes([request[6], request[7]]),
+        },
+    ))
+}
+
+fn build_packet(request_type: u8, seq: u16, pid: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0u8; 8 + payload.len()]; // ICMP header: type(1 byte), code(1 byte), checksum(2 bytes), id(2), seq(2 byte), then the payload
+    packet[0] = request_type; // Type
     packet[1] = 0; // Code
     packet[2] = 0; // Checksum placeholder for 1st checksum byte
     packet[3] = 0; // Checksum placeholder for 2nd checksum byte
     packet[4..6].copy_from_slice(&pid.to_be_bytes());
     packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    packet[8..].copy_from_slice(payload);
 
     let cs = checksum(&packet);
 
@@ -119,8 +1583,20 @@ fn build_packet(seq: u16, pid: u16) -> Vec<u8> {
 /// # Returns
 ///
 /// * `u16` - The computed checksum value.
-fn checksum(data: &[u8]) -> u16 {
-    let mut sum = 0u32;
+///
+/// # Example
+/// ```
+/// use crabyknife::ping::checksum;
+///
+/// // A valid checksum makes the checksum of the whole packet zero.
+/// let mut packet = [8, 0, 0, 0, 0x12, 0x34, 0, 1];
+/// let sum = checksum(&packet);
+/// packet[2..4].copy_from_slice(&sum.to_be_bytes());
+/// assert_eq!(checksum(&packet), 0);
+/// ```
+pub fn checksum(data: &[u8]) -> u16 {
+    // Wide enough for the words of any input to add up without overflowing.
+    let mut sum = 0u64;
     let chunks = data.chunks(2);
 
     for chunk in chunks {
@@ -136,10 +1612,10 @@ fn checksum(data: &[u8]) -> u16 {
             // << 8 shifts it left by 8 bits → becomes 0xAB00.
             (chunk[0] as u16) << 8
         };
-        sum += val as u32;
+        sum += u64::from(val);
     }
 
-    // The checksum is computed as a sum of 16-bit words, but the accumulator sum is a u32, so it can grow beyond 16 bits. If any carry bits (bits beyond the lowest 16) are generated, we have to add them back into the result.
+    // The checksum is computed as a sum of 16-bit words, but the accumulator sum is a u64, so it can grow beyond 16 bits. If any carry bits (bits beyond the lowest 16) are generated, we have to add them back into the result.
     //
     // This is called "end-around carry."
     //
@@ -166,3 +1642,323 @@ fn checksum(data: &[u8]) -> u16 {
     // ! would flip the bits in the sum.
     !(sum as u16)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_header_len() {
+        let mut packet = vec![0u8; 32];
+        packet[0] = 0x45;
+        assert_eq!(ip_header_len(IpFamily::V4, &packet), Some(20));
+        // A header with options.
+        packet[0] = 0x46;
+        assert_eq!(ip_header_len(IpFamily::V4, &packet), Some(24));
+        // An echo reply without IP header, as read from a datagram socket.
+        assert_eq!(ip_header_len(IpFamily::V4, &[ICMP_ECHO_REPLY; 8]), Some(0));
+        assert_eq!(ip_header_len(IpFamily::V6, &[0x60; 8]), Some(0));
+        // Headers shorter than the minimum, or than the IHL field tells.
+        assert_eq!(ip_header_len(IpFamily::V4, &[0x44; 32]), None);
+        assert_eq!(ip_header_len(IpFamily::V4, &[0x4f; 32]), None);
+    }
+
+    #[test]
+    fn test_parse_echo_reply() {
+        let mut reply = build_packet(ICMP_ECHO_REPLY, 7, 0x1234, &[0, 1, 2, 3, 4]);
+        assert_eq!(
+            parse_echo_reply(&reply, ICMP_ECHO_REPLY, true),
+            Ok(Some(EchoReply { id: 0x1234, seq: 7 }))
+        );
+
+        let request = build_packet(ICMP_ECHO_REQUEST, 7, 0x1234, &[]);
+        assert_eq!(parse_echo_reply(&request, ICMP_ECHO_REPLY, true), Ok(None));
+        assert_eq!(parse_echo_reply(&[], ICMP_ECHO_REPLY, true), Ok(None));
+        assert_eq!(
+            parse_echo_reply(&reply[..6], ICMP_ECHO_REPLY, true),
+            Err(())
+        );
+
+        reply[9] ^= 0xff;
+        assert_eq!(parse_echo_reply(&reply, ICMP_ECHO_REPLY, true), Err(()));
+        assert!(parse_echo_reply(&reply, ICMP_ECHO_REPLY, false).is_ok());
+    }
+
+    #[test]
+    fn test_parse_echo_request() {
+        let from: IpAddr = "192.0.2.1".parse().unwrap();
+        // An IPv4 header without options, with a TTL of 63.
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 63, 1];
+        packet.resize(20, 0);
+        packet.extend(build_packet(ICMP_ECHO_REQUEST, 3, 0xbeef, b"hello"));
+        assert_eq!(
+            parse_echo_request(IpFamily::V4, &packet, from),
+            Some(EchoRequest {
+                from,
+                id: 0xbeef,
+                seq: 3,
+                ttl: Some(63),
+                payload: b"hello".to_vec(),
+                answered: false,
+            })
+        );
+
+        let last = packet.len() - 1;
+        packet[last] ^= 0xff;
+        assert_eq!(parse_echo_request(IpFamily::V4, &packet, from), None);
+        let reply = build_packet(ICMP_ECHO_REPLY, 3, 0xbeef, &[]);
+        assert_eq!(parse_echo_request(IpFamily::V4, &reply, from), None);
+
+        let request = build_packet(ICMPV6_ECHO_REQUEST, 4, 1, &[]);
+        let from: IpAddr = "::1".parse().unwrap();
+        let parsed = parse_echo_request(IpFamily::V6, &request, from).unwrap();
+        assert_eq!((parsed.seq, parsed.ttl), (4, None));
+    }
+
+    #[test]
+    fn test_payload() {
+        let config = PingConfig::new().payload_size(5);
+        assert_eq!(config.payload(), [0, 1, 2, 3, 4]);
+        let config = config.pattern(vec![0xff, 0x00]);
+        assert_eq!(config.payload(), [0xff, 0x00, 0xff, 0x00, 0xff]);
+
+        let payload = config.payload();
+        assert_eq!(corrupted_offset(&payload, &payload), None);
+        assert_eq!(corrupted_offset(&[0xff, 0x00, 0xfe], &payload), Some(2));
+        assert_eq!(corrupted_offset(&payload[..3], &payload), Some(3));
+    }
+
+    #[test]
+    fn test_parse_error_message() {
+        let request = build_packet(ICMP_ECHO_REQUEST, 3, 0x1234, &[]);
+        let mut message = vec![ICMP_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0];
+        let mut header = vec![0u8; 20];
+        header[0] = 0x45;
+        header[16..20].copy_from_slice(&[192, 0, 2, 1]);
+        message.extend(header);
+        message.extend(&request);
+        let request_id = EchoReply { id: 0x1234, seq: 3 };
+        let destination: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V4),
+            Some((IcmpError::TimeExceeded, destination, request_id))
+        );
+        assert_eq!(parse_error_message(&message[..30], IpFamily::V4), None);
+        assert_eq!(parse_error_message(&request, IpFamily::V4), None);
+
+        message[..2].copy_from_slice(&[ICMP_DEST_UNREACHABLE, ICMP_FRAGMENTATION_NEEDED]);
+        message[6..8].copy_from_slice(&1400u16.to_be_bytes());
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V4),
+            Some((IcmpError::TooBig { mtu: 1400 }, destination, request_id))
+        );
+        // Other unreachable codes are not about the size.
+        message[1] = 1;
+        let unreachable = IcmpError::Unreachable(Unreachable::Host);
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V4),
+            Some((unreachable, destination, request_id))
+        );
+        message[1] = 14;
+        let unreachable = IcmpError::Unreachable(Unreachable::Other(14));
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V4),
+            Some((unreachable, destination, request_id))
+        );
+        // A quoted header shorter than the minimum.
+        message[8] = 0x44;
+        assert_eq!(parse_error_message(&message, IpFamily::V4), None);
+
+        let request = build_packet(ICMPV6_ECHO_REQUEST, 9, 0x4321, &[]);
+        let mut message = vec![ICMPV6_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0];
+        let mut header = vec![0u8; 40];
+        header[0] = 0x60;
+        header[39] = 1;
+        message.extend(header);
+        message.extend(&request);
+        let request_id = EchoReply { id: 0x4321, seq: 9 };
+        let destination: IpAddr = "::1".parse().unwrap();
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V6),
+            Some((IcmpError::TimeExceeded, destination, request_id))
+        );
+
+        message[0] = ICMPV6_PACKET_TOO_BIG;
+        message[4..8].copy_from_slice(&1280u32.to_be_bytes());
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V6),
+            Some((IcmpError::TooBig { mtu: 1280 }, destination, request_id))
+        );
+        message[..2].copy_from_slice(&[ICMPV6_DEST_UNREACHABLE, 4]);
+        let unreachable = IcmpError::Unreachable(Unreachable::Port);
+        assert_eq!(
+            parse_error_message(&message, IpFamily::V6),
+            Some((unreachable, destination, request_id))
+        );
+    }
+
+    #[test]
+    fn test_resolve_addresses() {
+        let mut resolver = Resolver::new();
+        for spec in ["h:127.0.0.1", "h:[::1]", "h:127.0.0.2"] {
+            resolver.add_override(spec).unwrap();
+        }
+        let addresses = |config: PingConfig| -> Vec<String> {
+            let resolution = resolve_addresses("h", &resolver, &config).unwrap();
+            resolution
+                .addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(
+            addresses(PingConfig::new()),
+            ["127.0.0.1", "127.0.0.2", "::1"]
+        );
+        assert_eq!(
+            addresses(PingConfig::new().preferred_family(IpFamily::V6)),
+            ["::1", "127.0.0.1", "127.0.0.2"]
+        );
+        assert_eq!(
+            addresses(PingConfig::new().family(Some(IpFamily::V6))),
+            ["::1"]
+        );
+
+        let config = PingConfig::new().family(Some(IpFamily::V6));
+        assert!(resolve_addresses("127.0.0.1", &resolver, &config).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_kernel_timestamps() {
+        let udp = |address: &str| {
+            let socket =
+                socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, None).unwrap();
+            let address: std::net::SocketAddr = address.parse().unwrap();
+            socket.bind(&address.into()).unwrap();
+            socket
+        };
+        let receiver = udp("127.0.0.1:0");
+        assert_eq!(enable_timestamps(&receiver), Timestamps::Kernel);
+        let sender = udp("127.0.0.1:0");
+        sender
+            .send_to(b"ping", &receiver.local_addr().unwrap())
+            .unwrap();
+
+        // The packet arrived before it is read, however late.
+        std::thread::sleep(Duration::from_millis(50));
+        let mut buf = [MaybeUninit::uninit(); 16];
+        let (n, from, arrived) = recv_timestamped(&receiver, &mut buf).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(from.as_socket(), sender.local_addr().unwrap().as_socket());
+        assert!(arrived.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_tcp_ping() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = PingConfig::new()
+            .count(2)
+            .interval(Duration::ZERO)
+            .timeout(Duration::from_secs(1));
+
+        let mut events = Vec::new();
+        let report =
+            tcp_ping_with_progress("127.0.0.1", port, &Resolver::new(), &config, |event| {
+                events.push(event)
+            })
+            .unwrap();
+        assert_eq!((report.sent, report.received, report.refused), (2, 2, 0));
+        assert_eq!(events[1], PingEvent::Timestamps(Timestamps::Userspace));
+        assert!(matches!(events[2], PingEvent::Reply { seq: 0, .. }));
+
+        // Nothing listens on the port once the listener is closed.
+        drop(listener);
+        let report =
+            tcp_ping_with_progress("127.0.0.1", port, &Resolver::new(), &config, |_| {}).unwrap();
+        assert_eq!((report.sent, report.received, report.refused), (2, 0, 2));
+    }
+
+    #[test]
+    fn test_ping_many() {
+        // Hostnames are not looked up, so both hosts fail before probing.
+        let resolver = Resolver::new().literal_only(true);
+        let mut events = Vec::new();
+        let reports = ping_many_with_progress(
+            &["first.invalid", "second.invalid"],
+            &resolver,
+            &PingConfig::new(),
+            |index, event| events.push((index, event)),
+        );
+        assert!(events.is_empty());
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("first.invalid"));
+        assert!(reports[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("second.invalid"));
+    }
+
+    #[cfg(all(feature = "tokio", unix))]
+    #[test]
+    fn test_ping_async_config() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let ping = |config| runtime.block_on(ping_async("127.0.0.1", &Resolver::new(), &config));
+        assert!(ping(PingConfig::new().timeout(Duration::ZERO)).is_err());
+        assert!(ping(PingConfig::new().payload_size(70_000)).is_err());
+    }
+
+    #[test]
+    fn test_report_statistics() {
+        let address: IpAddr = "::1".parse().unwrap();
+        let report = PingReport::new(
+            address,
+            vec![
+                Some(Duration::from_millis(2)),
+                Some(Duration::from_millis(4)),
+                None,
+                Some(Duration::from_millis(4)),
+                Some(Duration::from_millis(6)),
+            ],
+        );
+        assert_eq!((report.sent, report.received), (5, 4));
+        assert_eq!(report.loss(), 0.2);
+        assert_eq!(report.min, Some(Duration::from_millis(2)));
+        assert_eq!(report.avg, Some(Duration::from_millis(4)));
+        assert_eq!(report.max, Some(Duration::from_millis(6)));
+        assert_eq!(report.stddev.map(|stddev| stddev.as_micros()), Some(1414));
+
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(4)));
+        assert_eq!(report.percentile(90.0), Some(Duration::from_millis(6)));
+        assert_eq!(report.percentile(0.0), Some(Duration::from_millis(2)));
+        let ms = Duration::from_millis;
+        assert_eq!(report.histogram(2), [(ms(2)..ms(4), 1), (ms(4)..ms(6), 3)]);
+        assert_eq!(
+            report.histogram(4),
+            [
+                (ms(2)..ms(3), 1),
+                (ms(3)..ms(4), 0),
+                (ms(4)..ms(5), 2),
+                (ms(5)..ms(6), 1)
+            ]
+        );
+
+        let report = PingReport::new(address, vec![None, None]);
+        assert_eq!(report.loss(), 1.0);
+        assert_eq!((report.min, report.avg, report.stddev), (None, None, None));
+        assert_eq!(report.percentile(99.0), None);
+        assert!(report.histogram(10).is_empty());
+
+        let report = PingReport::new(address, vec![Some(ms(3)), Some(ms(3))]);
+        assert_eq!(report.histogram(10), [(ms(3)..ms(3), 2)]);
+    }
+}
@@ -0,0 +1,331 @@
+//! Path MTU discovery: echo requests with the Don't Fragment bit set are
+//! sent to a host, binary searching the largest packet that reaches it
+//! whole.
+//!
+//! A router that cannot forward a probe without fragmenting it drops it and
+//! answers with Fragmentation Needed (Packet Too Big over IPv6), telling the
+//! MTU of its next hop. Only raw sockets receive those messages, so the
+//! discovery needs root or `CAP_NET_RAW`. Routers filtering ICMP drop the
+//! probes silently, which is told apart from the loss of small probes by
+//! sending each size a few times.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::ping::{self, IcmpError, IcmpSocket, IpFamily, PingConfig, Received};
+use crate::resolver::Resolver;
+
+/// The sizes of the IPv4 and IPv6 headers, and of the ICMP echo header,
+/// in front of the payload of a probe.
+const IPV4_HEADER_LEN: u32 = 20;
+const IPV6_HEADER_LEN: u32 = 40;
+const ICMP_HEADER_LEN: u32 = 8;
+
+/// The smallest MTU every IPv4 (RFC 791) and IPv6 (RFC 8200) link has.
+const IPV4_MIN_MTU: u32 = 68;
+const IPV6_MIN_MTU: u32 = 1280;
+
+/// The size of the largest IP packet.
+const MAX_MTU: u32 = 65_535;
+
+/// How [`pmtu_with_progress`] probes the path to a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmtuConfig {
+    timeout: Duration,
+    attempts: usize,
+    max_mtu: u32,
+    family: Option<IpFamily>,
+}
+
+impl Default for PmtuConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+            attempts: 3,
+            max_mtu: MAX_MTU,
+            family: None,
+        }
+    }
+}
+
+impl PmtuConfig {
+    /// Creates the default config: probes of up to 65535 bytes, each size
+    /// sent up to 3 times and waited for one second.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long the answer to a probe is waited for.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a size is probed before it counts as dropped.
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Sets the largest MTU probed, at most 65535.
+    pub fn max_mtu(mut self, max_mtu: u32) -> Self {
+        self.max_mtu = max_mtu;
+        self
+    }
+
+    /// Restricts the target to addresses of `family`, instead of preferring IPv4.
+    pub fn family(mut self, family: Option<IpFamily>) -> Self {
+        self.family = family;
+        self
+    }
+}
+
+/// What became of a probe, see [`pmtu_with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// The target answered it.
+    Fits,
+    /// The local interface cannot send a packet that large.
+    TooBigForInterface,
+    /// The router `address` cannot forward it, its next hop taking at most
+    /// `mtu` bytes (0 when the router does not tell).
+    TooBigAt { address: IpAddr, mtu: u32 },
+    /// No answer to any attempt.
+    Lost,
+}
+
+/// What limits the MTU of a path, see [`Pmtu`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bottleneck {
+    /// The MTU of the local interface.
+    Interface,
+    /// The router `address`, which needs larger packets fragmented as its
+    /// next hop takes at most `mtu` bytes (0 when it does not tell).
+    Router { address: IpAddr, mtu: u32 },
+    /// Larger probes vanished without an answer, e.g. dropped by a router
+    /// filtering ICMP.
+    Silent,
+}
+
+/// The outcome of a path MTU discovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pmtu {
+    /// The address of the target.
+    pub address: IpAddr,
+    /// The size of the largest IP packet that reached the target whole.
+    pub mtu: u32,
+    /// What stopped larger packets, `None` when the largest size probed fit.
+    pub bottleneck: Option<Bottleneck>,
+}
+
+/// Discovers the MTU of the path to the specified host.
+///
+/// `on_probe` is called with the size of each IP packet probed and what
+/// became of it, e.g. to print progress.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be resolved or does not answer
+/// the smallest probes, the config has a zero timeout or number of
+/// attempts, no raw ICMP socket can be opened, the Don't Fragment bit
+/// cannot be set (Linux only), or sending a probe fails.
+pub fn pmtu_with_progress(
+    target: &str,
+    resolver: &Resolver,
+    config: &PmtuConfig,
+    mut on_probe: impl FnMut(u32, Probe),
+) -> Result<Pmtu, Box<dyn std::error::Error>> {
+    if config.timeout.is_zero() {
+        return Err("the probe timeout must be greater than zero".into());
+    }
+    if config.attempts == 0 {
+        return Err("the number of attempts must be greater than zero".into());
+    }
+
+    let ping_config = PingConfig::new().family(config.family);
+    let address = ping::resolve_target(target, resolver, &ping_config, &mut |_| {})?;
+    let (header_len, min_mtu) = match IpFamily::of(&address) {
+        IpFamily::V4 => (IPV4_HEADER_LEN + ICMP_HEADER_LEN, IPV4_MIN_MTU),
+        IpFamily::V6 => (IPV6_HEADER_LEN + ICMP_HEADER_LEN, IPV6_MIN_MTU),
+    };
+    if !(min_mtu..=MAX_MTU).contains(&config.max_mtu) {
+        return Err(format!(
+            "the maximum MTU ({}) must be from {min_mtu} to {MAX_MTU} bytes",
+            config.max_mtu
+        )
+        .into());
+    }
+
+    let icmp = IcmpSocket::open(address, None)?;
+    if !icmp.raw {
+        return Err("discovering the path MTU needs a raw ICMP socket to receive Fragmentation Needed messages, run as root or with CAP_NET_RAW".into());
+    }
+    icmp.set_dont_fragment()?;
+
+    let mut seq: u16 = 0;
+    let mut probe = |mtu: u32| -> Result<Probe, Box<dyn std::error::Error>> {
+        let payload_size = (mtu - header_len) as usize;
+        let payload = vec![0u8; payload_size];
+        for _ in 0..config.attempts {
+            seq = seq.wrapping_add(1);
+            match icmp.try_send(seq, &payload) {
+                Ok(()) => {}
+                Err(err) if err.raw_os_error() == Some(EMSGSIZE) => {
+                    return Ok(Probe::TooBigForInterface)
+                }
+                Err(err) => return Err(format!("failed to send a probe: {err}").into()),
+            }
+
+            let deadline = Instant::now() + config.timeout;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                icmp.socket
+                    .set_read_timeout(Some(remaining))
+                    .map_err(|err| format!("failed to set socket timeout: {err}"))?;
+                let Ok((packet, from, _)) = icmp.recv(payload_size) else {
+                    break;
+                };
+                match icmp.classify(&packet, from, &payload) {
+                    Received::Echo { seq: answered, .. }
+                    | Received::Corrupted { seq: answered, .. }
+                        if answered == seq =>
+                    {
+                        return Ok(Probe::Fits)
+                    }
+                    Received::Error {
+                        seq: answered,
+                        from,
+                        error: IcmpError::TooBig { mtu },
+                    } if answered == seq => return Ok(Probe::TooBigAt { address: from, mtu }),
+                    // Answers to earlier attempts, or other traffic.
+                    _ => {}
+                }
+            }
+        }
+        Ok(Probe::Lost)
+    };
+
+    let (mtu, bottleneck) = search(min_mtu, config.max_mtu, |mtu| {
+        let outcome = probe(mtu)?;
+        on_probe(mtu, outcome);
+        Ok(outcome)
+    })?;
+    Ok(Pmtu {
+        address,
+        mtu,
+        bottleneck,
+    })
+}
+
+/// The errno of a packet too big to send.
+#[cfg(unix)]
+const EMSGSIZE: i32 = libc::EMSGSIZE;
+/// WSAEMSGSIZE.
+#[cfg(windows)]
+const EMSGSIZE: i32 = 10040;
+
+/// Binary searches the largest size from `min` to `max` that `probe` finds
+/// fitting, starting with `min` to check that the target answers at all,
+/// then `max`. Sizes that do not fit are answered by what stopped them,
+/// which the MTU reported by routers narrows the search to.
+///
+/// Returns the size found, and what stopped the next larger size.
+fn search(
+    min: u32,
+    max: u32,
+    mut probe: impl FnMut(u32) -> Result<Probe, Box<dyn std::error::Error>>,
+) -> Result<(u32, Option<Bottleneck>), Box<dyn std::error::Error>> {
+    if probe(min)? != Probe::Fits {
+        return Err(format!("the target does not answer echo requests of {min} bytes").into());
+    }
+
+    // The largest size known to fit, and the smallest known not to with
+    // what stopped it.
+    let mut fits = min;
+    let mut stopped: Option<(u32, Bottleneck)> = None;
+    // The latest next-hop MTU reported by a router.
+    let mut reported_mtu = None;
+    let mut next = Some(max);
+    while let Some(mtu) = next {
+        let bottleneck = match probe(mtu)? {
+            Probe::Fits => None,
+            Probe::TooBigForInterface => Some(Bottleneck::Interface),
+            Probe::TooBigAt { address, mtu } => Some(Bottleneck::Router { address, mtu }),
+            Probe::Lost => Some(Bottleneck::Silent),
+        };
+        let reported = match bottleneck {
+            Some(Bottleneck::Router { mtu, .. }) => Some(mtu),
+            _ => None,
+        };
+        reported_mtu = reported.or(reported_mtu);
+        match bottleneck {
+            None => fits = mtu,
+            Some(bottleneck) => stopped = Some((mtu, bottleneck)),
+        }
+
+        let limit = stopped.map_or(max + 1, |(size, _)| size);
+        next = match reported {
+            // The size the router takes is the likeliest answer.
+            Some(reported) if reported > fits && reported < limit => Some(reported),
+            _ if limit - fits <= 1 => None,
+            // It fit, so the next size up is the one to rule out.
+            _ if reported_mtu == Some(fits) => Some(fits + 1),
+            _ => Some(fits + (limit - fits) / 2),
+        };
+    }
+    Ok((fits, stopped.map(|(_, bottleneck)| bottleneck)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pmtu_config() {
+        let pmtu = |config| pmtu_with_progress("127.0.0.1", &Resolver::new(), &config, |_, _| {});
+        assert!(pmtu(PmtuConfig::new().timeout(Duration::ZERO)).is_err());
+        assert!(pmtu(PmtuConfig::new().attempts(0)).is_err());
+        assert!(pmtu(PmtuConfig::new().max_mtu(67)).is_err());
+        assert!(pmtu(PmtuConfig::new().max_mtu(70_000)).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let router: IpAddr = "192.0.2.1".parse().unwrap();
+        // A 9000 bytes interface, and a router forwarding up to 1400 bytes.
+        let path = |reports: bool, probed: &mut Vec<u32>, mtu: u32| {
+            probed.push(mtu);
+            Ok(match mtu {
+                ..=1400 => Probe::Fits,
+                1401..=9000 if reports => Probe::TooBigAt {
+                    address: router,
+                    mtu: 1400,
+                },
+                1401..=9000 => Probe::Lost,
+                _ => Probe::TooBigForInterface,
+            })
+        };
+
+        let mut probed = Vec::new();
+        let found = search(68, 65_535, |mtu| path(true, &mut probed, mtu)).unwrap();
+        let bottleneck = Bottleneck::Router {
+            address: router,
+            mtu: 1400,
+        };
+        assert_eq!(found, (1400, Some(bottleneck)));
+        // The MTU reported by the router is probed right away.
+        assert_eq!(probed, [68, 65_535, 32_801, 16_434, 8_251, 1_400, 1_401]);
+
+        let mut probed = Vec::new();
+        let found = search(68, 65_535, |mtu| path(false, &mut probed, mtu)).unwrap();
+        assert_eq!(found, (1400, Some(Bottleneck::Silent)));
+
+        let found = search(68, 1000, |mtu| path(true, &mut Vec::new(), mtu)).unwrap();
+        assert_eq!(found, (1000, None));
+
+        assert!(search(68, 1000, |_| Ok(Probe::Lost)).is_err());
+    }
+}
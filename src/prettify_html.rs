@@ -0,0 +1,437 @@
+//! Prettifying of real-world HTML.
+//!
+//! HTML is first normalized into well-formed markup by a lenient parser,
+//! then laid out by the XML prettifier, so both share their formatting and
+//! [`PrettifyOptions`]. The parser:
+//!
+//! - matches tag and attribute names case-insensitively and writes them in
+//!   lowercase, except inside `<svg>` and `<math>`,
+//! - knows the void elements, e.g. `<br>` and `<img>`, which have no end tag,
+//! - closes elements whose end tag is implied, e.g. `<p>` and `<li>`,
+//!   or missing at the end of the document,
+//! - ignores stray end tags and repeated attributes,
+//! - accepts unquoted and valueless attributes,
+//! - keeps the content of `<script>`, `<style>`, `<pre>` and `<textarea>` as is.
+
+use crate::prettify_xml::{prettify_xml_with_options, PrettifyOptions};
+
+/// Elements that never have content nor an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose content is raw text, ended only by their end tag.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements whose content is text, ended only by their end tag.
+const TEXT_ONLY_ELEMENTS: &[&str] = &["textarea", "title"];
+
+/// Elements whose whitespace is significant.
+const WHITESPACE_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Elements that start a block, closing an open `<p>`.
+const CLOSES_PARAGRAPH: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Prettify a given HTML document or fragment.
+///
+/// # Example
+/// ```
+/// use crabyknife::prettify_html::prettify_html;
+///
+/// assert_eq!(
+///     prettify_html("<UL><LI>one<LI>two<BR></UL>").unwrap(),
+///     "<ul>\n  <li>one</li>\n  <li>two\n    <br></li>\n</ul>"
+/// );
+/// ```
+pub fn prettify_html(html: &str) -> Result<String, Box<dyn std::error::Error>> {
+    prettify_html_with_options(html, &PrettifyOptions::default())
+}
+
+/// Prettify a given HTML document or fragment using the layout described
+/// by `options`. Whitespace is always kept in `<pre>`, `<textarea>`,
+/// `<script>` and `<style>`.
+pub fn prettify_html_with_options(
+    html: &str,
+    options: &PrettifyOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut options = options.clone().html();
+    for tag in WHITESPACE_ELEMENTS {
+        options = options.preserve_whitespace_in(tag);
+    }
+
+    Ok(prettify_xml_with_options(&normalize(html), &options)?)
+}
+
+/// Rewrites lenient HTML into well-formed markup the XML reader accepts.
+fn normalize(html: &str) -> String {
+    let mut normalizer = Normalizer {
+        output: String::with_capacity(html.len()),
+        open: Vec::new(),
+    };
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        normalizer.text(&rest[..start]);
+        rest = &rest[start..];
+
+        let next = rest.as_bytes().get(1).copied().unwrap_or_default();
+        rest = if let Some(comment) = rest.strip_prefix("<!--") {
+            let (content, after) = comment.split_once("-->").unwrap_or((comment, ""));
+            normalizer.comment(content);
+            after
+        } else if starts_with_ignore_case(rest, "<!doctype") {
+            let doctype = &rest["<!doctype".len()..];
+            let (content, after) = doctype.split_once('>').unwrap_or((doctype, ""));
+            normalizer.output.push_str("<!DOCTYPE ");
+            normalizer.output.push_str(content.trim());
+            normalizer.output.push('>');
+            after
+        } else if next == b'!' || next == b'?' {
+            // Anything else starting with `<!` or `<?` is a bogus comment in HTML.
+            let (content, after) = rest[2..].split_once('>').unwrap_or((&rest[2..], ""));
+            normalizer.comment(content);
+            after
+        } else if next == b'/' && rest.as_bytes().get(2).is_some_and(u8::is_ascii_alphabetic) {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let name = rest[2..end].split_whitespace().next().unwrap_or_default();
+            normalizer.end_tag(name);
+            &rest[(end + 1).min(rest.len())..]
+        } else if next.is_ascii_alphabetic() {
+            let after = normalizer.start_tag(&rest[1..]);
+            normalizer.raw_content(after)
+        } else {
+            normalizer.output.push_str("&lt;");
+            &rest[1..]
+        };
+    }
+    normalizer.text(rest);
+
+    while let Some(name) = normalizer.open.pop() {
+        normalizer.close(&name);
+    }
+
+    normalizer.output
+}
+
+struct Normalizer {
+    output: String,
+    /// The open elements, innermost last.
+    open: Vec<String>,
+}
+
+impl Normalizer {
+    fn text(&mut self, text: &str) {
+        self.output.push_str(&text.replace('<', "&lt;"));
+    }
+
+    fn comment(&mut self, content: &str) {
+        self.output.push_str("<!--");
+        self.output.push_str(content);
+        self.output.push_str("-->");
+    }
+
+    fn close(&mut self, name: &str) {
+        self.output.push_str("</");
+        self.output.push_str(name);
+        self.output.push('>');
+    }
+
+    /// Whether names are case-sensitive, inside `<svg>` and `<math>`.
+    fn in_foreign_content(&self) -> bool {
+        self.open.iter().any(|name| name == "svg" || name == "math")
+    }
+
+    /// Parses a start tag from just after its `<` and returns what follows it.
+    fn start_tag<'a>(&mut self, tag: &'a str) -> &'a str {
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .unwrap_or(tag.len());
+        let mut name = tag[..name_end].to_string();
+        let foreign = self.in_foreign_content()
+            || name.eq_ignore_ascii_case("svg")
+            || name.eq_ignore_ascii_case("math");
+        if !foreign {
+            name.make_ascii_lowercase();
+        }
+
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        let mut self_closing = false;
+        let mut rest = &tag[name_end..];
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(after) = rest.strip_prefix('>') {
+                rest = after;
+                break;
+            }
+            if let Some(after) = rest.strip_prefix("/>") {
+                self_closing = true;
+                rest = after;
+                break;
+            }
+            if let Some(after) = rest.strip_prefix('/') {
+                rest = after;
+                continue;
+            }
+
+            let key_end = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+                .unwrap_or(rest.len())
+                .max(1);
+            let mut key = rest[..key_end].to_string();
+            if !foreign {
+                key.make_ascii_lowercase();
+            }
+            rest = rest[key_end..].trim_start();
+
+            let mut value = "";
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                (value, rest) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        after[1..].split_once(quote).unwrap_or((&after[1..], ""))
+                    }
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        after.split_at(end)
+                    }
+                };
+            }
+
+            // Like browsers, the first of repeated attributes wins. Names
+            // that cannot be written back as markup are dropped.
+            let writable = !key.contains(['"', '\'', '<', '=']);
+            if writable && !attributes.iter().any(|(existing, _)| *existing == key) {
+                attributes.push((key, value.to_string()));
+            }
+        }
+
+        // Open elements whose end tag is implied by this one.
+        while let Some(open) = self.open.last() {
+            if !implies_end(open, &name) {
+                break;
+            }
+            let open = self.open.pop().unwrap_or_default();
+            self.close(&open);
+        }
+
+        self.output.push('<');
+        self.output.push_str(&name);
+        for (key, value) in &attributes {
+            self.output.push(' ');
+            self.output.push_str(key);
+            self.output.push_str("=\"");
+            self.output
+                .push_str(&value.replace('"', "&quot;").replace('<', "&lt;"));
+            self.output.push('"');
+        }
+
+        if VOID_ELEMENTS.contains(&name.as_str()) {
+            self.output.push_str("/>");
+        } else if self_closing {
+            self.output.push('>');
+            self.close(&name);
+        } else {
+            self.output.push('>');
+            self.open.push(name);
+        }
+
+        rest
+    }
+
+    /// Consumes the content of a raw text or text only element just opened,
+    /// up to and including its end tag, and returns what follows.
+    fn raw_content<'a>(&mut self, html: &'a str) -> &'a str {
+        let Some(name) = self.open.last().cloned() else {
+            return html;
+        };
+        let raw = RAW_TEXT_ELEMENTS.contains(&name.as_str());
+        if !raw && !TEXT_ONLY_ELEMENTS.contains(&name.as_str()) {
+            return html;
+        }
+
+        let end_tag = format!("</{name}");
+        let end = (0..html.len())
+            .filter(|i| html.is_char_boundary(*i))
+            .find(|i| starts_with_ignore_case(&html[*i..], &end_tag))
+            .unwrap_or(html.len());
+        let content = &html[..end];
+        if raw && !content.is_empty() {
+            // Written back as is by the HTML mode of the prettifier.
+            self.output.push_str("<![CDATA[");
+            self.output
+                .push_str(&content.replace("]]>", "]]]]><![CDATA[>"));
+            self.output.push_str("]]>");
+        } else {
+            self.text(content);
+        }
+
+        self.open.pop();
+        self.close(&name);
+        let after = &html[end..];
+        match after.find('>') {
+            Some(close) => &after[close + 1..],
+            None => "",
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        // Stray end tags are ignored, others close every element opened since.
+        let Some(position) = self
+            .open
+            .iter()
+            .rposition(|open| open.eq_ignore_ascii_case(name))
+        else {
+            return;
+        };
+        while self.open.len() > position {
+            let open = self.open.pop().unwrap_or_default();
+            self.close(&open);
+        }
+    }
+}
+
+/// Whether starting a `new` element ends the `open` element.
+fn implies_end(open: &str, new: &str) -> bool {
+    match open {
+        "p" => CLOSES_PARAGRAPH.contains(&new),
+        "li" => new == "li",
+        "dt" | "dd" => matches!(new, "dt" | "dd"),
+        "tr" => new == "tr",
+        "td" | "th" => matches!(new, "td" | "th" | "tr"),
+        "thead" | "tbody" | "tfoot" => matches!(new, "thead" | "tbody" | "tfoot"),
+        "option" => matches!(new, "option" | "optgroup"),
+        _ => false,
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len()
+        && text.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_with_implied_end_tags() {
+        let input = "<!doctype html><HTML><Body><P>One<P CLASS=intro>Two</body></html>";
+        let expected = concat!(
+            "<!DOCTYPE html>\n",
+            "<html>\n",
+            "  <body>\n",
+            "    <p>One</p>\n",
+            "    <p class=\"intro\">Two</p>\n",
+            "  </body>\n",
+            "</html>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_void_and_empty_elements() {
+        let input = "<div><img src='a.png' alt><br/><span></span><input disabled></div>";
+        let expected = concat!(
+            "<div>\n",
+            "  <img src=\"a.png\" alt=\"\">\n",
+            "  <br>\n",
+            "  <span></span>\n",
+            "  <input disabled=\"\">\n",
+            "</div>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_unclosed_and_stray_tags() {
+        let input = "<table><tr><td>a<td>b<tr><td>c</table></span><div>open";
+        let expected = concat!(
+            "<table>\n",
+            "  <tr>\n",
+            "    <td>a</td>\n",
+            "    <td>b</td>\n",
+            "  </tr>\n",
+            "  <tr>\n",
+            "    <td>c</td>\n",
+            "  </tr>\n",
+            "</table>\n",
+            "<div>open</div>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_raw_text_elements_are_kept() {
+        let input = "<head><script>if (a < b && c) { x(\"</p>\"); }</SCRIPT><style>\n  p > a { }\n</style></head>";
+        let expected = concat!(
+            "<head>\n",
+            "  <script>if (a < b && c) { x(\"</p>\"); }</script>\n",
+            "  <style>\n  p > a { }\n</style>\n",
+            "</head>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_pre_keeps_whitespace_and_entities() {
+        let input = "<body><pre>  a &amp; b\n  &nbsp;c</pre><p>x &lt; y</p></body>";
+        let expected = concat!(
+            "<body>\n",
+            "  <pre>  a &amp; b\n  &nbsp;c</pre>\n",
+            "  <p>x &lt; y</p>\n",
+            "</body>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_svg_keeps_case() {
+        let input = "<DIV><svg viewBox='0 0 1 1'><linearGradient/></svg></DIV>";
+        let expected = concat!(
+            "<div>\n",
+            "  <svg viewBox=\"0 0 1 1\">\n",
+            "    <linearGradient></linearGradient>\n",
+            "  </svg>\n",
+            "</div>"
+        );
+        assert_eq!(prettify_html(input).unwrap(), expected);
+    }
+}
@@ -1,4 +1,540 @@
-use quick_xml::{events::Event, Reader};
+use std::io::{BufRead, Read, Write};
+
+use quick_xml::{
+    errors::IllFormedError,
+    escape::partial_escape,
+    events::{attributes::Attributes, BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader,
+};
+
+/// The line ending written between lines of prettified output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlineStyle {
+    /// Unix style `\n`.
+    #[default]
+    Lf,
+    /// Windows style `\r\n`.
+    CrLf,
+}
+
+impl NewlineStyle {
+    fn as_str(self) -> &'static str {
+        match self {
+            NewlineStyle::Lf => "\n",
+            NewlineStyle::CrLf => "\r\n",
+        }
+    }
+}
+
+/// How [`prettify_xml_with_options`] writes elements without content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyElements {
+    /// Keep `<a></a>` and `<a/>` as they appear in the input.
+    #[default]
+    Preserve,
+    /// Write `<a></a>` as `<a />`.
+    Collapse,
+    /// Write `<a/>` as `<a></a>`, keeping both tags on one line.
+    Expand,
+}
+
+/// Options controlling how [`prettify_xml_with_options`] lays out the document.
+///
+/// # Example
+/// ```
+/// use crabyknife::prettify_xml::{prettify_xml_with_options, PrettifyOptions};
+///
+/// let options = PrettifyOptions::new().indent_width(4);
+/// assert_eq!(
+///     prettify_xml_with_options("<root><child/></root>", &options).unwrap(),
+///     "<root>\n    <child />\n</root>"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettifyOptions {
+    indent_width: usize,
+    use_tabs: bool,
+    newline: NewlineStyle,
+    max_attributes_per_line: Option<usize>,
+    max_width: Option<usize>,
+    empty_elements: EmptyElements,
+    sort_attributes: bool,
+    namespace_aware: bool,
+    hoist_namespaces: bool,
+    /// Canonical prefix per namespace uri, as `(uri, prefix)`.
+    namespace_prefixes: Vec<(String, String)>,
+    preserve_whitespace_in: Vec<String>,
+    /// Writes HTML rather than XML, see [`PrettifyOptions::html`].
+    html: bool,
+    max_depth: Option<usize>,
+    max_size: Option<u64>,
+}
+
+impl Default for PrettifyOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            use_tabs: false,
+            newline: NewlineStyle::Lf,
+            max_attributes_per_line: None,
+            max_width: None,
+            empty_elements: EmptyElements::Preserve,
+            sort_attributes: false,
+            namespace_aware: false,
+            hoist_namespaces: false,
+            namespace_prefixes: Vec::new(),
+            preserve_whitespace_in: Vec::new(),
+            html: false,
+            max_depth: None,
+            max_size: None,
+        }
+    }
+}
+
+impl PrettifyOptions {
+    /// Creates the default options: two-space indentation, `\n` newlines
+    /// and every attribute on the same line as its tag.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many spaces make up one indentation level.
+    /// Ignored when [`PrettifyOptions::use_tabs`] is enabled.
+    pub fn indent_width(mut self, width: usize) -> Self {
+        self.indent_width = width;
+        self
+    }
+
+    /// Indents with one tab per level instead of spaces.
+    pub fn use_tabs(mut self, use_tabs: bool) -> Self {
+        self.use_tabs = use_tabs;
+        self
+    }
+
+    /// Sets the line ending used in the output.
+    pub fn newline_style(mut self, newline: NewlineStyle) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    /// Limits how many attributes are written on a single line.
+    /// Remaining attributes wrap onto continuation lines indented one level
+    /// deeper than their tag. `None` keeps all attributes on one line.
+    pub fn max_attributes_per_line(mut self, max: Option<usize>) -> Self {
+        self.max_attributes_per_line = max.filter(|max| *max > 0);
+        self
+    }
+
+    /// Wraps the attributes of tags that would not fit in `width` columns,
+    /// one per line, aligned with the first attribute. Takes precedence
+    /// over [`PrettifyOptions::max_attributes_per_line`] for such tags.
+    /// `None` never wraps because of the width.
+    pub fn max_width(mut self, width: Option<usize>) -> Self {
+        self.max_width = width.filter(|width| *width > 0);
+        self
+    }
+
+    /// Sets how elements without content are written, see [`EmptyElements`].
+    pub fn empty_elements(mut self, empty_elements: EmptyElements) -> Self {
+        self.empty_elements = empty_elements;
+        self
+    }
+
+    /// Sorts attributes alphabetically, with `xmlns` declarations first,
+    /// so that diffs between generated documents are stable.
+    pub fn sort_attributes(mut self, sort: bool) -> Self {
+        self.sort_attributes = sort;
+        self
+    }
+
+    /// Tracks `xmlns` declarations and fails with an error on element or
+    /// attribute prefixes that are not declared, instead of passing them
+    /// through silently.
+    pub fn namespace_aware(mut self, enabled: bool) -> Self {
+        self.namespace_aware = enabled;
+        self
+    }
+
+    /// Moves prefixed namespace declarations to the root element and drops
+    /// their repetitions on descendants. Only prefixes bound to the same
+    /// namespace throughout the document are hoisted.
+    /// Enables [`PrettifyOptions::namespace_aware`].
+    ///
+    /// The whole document is read into memory to find the declarations,
+    /// even by [`prettify_xml_stream`].
+    pub fn hoist_namespaces(mut self, hoist: bool) -> Self {
+        self.hoist_namespaces = hoist;
+        self.namespace_aware |= hoist;
+        self
+    }
+
+    /// Renames whatever prefix the document uses for the namespace `uri`
+    /// to `prefix`. Enables [`PrettifyOptions::namespace_aware`].
+    pub fn namespace_prefix(mut self, uri: &str, prefix: &str) -> Self {
+        self.namespace_prefixes
+            .retain(|(existing_uri, _)| existing_uri != uri);
+        self.namespace_prefixes
+            .push((uri.to_string(), prefix.to_string()));
+        self.namespace_aware = true;
+        self
+    }
+
+    /// Passes the content of elements named `tag` through untouched, as if
+    /// they had `xml:space="preserve"`, e.g. `pre` in XHTML. `tag` matches
+    /// either the qualified or the local name.
+    pub fn preserve_whitespace_in(mut self, tag: &str) -> Self {
+        if !self
+            .preserve_whitespace_in
+            .iter()
+            .any(|existing| existing == tag)
+        {
+            self.preserve_whitespace_in.push(tag.to_string());
+        }
+        self
+    }
+
+    /// Fails when elements are nested deeper than `depth`, the root being
+    /// at depth 1. Unlimited by default.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Fails as soon as more than `bytes` bytes of input are read, before
+    /// buffering them. Unlimited by default.
+    ///
+    /// Entities declared in a DTD are never expanded but written back as
+    /// references, so no limit is needed against entity expansion attacks
+    /// such as "billion laughs".
+    pub fn max_size(mut self, bytes: Option<u64>) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Writes elements the HTML way: void elements as `<br>`, other elements
+    /// without content as `<div></div>` and CDATA sections as raw text, which
+    /// is how `prettify_html` passes the content of `<script>` and `<style>`.
+    pub(crate) fn html(mut self) -> Self {
+        self.html = true;
+        self
+    }
+
+    fn indent_unit(&self) -> String {
+        if self.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent_width)
+        }
+    }
+}
+
+/// Why prettifying or minifying a document failed.
+#[derive(Debug)]
+pub enum XmlError {
+    /// The document cannot be formatted: it is not well-formed, nests
+    /// elements deeper than [`PrettifyOptions::max_depth`] or breaks the
+    /// namespace-aware options.
+    Parse { message: String, location: Location },
+    /// The document is larger than [`PrettifyOptions::max_size`].
+    TooLarge { max_size: u64 },
+    /// Reading the document or writing the output failed.
+    Io(std::io::Error),
+}
+
+impl XmlError {
+    /// Where the problem was found, for problems of the document itself.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            XmlError::Parse { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for XmlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XmlError::Parse { message, location } => write!(
+                f,
+                "{message} at line {}, column {} (byte {})",
+                location.line, location.column, location.position
+            ),
+            XmlError::TooLarge { max_size } => write!(
+                f,
+                "the document is larger than the maximum size of {max_size} bytes"
+            ),
+            XmlError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for XmlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XmlError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for XmlError {
+    fn from(err: std::io::Error) -> Self {
+        XmlError::Io(err)
+    }
+}
+
+/// Where in a document a problem was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// The byte offset of the problem in the document.
+    pub position: u64,
+    /// The 1-based line of the problem.
+    pub line: usize,
+    /// The 1-based column (in characters) of the problem.
+    pub column: usize,
+    /// The markup the problem was found in, when known.
+    pub event: Option<EventKind>,
+}
+
+impl Location {
+    /// Renders `line`, the text of the line of the location, with a caret
+    /// under its column on the next line. Long lines are cut around the
+    /// column.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::prettify_xml::prettify_xml;
+    ///
+    /// let err = prettify_xml("<a>\n  <b></c>\n</a>").unwrap_err();
+    /// let location = err.location().unwrap();
+    /// assert_eq!((location.line, location.column), (2, 6));
+    /// assert_eq!(location.excerpt("  <b></c>"), "  <b></c>\n     ^");
+    /// ```
+    pub fn excerpt(&self, line: &str) -> String {
+        const CONTEXT: usize = 40;
+        let chars: Vec<char> = line.trim_end_matches(['\r', '\n']).chars().collect();
+        let column = self.column.saturating_sub(1).min(chars.len());
+        let start = column.saturating_sub(CONTEXT);
+        let end = chars.len().min(column + CONTEXT);
+
+        let mut excerpt = String::new();
+        let mut caret = String::new();
+        if start > 0 {
+            excerpt.push_str("...");
+            caret.push_str("   ");
+        }
+        excerpt.extend(&chars[start..end]);
+        if end < chars.len() {
+            excerpt.push_str("...");
+        }
+        // Tabs are kept so the caret lines up however they are shown.
+        caret.extend(
+            chars[start..column]
+                .iter()
+                .map(|&c| if c == '\t' { '\t' } else { ' ' }),
+        );
+        caret.push('^');
+        format!("{excerpt}\n{caret}")
+    }
+}
+
+/// The kinds of markup of a document, see [`Location::event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    StartTag,
+    EndTag,
+    EmptyTag,
+    Text,
+    CData,
+    Comment,
+    Declaration,
+    ProcessingInstruction,
+    DocType,
+}
+
+impl EventKind {
+    /// The kind of `event`, `None` at the end of the document.
+    fn of(event: &Event) -> Option<Self> {
+        Some(match event {
+            Event::Start(_) => EventKind::StartTag,
+            Event::End(_) => EventKind::EndTag,
+            Event::Empty(_) => EventKind::EmptyTag,
+            Event::Text(_) => EventKind::Text,
+            Event::CData(_) => EventKind::CData,
+            Event::Comment(_) => EventKind::Comment,
+            Event::Decl(_) => EventKind::Declaration,
+            Event::PI(_) => EventKind::ProcessingInstruction,
+            Event::DocType(_) => EventKind::DocType,
+            Event::Eof => return None,
+        })
+    }
+
+    /// The kind of the markup starting `bytes`, for problems found before
+    /// its event is read. Tags are taken for start tags until closed.
+    fn starting(bytes: &[u8]) -> Option<Self> {
+        Some(match bytes {
+            [] => return None,
+            [b'<', b'/', ..] => EventKind::EndTag,
+            [b'<', b'!', b'-', b'-', ..] => EventKind::Comment,
+            [b'<', b'!', b'[', ..] => EventKind::CData,
+            [b'<', b'!', ..] => EventKind::DocType,
+            [b'<', b'?', b'x', b'm', b'l', b' ' | b'\t' | b'\r' | b'\n' | b'?', ..] => {
+                EventKind::Declaration
+            }
+            [b'<', b'?', ..] => EventKind::ProcessingInstruction,
+            [b'<', ..] => EventKind::StartTag,
+            _ => EventKind::Text,
+        })
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EventKind::StartTag => "start tag",
+            EventKind::EndTag => "end tag",
+            EventKind::EmptyTag => "empty tag",
+            EventKind::Text => "text",
+            EventKind::CData => "CDATA section",
+            EventKind::Comment => "comment",
+            EventKind::Declaration => "XML declaration",
+            EventKind::ProcessingInstruction => "processing instruction",
+            EventKind::DocType => "DOCTYPE",
+        })
+    }
+}
+
+/// A line and column in a document, advanced over the bytes read.
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    position: u64,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    const START: Self = Self {
+        position: 0,
+        line: 1,
+        column: 1,
+    };
+
+    fn advance(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.position += 1;
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if byte & 0xC0 != 0x80 {
+                // Continuation bytes of UTF-8 are part of the same character.
+                self.column += 1;
+            }
+        }
+    }
+
+    /// The location of `position`, in `bytes` which follow this position.
+    /// `event` defaults to the markup starting there.
+    fn locate(mut self, bytes: &[u8], position: u64, event: Option<EventKind>) -> Location {
+        let mut offset = position
+            .saturating_sub(self.position)
+            .min(bytes.len() as u64) as usize;
+        // Markup is read with the whitespace trimmed before it.
+        if event.is_some_and(|event| event != EventKind::Text) {
+            offset += bytes[offset..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count();
+        }
+        self.advance(&bytes[..offset]);
+        Location {
+            position: self.position,
+            line: self.line,
+            column: self.column,
+            event: event.or_else(|| EventKind::starting(&bytes[offset..])),
+        }
+    }
+}
+
+/// A reader keeping the bytes of the event being read, so the problems of
+/// streamed documents are located without holding them in memory.
+struct Tracked<R> {
+    inner: R,
+    /// Where the event being read starts.
+    start: Position,
+    /// The bytes read since the start of the event.
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead> Tracked<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            start: Position::START,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Moves the start of the event to what is read next.
+    fn mark(&mut self) {
+        self.start.advance(&self.pending);
+        self.pending.clear();
+    }
+
+    fn locate(&self, position: u64, event: Option<EventKind>) -> Location {
+        self.start.locate(&self.pending, position, event)
+    }
+}
+
+impl<R: BufRead> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Tracked<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        // The bytes consumed are still those filled last.
+        if let Ok(available) = self.inner.fill_buf() {
+            let n = amount.min(available.len());
+            self.pending.extend_from_slice(&available[..n]);
+        }
+        self.inner.consume(amount);
+    }
+}
+
+/// Converts an error of the parser, located at `location`.
+fn read_error(err: quick_xml::Error, location: Location, max_size: Option<u64>) -> XmlError {
+    match err {
+        quick_xml::Error::Io(err) => io_error(
+            std::sync::Arc::try_unwrap(err)
+                .unwrap_or_else(|err| std::io::Error::new(err.kind(), err.to_string())),
+            max_size,
+        ),
+        err => XmlError::Parse {
+            message: err.to_string(),
+            location,
+        },
+    }
+}
+
+/// Converts an error reading the document, telling when the document is
+/// larger than `max_size`, see [`SizeLimit`].
+fn io_error(err: std::io::Error, max_size: Option<u64>) -> XmlError {
+    match max_size {
+        Some(max_size) if err.kind() == std::io::ErrorKind::FileTooLarge => {
+            XmlError::TooLarge { max_size }
+        }
+        _ => XmlError::Io(err),
+    }
+}
 
 /// Prettify a given raw(unprettified) xml text,
 /// format it with identations and newlines.
@@ -11,35 +547,161 @@ use quick_xml::{events::Event, Reader};
 ///
 /// ```
 ///
-pub fn prettify_xml(unprettified_xml: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let mut reader = Reader::from_str(unprettified_xml);
+pub fn prettify_xml(unprettified_xml: &str) -> Result<String, XmlError> {
+    prettify_xml_with_options(unprettified_xml, &PrettifyOptions::default())
+}
+
+/// Prettify a given raw(unprettified) xml text using the layout described
+/// by `options`.
+pub fn prettify_xml_with_options(
+    unprettified_xml: &str,
+    options: &PrettifyOptions,
+) -> Result<String, XmlError> {
+    let mut output = Vec::new();
+    prettify_xml_stream(unprettified_xml.as_bytes(), &mut output, options)?;
+    String::from_utf8(output)
+        .map_err(|err| XmlError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+}
+
+/// Prettify an xml document read from `reader`, writing the result to `writer`
+/// as it goes.
+///
+/// Only the event being formatted is held in memory, so this can process
+/// documents far larger than the available memory. The output is identical
+/// to [`prettify_xml_with_options`], without a trailing newline.
+///
+/// # Example
+/// ```
+/// use crabyknife::prettify_xml::{prettify_xml_stream, PrettifyOptions};
+///
+/// let mut output = Vec::new();
+/// prettify_xml_stream("<a><b/></a>".as_bytes(), &mut output, &PrettifyOptions::new()).unwrap();
+/// assert_eq!(output, b"<a>\n  <b />\n</a>");
+/// ```
+pub fn prettify_xml_stream(
+    reader: impl BufRead,
+    writer: impl Write,
+    options: &PrettifyOptions,
+) -> Result<(), XmlError> {
+    let mut reader = SizeLimit {
+        inner: reader,
+        remaining: options.max_size,
+        limit: options.max_size.unwrap_or_default(),
+    };
+
+    if !options.namespace_aware {
+        return prettify_events(reader, writer, options, None);
+    }
+
+    let mut namespaces = Namespaces::new(options);
+    if options.hoist_namespaces {
+        // The declarations to hoist must be known before the root element
+        // is written, so the document is read twice from memory.
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| io_error(err, options.max_size))?;
+        namespaces.hoisted = hoistable_declarations(&content, &namespaces)?;
+        return prettify_events(&content[..], writer, options, Some(namespaces));
+    }
+
+    prettify_events(reader, writer, options, Some(namespaces))
+}
+
+/// Formats the events read from `reader`, see [`prettify_xml_stream`].
+/// `namespaces` is given when the namespace-aware options are enabled.
+fn prettify_events(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    options: &PrettifyOptions,
+    mut namespaces: Option<Namespaces>,
+) -> Result<(), XmlError> {
+    let mut reader = Reader::from_reader(Tracked::new(reader));
     reader.config_mut().trim_text(true);
 
     let mut buf = Vec::new();
+    // The formatted text of the current event, flushed to `writer` after each event.
     let mut output = String::new();
+    // Leading whitespace of the document is trimmed until something is written.
+    let mut at_document_start = true;
     let mut indent = 0;
-    let indent_str = "  ";
+    let indent_str = options.indent_unit();
+    let newline = options.newline.as_str();
     let mut child_is_text = false;
+    // Whether the last start tag was written without its closing `>`, so
+    // that an element without content can be rewritten, see `EmptyElements`.
+    let mut start_tag_open = false;
+    // Whether each open element keeps its whitespace, innermost last.
+    // Content of such elements is written as is, without indentation.
+    let mut preserving_stack: Vec<bool> = Vec::new();
 
     loop {
-        match reader.read_event_into(&mut buf)? {
+        reader.get_mut().mark();
+        let start = reader.buffer_position();
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(err) => {
+                let location = reader.get_ref().locate(reader.error_position(), None);
+                return Err(read_error(err, location, options.max_size));
+            }
+        };
+        // Problems of the event are located at its start.
+        let location = reader.get_ref().locate(start, EventKind::of(&event));
+        let invalid = |err: &dyn std::fmt::Display| XmlError::Parse {
+            message: err.to_string(),
+            location,
+        };
+
+        // An open start tag directly followed by its end tag is an empty
+        // element and is written in the configured form, otherwise it is
+        // closed as usual.
+        let element_is_empty = start_tag_open && matches!(event, Event::End(_));
+        if start_tag_open && !element_is_empty {
+            output.push('>');
+        }
+        start_tag_open = false;
+        let preserving = preserving_stack.last().copied().unwrap_or(false);
+
+        match event {
             Event::Start(ref e) => {
-                output.push('\n');
-                output.push_str(&indent_str.repeat(indent));
+                check_depth(e, preserving_stack.len() + 1, options).map_err(|err| invalid(&err))?;
+                let (name, attributes) =
+                    element_parts(e, options, namespaces.as_mut()).map_err(|err| invalid(&err))?;
+                if !preserving {
+                    output.push_str(newline);
+                    output.push_str(&indent_str.repeat(indent));
+                }
+                preserving_stack.push(
+                    preserves_whitespace(e, options, preserving).map_err(|err| invalid(&err))?,
+                );
+                // Text before the element is not its content.
+                child_is_text = false;
                 output.push('<');
-                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
-                for attr in e.attributes().with_checks(false) {
-                    let attr = attr?;
-                    output.push(' ');
-                    output.push_str(&String::from_utf8_lossy(attr.key.as_ref()));
-                    output.push_str("=\"");
-                    output.push_str(&String::from_utf8_lossy(&attr.value));
-                    output.push('"');
+                output.push_str(&name);
+                push_attributes(&mut output, &attributes, options, indent);
+                if options.empty_elements != EmptyElements::Preserve || options.html {
+                    start_tag_open = true;
+                } else {
+                    output.push('>');
                 }
-                output.push('>');
                 indent += 1;
             }
+            Event::End(ref e) if element_is_empty => {
+                let name = end_element_name(e, namespaces.as_mut()).map_err(|err| invalid(&err))?;
+                preserving_stack.pop();
+                indent -= 1;
+                if options.empty_elements == EmptyElements::Expand || options.html {
+                    output.push_str("></");
+                    output.push_str(&name);
+                    output.push('>');
+                } else {
+                    output.push_str(" />");
+                }
+                child_is_text = false;
+            }
             Event::End(ref e) => {
+                let name = end_element_name(e, namespaces.as_mut()).map_err(|err| invalid(&err))?;
+                preserving_stack.pop();
                 indent -= 1;
 
                 // if the child of the current tag is `Text`,
@@ -56,151 +718,1503 @@ pub fn prettify_xml(unprettified_xml: &str) -> Result<String, Box<dyn std::error
                 // <parent>
                 //   <child />
                 // </parent>
-                if !child_is_text {
-                    output.push('\n');
+                //
+                // Nor do we when the element keeps its whitespace.
+                if !child_is_text && !preserving {
+                    output.push_str(newline);
                     output.push_str(&indent_str.repeat(indent));
                 }
                 output.push_str("</");
-                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                output.push_str(&name);
                 output.push('>');
                 child_is_text = false;
             }
             Event::Text(e) => {
-                let text = e.into_inner();
-                if !text.is_empty() {
-                    output.push_str(&String::from_utf8_lossy(&text));
-                }
+                push_text(&mut output, &e);
                 child_is_text = true;
             }
+            Event::CData(e) if options.html => {
+                output.push_str(&e.decode().map_err(|err| invalid(&err))?)
+            }
             Event::CData(e) => {
                 output.push_str("<![CDATA[");
-                output.push_str(&e.decode()?);
+                output.push_str(&e.decode().map_err(|err| invalid(&err))?);
                 output.push_str("]]>");
             }
             Event::Comment(e) => {
-                output.push('\n');
-                output.push_str(&indent_str.repeat(indent));
+                if !preserving {
+                    output.push_str(newline);
+                    output.push_str(&indent_str.repeat(indent));
+                }
                 output.push_str("<!--");
-                output.push_str(&e.unescape()?);
+                output.push_str(&String::from_utf8_lossy(&e));
                 output.push_str("-->");
             }
             Event::Decl(e) => {
-                output.push('\n');
-                output.push_str("<?xml");
-
-                output.push_str(" version=\"");
-                output.push_str(std::str::from_utf8(&e.version()?)?);
-                output.push('"');
-
-                if let Some(encoding) = e.encoding() {
-                    output.push_str(" encoding=\"");
-                    output.push_str(std::str::from_utf8(&encoding?)?);
-                    output.push('"');
-                }
-
-                if let Some(standalone) = e.standalone() {
-                    output.push_str(" standalone=\"");
-                    output.push_str(std::str::from_utf8(&standalone?)?);
-                    output.push('"');
-                }
-
-                output.push_str("?>");
+                output.push_str(newline);
+                push_declaration(&mut output, &e).map_err(|err| invalid(&err))?;
             }
             Event::Empty(e) => {
-                output.push('\n');
-                output.push_str(&indent_str.repeat(indent));
+                check_depth(&e, preserving_stack.len() + 1, options)
+                    .map_err(|err| invalid(&err))?;
+                let (name, attributes) =
+                    element_parts(&e, options, namespaces.as_mut()).map_err(|err| invalid(&err))?;
+                if let Some(namespaces) = namespaces.as_mut() {
+                    namespaces.scopes.pop();
+                }
+                if !preserving {
+                    output.push_str(newline);
+                    output.push_str(&indent_str.repeat(indent));
+                }
                 output.push('<');
-                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
-                for attr in e.attributes().with_checks(false) {
-                    let attr = attr?;
-                    output.push(' ');
-                    output.push_str(&String::from_utf8_lossy(attr.key.as_ref()));
-                    output.push_str("=\"");
-                    output.push_str(&String::from_utf8_lossy(&attr.value));
-                    output.push('"');
+                output.push_str(&name);
+                push_attributes(&mut output, &attributes, options, indent);
+                if options.html {
+                    output.push('>');
+                } else if options.empty_elements == EmptyElements::Expand {
+                    output.push_str("></");
+                    output.push_str(&name);
+                    output.push('>');
+                } else {
+                    output.push_str(" />");
                 }
-                output.push_str(" />");
             }
             Event::PI(e) => {
-                output.push('\n');
-                output.push_str(&indent_str.repeat(indent));
+                if !preserving {
+                    output.push_str(newline);
+                    output.push_str(&indent_str.repeat(indent));
+                }
                 output.push_str("<?");
                 output.push_str(&String::from_utf8_lossy(e.target()));
-                for attr in e.attributes().with_checks(false) {
-                    let attr = attr?;
-                    output.push(' ');
-                    output.push_str(&String::from_utf8_lossy(attr.key.as_ref()));
-                    output.push_str("=\"");
-                    output.push_str(&String::from_utf8_lossy(&attr.value));
-                    output.push('"');
-                }
+                push_attributes(
+                    &mut output,
+                    &attribute_pairs(e.attributes()).map_err(|err| invalid(&err))?,
+                    options,
+                    indent,
+                );
                 output.push_str("?>");
             }
             Event::DocType(e) => {
-                output.push('\n');
+                output.push_str(newline);
                 output.push_str(&indent_str.repeat(indent));
                 output.push_str("<!DOCTYPE ");
-                output.push_str(&e.unescape()?);
+                output.push_str(&String::from_utf8_lossy(&e));
                 output.push('>');
             }
             Event::Eof => break,
         }
 
+        let chunk = if at_document_start {
+            output.trim_start()
+        } else {
+            &output
+        };
+        if !chunk.is_empty() {
+            writer.write_all(chunk.as_bytes())?;
+            at_document_start = false;
+        }
+
+        output.clear();
         buf.clear();
+
+        // Text is only trimmed outside of whitespace preserving elements.
+        let preserving = preserving_stack.last().copied().unwrap_or(false);
+        reader.config_mut().trim_text(!preserving);
     }
 
-    Ok(output.trim_start().to_string())
+    writer.flush()?;
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_simple_element() {
-        let input = "<root></root>";
-        let expected = "<root>\n</root>";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
+/// Fails when the element `e` at `depth` is nested deeper than allowed.
+fn check_depth(
+    e: &BytesStart,
+    depth: usize,
+    options: &PrettifyOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match options.max_depth {
+        Some(max_depth) if depth > max_depth => Err(format!(
+            "element {} is nested {depth} levels deep, more than the maximum depth of {max_depth}",
+            String::from_utf8_lossy(e.name().as_ref())
+        )
+        .into()),
+        _ => Ok(()),
     }
+}
 
-    #[test]
-    fn test_nested_elements() {
-        let input = "<root><child>text</child></root>";
-        let expected = "<root>\n  <child>text</child>\n</root>";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
-    }
+/// A reader failing once more than `limit` bytes are consumed, see
+/// [`PrettifyOptions::max_size`]. `remaining` is `None` without limit.
+struct SizeLimit<R> {
+    inner: R,
+    remaining: Option<u64>,
+    limit: u64,
+}
 
-    #[test]
-    fn test_attributes() {
-        let input = r#"<root><item id="1" value="x"/></root>"#;
-        let expected = "<root>\n  <item id=\"1\" value=\"x\" />\n</root>";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
+impl<R: BufRead> Read for SizeLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
     }
+}
 
-    #[test]
-    fn test_comment_and_cdata() {
-        let input = r#"<root><!--comment--><![CDATA[some <xml>]]></root>"#;
-        let expected = "<root>\n  <!--comment--><![CDATA[some <xml>]]>\n</root>";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
+impl<R: BufRead> BufRead for SizeLimit<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        let limit = self.limit;
+        let remaining = self.remaining;
+        let available = self.inner.fill_buf()?;
+        match remaining {
+            None => Ok(available),
+            Some(0) if !available.is_empty() => Err(std::io::Error::new(
+                std::io::ErrorKind::FileTooLarge,
+                format!("the document is larger than the maximum size of {limit} bytes"),
+            )),
+            Some(remaining) => {
+                let n = available
+                    .len()
+                    .min(remaining.try_into().unwrap_or(usize::MAX));
+                Ok(&available[..n])
+            }
+        }
     }
 
-    #[test]
-    fn test_doctype() {
-        let input = r#"<!DOCTYPE note><note><to>Tove</to></note>"#;
-        let expected = "<!DOCTYPE note>\n<note>\n  <to>Tove</to>\n</note>";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
+    fn consume(&mut self, amount: usize) {
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= amount as u64;
+        }
+        self.inner.consume(amount);
     }
+}
 
-    #[test]
-    fn test_xml_declaration() {
-        let input = r#"<?xml version="1.0" encoding="UTF-8"?><root/>"#;
-        let expected = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root />";
-        let result = prettify_xml(input).unwrap();
-        assert_eq!(result, expected);
+/// Minify a given xml text into a compact single-line document,
+/// dropping insignificant whitespace and newlines between tags.
+/// Comments are removed as well when `strip_comments` is `true`.
+///
+/// # Example
+/// ```
+/// use crabyknife::prettify_xml::minify_xml;
+///
+/// let xml = "<root>\n  <!--note-->\n  <child>text</child>\n</root>";
+/// assert_eq!(minify_xml(xml, true).unwrap(), "<root><child>text</child></root>");
+/// ```
+pub fn minify_xml(xml: &str, strip_comments: bool) -> Result<String, XmlError> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut output = String::new();
+    let options = PrettifyOptions::default();
+
+    loop {
+        let start = reader.buffer_position();
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(err) => {
+                let location =
+                    Position::START.locate(xml.as_bytes(), reader.error_position(), None);
+                return Err(read_error(err, location, None));
+            }
+        };
+        // Located only on errors, as locating scans the document from the start.
+        let kind = EventKind::of(&event);
+        let invalid = |err: &dyn std::fmt::Display| XmlError::Parse {
+            message: err.to_string(),
+            location: Position::START.locate(xml.as_bytes(), start, kind),
+        };
+
+        match event {
+            Event::Start(ref e) => {
+                output.push('<');
+                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                push_attributes(
+                    &mut output,
+                    &attribute_pairs(e.attributes()).map_err(|err| invalid(&err))?,
+                    &options,
+                    0,
+                );
+                output.push('>');
+            }
+            Event::End(ref e) => {
+                output.push_str("</");
+                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                output.push('>');
+            }
+            Event::Empty(ref e) => {
+                output.push('<');
+                output.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                push_attributes(
+                    &mut output,
+                    &attribute_pairs(e.attributes()).map_err(|err| invalid(&err))?,
+                    &options,
+                    0,
+                );
+                output.push_str("/>");
+            }
+            Event::Text(e) => push_text(&mut output, &e),
+            Event::CData(e) => {
+                output.push_str("<![CDATA[");
+                output.push_str(&e.decode().map_err(|err| invalid(&err))?);
+                output.push_str("]]>");
+            }
+            Event::Comment(e) => {
+                if !strip_comments {
+                    output.push_str("<!--");
+                    output.push_str(&String::from_utf8_lossy(&e));
+                    output.push_str("-->");
+                }
+            }
+            Event::Decl(e) => push_declaration(&mut output, &e).map_err(|err| invalid(&err))?,
+            Event::PI(e) => {
+                output.push_str("<?");
+                output.push_str(&String::from_utf8_lossy(e.target()));
+                push_attributes(
+                    &mut output,
+                    &attribute_pairs(e.attributes()).map_err(|err| invalid(&err))?,
+                    &options,
+                    0,
+                );
+                output.push_str("?>");
+            }
+            Event::DocType(e) => {
+                output.push_str("<!DOCTYPE ");
+                output.push_str(&String::from_utf8_lossy(&e));
+                output.push('>');
+            }
+            Event::Eof => break,
+        }
+
+        buf.clear();
+    }
+
+    Ok(output)
+}
+
+/// Describes why a document is not well-formed and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// A human readable description of the problem.
+    pub message: String,
+    /// The byte offset of the problem in the input.
+    pub position: usize,
+    /// The 1-based line of the problem.
+    pub line: usize,
+    /// The 1-based column (in characters) of the problem.
+    pub column: usize,
+    /// The name of the offending tag, if the problem involves one.
+    pub tag: Option<String>,
+    /// For mismatched or unclosed tags, the open tag that was left unmatched
+    /// with its 1-based line and column.
+    pub open_tag: Option<(String, usize, usize)>,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {} (byte {})",
+            self.message, self.line, self.column, self.position
+        )?;
+        if let Some((name, line, column)) = &self.open_tag {
+            write!(f, "; <{name}> was opened at line {line}, column {column}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks that `xml` is a well-formed document.
+///
+/// Besides syntax errors this reports close tags that do not match
+/// their open tag, tags left unclosed at the end of the document,
+/// duplicated or malformed attributes and content outside the single
+/// root element.
+///
+/// # Example
+/// ```
+/// use crabyknife::prettify_xml::validate;
+///
+/// assert!(validate("<a><b/></a>").is_ok());
+///
+/// let err = validate("<a>\n  <b></c>\n</a>").unwrap_err();
+/// assert_eq!((err.line, err.column), (2, 6));
+/// assert_eq!(err.tag.as_deref(), Some("c"));
+/// ```
+pub fn validate(xml: &str) -> Result<(), ValidationError> {
+    let mut reader = Reader::from_str(xml);
+    // We match close tags ourselves to report both ends of a mismatch.
+    reader.config_mut().check_end_names = false;
+
+    let error_at = |message: String, position: usize, tag: Option<String>| {
+        let (line, column) = line_column(xml, position);
+        ValidationError {
+            message,
+            position,
+            line,
+            column,
+            tag,
+            open_tag: None,
+        }
+    };
+
+    // Open tags with the byte offset of their `<`.
+    let mut open_tags: Vec<(String, usize)> = Vec::new();
+    let mut root_closed = false;
+    let mut has_root = false;
+
+    loop {
+        let start = reader.buffer_position() as usize;
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(err) => {
+                let tag = match &err {
+                    quick_xml::Error::IllFormed(
+                        IllFormedError::UnmatchedEndTag(name) | IllFormedError::MissingEndTag(name),
+                    ) => Some(name.clone()),
+                    _ => None,
+                };
+                return Err(error_at(
+                    err.to_string(),
+                    reader.error_position() as usize,
+                    tag,
+                ));
+            }
+        };
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                if root_closed && open_tags.is_empty() {
+                    return Err(error_at(
+                        "element after the root element".to_string(),
+                        start,
+                        Some(name),
+                    ));
+                }
+                if let Some(Err(err)) = e.attributes().find(|attr| attr.is_err()) {
+                    return Err(error_at(
+                        format!("invalid attribute: {err}"),
+                        start,
+                        Some(name),
+                    ));
+                }
+                has_root = true;
+                if matches!(event, Event::Start(_)) {
+                    open_tags.push((name, start));
+                } else if open_tags.is_empty() {
+                    root_closed = true;
+                }
+            }
+            Event::End(ref e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                match open_tags.pop() {
+                    Some((open_name, _)) if open_name == name => {
+                        root_closed = open_tags.is_empty();
+                    }
+                    Some((open_name, open_start)) => {
+                        let mut err = error_at(
+                            format!("mismatched close tag </{name}>, expected </{open_name}>"),
+                            start,
+                            Some(name),
+                        );
+                        let (line, column) = line_column(xml, open_start);
+                        err.open_tag = Some((open_name, line, column));
+                        return Err(err);
+                    }
+                    None => {
+                        return Err(error_at(
+                            format!("close tag </{name}> has no matching open tag"),
+                            start,
+                            Some(name),
+                        ))
+                    }
+                }
+            }
+            Event::Text(ref e)
+                if open_tags.is_empty() && !e.iter().all(u8::is_ascii_whitespace) =>
+            {
+                return Err(error_at(
+                    "text outside the root element".to_string(),
+                    start,
+                    None,
+                ));
+            }
+            Event::CData(_) if open_tags.is_empty() => {
+                return Err(error_at(
+                    "CDATA outside the root element".to_string(),
+                    start,
+                    None,
+                ));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if let Some((open_name, open_start)) = open_tags.pop() {
+        let mut err = error_at(
+            format!("unexpected end of document, <{open_name}> is not closed"),
+            xml.len(),
+            Some(open_name.clone()),
+        );
+        let (line, column) = line_column(xml, open_start);
+        err.open_tag = Some((open_name, line, column));
+        return Err(err);
+    }
+
+    if !has_root {
+        return Err(error_at("no root element".to_string(), xml.len(), None));
+    }
+
+    Ok(())
+}
+
+/// Converts a byte offset in `text` into a 1-based line and column.
+fn line_column(text: &str, position: usize) -> (usize, usize) {
+    let mut position = position.min(text.len());
+    while !text.is_char_boundary(position) {
+        position -= 1;
+    }
+
+    let before = &text[..position];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// The name of a tag and its attributes as name and escaped value pairs.
+type TagParts = (String, Vec<(String, String)>);
+
+/// Whether the content of `e` keeps its whitespace: `xml:space` decides
+/// when present, then [`PrettifyOptions::preserve_whitespace_in`], then
+/// the parent element.
+fn preserves_whitespace(
+    e: &BytesStart,
+    options: &PrettifyOptions,
+    inherited: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if let Some(space) = e.try_get_attribute("xml:space")? {
+        match space.value.as_ref() {
+            b"preserve" => return Ok(true),
+            b"default" => return Ok(false),
+            _ => {}
+        }
+    }
+
+    let name = e.name();
+    let local_name = e.local_name();
+    let listed = options
+        .preserve_whitespace_in
+        .iter()
+        .any(|tag| tag.as_bytes() == name.as_ref() || tag.as_bytes() == local_name.as_ref());
+    Ok(listed || inherited)
+}
+
+/// Reads the name and attributes (as name and escaped value pairs) of a
+/// start or empty tag, applying the namespace and sorting options.
+///
+/// With `namespaces`, the element opens a new namespace scope which the
+/// caller must close, through [`end_element_name`] or directly for empty tags.
+fn element_parts(
+    e: &BytesStart,
+    options: &PrettifyOptions,
+    namespaces: Option<&mut Namespaces>,
+) -> Result<TagParts, Box<dyn std::error::Error>> {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    let attributes = attribute_pairs(e.attributes())?;
+    let (name, mut attributes) = match namespaces {
+        Some(namespaces) => namespaces.start_element(&name, attributes)?,
+        None => (name, attributes),
+    };
+
+    if options.sort_attributes {
+        // Namespace declarations come first: the default namespace, then
+        // prefixed declarations, then every other attribute.
+        attributes.sort_by(|(a, _), (b, _)| {
+            let rank = |name: &str| match name {
+                "xmlns" => 0,
+                _ if name.starts_with("xmlns:") => 1,
+                _ => 2,
+            };
+            rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+        });
+    }
+    Ok((name, attributes))
+}
+
+/// Reads the name of an end tag, closing its namespace scope.
+fn end_element_name(
+    e: &BytesEnd,
+    namespaces: Option<&mut Namespaces>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    match namespaces {
+        Some(namespaces) => {
+            // The start tag declared the prefix again if it had to.
+            let name = match namespaces.qualified_name(&name, "element")? {
+                Qualified::Name(name) => name,
+                Qualified::Unbound { .. } => name,
+            };
+            namespaces.scopes.pop();
+            Ok(name)
+        }
+        None => Ok(name),
+    }
+}
+
+/// Tracks the `xmlns` declarations in scope for the namespace-aware
+/// options of [`PrettifyOptions`].
+struct Namespaces {
+    /// The scope of each open element, innermost last.
+    scopes: Vec<Scope>,
+    /// Canonical prefix per namespace uri, as `(uri, prefix)`.
+    prefixes: Vec<(String, String)>,
+    /// Declarations written on the root element instead of on the elements
+    /// declaring them, as `(prefix, uri)` after renaming.
+    hoisted: Vec<(String, String)>,
+}
+
+/// The namespace declarations of an element, as `(prefix, uri)`.
+/// The default namespace has an empty prefix.
+struct Scope {
+    /// The declarations written in the input.
+    declared: Vec<(String, String)>,
+    /// The declarations in effect in the output, after renaming prefixes.
+    written: Vec<(String, String)>,
+}
+
+impl Namespaces {
+    fn new(options: &PrettifyOptions) -> Self {
+        Self {
+            scopes: Vec::new(),
+            prefixes: options.namespace_prefixes.clone(),
+            hoisted: Vec::new(),
+        }
+    }
+
+    /// Opens the scope of an element, returning its name and attributes
+    /// with prefixes renamed and hoisted declarations moved.
+    fn start_element(
+        &mut self,
+        name: &str,
+        attributes: Vec<(String, String)>,
+    ) -> Result<TagParts, Box<dyn std::error::Error>> {
+        let is_root = self.scopes.is_empty();
+        let declared: Vec<(String, String)> = attributes
+            .iter()
+            .filter_map(|(key, uri)| declared_prefix(key).map(|p| (p.to_string(), uri.clone())))
+            .collect();
+        let mut written = if is_root {
+            self.hoisted.clone()
+        } else {
+            Vec::new()
+        };
+        for (prefix, uri) in &declared {
+            let prefix = match self.canonical_prefix(uri) {
+                Some(canonical) if !prefix.is_empty() => canonical,
+                _ => prefix,
+            };
+            written.push((prefix.to_string(), uri.clone()));
+        }
+        self.scopes.push(Scope { declared, written });
+
+        let mut redeclared = Vec::new();
+        let name = self.start_name(name, "element", &mut redeclared)?;
+
+        let mut renamed: Vec<(String, String)> = Vec::new();
+        if is_root {
+            for (prefix, uri) in &self.hoisted {
+                renamed.push((format!("xmlns:{prefix}"), uri.clone()));
+            }
+        }
+
+        for (key, value) in attributes {
+            let key = match declared_prefix(&key) {
+                // The default namespace has no prefix to rename.
+                Some("") => key,
+                Some(prefix) => {
+                    let prefix = self.canonical_prefix(&value).unwrap_or(prefix).to_string();
+                    if self.hoisted.contains(&(prefix.clone(), value.clone())) {
+                        continue;
+                    }
+                    format!("xmlns:{prefix}")
+                }
+                None => self.start_name(&key, "attribute", &mut redeclared)?,
+            };
+            push_declared(&mut renamed, &name, key, value)?;
+        }
+        for (prefix, uri) in redeclared {
+            push_declared(&mut renamed, &name, format!("xmlns:{prefix}"), uri)?;
+        }
+
+        Ok((name, renamed))
+    }
+
+    /// Qualifies `name` in the scope just opened, declaring its prefix there
+    /// again, into `redeclared`, when renaming left it unbound.
+    fn start_name(
+        &mut self,
+        name: &str,
+        kind: &str,
+        redeclared: &mut Vec<(String, String)>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self.qualified_name(name, kind)? {
+            Qualified::Name(name) => Ok(name),
+            Qualified::Unbound { prefix, uri } => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.written.push((prefix.clone(), uri.clone()));
+                }
+                redeclared.push((prefix, uri));
+                Ok(name.to_string())
+            }
+        }
+    }
+
+    /// Checks that the prefix of `name` is declared and renames it to the
+    /// canonical prefix of its namespace, if any and bound to that namespace
+    /// in the output, e.g. not re-declared for another one on a descendant.
+    fn qualified_name(
+        &self,
+        name: &str,
+        kind: &str,
+    ) -> Result<Qualified, Box<dyn std::error::Error>> {
+        let Some((prefix, local)) = name.split_once(':') else {
+            return Ok(Qualified::Name(name.to_string()));
+        };
+        // The `xml` prefix is bound by definition and never declared.
+        if prefix == "xml" {
+            return Ok(Qualified::Name(name.to_string()));
+        }
+
+        let uri = self
+            .lookup(prefix, |scope| &scope.declared)
+            .ok_or_else(|| format!("undeclared namespace prefix `{prefix}` on {kind} {name}"))?;
+        let binds = |prefix: &str| self.lookup(prefix, |scope| &scope.written) == Some(uri);
+        Ok(match self.canonical_prefix(uri) {
+            Some(canonical) if binds(canonical) => Qualified::Name(format!("{canonical}:{local}")),
+            _ if binds(prefix) => Qualified::Name(name.to_string()),
+            _ => Qualified::Unbound {
+                prefix: prefix.to_string(),
+                uri: uri.to_string(),
+            },
+        })
+    }
+
+    /// The uri bound to `prefix` by the innermost of the declarations picked
+    /// from each scope.
+    fn lookup(
+        &self,
+        prefix: &str,
+        declarations: impl Fn(&Scope) -> &Vec<(String, String)>,
+    ) -> Option<&str> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| declarations(scope).iter())
+            .find(|(declared, _)| declared == prefix)
+            .map(|(_, uri)| uri.as_str())
+    }
+
+    fn canonical_prefix(&self, uri: &str) -> Option<&str> {
+        self.prefixes
+            .iter()
+            .find(|(canonical_uri, _)| canonical_uri == uri)
+            .map(|(_, prefix)| prefix.as_str())
+    }
+}
+
+/// A name qualified by [`Namespaces::qualified_name`].
+enum Qualified {
+    /// The name to write.
+    Name(String),
+    /// The name keeps its `prefix`, which is bound to no or another
+    /// namespace in the output and must be declared again for `uri`.
+    Unbound { prefix: String, uri: String },
+}
+
+/// Adds the attribute `key` to the `attributes` of element `name`, once,
+/// and fails when renaming prefixes gave it two values.
+fn push_declared(
+    attributes: &mut Vec<(String, String)>,
+    name: &str,
+    key: String,
+    value: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match attributes.iter().find(|(existing, _)| *existing == key) {
+        Some((_, existing)) if *existing == value => Ok(()),
+        Some(_) => Err(format!(
+            "conflicting declarations of {key} on element <{name}> after renaming prefixes"
+        )
+        .into()),
+        None => {
+            attributes.push((key, value));
+            Ok(())
+        }
+    }
+}
+
+/// Returns the prefix declared by an `xmlns` attribute,
+/// an empty prefix for the default namespace.
+fn declared_prefix(key: &str) -> Option<&str> {
+    if key == "xmlns" {
+        Some("")
+    } else {
+        key.strip_prefix("xmlns:")
+    }
+}
+
+/// Finds the prefixed declarations of `content` that can be hoisted to the
+/// root: those whose prefix (after renaming) is bound to a single namespace
+/// throughout the document, in order of first appearance.
+fn hoistable_declarations(
+    content: &[u8],
+    namespaces: &Namespaces,
+) -> Result<Vec<(String, String)>, XmlError> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    let mut declarations: Vec<(String, String)> = Vec::new();
+    let mut conflicting: Vec<String> = Vec::new();
+
+    loop {
+        let start = reader.buffer_position();
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(err) => {
+                let location = Position::START.locate(content, reader.error_position(), None);
+                return Err(read_error(err, location, None));
+            }
+        };
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let attributes =
+                    attribute_pairs(e.attributes()).map_err(|err| XmlError::Parse {
+                        message: err.to_string(),
+                        location: Position::START.locate(content, start, EventKind::of(&event)),
+                    })?;
+                for (key, uri) in attributes {
+                    let Some(prefix) = declared_prefix(&key).filter(|p| !p.is_empty()) else {
+                        continue;
+                    };
+                    let prefix = namespaces
+                        .canonical_prefix(&uri)
+                        .unwrap_or(prefix)
+                        .to_string();
+                    match declarations
+                        .iter()
+                        .find(|(declared, _)| *declared == prefix)
+                    {
+                        Some((_, declared_uri)) if *declared_uri != uri => conflicting.push(prefix),
+                        Some(_) => {}
+                        None => declarations.push((prefix, uri)),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    declarations.retain(|(prefix, _)| !conflicting.contains(prefix));
+    Ok(declarations)
+}
+
+/// Reads attributes as name and value pairs, with the value escaped
+/// for writing between double quotes.
+fn attribute_pairs(
+    mut attributes: Attributes,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let mut pairs = Vec::new();
+    for attr in attributes.with_checks(false) {
+        let attr = attr?;
+        // Values are unescaped and re-escaped for double quotes, as the
+        // source may have used single quotes around a `"`. Values with
+        // entities we cannot resolve (e.g. declared in a DTD) are kept as is.
+        let value = match attr.unescape_value() {
+            Ok(value) => escape_attribute(&value),
+            Err(_) => String::from_utf8_lossy(&attr.value).replace('"', "&quot;"),
+        };
+        pairs.push((
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            value,
+        ));
+    }
+    Ok(pairs)
+}
+
+/// Writes the attributes of a tag, wrapping onto continuation lines
+/// when `options` limits the line width or the number of attributes per line.
+fn push_attributes(
+    output: &mut String,
+    attributes: &[(String, String)],
+    options: &PrettifyOptions,
+    indent: usize,
+) {
+    // The line is only measured when the width is limited, as a minified
+    // document is a single line and measuring it for every tag is quadratic.
+    let aligned = match options.max_width {
+        Some(width) if attributes.len() > 1 => {
+            // The tag written so far, from its indentation to its name.
+            let line = output.rsplit('\n').next().unwrap_or_default();
+            let line_width = line.chars().count()
+                + attributes
+                    .iter()
+                    .map(|(key, value)| key.chars().count() + value.chars().count() + 4)
+                    .sum::<usize>()
+                + 1;
+            (line_width > width).then(|| {
+                let tag_indent = &line[..line.len() - line.trim_start().len()];
+                let name_width = line.trim_start().chars().count();
+                format!("{tag_indent}{}", " ".repeat(name_width + 1))
+            })
+        }
+        _ => None,
+    };
+
+    for (i, (key, value)) in attributes.iter().enumerate() {
+        match (&aligned, options.max_attributes_per_line) {
+            (Some(aligned), _) if i > 0 => {
+                output.push_str(options.newline.as_str());
+                output.push_str(aligned);
+            }
+            (None, Some(max)) if i > 0 && i % max == 0 => {
+                output.push_str(options.newline.as_str());
+                output.push_str(&options.indent_unit().repeat(indent + 1));
+            }
+            _ => output.push(' '),
+        }
+        output.push_str(key);
+        output.push_str("=\"");
+        output.push_str(value);
+        output.push('"');
+    }
+}
+
+/// Writes text content, unescaping it and escaping it again so that
+/// entities are written consistently and markup characters stay escaped.
+fn push_text(output: &mut String, text: &BytesText) {
+    match text.unescape() {
+        Ok(unescaped) => output.push_str(&partial_escape(unescaped.as_ref())),
+        // Keep text with entities we cannot resolve (e.g. declared in a DTD) as is.
+        Err(_) => output.push_str(&String::from_utf8_lossy(text)),
+    }
+}
+
+/// Escapes an attribute value for writing between double quotes. Tabs and
+/// line breaks are written as character references, which parsers would
+/// otherwise normalize to spaces.
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' => escaped.push_str("&#9;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an xml declaration such as `<?xml version="1.0" encoding="UTF-8"?>`.
+fn push_declaration(output: &mut String, e: &BytesDecl) -> Result<(), Box<dyn std::error::Error>> {
+    output.push_str("<?xml");
+
+    output.push_str(" version=\"");
+    output.push_str(std::str::from_utf8(&e.version()?)?);
+    output.push('"');
+
+    if let Some(encoding) = e.encoding() {
+        output.push_str(" encoding=\"");
+        output.push_str(std::str::from_utf8(&encoding?)?);
+        output.push('"');
+    }
+
+    if let Some(standalone) = e.standalone() {
+        output.push_str(" standalone=\"");
+        output.push_str(std::str::from_utf8(&standalone?)?);
+        output.push('"');
+    }
+
+    output.push_str("?>");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_element() {
+        let input = "<root></root>";
+        let expected = "<root>\n</root>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_nested_elements() {
+        let input = "<root><child>text</child></root>";
+        let expected = "<root>\n  <child>text</child>\n</root>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let input = r#"<root><item id="1" value="x"/></root>"#;
+        let expected = "<root>\n  <item id=\"1\" value=\"x\" />\n</root>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_comment_and_cdata() {
+        let input = r#"<root><!--comment--><![CDATA[some <xml>]]></root>"#;
+        let expected = "<root>\n  <!--comment--><![CDATA[some <xml>]]>\n</root>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_doctype() {
+        let input = r#"<!DOCTYPE note><note><to>Tove</to></note>"#;
+        let expected = "<!DOCTYPE note>\n<note>\n  <to>Tove</to>\n</note>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_xml_declaration() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?><root/>"#;
+        let expected = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root />";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_indent_width_option() {
+        let input = "<root><child><leaf/></child></root>";
+        let expected = "<root>\n    <child>\n        <leaf />\n    </child>\n</root>";
+        let options = PrettifyOptions::new().indent_width(4);
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_tabs_option() {
+        let input = "<root><child>text</child></root>";
+        let expected = "<root>\n\t<child>text</child>\n</root>";
+        let options = PrettifyOptions::new().use_tabs(true);
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_crlf_newline_option() {
+        let input = "<root><child/></root>";
+        let expected = "<root>\r\n  <child />\r\n</root>";
+        let options = PrettifyOptions::new().newline_style(NewlineStyle::CrLf);
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_max_attributes_per_line_option() {
+        let input = r#"<root><item a="1" b="2" c="3"/></root>"#;
+        let expected = "<root>\n  <item a=\"1\" b=\"2\"\n    c=\"3\" />\n</root>";
+        let options = PrettifyOptions::new().max_attributes_per_line(Some(2));
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_max_width_option() {
+        let input = r#"<beans><bean id="dataSource" class="org.example.DataSource" scope="singleton"/><bean id="a"/></beans>"#;
+        let expected = concat!(
+            "<beans>\n",
+            "  <bean id=\"dataSource\"\n",
+            "        class=\"org.example.DataSource\"\n",
+            "        scope=\"singleton\" />\n",
+            "  <bean id=\"a\" />\n",
+            "</beans>"
+        );
+        let options = PrettifyOptions::new().max_width(Some(40));
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert_eq!(result, expected);
+
+        // Tabs are kept in front of the aligned attributes.
+        let options = options.use_tabs(true);
+        let result = prettify_xml_with_options(input, &options).unwrap();
+        assert!(result.contains("\t<bean id=\"dataSource\"\n\t      class="));
+    }
+
+    #[test]
+    fn test_minify_strips_whitespace() {
+        let input = "<?xml version=\"1.0\"?>\n<root>\n  <item  id=\"1\" />\n  <child>text</child>\n</root>\n";
+        let expected = "<?xml version=\"1.0\"?><root><item id=\"1\"/><child>text</child></root>";
+        let result = minify_xml(input, false).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_minify_keeps_comments_unless_stripped() {
+        let input = "<root>\n  <!--note-->\n  <![CDATA[ raw ]]>\n</root>";
+        assert_eq!(
+            minify_xml(input, false).unwrap(),
+            "<root><!--note--><![CDATA[ raw ]]></root>"
+        );
+        assert_eq!(
+            minify_xml(input, true).unwrap(),
+            "<root><![CDATA[ raw ]]></root>"
+        );
+    }
+
+    #[test]
+    fn test_minify_then_prettify_round_trip() {
+        let pretty = "<root>\n  <child>text</child>\n  <item id=\"1\" />\n</root>";
+        let minified = minify_xml(pretty, false).unwrap();
+        assert_eq!(prettify_xml(&minified).unwrap(), pretty);
+    }
+
+    #[test]
+    fn test_stream_matches_string_output() {
+        let input = r#"<?xml version="1.0"?><root><a x="1"><b>text</b></a><!--c--></root>"#;
+        let mut output = Vec::new();
+        prettify_xml_stream(input.as_bytes(), &mut output, &PrettifyOptions::new()).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            prettify_xml(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_well_formed() {
+        assert!(validate(r#"<?xml version="1.0"?><!--c--><root a="1"><b/>text</root>"#).is_ok());
+    }
+
+    #[test]
+    fn test_validate_mismatched_tags() {
+        let err = validate("<root>\n  <a>\n  </b>\n</root>").unwrap_err();
+        assert_eq!(err.tag.as_deref(), Some("b"));
+        assert_eq!((err.line, err.column, err.position), (3, 3, 15));
+        assert_eq!(err.open_tag, Some(("a".to_string(), 2, 3)));
+    }
+
+    #[test]
+    fn test_validate_unclosed_tag() {
+        let err = validate("<root><a>").unwrap_err();
+        assert_eq!(err.open_tag, Some(("a".to_string(), 1, 7)));
+    }
+
+    #[test]
+    fn test_validate_unmatched_close_tag() {
+        let err = validate("<root/></root>").unwrap_err();
+        assert_eq!(err.tag.as_deref(), Some("root"));
+        assert_eq!(err.column, 8);
+    }
+
+    #[test]
+    fn test_validate_duplicate_attribute() {
+        let err = validate(r#"<root a="1" a="2"/>"#).unwrap_err();
+        assert_eq!(err.tag.as_deref(), Some("root"));
+    }
+
+    #[test]
+    fn test_validate_multiple_roots() {
+        assert!(validate("<a/><b/>").is_err());
+        assert!(validate("<a/>text").is_err());
+        assert!(validate("").is_err());
+    }
+
+    #[test]
+    fn test_validate_syntax_error_position() {
+        let err = validate("<root>\n<a").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
+
+    #[test]
+    fn test_text_entities_stay_escaped() {
+        let input = "<root><a>fish &amp; chips &lt;3 &#233;</a></root>";
+        let expected = "<root>\n  <a>fish &amp; chips &lt;3 é</a>\n</root>";
+        assert_eq!(prettify_xml(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_quotes_in_attributes() {
+        let input = r#"<root><a title='say "hi"' q="it's &amp; &quot;x&quot;"/></root>"#;
+        let expected =
+            "<root>\n  <a title=\"say &quot;hi&quot;\" q=\"it's &amp; &quot;x&quot;\" />\n</root>";
+        let result = prettify_xml(input).unwrap();
+        assert_eq!(result, expected);
+        assert!(validate(&result).is_ok());
+    }
+
+    #[test]
+    fn test_whitespace_references_in_attributes() {
+        let input = "<a x=\"1&#9;2&#10;3&#13;4\"/>";
+        assert_eq!(prettify_xml(input).unwrap(), input.replace("/>", " />"));
+        assert_eq!(minify_xml(input, false).unwrap(), input);
+    }
+
+    #[test]
+    fn test_non_ascii_content() {
+        let input = r#"<café name="naïve 日本"><t>Grüße &#x1F980;</t></café>"#;
+        let expected = "<café name=\"naïve 日本\">\n  <t>Grüße 🦀</t>\n</café>";
+        assert_eq!(prettify_xml(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_comment_is_not_unescaped() {
+        let input = "<root><!-- a &amp; b --></root>";
+        let expected = "<root>\n  <!-- a &amp; b -->\n</root>";
+        assert_eq!(prettify_xml(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_minify_escapes_text_and_attributes() {
+        let input = "<a x='1 \"2\"'>\n  &lt;b&gt;\n</a>";
+        let expected = "<a x=\"1 &quot;2&quot;\">&lt;b&gt;</a>";
+        assert_eq!(minify_xml(input, false).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_collapse_empty_elements() {
+        let input = r#"<root><a x="1"></a><b>text</b><c><d></d></c></root>"#;
+        let expected = "<root>\n  <a x=\"1\" />\n  <b>text</b>\n  <c>\n    <d />\n  </c>\n</root>";
+        let options = PrettifyOptions::new().empty_elements(EmptyElements::Collapse);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_expand_empty_elements() {
+        let input = r#"<root><a x="1"/><b></b></root>"#;
+        let expected = "<root>\n  <a x=\"1\"></a>\n  <b></b>\n</root>";
+        let options = PrettifyOptions::new().empty_elements(EmptyElements::Expand);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sort_attributes() {
+        let input = r#"<root z="1" xmlns:b="urn:b" a='2' xmlns="urn:default" xmlns:a="urn:a"/>"#;
+        let expected =
+            r#"<root xmlns="urn:default" xmlns:a="urn:a" xmlns:b="urn:b" a="2" z="1" />"#;
+        let options = PrettifyOptions::new().sort_attributes(true);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_processing_instruction_attributes_are_not_sorted() {
+        let input = r#"<?xml-stylesheet type="text/xsl" href="style.xsl"?><root/>"#;
+        let expected = "<?xml-stylesheet type=\"text/xsl\" href=\"style.xsl\"?>\n<root />";
+        let options = PrettifyOptions::new().sort_attributes(true);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_undeclared_prefix_is_an_error() {
+        let options = PrettifyOptions::new().namespace_aware(true);
+        assert!(prettify_xml_with_options("<a:root/>", &options).is_err());
+        assert!(prettify_xml_with_options(r#"<root b:x="1"/>"#, &options).is_err());
+        assert!(prettify_xml_with_options(r#"<root xml:lang="en"/>"#, &options).is_ok());
+        // Without the namespace-aware mode prefixes pass through.
+        assert!(prettify_xml("<a:root/>").is_ok());
+    }
+
+    #[test]
+    fn test_declaration_scope_ends_with_element() {
+        let input = r#"<root><a:x xmlns:a="urn:a"/><a:y/></root>"#;
+        let options = PrettifyOptions::new().namespace_aware(true);
+        assert!(prettify_xml_with_options(input, &options).is_err());
+    }
+
+    #[test]
+    fn test_hoist_namespaces() {
+        let input = r#"<root><a:x xmlns:a="urn:a"/><a:y xmlns:a="urn:a"><b:z xmlns:b="urn:b"/></a:y></root>"#;
+        let expected = concat!(
+            "<root xmlns:a=\"urn:a\" xmlns:b=\"urn:b\">\n",
+            "  <a:x />\n",
+            "  <a:y>\n",
+            "    <b:z />\n",
+            "  </a:y>\n",
+            "</root>"
+        );
+        let options = PrettifyOptions::new().hoist_namespaces(true);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_hoist_skips_conflicting_prefixes() {
+        let input = r#"<root><p:x xmlns:p="urn:1"/><p:y xmlns:p="urn:2"/></root>"#;
+        let expected = concat!(
+            "<root>\n",
+            "  <p:x xmlns:p=\"urn:1\" />\n",
+            "  <p:y xmlns:p=\"urn:2\" />\n",
+            "</root>"
+        );
+        let options = PrettifyOptions::new().hoist_namespaces(true);
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_rename_namespace_prefix() {
+        let input = r#"<ns0:Envelope xmlns:ns0="urn:soap" ns0:id="1"><ns0:Body/></ns0:Envelope>"#;
+        let expected = concat!(
+            "<soap:Envelope xmlns:soap=\"urn:soap\" soap:id=\"1\">\n",
+            "  <soap:Body />\n",
+            "</soap:Envelope>"
+        );
+        let options = PrettifyOptions::new().namespace_prefix("urn:soap", "soap");
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_rename_namespace_prefix_redeclared_below() {
+        // `soap` is bound to another namespace inside <soap:b>, so <c> keeps
+        // its prefix and declares it again.
+        let input = concat!(
+            r#"<ns0:a xmlns:ns0="urn:soap"><soap:b xmlns:soap="urn:other">"#,
+            "<ns0:c/><soap:d/></soap:b><ns0:e/></ns0:a>"
+        );
+        let expected = concat!(
+            "<soap:a xmlns:soap=\"urn:soap\">\n",
+            "  <soap:b xmlns:soap=\"urn:other\">\n",
+            "    <ns0:c xmlns:ns0=\"urn:soap\" />\n",
+            "    <soap:d />\n",
+            "  </soap:b>\n",
+            "  <soap:e />\n",
+            "</soap:a>"
+        );
+        let options = PrettifyOptions::new().namespace_prefix("urn:soap", "soap");
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_max_depth_option() {
+        let options = PrettifyOptions::new().max_depth(Some(2));
+        assert!(prettify_xml_with_options("<a><b>x</b><c/></a>", &options).is_ok());
+        let err = prettify_xml_with_options("<a><b><c/></b></a>", &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element c is nested 3 levels deep, more than the maximum depth of 2 at line 1, column 7 (byte 6)"
+        );
+        assert_eq!(err.location().unwrap().event, Some(EventKind::EmptyTag));
+    }
+
+    #[test]
+    fn test_max_size_option() {
+        let xml = "<root><child>text</child></root>";
+        let exact = PrettifyOptions::new().max_size(Some(xml.len() as u64));
+        assert!(prettify_xml_with_options(xml, &exact).is_ok());
+
+        let smaller = PrettifyOptions::new().max_size(Some(10));
+        let err = prettify_xml_with_options(xml, &smaller).unwrap_err();
+        assert!(matches!(err, XmlError::TooLarge { max_size: 10 }));
+        assert_eq!(
+            err.to_string(),
+            "the document is larger than the maximum size of 10 bytes"
+        );
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let locate = |xml: &str| {
+            // Read a few bytes at a time, as from a file.
+            let reader = std::io::BufReader::with_capacity(4, xml.as_bytes());
+            let err = prettify_xml_stream(reader, Vec::new(), &PrettifyOptions::new()).unwrap_err();
+            let location = *err.location().unwrap();
+            (location.line, location.column, location.event)
+        };
+        assert_eq!(
+            locate("<a>\n  <b>\n  </c>\n</a>"),
+            (3, 3, Some(EventKind::EndTag))
+        );
+        // Columns count characters rather than bytes.
+        assert_eq!(
+            locate("<a>\n  <b>\u{e9}t\u{e9}</b><!-- x\n</a>"),
+            (2, 13, Some(EventKind::Comment))
+        );
+        assert_eq!(
+            locate("<a>\n<b x=1/></a>"),
+            (2, 1, Some(EventKind::EmptyTag))
+        );
+
+        let options = PrettifyOptions::new().namespace_aware(true);
+        let err = prettify_xml_with_options("<a>\n  <b:c/>\n</a>", &options).unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!(
+            (location.line, location.column, location.position),
+            (2, 3, 6)
+        );
+
+        let err = minify_xml("<?xml version=\"1.0\"?>\n<a><![CDATA[x</a>", false).unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!(
+            (location.line, location.column, location.event),
+            (2, 4, Some(EventKind::CData))
+        );
+
+        let location = Location {
+            position: 0,
+            line: 1,
+            column: 60,
+            event: None,
+        };
+        let line = format!("{}\t<b>{}", "x".repeat(55), "y".repeat(60));
+        assert_eq!(
+            location.excerpt(&line),
+            format!(
+                "...{}\t<b>{}...\n   {}\t   ^",
+                "x".repeat(36),
+                "y".repeat(40),
+                " ".repeat(36)
+            )
+        );
+    }
+
+    #[test]
+    fn test_dtd_entities_are_not_expanded() {
+        let xml = concat!(
+            r#"<!DOCTYPE lolz [<!ENTITY lol "lol"><!ENTITY lol2 "&lol;&lol;&lol;&lol;">]>"#,
+            "<lolz>&lol2;</lolz>"
+        );
+        assert_eq!(
+            prettify_xml(xml).unwrap(),
+            concat!(
+                r#"<!DOCTYPE lolz [<!ENTITY lol "lol"><!ENTITY lol2 "&lol;&lol;&lol;&lol;">]>"#,
+                "\n<lolz>&lol2;</lolz>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_xml_space_preserve() {
+        let input =
+            "<doc><p xml:space=\"preserve\">Hello <b>world</b>,\n  bye </p><q> x </q></doc>";
+        let expected = concat!(
+            "<doc>\n",
+            "  <p xml:space=\"preserve\">Hello <b>world</b>,\n  bye </p>\n",
+            "  <q>x</q>\n",
+            "</doc>"
+        );
+        assert_eq!(prettify_xml(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_preserve_whitespace_in_option() {
+        let input = "<html><pre> a\n<code>b</code></pre><div><span>c</span></div></html>";
+        let expected = concat!(
+            "<html>\n",
+            "  <pre> a\n<code>b</code></pre>\n",
+            "  <div>\n",
+            "    <span>c</span>\n",
+            "  </div>\n",
+            "</html>"
+        );
+        let options = PrettifyOptions::new().preserve_whitespace_in("pre");
+        assert_eq!(
+            prettify_xml_with_options(input, &options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_xml_space_default_overrides_preserve() {
+        let input = "<a xml:space=\"preserve\"> <b xml:space=\"default\"><c/></b> </a>";
+        let expected =
+            "<a xml:space=\"preserve\"> <b xml:space=\"default\">\n    <c />\n  </b> </a>";
+        assert_eq!(prettify_xml(input).unwrap(), expected);
+    }
+
+    /// A small deterministic generator of random XML documents, so the
+    /// round-trip properties below run on the same inputs every time.
+    struct DocumentGenerator(u64);
+
+    impl DocumentGenerator {
+        fn next(&mut self, bound: usize) -> usize {
+            // xorshift64
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.next(choices.len())]
+        }
+
+        fn element(&mut self, depth: usize, output: &mut String) {
+            let name = self.pick(&["a", "b", "item", "x:y", "long-name"]);
+            output.push('<');
+            output.push_str(name);
+            for attribute in ["id", "k", "xml:lang"] {
+                if self.next(3) == 0 {
+                    let value = self.pick(&["1", "a &amp; b", "&lt;tag&gt;", "it's", " spaced "]);
+                    output.push_str(&format!(" {attribute}=\"{value}\""));
+                }
+            }
+            if depth > 3 || self.next(4) == 0 {
+                output.push_str("/>");
+                return;
+            }
+            output.push('>');
+            for _ in 0..self.next(4) {
+                match self.next(5) {
+                    0 | 1 => self.element(depth + 1, output),
+                    2 => output.push_str(self.pick(&["text", "x &amp; y", "  padded  ", "\n"])),
+                    3 => output.push_str(self.pick(&["<!--c-->", "<!-- two words -->"])),
+                    _ => output.push_str(self.pick(&["<![CDATA[<raw> & ]]>", "<![CDATA[]]>"])),
+                }
+            }
+            output.push_str(&format!("</{name}>"));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_properties() {
+        let mut generator = DocumentGenerator(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let mut document = String::new();
+            generator.element(0, &mut document);
+
+            let pretty = prettify_xml(&document).unwrap();
+            // The output is always well-formed.
+            assert!(validate(&pretty).is_ok(), "{document} -> {pretty}");
+            // Prettifying is idempotent, and stable through minifying.
+            assert_eq!(prettify_xml(&pretty).unwrap(), pretty, "{document}");
+            let minified = minify_xml(&pretty, false).unwrap();
+            assert_eq!(prettify_xml(&minified).unwrap(), pretty, "{document}");
+        }
     }
 }
@@ -0,0 +1,280 @@
+//! Interactive questions on the terminal: confirmations with a default
+//! answer, selections from a list, and secrets typed without echo.
+//!
+//! Questions are written to stderr, so they never mix with the output of
+//! a subcommand. When stdin is not a terminal nobody can answer, so every
+//! question takes its default answer, and the global `--yes` confirms
+//! everything without asking, for scripts.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Asks questions, reading the answers from `R` and writing the questions
+/// to `W`.
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+    interactive: bool,
+    terminal: bool,
+    assume_yes: bool,
+}
+
+impl Prompter<std::io::StdinLock<'static>, std::io::Stderr> {
+    /// Creates a prompter asking on stderr and reading stdin, only
+    /// interactive when stdin is a terminal.
+    pub fn stdin() -> Self {
+        let terminal = std::io::stdin().is_terminal();
+        Self {
+            input: std::io::stdin().lock(),
+            output: std::io::stderr(),
+            interactive: terminal,
+            terminal,
+            assume_yes: false,
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Creates an interactive prompter reading the answers from `input`.
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            interactive: true,
+            terminal: false,
+            assume_yes: false,
+        }
+    }
+
+    /// Sets whether confirmations are answered yes without asking, for `--yes`.
+    pub fn assume_yes(mut self, assume_yes: bool) -> Self {
+        self.assume_yes = assume_yes;
+        self
+    }
+
+    /// Whether questions are actually asked, rather than answered by
+    /// their default or `--yes`.
+    pub fn interactive(&self) -> bool {
+        self.interactive && !self.assume_yes
+    }
+
+    /// Whether confirmations are answered yes without asking.
+    pub fn assumes_yes(&self) -> bool {
+        self.assume_yes
+    }
+
+    /// Asks a yes/no `question`, `default` being the answer to an empty
+    /// line, to the end of input, and when nobody can answer.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::prompt::Prompter;
+    ///
+    /// let mut prompter = Prompter::new("maybe\ny\n".as_bytes(), Vec::new());
+    /// assert!(prompter.confirm("Delete 3 files?", false).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the answer or writing the question fails.
+    pub fn confirm(&mut self, question: &str, default: bool) -> std::io::Result<bool> {
+        if self.assume_yes {
+            return Ok(true);
+        }
+        let choices = if default { "[Y/n]" } else { "[y/N]" };
+        loop {
+            let Some(answer) = self.ask(&format!("{question} {choices} "))? else {
+                return Ok(default);
+            };
+            match answer.to_ascii_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "Please answer y or n.")?,
+            }
+        }
+    }
+
+    /// Asks to pick one of `choices`, listed with numbers from 1, and
+    /// returns its index. `default` is the answer to an empty line, to the
+    /// end of input, to `--yes`, and when nobody can answer; `None` without
+    /// a default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the answer or writing the question fails.
+    pub fn select(
+        &mut self,
+        question: &str,
+        choices: &[&str],
+        default: Option<usize>,
+    ) -> std::io::Result<Option<usize>> {
+        if self.assume_yes || choices.is_empty() {
+            return Ok(default);
+        }
+        if self.interactive {
+            writeln!(self.output, "{question}")?;
+            for (index, choice) in choices.iter().enumerate() {
+                let mark = if Some(index) == default { "*" } else { " " };
+                writeln!(self.output, "{mark}{:>3}) {choice}", index + 1)?;
+            }
+        }
+        let prompt = match default {
+            Some(default) => format!("Choice [{}]: ", default + 1),
+            None => "Choice: ".to_string(),
+        };
+        loop {
+            let Some(answer) = self.ask(&prompt)? else {
+                return Ok(default);
+            };
+            if answer.is_empty() {
+                return Ok(default);
+            }
+            match answer.parse::<usize>() {
+                Ok(number) if (1..=choices.len()).contains(&number) => return Ok(Some(number - 1)),
+                _ => writeln!(
+                    self.output,
+                    "Please answer a number from 1 to {}.",
+                    choices.len()
+                )?,
+            }
+        }
+    }
+
+    /// Asks for a secret, such as a password, without echoing it on the
+    /// terminal (on unix). Unlike other questions, a secret is read even
+    /// when stdin is not a terminal, so scripts can pipe it in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input ends, or reading the answer or
+    /// writing the question fails.
+    pub fn secret(&mut self, question: &str) -> std::io::Result<String> {
+        write!(self.output, "{question} ")?;
+        self.output.flush()?;
+        let echo = self.terminal.then(EchoOff::new).flatten();
+        let mut line = String::new();
+        let read = self.input.read_line(&mut line);
+        if echo.is_some() {
+            drop(echo);
+            // The newline typed was not echoed either.
+            writeln!(self.output)?;
+        }
+        if read? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "no secret was given",
+            ));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Writes `prompt` and reads a trimmed answer line, `None` at the end of
+    /// input or when nobody can answer.
+    fn ask(&mut self, prompt: &str) -> std::io::Result<Option<String>> {
+        if !self.interactive {
+            return Ok(None);
+        }
+        write!(self.output, "{prompt}")?;
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            writeln!(self.output)?;
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+}
+
+/// Turns the echo of the terminal on stdin off until dropped.
+struct EchoOff {
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+impl EchoOff {
+    /// Turns the echo off, `None` when it cannot be.
+    #[cfg(unix)]
+    fn new() -> Option<Self> {
+        // SAFETY: termios is plain data, which tcgetattr fills.
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return None;
+        }
+        let mut silent = saved;
+        silent.c_lflag &= !libc::ECHO;
+        // SAFETY: `silent` is a valid termios from tcgetattr.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) } != 0 {
+            return None;
+        }
+        Some(Self { saved })
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Option<Self> {
+        None
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        // SAFETY: `saved` is the termios read from the same terminal.
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompter(input: &str) -> Prompter<&[u8], Vec<u8>> {
+        Prompter::new(input.as_bytes(), Vec::new())
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut asked = prompter("\nno\nwhat\nYES\n");
+        assert!(asked.confirm("Go?", true).unwrap());
+        assert!(!asked.confirm("Go?", true).unwrap());
+        assert!(asked.confirm("Go?", false).unwrap());
+        // The end of input takes the default.
+        assert!(!asked.confirm("Go?", false).unwrap());
+        let output = String::from_utf8(asked.output).unwrap();
+        assert_eq!(
+            output,
+            "Go? [Y/n] Go? [Y/n] Go? [y/N] Please answer y or n.\nGo? [y/N] Go? [y/N] \n"
+        );
+
+        let mut yes = prompter("n\n").assume_yes(true);
+        assert!(yes.confirm("Go?", false).unwrap());
+        assert!(!yes.interactive());
+        assert!(yes.output.is_empty());
+    }
+
+    #[test]
+    fn test_select() {
+        let choices = ["cargo", "node", "venv"];
+        let mut asked = prompter("4\n2\n\n");
+        assert_eq!(asked.select("Which?", &choices, None).unwrap(), Some(1));
+        assert_eq!(asked.select("Which?", &choices, Some(2)).unwrap(), Some(2));
+        assert_eq!(asked.select("Which?", &choices, None).unwrap(), None);
+        let output = String::from_utf8(asked.output).unwrap();
+        assert!(output.starts_with(
+            "Which?\n   1) cargo\n   2) node\n   3) venv\nChoice: Please answer a number from 1 to 3.\n"
+        ));
+        assert!(output.contains("*  3) venv\nChoice [3]: "));
+
+        let mut yes = prompter("").assume_yes(true);
+        assert_eq!(yes.select("Which?", &choices, Some(0)).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_secret() {
+        let mut asked = prompter("hunter2\r\n");
+        assert_eq!(asked.secret("Password:").unwrap(), "hunter2");
+        assert_eq!(asked.output, b"Password: ");
+        assert!(asked.secret("Password:").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! A common tabular result schema for diagnostic subcommands,
+//! with writers for CSV, JSON and Markdown tables.
+//!
+//! Subcommands build a [`Report`] from their results and hand it to
+//! [`export`] so every `--export <path>` produces the same shapes.
+
+use std::io::Write;
+
+/// A single cell of a report.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    /// No value, e.g. the round-trip time of a lost packet.
+    Missing,
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Missing, Into::into)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{text}"),
+            Value::Integer(integer) => write!(f, "{integer}"),
+            Value::Float(float) => write!(f, "{float}"),
+            Value::Missing => Ok(()),
+        }
+    }
+}
+
+/// The results of one subcommand run as named columns and rows of values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The subcommand that produced the report, e.g. `"ping"`.
+    pub tool: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Report {
+    /// Creates an empty report with the given column names.
+    pub fn new(tool: &str, columns: &[&str]) -> Self {
+        Self {
+            tool: tool.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Appends a row. The row must have one value per column.
+    pub fn push_row(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+}
+
+/// The file formats a [`Report`] can be written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl Format {
+    /// Picks the format from the extension of `path`
+    /// (`.csv`, `.json`, `.md` or `.markdown`).
+    pub fn from_path(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "md" | "markdown" => Ok(Format::Markdown),
+            _ => Err(format!(
+                "unsupported export format for {path}, expected a .csv, .json or .md file"
+            )
+            .into()),
+        }
+    }
+}
+
+/// Writes `report` to the file at `path`, choosing the format from its extension.
+pub fn export(report: &Report, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let format = Format::from_path(path)?;
+    let file =
+        std::fs::File::create(path).map_err(|err| format!("failed to create {path}: {err}"))?;
+    let mut writer = std::io::BufWriter::new(file);
+    write(report, format, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `report` in the given `format`.
+pub fn write(report: &Report, format: Format, writer: &mut impl Write) -> std::io::Result<()> {
+    match format {
+        Format::Csv => write_csv(report, writer),
+        Format::Json => write_json(report, writer),
+        Format::Markdown => write_markdown(report, writer),
+    }
+}
+
+fn write_csv(report: &Report, writer: &mut impl Write) -> std::io::Result<()> {
+    let header: Vec<String> = report.columns.iter().map(|c| csv_field(c)).collect();
+    writeln!(writer, "{}", header.join(","))?;
+
+    for row in &report.rows {
+        let fields: Vec<String> = row.iter().map(|v| csv_field(&v.to_string())).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// Quotes a CSV field when it contains a separator, quote or newline,
+/// as described in [RFC 4180](https://datatracker.ietf.org/doc/html/rfc4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_json(report: &Report, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"tool\": {},", json_string(&report.tool))?;
+    writeln!(writer, "  \"rows\": [")?;
+
+    for (i, row) in report.rows.iter().enumerate() {
+        let fields: Vec<String> = report
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| format!("{}: {}", json_string(column), json_value(value)))
+            .collect();
+        let separator = if i + 1 < report.rows.len() { "," } else { "" };
+        writeln!(writer, "    {{{}}}{separator}", fields.join(", "))?;
+    }
+
+    writeln!(writer, "  ]")?;
+    writeln!(writer, "}}")
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Text(text) => json_string(text),
+        Value::Integer(integer) => integer.to_string(),
+        // JSON has no representation for NaN or infinity.
+        Value::Float(float) if float.is_finite() => float.to_string(),
+        Value::Float(_) | Value::Missing => "null".to_string(),
+    }
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn write_markdown(report: &Report, writer: &mut impl Write) -> std::io::Result<()> {
+    let header: Vec<String> = report.columns.iter().map(|c| markdown_cell(c)).collect();
+    writeln!(writer, "| {} |", header.join(" | "))?;
+    writeln!(writer, "|{}", " --- |".repeat(report.columns.len()))?;
+
+    for row in &report.rows {
+        let cells: Vec<String> = row.iter().map(|v| markdown_cell(&v.to_string())).collect();
+        writeln!(writer, "| {} |", cells.join(" | "))?;
+    }
+
+    Ok(())
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_report() -> Report {
+        let mut report = Report::new("ping", &["target", "seq", "rtt_ms"]);
+        report.push_row(vec!["a,b".into(), 0.into(), Some(1.5).into()]);
+        report.push_row(vec!["say \"hi\"".into(), 1.into(), None::<f64>.into()]);
+        report
+    }
+
+    fn render(format: Format) -> String {
+        let mut output = Vec::new();
+        write(&sample_report(), format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let expected = "target,seq,rtt_ms\n\"a,b\",0,1.5\n\"say \"\"hi\"\"\",1,\n";
+        assert_eq!(render(Format::Csv), expected);
+    }
+
+    #[test]
+    fn test_json() {
+        let expected = concat!(
+            "{\n",
+            "  \"tool\": \"ping\",\n",
+            "  \"rows\": [\n",
+            "    {\"target\": \"a,b\", \"seq\": 0, \"rtt_ms\": 1.5},\n",
+            "    {\"target\": \"say \\\"hi\\\"\", \"seq\": 1, \"rtt_ms\": null}\n",
+            "  ]\n",
+            "}\n",
+        );
+        assert_eq!(render(Format::Json), expected);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let expected = concat!(
+            "| target | seq | rtt_ms |\n",
+            "| --- | --- | --- |\n",
+            "| a,b | 0 | 1.5 |\n",
+            "| say \"hi\" | 1 |  |\n",
+        );
+        assert_eq!(render(Format::Markdown), expected);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("out.CSV").unwrap(), Format::Csv);
+        assert_eq!(Format::from_path("out.md").unwrap(), Format::Markdown);
+        assert!(Format::from_path("out.txt").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Hostname resolution shared by the network subcommands.
+//!
+//! Lookups are answered, in order, by IP literals, `--resolve host:ip`
+//! overrides, the hosts file and finally the system resolver. Answers are
+//! cached for the lifetime of the [`Resolver`], and each answer records
+//! which source produced it and how long the lookup took.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Where the addresses of a [`Resolution`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The host was already an IP address.
+    Literal,
+    /// A `--resolve host:ip` override given on the command line.
+    Override,
+    /// The hosts file (`/etc/hosts` on unix).
+    HostsFile,
+    /// The system resolver (DNS).
+    System,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self {
+            Source::Literal => "literal address",
+            Source::Override => "--resolve override",
+            Source::HostsFile => "hosts file",
+            Source::System => "system resolver",
+        };
+        write!(f, "{source}")
+    }
+}
+
+/// The addresses a hostname resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub addresses: Vec<IpAddr>,
+    pub source: Source,
+    /// How long the lookup took, next to nothing for answers from the cache.
+    pub elapsed: Duration,
+}
+
+/// Resolves hostnames, caching every answer for the lifetime of the resolver.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    overrides: HashMap<String, Vec<IpAddr>>,
+    hosts: HashMap<String, Vec<IpAddr>>,
+    cache: Mutex<HashMap<String, Resolution>>,
+    literal_only: bool,
+}
+
+impl Resolver {
+    /// Creates a resolver that honors the system hosts file.
+    /// A missing or unreadable hosts file is treated as empty.
+    pub fn new() -> Self {
+        let hosts = std::fs::read_to_string(crate::hosts::HOSTS_FILE).unwrap_or_default();
+        Self::with_hosts(&hosts)
+    }
+
+    /// Creates a resolver using `hosts` as the content of the hosts file.
+    pub fn with_hosts(hosts: &str) -> Self {
+        Self {
+            hosts: parse_hosts(hosts),
+            ..Self::default()
+        }
+    }
+
+    /// Adds an override in the `host:ip` form used by `--resolve`,
+    /// e.g. `example.com:127.0.0.1` or `example.com:[::1]`.
+    /// Repeating a host adds more addresses to it.
+    pub fn add_override(&mut self, spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (host, ip) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("invalid --resolve value ({spec}), expected host:ip"))?;
+        let ip = ip.trim_start_matches('[').trim_end_matches(']');
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|err| format!("invalid address in --resolve value ({spec}): {err}"))?;
+
+        self.overrides
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .push(ip);
+        Ok(())
+    }
+
+    /// Accepts only IP literals, for `--no-dns`, so a slow resolver cannot
+    /// be taken for a slow network.
+    pub fn literal_only(mut self, literal_only: bool) -> Self {
+        self.literal_only = literal_only;
+        self
+    }
+
+    /// Resolves `host` to its addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system resolver fails or finds no address,
+    /// or `host` is no IP literal and the resolver only accepts those.
+    pub fn resolve(&self, host: &str) -> Result<Resolution, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let mut resolution = match self.lookup(host)? {
+            Some(resolution) => resolution,
+            None => self.remember(host, lookup_system(host)?),
+        };
+        resolution.elapsed = start.elapsed();
+        Ok(resolution)
+    }
+
+    /// Resolves `host` like [`Resolver::resolve`], without blocking the
+    /// thread on the system resolver.
+    ///
+    /// # Errors
+    ///
+    /// See [`Resolver::resolve`].
+    #[cfg(feature = "tokio")]
+    pub async fn resolve_async(
+        &self,
+        host: &str,
+    ) -> Result<Resolution, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let mut resolution = match self.lookup(host)? {
+            Some(resolution) => resolution,
+            None => {
+                let addresses = tokio::net::lookup_host((host, 0)).await;
+                self.remember(host, system_addresses(host, addresses)?)
+            }
+        };
+        resolution.elapsed = start.elapsed();
+        Ok(resolution)
+    }
+
+    /// Answers `host` from an IP literal, the cache, the overrides or the
+    /// hosts file, `None` when the system resolver must be asked.
+    fn lookup(&self, host: &str) -> Result<Option<Resolution>, Box<dyn std::error::Error>> {
+        if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+            return Ok(Some(Resolution {
+                addresses: vec![ip],
+                source: Source::Literal,
+                elapsed: Duration::ZERO,
+            }));
+        }
+        if self.literal_only {
+            return Err(format!("{host} is not an IP address, which --no-dns requires").into());
+        }
+
+        let key = host.to_ascii_lowercase();
+        if let Some(resolution) = self.cached(&key) {
+            return Ok(Some(resolution));
+        }
+
+        let found = |addresses: &Vec<IpAddr>, source| Resolution {
+            addresses: addresses.clone(),
+            source,
+            elapsed: Duration::ZERO,
+        };
+        Ok(match (self.overrides.get(&key), self.hosts.get(&key)) {
+            (Some(addresses), _) => Some(found(addresses, Source::Override)),
+            (None, Some(addresses)) => Some(found(addresses, Source::HostsFile)),
+            (None, None) => None,
+        })
+    }
+
+    /// Caches the `addresses` the system resolver found for `host`.
+    fn remember(&self, host: &str, addresses: Vec<IpAddr>) -> Resolution {
+        let resolution = Resolution {
+            addresses,
+            source: Source::System,
+            elapsed: Duration::ZERO,
+        };
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(host.to_ascii_lowercase(), resolution.clone());
+        }
+        resolution
+    }
+
+    fn cached(&self, key: &str) -> Option<Resolution> {
+        self.cache.lock().ok()?.get(key).cloned()
+    }
+}
+
+fn lookup_system(host: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    // `ToSocketAddrs`'s `to_socket_addrs` method expect the str to be parsed
+    // in the format of `hostname:port`, so we append a dummy port `0`.
+    system_addresses(host, (host, 0).to_socket_addrs())
+}
+
+/// The addresses of `host` in the answer of the system resolver.
+fn system_addresses(
+    host: &str,
+    answer: std::io::Result<impl Iterator<Item = SocketAddr>>,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    let addresses: Vec<IpAddr> = answer
+        .map_err(|err| format!("DNS lookup failed on the target host ({host}): {err}"))?
+        .map(|addr| addr.ip())
+        .collect();
+
+    if addresses.is_empty() {
+        return Err(format!("no DNS record is found for target host ({host})").into());
+    }
+
+    Ok(addresses)
+}
+
+/// Parses the content of a hosts file into a map from lowercase hostname
+/// to addresses, in file order. Comments and malformed lines are skipped.
+fn parse_hosts(content: &str) -> HashMap<String, Vec<IpAddr>> {
+    let mut hosts: HashMap<String, Vec<IpAddr>> = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(Ok(ip)) = fields.next().map(str::parse::<IpAddr>) else {
+            continue;
+        };
+
+        for name in fields {
+            let addresses = hosts.entry(name.to_ascii_lowercase()).or_default();
+            if !addresses.contains(&ip) {
+                addresses.push(ip);
+            }
+        }
+    }
+
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTS: &str = "\
+# comment line
+127.0.0.1   localhost Dev.Local # trailing comment
+::1         localhost
+not-an-ip   ignored
+";
+
+    #[test]
+    fn test_parse_hosts() {
+        let hosts = parse_hosts(HOSTS);
+        assert_eq!(
+            hosts["localhost"],
+            vec![
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            hosts["dev.local"],
+            vec!["127.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(!hosts.contains_key("ignored"));
+    }
+
+    #[test]
+    fn test_resolve_literal() {
+        let resolver = Resolver::with_hosts("");
+        let resolution = resolver.resolve("10.0.0.1").unwrap();
+        assert_eq!(resolution.source, Source::Literal);
+        assert_eq!(
+            resolution.addresses,
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_resolve_hosts_file_case_insensitive() {
+        let resolver = Resolver::with_hosts(HOSTS);
+        let resolution = resolver.resolve("DEV.local").unwrap();
+        assert_eq!(resolution.source, Source::HostsFile);
+    }
+
+    #[test]
+    fn test_override_wins_over_hosts_file() {
+        let mut resolver = Resolver::with_hosts(HOSTS);
+        resolver.add_override("dev.local:[::2]").unwrap();
+        let resolution = resolver.resolve("dev.local").unwrap();
+        assert_eq!(resolution.source, Source::Override);
+        assert_eq!(resolution.addresses, vec!["::2".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_literal_only() {
+        let resolver = Resolver::with_hosts(HOSTS).literal_only(true);
+        assert!(resolver.resolve("dev.local").is_err());
+        let resolution = resolver.resolve("[::1]").unwrap();
+        assert_eq!(resolution.source, Source::Literal);
+    }
+
+    #[test]
+    fn test_invalid_override() {
+        let mut resolver = Resolver::with_hosts("");
+        assert!(resolver.add_override("no-address").is_err());
+        assert!(resolver.add_override("host:not-an-ip").is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! A JSON-RPC 2.0 server exposing the formatters and converters, so
+//! editor plugins can keep one process running instead of spawning one
+//! per keystroke.
+//!
+//! Messages are exchanged one per line on stdin and stdout. Every method
+//! takes the document as the `text` parameter, plus the options of the
+//! matching subcommand, and returns `{"text": <output>}`:
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "prettify-xml", "params": {"text": "<a><b/></a>", "indent": 4}}
+//! <-- {"id":1,"jsonrpc":"2.0","result":{"text":"<a>\n    <b />\n</a>"}}
+//! ```
+//!
+//! | Method             | Options                          |
+//! |--------------------|----------------------------------|
+//! | `prettify-xml`     | `indent`, `tabs`, `sort_attrs`   |
+//! | `prettify-html`    | `indent`, `tabs`                 |
+//! | `minify-xml`       | `strip_comments`                 |
+//! | `canonicalize-xml` | `with_comments`                  |
+//! | `xml-to-json`      |                                  |
+//! | `xml-to-yaml`      |                                  |
+//! | `yaml-to-xml`      |                                  |
+//!
+//! `validate-xml` returns `{"valid": true}`, or `{"valid": false, "error":
+//! <message>, "line": <n>, "column": <n>}`. The `shutdown` method, or the
+//! end of the input, stops the server.
+
+use std::io::{BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::{canonicalize_xml, convert, prettify_html, prettify_xml};
+
+// Standard JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The tool failed on the given document, e.g. malformed XML.
+const TOOL_ERROR: i64 = -32000;
+
+/// A failed call, answered as a JSON-RPC error object.
+struct CallError {
+    code: i64,
+    message: String,
+}
+
+impl CallError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Serves the requests read line by line from `reader`, writing each
+/// response as a line to `writer`, until `shutdown` or the end of input.
+///
+/// # Example
+/// ```
+/// use crabyknife::server::serve;
+///
+/// let input = r#"{"jsonrpc": "2.0", "id": 1, "method": "minify-xml", "params": {"text": "<a> <b/> </a>"}}"#;
+/// let mut output = Vec::new();
+/// serve(input.as_bytes(), &mut output).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{\"text\":\"<a><b/></a>\"}}\n"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns an error only if reading or writing fails. Invalid requests
+/// are answered with JSON-RPC errors.
+pub fn serve(
+    reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, shutdown) = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => (
+                handle(&request),
+                request.get("method") == Some(&json!("shutdown")),
+            ),
+            Err(err) => (
+                Some(error_response(
+                    Value::Null,
+                    CallError::new(PARSE_ERROR, format!("invalid JSON: {err}")),
+                )),
+                false,
+            ),
+        };
+        if let Some(response) = response {
+            writeln!(writer, "{response}")?;
+            writer.flush()?;
+        }
+        if shutdown {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers a single JSON-RPC request, or nothing for notifications (requests without `id`).
+pub fn handle(request: &Value) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let result = match (request.get("jsonrpc"), request.get("method")) {
+        (Some(version), Some(Value::String(method))) if version == "2.0" => {
+            call(method, request.get("params").unwrap_or(&Value::Null))
+        }
+        _ => Err(CallError::new(
+            INVALID_REQUEST,
+            "expected a JSON-RPC 2.0 request with a method",
+        )),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: CallError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": err.code, "message": err.message},
+    })
+}
+
+fn call(method: &str, params: &Value) -> Result<Value, CallError> {
+    if method == "shutdown" {
+        return Ok(Value::Null);
+    }
+
+    let text = params
+        .get("text")
+        .and_then(Value::as_str)
+        .ok_or_else(|| CallError::new(INVALID_PARAMS, "missing string parameter: text"))?;
+    let output = match method {
+        "prettify-xml" | "prettify-html" => {
+            let mut options = prettify_xml::PrettifyOptions::new();
+            if let Some(indent) = optional(params, "indent", Value::as_u64)? {
+                options = options.indent_width(indent as usize);
+            }
+            if let Some(tabs) = optional(params, "tabs", Value::as_bool)? {
+                options = options.use_tabs(tabs);
+            }
+            if method == "prettify-html" {
+                prettify_html::prettify_html_with_options(text, &options)
+            } else {
+                if let Some(sort) = optional(params, "sort_attrs", Value::as_bool)? {
+                    options = options.sort_attributes(sort);
+                }
+                prettify_xml::prettify_xml_with_options(text, &options).map_err(Into::into)
+            }
+        }
+        "minify-xml" => {
+            let strip_comments = optional(params, "strip_comments", Value::as_bool)?;
+            prettify_xml::minify_xml(text, strip_comments.unwrap_or(false)).map_err(Into::into)
+        }
+        "canonicalize-xml" => {
+            let with_comments = optional(params, "with_comments", Value::as_bool)?;
+            canonicalize_xml::canonicalize_xml(text, with_comments.unwrap_or(false))
+        }
+        "xml-to-json" => convert::xml_to_json(text, &convert::ConvertOptions::new()),
+        "xml-to-yaml" => convert::xml_to_yaml(text, &convert::ConvertOptions::new()),
+        "yaml-to-xml" => convert::yaml_to_xml(text, &convert::ConvertOptions::new()),
+        "validate-xml" => {
+            return Ok(match prettify_xml::validate(text) {
+                Ok(()) => json!({"valid": true}),
+                Err(err) => json!({
+                    "valid": false,
+                    "error": err.to_string(),
+                    "line": err.line,
+                    "column": err.column,
+                }),
+            });
+        }
+        _ => {
+            return Err(CallError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method ({method})"),
+            ))
+        }
+    };
+
+    output
+        .map(|text| json!({ "text": text }))
+        .map_err(|err| CallError::new(TOOL_ERROR, err.to_string()))
+}
+
+/// Reads the optional parameter `name` with `as_type`, failing if it has another type.
+fn optional<T>(
+    params: &Value,
+    name: &str,
+    as_type: impl Fn(&Value) -> Option<T>,
+) -> Result<Option<T>, CallError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => as_type(value)
+            .map(Some)
+            .ok_or_else(|| CallError::new(INVALID_PARAMS, format!("invalid parameter: {name}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params})
+    }
+
+    #[test]
+    fn test_methods() {
+        let result = |method, params| handle(&request(method, params)).unwrap()["result"].clone();
+        assert_eq!(
+            result(
+                "prettify-xml",
+                json!({"text": "<a b='1' a='2'><c/></a>", "indent": 1, "sort_attrs": true})
+            ),
+            json!({"text": "<a a=\"2\" b=\"1\">\n <c />\n</a>"})
+        );
+        assert_eq!(
+            result("xml-to-yaml", json!({"text": "<a><b>x</b></a>"})),
+            json!({"text": "a:\n  b: x\n"})
+        );
+        assert_eq!(
+            result("validate-xml", json!({"text": "<a><b></a>"}))["valid"],
+            json!(false)
+        );
+        assert_eq!(
+            result("validate-xml", json!({"text": "<a/>"})),
+            json!({"valid": true})
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let code = |request: Value| handle(&request).unwrap()["error"]["code"].clone();
+        assert_eq!(
+            code(request("format-rust", json!({"text": ""}))),
+            json!(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            code(request("minify-xml", json!({}))),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(request(
+                "minify-xml",
+                json!({"text": "<a/>", "strip_comments": "yes"})
+            )),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(request("xml-to-json", json!({"text": "<a>"}))),
+            json!(TOOL_ERROR)
+        );
+        assert_eq!(
+            code(json!({"id": 1, "method": "minify-xml"})),
+            json!(INVALID_REQUEST)
+        );
+
+        // Notifications are not answered.
+        assert_eq!(
+            handle(&json!({"jsonrpc": "2.0", "method": "minify-xml"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let input = concat!(
+            "not json\n",
+            "\n",
+            "{\"jsonrpc\": \"2.0\", \"id\": \"a\", \"method\": \"shutdown\"}\n",
+            "{\"jsonrpc\": \"2.0\", \"id\": \"b\", \"method\": \"minify-xml\", \"params\": {\"text\": \"<a/>\"}}\n",
+        );
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(
+            responses[1],
+            json!({"jsonrpc": "2.0", "id": "a", "result": null})
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! Degrades the traffic of a network interface, adding delay, jitter,
+//! packet loss or a bandwidth limit, to test apps under poor network
+//! conditions.
+//!
+//! The platform's traffic control does the work: a netem queueing
+//! discipline set with `tc` on Linux, and a dummynet pipe of `dnctl` fed by
+//! a `pf` anchor on macOS. Both need root. Shaping applies to the packets
+//! the interface sends, so on the loopback interface to both directions.
+//!
+//! [`reset`] only removes what [`apply`] set: on Linux it leaves a root
+//! queueing discipline other than netem alone, and [`apply`] refuses to
+//! replace one other than the kernel defaults, which could not be restored.
+
+use std::time::Duration;
+
+/// The dummynet pipe and the pf anchor used on macOS.
+const PIPE: &str = "1";
+const ANCHOR: &str = "crabyknife";
+
+/// Where the token of the `pfctl -E` reference [`apply`] holds on pf is
+/// kept on macOS, for [`reset`] to release it.
+const PF_TOKEN_FILE: &str = "/var/run/crabyknife-shape.pf-token";
+
+/// The root queueing disciplines the kernel sets up by itself, which
+/// removing the netem one brings back.
+const DEFAULT_QDISCS: &[&str] = &["noqueue", "pfifo_fast", "fq_codel", "fq", "mq", "pfifo"];
+
+/// The platforms whose traffic control is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// `tc` and netem.
+    Linux,
+    /// `dnctl` and `pfctl`.
+    MacOs,
+}
+
+impl Platform {
+    /// The platform running, `None` when unsupported.
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "linux") {
+            Some(Self::Linux)
+        } else if cfg!(target_os = "macos") {
+            Some(Self::MacOs)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the traffic of an interface is degraded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Shaping {
+    delay: Option<Duration>,
+    jitter: Option<Duration>,
+    loss: Option<f64>,
+    rate: Option<u64>,
+}
+
+impl Shaping {
+    /// Creates a shaping changing nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delays every packet sent by `delay`.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Varies the delay by up to `jitter` either way (Linux only).
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Drops the given percentage of the packets sent, from 0 to 100.
+    pub fn loss(mut self, percent: f64) -> Self {
+        self.loss = Some(percent);
+        self
+    }
+
+    /// Limits the bandwidth to `bits_per_second`.
+    pub fn rate(mut self, bits_per_second: u64) -> Self {
+        self.rate = Some(bits_per_second);
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.delay.is_none() && self.jitter.is_none() && self.loss.is_none() && self.rate.is_none()
+    }
+}
+
+/// A command run to shape the traffic, shown as is by `--dry-run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub program: &'static str,
+    pub args: Vec<String>,
+    /// What the command reads from stdin, if anything.
+    pub stdin: Option<String>,
+}
+
+impl Step {
+    fn new(program: &'static str, args: &[&str]) -> Self {
+        Self {
+            program,
+            args: args.iter().map(ToString::to_string).collect(),
+            stdin: None,
+        }
+    }
+
+    fn stdin(mut self, stdin: String) -> Self {
+        self.stdin = Some(stdin);
+        self
+    }
+
+    /// Runs the command, returning its stdout.
+    fn run(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let output = self.output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Runs the command, returning its output when it succeeds.
+    fn output(&self) -> Result<std::process::Output, Box<dyn std::error::Error>> {
+        let mut command = std::process::Command::new(self.program);
+        command
+            .args(&self.args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = command
+            .spawn()
+            .map_err(|err| format!("failed to run {}: {err}", self.program))?;
+        {
+            use std::io::Write;
+            // Dropping stdin closes it, ending the input.
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            if let Some(input) = &self.stdin {
+                stdin
+                    .write_all(input.as_bytes())
+                    .map_err(|err| format!("failed to write to {}: {err}", self.program))?;
+            }
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|err| format!("failed to run {}: {err}", self.program))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("`{self}` failed: {}", stderr.trim()).into());
+        }
+        Ok(output)
+    }
+}
+
+impl std::fmt::Display for Step {
+    /// Writes the command as a shell would run it, stdin as a here-document.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"') {
+                write!(f, " '{arg}'")?;
+            } else {
+                write!(f, " {arg}")?;
+            }
+        }
+        if let Some(stdin) = &self.stdin {
+            write!(f, " <<'EOF'\n{stdin}EOF")?;
+        }
+        Ok(())
+    }
+}
+
+/// The commands shaping the traffic `interface` sends on `platform`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use crabyknife::shape::{apply_steps, Platform, Shaping};
+///
+/// let shaping = Shaping::new().delay(Duration::from_millis(100)).loss(1.0);
+/// let steps = apply_steps(Platform::Linux, "lo", &shaping).unwrap();
+/// assert_eq!(
+///     steps[0].to_string(),
+///     "tc qdisc replace dev lo root netem delay 100ms loss 1%"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns an error when `shaping` changes nothing, or asks for what the
+/// platform cannot do, e.g. jitter on macOS.
+pub fn apply_steps(
+    platform: Platform,
+    interface: &str,
+    shaping: &Shaping,
+) -> Result<Vec<Step>, Box<dyn std::error::Error>> {
+    if shaping.is_empty() {
+        return Err("nothing to shape, expected a delay, a loss or a rate".into());
+    }
+    if let Some(loss) = shaping.loss {
+        if !(0.0..=100.0).contains(&loss) {
+            return Err(format!("invalid loss ({loss}%), expected 0 to 100%").into());
+        }
+    }
+    if shaping.jitter.is_some() && shaping.delay.is_none() {
+        return Err("jitter needs a delay to vary".into());
+    }
+    let ms = |time: Duration| format!("{}", time.as_secs_f64() * 1000.0);
+
+    match platform {
+        Platform::Linux => {
+            let mut args = vec!["qdisc", "replace", "dev", interface, "root", "netem"];
+            let delay = shaping.delay.map(|delay| format!("{}ms", ms(delay)));
+            let jitter = shaping.jitter.map(|jitter| format!("{}ms", ms(jitter)));
+            let loss = shaping.loss.map(|loss| format!("{loss}%"));
+            let rate = shaping.rate.map(|rate| format!("{rate}bit"));
+            for (name, value) in [
+                ("delay", &delay),
+                ("", &jitter),
+                ("loss", &loss),
+                ("rate", &rate),
+            ] {
+                if let Some(value) = value {
+                    // The jitter follows the delay without a keyword.
+                    if !name.is_empty() {
+                        args.push(name);
+                    }
+                    args.push(value);
+                }
+            }
+            Ok(vec![Step::new("tc", &args)])
+        }
+        Platform::MacOs => {
+            if shaping.jitter.is_some() {
+                return Err("jitter is only supported on Linux".into());
+            }
+            let mut args = vec!["pipe".to_string(), PIPE.to_string(), "config".to_string()];
+            if let Some(delay) = shaping.delay {
+                // dummynet delays by whole milliseconds.
+                args.extend(["delay".to_string(), delay.as_millis().to_string()]);
+            }
+            if let Some(loss) = shaping.loss {
+                args.extend(["plr".to_string(), (loss / 100.0).to_string()]);
+            }
+            if let Some(rate) = shaping.rate {
+                args.extend(["bw".to_string(), format!("{rate}bit/s")]);
+            }
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            Ok(vec![
+                Step::new("dnctl", &args),
+                // The anchor is only evaluated once the main ruleset refers to it.
+                Step::new("pfctl", &["-f", "-"]).stdin(format!(
+                    "include \"/etc/pf.conf\"\ndummynet-anchor \"{ANCHOR}\"\nanchor \"{ANCHOR}\"\n"
+                )),
+                Step::new("pfctl", &["-a", ANCHOR, "-f", "-"]).stdin(format!(
+                    "dummynet out quick on {interface} all pipe {PIPE}\n"
+                )),
+                Step::new("pfctl", &["-E"]),
+            ])
+        }
+    }
+}
+
+/// The commands removing the shaping of `interface` on `platform`. On
+/// macOS, `pf_token` is the token of the reference on pf to release, see
+/// [`pf_token`].
+pub fn reset_steps(platform: Platform, interface: &str, pf_token: Option<&str>) -> Vec<Step> {
+    match platform {
+        Platform::Linux => vec![Step::new("tc", &["qdisc", "del", "dev", interface, "root"])],
+        Platform::MacOs => {
+            let mut steps = vec![
+                Step::new("pfctl", &["-a", ANCHOR, "-F", "all"]),
+                Step::new("dnctl", &["pipe", "delete", PIPE]),
+                Step::new("pfctl", &["-f", "/etc/pf.conf"]),
+            ];
+            // pf stays enabled as long as another reference holds it.
+            if let Some(token) = pf_token {
+                steps.push(Step::new("pfctl", &["-X", token]));
+            }
+            steps
+        }
+    }
+}
+
+/// The token of the reference on pf that shaping holds on macOS, saved by
+/// [`apply`] from the output of `pfctl -E`.
+pub fn pf_token() -> Option<String> {
+    let token = std::fs::read_to_string(PF_TOKEN_FILE).ok()?;
+    Some(token.trim().to_string()).filter(|token| !token.is_empty())
+}
+
+/// The token in the output of `pfctl -E`, e.g. `Token : 1234567890`.
+fn parse_pf_token(output: &str) -> Option<&str> {
+    output.lines().find_map(|line| {
+        let (name, token) = line.split_once(':')?;
+        (name.trim() == "Token").then(|| token.trim())
+    })
+}
+
+/// Shapes the traffic `interface` sends, replacing its previous shaping.
+///
+/// # Errors
+///
+/// Returns an error on unsupported platforms, for the shapings
+/// [`apply_steps`] rejects, when the interface has a root queueing
+/// discipline of its own (Linux), and when a command fails, e.g. without
+/// root.
+pub fn apply(interface: &str, shaping: &Shaping) -> Result<(), Box<dyn std::error::Error>> {
+    let platform = Platform::current().ok_or("shaping is only supported on Linux and macOS")?;
+    let steps = apply_steps(platform, interface, shaping)?;
+    if platform == Platform::Linux {
+        match root_qdisc(interface)? {
+            Some(kind) if kind != "netem" && !DEFAULT_QDISCS.contains(&kind.as_str()) => {
+                return Err(format!(
+                    "{interface} has a {kind} root queueing discipline, which shaping would replace for good; remove it first"
+                )
+                .into());
+            }
+            _ => {}
+        }
+    }
+    for step in &steps {
+        let output = step.output().map_err(|err| -> Box<dyn std::error::Error> {
+            if err.to_string().contains("qdisc kind is unknown") {
+                format!("{err} (the kernel lacks netem, try `modprobe sch_netem`)").into()
+            } else {
+                err
+            }
+        })?;
+        if step.args == ["-E"] {
+            // pfctl tells the token of the reference on stderr.
+            let output = String::from_utf8_lossy(&output.stderr);
+            let token = parse_pf_token(&output)
+                .ok_or_else(|| format!("no token in the output of `{step}`: {}", output.trim()))?;
+            // Shaping again keeps a single reference.
+            if let Some(previous) = pf_token() {
+                Step::new("pfctl", &["-X", &previous]).run()?;
+            }
+            std::fs::write(PF_TOKEN_FILE, token)
+                .map_err(|err| format!("failed to save the pf token to {PF_TOKEN_FILE}: {err}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the shaping of `interface`, returning whether there was one.
+///
+/// # Errors
+///
+/// Returns an error on unsupported platforms, and when a command fails,
+/// e.g. without root.
+pub fn reset(interface: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let platform = Platform::current().ok_or("shaping is only supported on Linux and macOS")?;
+    match platform {
+        Platform::Linux => {
+            // Another root queueing discipline is not ours to remove.
+            if root_qdisc(interface)?.as_deref() != Some("netem") {
+                return Ok(false);
+            }
+        }
+        Platform::MacOs => {
+            let rules = Step::new("pfctl", &["-a", ANCHOR, "-s", "dummynet"]).run()?;
+            if rules.trim().is_empty() {
+                return Ok(false);
+            }
+        }
+    }
+    let pf_token = pf_token();
+    for step in reset_steps(platform, interface, pf_token.as_deref()) {
+        step.run()?;
+    }
+    if pf_token.is_some() {
+        let _ = std::fs::remove_file(PF_TOKEN_FILE);
+    }
+    Ok(true)
+}
+
+/// Describes the shaping of `interface`, `None` when it has none.
+///
+/// # Errors
+///
+/// Returns an error on unsupported platforms, and when a command fails.
+pub fn show(interface: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let platform = Platform::current().ok_or("shaping is only supported on Linux and macOS")?;
+    match platform {
+        Platform::Linux => {
+            let qdiscs = Step::new("tc", &["qdisc", "show", "dev", interface, "root"]).run()?;
+            Ok(qdiscs
+                .lines()
+                .find(|line| qdisc_kind(line) == Some("netem"))
+                .map(|line| line.trim().to_string()))
+        }
+        Platform::MacOs => {
+            let rules = Step::new("pfctl", &["-a", ANCHOR, "-s", "dummynet"]).run()?;
+            if !rules.contains(&format!("on {interface} ")) {
+                return Ok(None);
+            }
+            let pipe = Step::new("dnctl", &["pipe", "show", PIPE]).run()?;
+            Ok(Some(pipe.trim().to_string()))
+        }
+    }
+}
+
+/// The kind of the root queueing discipline of `interface`, e.g.
+/// `fq_codel`, from `tc`.
+fn root_qdisc(interface: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let qdiscs = Step::new("tc", &["qdisc", "show", "dev", interface, "root"]).run()?;
+    Ok(qdiscs.lines().find_map(qdisc_kind).map(str::to_string))
+}
+
+/// The kind of the queueing discipline of a line of `tc qdisc show`, such
+/// as `qdisc netem 8001: root refcnt 2 limit 1000 delay 100ms`.
+fn qdisc_kind(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    (words.next() == Some("qdisc"))
+        .then(|| words.next())
+        .flatten()
+}
+
+/// Parses a time such as `100ms`, `1.5s` or `250us`. A number without unit
+/// is in milliseconds, like `tc` takes them.
+///
+/// # Errors
+///
+/// Returns an error for negative or malformed times and unknown units.
+pub fn parse_time(text: &str) -> Result<Duration, Box<dyn std::error::Error>> {
+    let (number, unit) = split_number(text);
+    let scale = match unit.to_ascii_lowercase().as_str() {
+        "us" | "µs" => 1e-6,
+        "" | "ms" => 1e-3,
+        "s" => 1.0,
+        _ => return Err(format!("invalid time ({text}): unknown unit {unit}").into()),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|err| format!("invalid time ({text}): {err}"))?;
+    Duration::try_from_secs_f64(number * scale)
+        .map_err(|err| format!("invalid time ({text}): {err}").into())
+}
+
+/// Parses a percentage such as `1%` or `0.5`, from 0 to 100.
+///
+/// # Errors
+///
+/// Returns an error for malformed percentages and ones out of range.
+pub fn parse_percent(text: &str) -> Result<f64, Box<dyn std::error::Error>> {
+    let number = text.trim().strip_suffix('%').unwrap_or(text.trim());
+    let percent: f64 = number
+        .parse()
+        .map_err(|err| format!("invalid percentage ({text}): {err}"))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("invalid percentage ({text}), expected 0 to 100%").into());
+    }
+    Ok(percent)
+}
+
+/// Parses a bandwidth such as `1mbit`, `512kbit` or `2.5gbit` into bits
+/// per second. A number without unit is in bits per second.
+///
+/// # Errors
+///
+/// Returns an error for malformed rates, unknown units and a rate of zero.
+pub fn parse_rate(text: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let (number, unit) = split_number(text);
+    let unit = unit.to_ascii_lowercase();
+    let multiplier = match unit.strip_suffix("/s").unwrap_or(&unit) {
+        "" | "bit" => 1e0,
+        "kbit" => 1e3,
+        "mbit" => 1e6,
+        "gbit" => 1e9,
+        _ => {
+            return Err(format!(
+                "invalid rate ({text}): unknown unit {unit}, expected e.g. 512kbit or 10mbit"
+            )
+            .into())
+        }
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|err| format!("invalid rate ({text}): {err}"))?;
+    let rate = (number * multiplier).round();
+    if !(1.0..=u64::MAX as f64).contains(&rate) {
+        return Err(format!("invalid rate ({text})").into());
+    }
+    Ok(rate as u64)
+}
+
+/// Splits `text` into its leading number and the unit after it.
+fn split_number(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    (number, unit.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_steps() {
+        let ms = Duration::from_millis;
+        let shaping = Shaping::new()
+            .delay(ms(100))
+            .jitter(ms(20))
+            .loss(0.5)
+            .rate(2_000_000);
+        let linux = apply_steps(Platform::Linux, "eth0", &shaping).unwrap();
+        assert_eq!(
+            linux[0].to_string(),
+            "tc qdisc replace dev eth0 root netem delay 100ms 20ms loss 0.5% rate 2000000bit"
+        );
+
+        assert!(apply_steps(Platform::MacOs, "lo0", &shaping).is_err());
+        let shaping = Shaping::new().delay(ms(100)).loss(1.0);
+        let macos = apply_steps(Platform::MacOs, "lo0", &shaping).unwrap();
+        assert_eq!(
+            macos[0].to_string(),
+            "dnctl pipe 1 config delay 100 plr 0.01"
+        );
+        assert_eq!(
+            macos[2].to_string(),
+            "pfctl -a crabyknife -f - <<'EOF'\ndummynet out quick on lo0 all pipe 1\nEOF"
+        );
+
+        assert!(apply_steps(Platform::Linux, "lo", &Shaping::new()).is_err());
+        assert!(apply_steps(Platform::Linux, "lo", &Shaping::new().jitter(ms(5))).is_err());
+        assert!(apply_steps(Platform::Linux, "lo", &Shaping::new().loss(101.0)).is_err());
+        assert_eq!(
+            reset_steps(Platform::Linux, "lo", None)[0].to_string(),
+            "tc qdisc del dev lo root"
+        );
+        let macos = reset_steps(Platform::MacOs, "lo0", Some("1234"));
+        assert_eq!(macos[3].to_string(), "pfctl -X 1234");
+        assert_eq!(reset_steps(Platform::MacOs, "lo0", None).len(), 3);
+    }
+
+    #[test]
+    fn test_parse_pf_token() {
+        let output = "No ALTQ support in kernel\nALTQ related functions disabled\npf enabled\nToken : 12345678901234567890\n";
+        assert_eq!(parse_pf_token(output), Some("12345678901234567890"));
+        assert_eq!(parse_pf_token("pf enabled\n"), None);
+    }
+
+    #[test]
+    fn test_qdisc_kind() {
+        let line = "qdisc netem 8001: root refcnt 2 limit 1000 delay 100ms";
+        assert_eq!(qdisc_kind(line), Some("netem"));
+        assert_eq!(
+            qdisc_kind("qdisc noqueue 0: root refcnt 2"),
+            Some("noqueue")
+        );
+        assert_eq!(qdisc_kind(""), None);
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse_time("100ms").unwrap(), Duration::from_millis(100));
+        assert_eq!(parse_time("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_time("250us").unwrap(), Duration::from_micros(250));
+        assert_eq!(parse_time("40").unwrap(), Duration::from_millis(40));
+        assert!(parse_time("1h").is_err());
+        assert!(parse_time("-5ms").is_err());
+
+        assert_eq!(parse_percent("1%").unwrap(), 1.0);
+        assert_eq!(parse_percent("0.5").unwrap(), 0.5);
+        assert!(parse_percent("150%").is_err());
+
+        assert_eq!(parse_rate("1mbit").unwrap(), 1_000_000);
+        assert_eq!(parse_rate("512kbit/s").unwrap(), 512_000);
+        assert_eq!(parse_rate("2.5Gbit").unwrap(), 2_500_000_000);
+        assert!(parse_rate("10MB").is_err());
+        assert!(parse_rate("0").is_err());
+    }
+}
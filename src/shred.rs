@@ -0,0 +1,270 @@
+//! Overwriting files before deleting them, so their content cannot be read
+//! back from the disk with recovery tools.
+//!
+//! Overwriting in place only destroys the old content where the storage
+//! writes new data over the same blocks. SSDs (wear leveling),
+//! copy-on-write file systems (btrfs, ZFS, APFS), data journaling,
+//! snapshots and backups all keep old copies elsewhere, which shredding
+//! cannot reach. There, only full-disk encryption protects deleted data.
+
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The size of the blocks written at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Options of [`shred`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShredOptions {
+    passes: usize,
+    recursive: bool,
+    unlink: bool,
+}
+
+impl Default for ShredOptions {
+    fn default() -> Self {
+        Self {
+            passes: 3,
+            recursive: false,
+            unlink: false,
+        }
+    }
+}
+
+impl ShredOptions {
+    /// Creates the default options: 3 passes over a single file, which is kept.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many times the content is overwritten with random data.
+    pub fn passes(mut self, passes: usize) -> Self {
+        self.passes = passes;
+        self
+    }
+
+    /// Sets whether directories are shredded with everything in them.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sets whether shredded files are then renamed to random names, to
+    /// hide the original names, and deleted, along with their directories
+    /// when recursive. Symlinks and special files are deleted without
+    /// touching their targets or devices.
+    pub fn unlink(mut self, unlink: bool) -> Self {
+        self.unlink = unlink;
+        self
+    }
+}
+
+/// Overwrites the content of the file at `path`, or of every file below
+/// it when recursive, and returns the shredded files.
+///
+/// Each pass writes random data over the whole file and syncs it to the
+/// disk. Only regular files are overwritten: symlinks, FIFOs, sockets and
+/// devices are skipped. See the [module documentation](self) for where
+/// this is not enough.
+///
+/// # Errors
+///
+/// Returns an error for directories when not recursive, no passes, and if a
+/// file cannot be overwritten or deleted. Files shredded before the error
+/// stay shredded.
+pub fn shred(
+    path: &Path,
+    options: &ShredOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if options.passes == 0 {
+        return Err("at least one pass is needed to shred a file".into());
+    }
+
+    let mut shredded = Vec::new();
+    shred_entry(path, options, &mut Random::new(), &mut shredded)?;
+    Ok(shredded)
+}
+
+fn shred_entry(
+    path: &Path,
+    options: &ShredOptions,
+    random: &mut Random,
+    shredded: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let metadata = std::fs::symlink_metadata(path)
+        .map_err(|err| format!("failed to read metadata of {}: {err}", path.display()))?;
+
+    // Writing to a FIFO blocks and to a device destroys it, so only regular
+    // files are overwritten.
+    if !metadata.is_file() && !metadata.is_dir() {
+        if options.unlink {
+            std::fs::remove_file(path)
+                .map_err(|err| format!("failed to delete {}: {err}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    if metadata.is_dir() {
+        if !options.recursive {
+            return Err(format!("{} is a directory, shred it with -r", path.display()).into());
+        }
+        let mut entries = std::fs::read_dir(path)
+            .map_err(|err| format!("failed to read directory {}: {err}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            shred_entry(&entry, options, random, shredded)?;
+        }
+        if options.unlink {
+            let renamed = rename_randomly(path, random)?;
+            std::fs::remove_dir(&renamed)
+                .map_err(|err| format!("failed to delete {}: {err}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    overwrite(path, metadata.len(), options.passes, random)
+        .map_err(|err| format!("failed to overwrite {}: {err}", path.display()))?;
+    if options.unlink {
+        let renamed = rename_randomly(path, random)?;
+        std::fs::remove_file(&renamed)
+            .map_err(|err| format!("failed to delete {}: {err}", path.display()))?;
+    }
+    shredded.push(path.to_path_buf());
+    Ok(())
+}
+
+/// Overwrites the `len` bytes of the file at `path` with random data, `passes` times.
+fn overwrite(path: &Path, len: u64, passes: usize, random: &mut Random) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let mut buf = vec![0u8; BLOCK_SIZE];
+
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(BLOCK_SIZE as u64) as usize;
+            random.fill(&mut buf[..n]);
+            file.write_all(&buf[..n])?;
+            remaining -= n as u64;
+        }
+        // Every pass must reach the disk, not only the page cache.
+        file.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Renames `path` to a random name in the same directory, so the original
+/// name does not stay behind in the directory, and returns the new path.
+fn rename_randomly(
+    path: &Path,
+    random: &mut Random,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut name = [0u8; 8];
+    random.fill(&mut name);
+    let name: String = name.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    let renamed = path.with_file_name(name);
+    std::fs::rename(path, &renamed)
+        .map_err(|err| format!("failed to rename {}: {err}", path.display()))?;
+    Ok(renamed)
+}
+
+/// A fast pseudo-random generator (xorshift64*) seeded from the system's
+/// random source, to overwrite files with unpredictable data.
+struct Random(u64);
+
+impl Random {
+    fn new() -> Self {
+        let seed = uuid::Uuid::new_v4().as_u64_pair().0;
+        // xorshift would stay stuck in the all-zero state.
+        Self(seed | 1)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_shred_file() {
+        let dir = temp_dir("shred-file");
+        let path = dir.join("secret.txt");
+        let secret = "password=hunter2\n".repeat(10_000);
+        std::fs::write(&path, &secret).unwrap();
+
+        let shredded = shred(&path, &ShredOptions::new().passes(2)).unwrap();
+        assert_eq!(shredded, [dir.join("secret.txt")]);
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(content.len(), secret.len());
+        assert!(!content.windows(8).any(|window| window == b"hunter2\n"));
+
+        assert!(shred(&path, &ShredOptions::new().passes(0)).is_err());
+        assert!(shred(&dir, &ShredOptions::new()).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_shred_recursive_and_unlink() {
+        let dir = temp_dir("shred-recursive");
+        let tree = dir.join("tree");
+        std::fs::create_dir_all(tree.join("sub")).unwrap();
+        std::fs::write(tree.join("a"), "a").unwrap();
+        std::fs::write(tree.join("sub/b"), "b").unwrap();
+        std::fs::write(dir.join("outside"), "kept").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../outside", tree.join("link")).unwrap();
+
+        let options = ShredOptions::new().recursive(true).unlink(true);
+        assert_eq!(
+            shred(&tree, &options).unwrap(),
+            [tree.join("a"), tree.join("sub/b")]
+        );
+        assert!(!tree.exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("outside")).unwrap(),
+            "kept"
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shred_skips_fifo() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = temp_dir("shred-fifo");
+        let fifo = dir.join("fifo");
+        let name = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0);
+        std::fs::write(dir.join("a"), "a").unwrap();
+
+        // Opening the FIFO for writing would block without a reader.
+        assert_eq!(
+            shred(&fifo, &ShredOptions::new()).unwrap(),
+            [] as [PathBuf; 0]
+        );
+        let options = ShredOptions::new().recursive(true);
+        assert_eq!(shred(&dir, &options).unwrap(), [dir.join("a")]);
+        assert!(fifo.exists());
+
+        shred(&dir, &options.unlink(true)).unwrap();
+        assert!(!dir.exists());
+    }
+}
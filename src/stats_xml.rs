@@ -0,0 +1,226 @@
+//! Statistics about the structure of an XML document.
+//!
+//! The document is streamed, so even large feeds can be sized up without
+//! holding them in memory: only counters are kept while reading.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    name::ResolveResult,
+    NsReader,
+};
+
+/// How a namespace is used in a document, see [`Stats::namespaces`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamespaceUsage {
+    /// The prefixes the namespace is declared with, `""` for the default namespace.
+    pub prefixes: Vec<String>,
+    pub elements: usize,
+    pub attributes: usize,
+}
+
+/// Statistics about an XML document, see [`stats_xml`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub elements: usize,
+    /// Element counts by qualified name.
+    pub element_counts: BTreeMap<String, usize>,
+    /// The nesting depth of the deepest element, 1 for the root.
+    pub max_depth: usize,
+    /// Attributes, not counting namespace declarations.
+    pub attributes: usize,
+    /// Attribute counts by qualified name.
+    pub attribute_counts: BTreeMap<String, usize>,
+    /// The bytes of unescaped text and CDATA, ignoring whitespace between tags.
+    pub text_bytes: u64,
+    /// Namespace usage by namespace URI.
+    pub namespaces: BTreeMap<String, NamespaceUsage>,
+}
+
+/// Streams the document from `reader` and collects its [`Stats`].
+///
+/// # Example
+/// ```
+/// use crabyknife::stats_xml::stats_xml;
+///
+/// let xml = r#"<feed><entry id="1">a &amp; b</entry><entry id="2"/></feed>"#;
+/// let stats = stats_xml(xml.as_bytes()).unwrap();
+/// assert_eq!(stats.elements, 3);
+/// assert_eq!(stats.element_counts["entry"], 2);
+/// assert_eq!(stats.max_depth, 2);
+/// assert_eq!(stats.attributes, 2);
+/// assert_eq!(stats.text_bytes, 5);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the document is not well-formed or uses an
+/// undeclared namespace prefix.
+pub fn stats_xml<R: BufRead>(reader: R) -> Result<Stats, Box<dyn std::error::Error>> {
+    let mut reader = NsReader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+
+    let mut stats = Stats::default();
+    let mut depth = 0;
+    let mut buf = Vec::new();
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event_into(&mut buf)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+                if matches!(event, Event::Empty(_)) {
+                    depth -= 1;
+                }
+
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                stats.elements += 1;
+                *stats.element_counts.entry(name.clone()).or_default() += 1;
+                record_namespace(&mut stats, namespace, &name, true)?;
+
+                count_attributes(&mut stats, &reader, e)?;
+            }
+            Event::End(_) => depth -= 1,
+            Event::Text(e) => stats.text_bytes += e.unescape()?.len() as u64,
+            Event::CData(e) => stats.text_bytes += e.len() as u64,
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(stats)
+}
+
+/// Counts the attributes of `e` and records its namespace declarations.
+fn count_attributes<R>(
+    stats: &mut Stats,
+    reader: &NsReader<R>,
+    e: &BytesStart,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for attribute in e.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+
+        let prefix = match key.strip_prefix("xmlns") {
+            Some("") => Some(""),
+            Some(rest) => rest.strip_prefix(':'),
+            None => None,
+        };
+        if let Some(prefix) = prefix {
+            let uri = attribute.unescape_value()?.into_owned();
+            let usage = stats.namespaces.entry(uri).or_default();
+            if !usage.prefixes.iter().any(|known| known == prefix) {
+                usage.prefixes.push(prefix.to_string());
+            }
+            continue;
+        }
+
+        stats.attributes += 1;
+        *stats.attribute_counts.entry(key.clone()).or_default() += 1;
+        // Unprefixed attributes are in no namespace, unlike elements.
+        let (namespace, _) = reader.resolve_attribute(attribute.key);
+        record_namespace(stats, namespace, &key, false)?;
+    }
+
+    Ok(())
+}
+
+/// Records an element or attribute in the namespace `name` resolved to, if any.
+fn record_namespace(
+    stats: &mut Stats,
+    namespace: ResolveResult,
+    name: &str,
+    element: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match namespace {
+        ResolveResult::Bound(uri) => {
+            let usage = stats
+                .namespaces
+                .entry(String::from_utf8_lossy(uri.as_ref()).into_owned())
+                .or_default();
+            if element {
+                usage.elements += 1;
+            } else {
+                usage.attributes += 1;
+            }
+            Ok(())
+        }
+        ResolveResult::Unbound => Ok(()),
+        ResolveResult::Unknown(prefix) => Err(format!(
+            "undeclared namespace prefix {} in {name}",
+            String::from_utf8_lossy(&prefix)
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_depth() {
+        let xml = r#"<?xml version="1.0"?>
+<root a="1">
+  <item b="2" c="3"><name>x</name></item>
+  <item b="4"><![CDATA[<raw>]]></item>
+  <!-- comment -->
+</root>"#;
+        let stats = stats_xml(xml.as_bytes()).unwrap();
+        assert_eq!(stats.elements, 4);
+        assert_eq!(
+            stats.element_counts,
+            BTreeMap::from([
+                ("item".to_string(), 2),
+                ("name".to_string(), 1),
+                ("root".to_string(), 1),
+            ])
+        );
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.attributes, 4);
+        assert_eq!(stats.attribute_counts["b"], 2);
+        assert_eq!(stats.text_bytes, 6);
+        assert!(stats.namespaces.is_empty());
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let xml = r#"<feed xmlns="urn:atom" xmlns:m="urn:media">
+  <m:thumb m:url="x" size="1"/>
+  <entry xmlns:media="urn:media"><media:title/></entry>
+</feed>"#;
+        let stats = stats_xml(xml.as_bytes()).unwrap();
+        assert_eq!(stats.attributes, 2);
+        assert_eq!(
+            stats.namespaces,
+            BTreeMap::from([
+                (
+                    "urn:atom".to_string(),
+                    NamespaceUsage {
+                        prefixes: vec!["".to_string()],
+                        elements: 2,
+                        attributes: 0,
+                    }
+                ),
+                (
+                    "urn:media".to_string(),
+                    NamespaceUsage {
+                        prefixes: vec!["m".to_string(), "media".to_string()],
+                        elements: 2,
+                        attributes: 1,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(stats_xml("<a><b></a>".as_bytes()).is_err());
+        assert!(stats_xml("<x:a/>".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Extraction of the text content of XML documents.
+//!
+//! Each text or CDATA node is written trimmed on its own line and blank
+//! ones are skipped, which makes large exports easy to grep. A
+//! [`PathFilter`] restricts the output to the text inside matching elements.
+
+use std::io::{BufRead, Write};
+
+use quick_xml::{events::Event, Reader};
+
+/// A simple path selecting elements, e.g. `/feed/entry/title` or `entry/title`.
+///
+/// An absolute path (starting with `/`) matches from the root, a relative
+/// one matches elements at any depth whose ancestors end with its segments.
+/// A segment is an element name, matched against the qualified name or,
+/// when it has no prefix, the local name, or `*` for any element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathFilter {
+    absolute: bool,
+    segments: Vec<String>,
+}
+
+impl PathFilter {
+    /// Parses a path filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for empty paths or empty segments, e.g. `a//b`.
+    pub fn parse(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (absolute, rest) = match path.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, path),
+        };
+        let segments: Vec<String> = rest.split('/').map(str::to_string).collect();
+        if segments.iter().any(String::is_empty) {
+            return Err(format!("invalid path filter ({path})").into());
+        }
+
+        Ok(Self { absolute, segments })
+    }
+
+    /// Whether the element whose ancestors and own name are `names`,
+    /// root first, is selected.
+    fn matches(&self, names: &[String]) -> bool {
+        if names.len() < self.segments.len()
+            || (self.absolute && names.len() != self.segments.len())
+        {
+            return false;
+        }
+
+        let names = &names[names.len() - self.segments.len()..];
+        self.segments.iter().zip(names).all(|(segment, name)| {
+            segment == "*"
+                || segment == name
+                || (!segment.contains(':')
+                    && name
+                        .split_once(':')
+                        .is_some_and(|(_, local)| local == segment))
+        })
+    }
+}
+
+/// Writes the text content of the document read from `reader` to `writer`.
+///
+/// # Example
+/// ```
+/// use crabyknife::strip_xml::{strip_xml, PathFilter};
+///
+/// let xml = "<feed><title>News</title><entry><title>First</title><p>Body</p></entry></feed>";
+///
+/// let mut output = Vec::new();
+/// strip_xml(xml.as_bytes(), None, &mut output).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "News\nFirst\nBody\n");
+///
+/// let filter = PathFilter::parse("entry/title").unwrap();
+/// let mut output = Vec::new();
+/// strip_xml(xml.as_bytes(), Some(&filter), &mut output).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "First\n");
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the document is not well-formed or writing fails.
+pub fn strip_xml<R: BufRead, W: Write>(
+    reader: R,
+    filter: Option<&PathFilter>,
+    mut writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+
+    let mut names: Vec<String> = Vec::new();
+    // The depth of the outermost selected element being read, if any.
+    let mut selected_at: Option<usize> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                names.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                if selected_at.is_none() && filter.is_some_and(|filter| filter.matches(&names)) {
+                    selected_at = Some(names.len());
+                }
+            }
+            Event::End(_) => {
+                if selected_at == Some(names.len()) {
+                    selected_at = None;
+                }
+                names.pop();
+            }
+            Event::Text(e) if filter.is_none() || selected_at.is_some() => {
+                write_text(&mut writer, &e.unescape()?)?
+            }
+            Event::CData(e) if filter.is_none() || selected_at.is_some() => {
+                write_text(&mut writer, &e.decode()?)?
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_text<W: Write>(writer: &mut W, text: &str) -> std::io::Result<()> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "{text}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(xml: &str, filter: Option<&str>) -> String {
+        let filter = filter.map(|filter| PathFilter::parse(filter).unwrap());
+        let mut output = Vec::new();
+        strip_xml(xml.as_bytes(), filter.as_ref(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_text_cdata_and_references() {
+        let xml = "<?xml version=\"1.0\"?>\n<a>\n  <b> one &amp; two </b>\n  <!-- note -->\n  <c><![CDATA[<raw>]]></c>\n  <d/>\n</a>";
+        assert_eq!(strip(xml, None), "one & two\n<raw>\n");
+    }
+
+    #[test]
+    fn test_path_filters() {
+        let xml = "<feed xmlns:m=\"urn:m\"><title>Feed</title>\
+                   <entry><title>A</title><m:title>M</m:title><summary>S <b>bold</b></summary></entry>\
+                   <entry><title>B</title></entry></feed>";
+        assert_eq!(strip(xml, Some("/feed/title")), "Feed\n");
+        assert_eq!(strip(xml, Some("title")), "Feed\nA\nM\nB\n");
+        assert_eq!(strip(xml, Some("m:title")), "M\n");
+        assert_eq!(strip(xml, Some("entry/*")), "A\nM\nS\nbold\nB\n");
+        assert_eq!(strip(xml, Some("/title")), "");
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(PathFilter::parse("a//b").is_err());
+        assert!(PathFilter::parse("").is_err());
+        let mut output = Vec::new();
+        assert!(strip_xml("<a><b></a>".as_bytes(), None, &mut output).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Ping sweeps: pinging every address of a subnet to find the live hosts.
+//!
+//! A bounded number of addresses are pinged concurrently, each by a ping
+//! of its own: asynchronous pings ([`ping::ping_async`]) on a single thread
+//! with the `tokio` feature (the default) on unix, otherwise a pool of worker
+//! threads. Replies are told apart by their source address, so the pings
+//! do not interfere with each other.
+
+use std::net::IpAddr;
+
+use crate::ping::{self, PingConfig, PingReport};
+use crate::resolver::Resolver;
+
+/// The largest number of addresses a sweep accepts, a /16 in IPv4.
+const MAX_HOSTS: u128 = 1 << 16;
+
+/// A subnet in CIDR notation, e.g. `192.168.1.0/24` or `fd00::/120`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    pub address: IpAddr,
+    pub prefix: u8,
+}
+
+impl std::str::FromStr for Network {
+    type Err = String;
+
+    /// Parses `address/prefix`. An address alone is a single host.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let address: IpAddr = address
+            .parse()
+            .map_err(|err| format!("invalid network address ({s}): {err}"))?;
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = if prefix.is_empty() {
+            bits
+        } else {
+            prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(|| format!("invalid prefix length ({s}), expected 0 to {bits}"))?
+        };
+
+        Ok(Self { address, prefix })
+    }
+}
+
+impl Network {
+    /// The host addresses of the network, in order. The network and
+    /// broadcast addresses of IPv4 networks larger than /31 are left out.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::sweep::Network;
+    ///
+    /// let network: Network = "10.0.0.7/30".parse().unwrap();
+    /// let hosts: Vec<String> = network.hosts().iter().map(|ip| ip.to_string()).collect();
+    /// assert_eq!(hosts, ["10.0.0.5", "10.0.0.6"]);
+    /// ```
+    pub fn hosts(&self) -> Vec<IpAddr> {
+        match self.address {
+            IpAddr::V4(address) => {
+                let size = 1u64 << (32 - self.prefix);
+                let first = u64::from(u32::from(address)) & !(size - 1);
+                let (first, last) = if size > 2 {
+                    (first + 1, first + size - 2)
+                } else {
+                    (first, first + size - 1)
+                };
+                (first..=last)
+                    .map(|ip| IpAddr::from(std::net::Ipv4Addr::from(ip as u32)))
+                    .collect()
+            }
+            IpAddr::V6(address) => {
+                let host_bits = 128 - u32::from(self.prefix);
+                let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+                let first = u128::from(address) & mask;
+                (0..=!mask)
+                    .map(|offset| IpAddr::from(std::net::Ipv6Addr::from(first + offset)))
+                    .collect()
+            }
+        }
+    }
+
+    /// The number of addresses in the network, hosts or not.
+    fn size(&self) -> u128 {
+        let bits = if self.address.is_ipv4() { 32 } else { 128 };
+        1u128
+            .checked_shl(bits - u32::from(self.prefix))
+            .unwrap_or(u128::MAX)
+    }
+}
+
+/// Pings every host of `network` with `config`, using up to `workers`
+/// pings at a time, and returns the report of each host in address order.
+///
+/// `on_report` is called with each report as soon as its ping ends, so in
+/// no particular order, e.g. to print live hosts as they are found.
+///
+/// # Errors
+///
+/// Returns an error if the network has more than 65536 addresses, and the
+/// error of the first host if the ping of every host failed, e.g. for
+/// lack of permission to open an ICMP socket.
+pub fn sweep_with_progress(
+    network: &Network,
+    config: &PingConfig,
+    workers: usize,
+    mut on_report: impl FnMut(&PingReport),
+) -> Result<Vec<PingReport>, Box<dyn std::error::Error>> {
+    if network.size() > MAX_HOSTS {
+        return Err(format!(
+            "the network {}/{} is too large to sweep, at most 65536 addresses are supported",
+            network.address, network.prefix
+        )
+        .into());
+    }
+
+    let hosts = network.hosts();
+    let mut results = Vec::new();
+    ping_all(&hosts, config, workers.max(1), |host, result| {
+        if let Ok(report) = &result {
+            on_report(report);
+        }
+        results.push((host, result));
+    })?;
+
+    let mut reports = Vec::new();
+    let mut first_error = None;
+    for (host, result) in results {
+        match result {
+            Ok(report) => reports.push(report),
+            Err(err) => {
+                // Kept as a host without any probe sent.
+                first_error.get_or_insert(err);
+                reports.push(PingReport::new(host, Vec::new()));
+            }
+        }
+    }
+    if let Some(err) = first_error {
+        if reports.iter().all(|report| report.received == 0) && !hosts.is_empty() {
+            return Err(err.into());
+        }
+    }
+
+    reports.sort_by_key(|report| report.address);
+    Ok(reports)
+}
+
+/// Pings each of `hosts`, `workers` at a time, passing each outcome to
+/// `on_result` as soon as it is known.
+#[cfg(all(feature = "tokio", unix))]
+fn ping_all(
+    hosts: &[IpAddr],
+    config: &PingConfig,
+    workers: usize,
+    mut on_result: impl FnMut(IpAddr, Result<PingReport, String>),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let tasks = tokio::task::LocalSet::new();
+
+    tasks.block_on(&runtime, async {
+        let mut pending = hosts.iter();
+        let mut running = tokio::task::JoinSet::new();
+        loop {
+            while running.len() < workers {
+                let Some(&host) = pending.next() else {
+                    break;
+                };
+                let config = config.clone();
+                running.spawn_local(async move {
+                    let result =
+                        ping::ping_async(&host.to_string(), &Resolver::with_hosts(""), &config)
+                            .await
+                            .map_err(|err| format!("{host}: {err}"));
+                    (host, result)
+                });
+            }
+            let Some(done) = running.join_next().await else {
+                break;
+            };
+            let (host, result) = done?;
+            on_result(host, result);
+        }
+        Ok(())
+    })
+}
+
+/// Pings each of `hosts`, `workers` at a time, passing each outcome to
+/// `on_result` as soon as it is known.
+#[cfg(not(all(feature = "tokio", unix)))]
+fn ping_all(
+    hosts: &[IpAddr],
+    config: &PingConfig,
+    workers: usize,
+    mut on_result: impl FnMut(IpAddr, Result<PingReport, String>),
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let next = AtomicUsize::new(0);
+    let resolver = Resolver::with_hosts("");
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(hosts.len()) {
+            let sender = sender.clone();
+            let (next, resolver) = (&next, &resolver);
+            scope.spawn(move || {
+                while let Some(&host) = hosts.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let result = ping::ping(&host.to_string(), resolver, config)
+                        .map_err(|err| format!("{host}: {err}"));
+                    if sender.send((host, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (host, result) in receiver {
+            on_result(host, result);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_hosts() {
+        let hosts = |network: &str| -> Vec<String> {
+            let network: Network = network.parse().unwrap();
+            network.hosts().iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(hosts("192.168.1.0/24").len(), 254);
+        assert_eq!(hosts("192.168.1.77/24")[0], "192.168.1.1");
+        assert_eq!(hosts("10.0.0.0/31"), ["10.0.0.0", "10.0.0.1"]);
+        assert_eq!(hosts("10.0.0.9"), ["10.0.0.9"]);
+        assert_eq!(
+            hosts("fd00::5/126"),
+            ["fd00::4", "fd00::5", "fd00::6", "fd00::7"]
+        );
+
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("10.0.0/24".parse::<Network>().is_err());
+        assert!("fd00::/x".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn test_sweep_too_large() {
+        let network: Network = "10.0.0.0/15".parse().unwrap();
+        assert!(sweep_with_progress(&network, &PingConfig::new(), 4, |_| {}).is_err());
+    }
+}
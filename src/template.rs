@@ -0,0 +1,180 @@
+//! A small template engine rendering [`Report`] rows for `--format`.
+//!
+//! A template is text with `{column}` placeholders, e.g. `{target} {rtt_ms}ms`.
+//! Floats take an optional precision of up to 17 digits, `{rtt_ms:.1}`,
+//! and `{{`/`}}` write literal braces. Missing values are rendered as an
+//! empty string.
+
+use crate::report::{Report, Value};
+
+/// The most digits after the point, enough for any `f64`.
+const MAX_PRECISION: usize = 17;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field {
+        /// The index of the column in the report.
+        column: usize,
+        precision: Option<usize>,
+    },
+}
+
+/// A parsed `--format` template, bound to the columns of a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses `template` for rows with the given `columns`.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::report::Report;
+    /// use crabyknife::template::Template;
+    ///
+    /// let mut report = Report::new("ping", &["target", "seq", "rtt_ms"]);
+    /// report.push_row(vec!["example.com".into(), 0.into(), Some(12.345).into()]);
+    ///
+    /// let template = Template::parse("{target} #{seq}: {rtt_ms:.1}ms", &report.columns).unwrap();
+    /// assert_eq!(template.render_all(&report), vec!["example.com #0: 12.3ms"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown columns, unbalanced braces and
+    /// invalid precisions.
+    pub fn parse(template: &str, columns: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => {
+                                return Err(format!("unclosed {{ in template ({template})").into())
+                            }
+                        }
+                    }
+
+                    let (name, precision) = match placeholder.split_once(':') {
+                        Some((name, spec)) => {
+                            let precision = spec
+                                .strip_prefix('.')
+                                .and_then(|digits| digits.parse().ok())
+                                .filter(|precision| *precision <= MAX_PRECISION)
+                                .ok_or_else(|| {
+                                    format!(
+                                        "invalid format ({spec}) for {name}, expected .<digits> up to .{MAX_PRECISION}"
+                                    )
+                                })?;
+                            (name, Some(precision))
+                        }
+                        None => (placeholder.as_str(), None),
+                    };
+                    let column = columns
+                        .iter()
+                        .position(|column| column == name)
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown field {{{name}}} in template, available fields: {}",
+                                columns.join(", ")
+                            )
+                        })?;
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field { column, precision });
+                }
+                '}' => return Err(format!("unmatched }} in template ({template})").into()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Renders one row, which must have the columns the template was parsed for.
+    pub fn render(&self, row: &[Value]) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Field { column, precision } => match (&row[*column], precision) {
+                    (Value::Float(float), Some(precision)) => {
+                        output.push_str(&format!("{float:.precision$}"))
+                    }
+                    (value, _) => output.push_str(&value.to_string()),
+                },
+            }
+        }
+        output
+    }
+
+    /// Renders every row of `report`.
+    pub fn render_all(&self, report: &Report) -> Vec<String> {
+        report.rows.iter().map(|row| self.render(row)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<String> {
+        vec!["target".to_string(), "rtt_ms".to_string()]
+    }
+
+    #[test]
+    fn test_render_values() {
+        let template = Template::parse("{{{target}}} {rtt_ms}|{rtt_ms:.0}", &columns()).unwrap();
+        assert_eq!(
+            template.render(&["a".into(), Some(1.5).into()]),
+            "{a} 1.5|2"
+        );
+        assert_eq!(template.render(&["b".into(), None::<f64>.into()]), "{b} |");
+    }
+
+    #[test]
+    fn test_precision_ignored_for_text() {
+        let template = Template::parse("{target:.1}", &columns()).unwrap();
+        assert_eq!(template.render(&["abc".into(), 1.0.into()]), "abc");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let err = Template::parse("{host}", &columns()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown field {host} in template, available fields: target, rtt_ms"
+        );
+        assert!(Template::parse("{target", &columns()).is_err());
+        assert!(Template::parse("target}", &columns()).is_err());
+        assert!(Template::parse("{rtt_ms:2}", &columns()).is_err());
+        assert!(Template::parse("{rtt_ms:.17}", &columns()).is_ok());
+        let err = Template::parse("{rtt_ms:.70000}", &columns()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid format (.70000) for rtt_ms, expected .<digits> up to .17"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Fixtures shared by the unit tests of the modules working on files.
+
+use std::path::PathBuf;
+
+/// A fresh directory under the system temp directory, named after `name`
+/// and the process, so concurrent test runs do not share it.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("crabyknife-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
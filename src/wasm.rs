@@ -0,0 +1,94 @@
+//! WebAssembly bindings of the tools that only compute, for a browser
+//! playground or serverless functions. Only built with the `wasm` feature:
+//!
+//! ```text
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! The tools touching the network or the file system are not part of the
+//! WebAssembly build. Every function throws a JavaScript `Error` with the
+//! message of the failure.
+
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use crate::{canonicalize_xml, convert, prettify_html, prettify_xml};
+
+fn js_error(err: impl std::fmt::Display) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Prettifies an XML document, indented with `indent` spaces.
+#[wasm_bindgen(js_name = prettifyXml)]
+pub fn prettify_xml(xml: &str, indent: usize) -> Result<String, JsError> {
+    let options = prettify_xml::PrettifyOptions::new().indent_width(indent);
+    prettify_xml::prettify_xml_with_options(xml, &options).map_err(js_error)
+}
+
+/// Prettifies an HTML document or fragment, indented with `indent` spaces.
+#[wasm_bindgen(js_name = prettifyHtml)]
+pub fn prettify_html(html: &str, indent: usize) -> Result<String, JsError> {
+    let options = prettify_xml::PrettifyOptions::new().indent_width(indent);
+    prettify_html::prettify_html_with_options(html, &options).map_err(js_error)
+}
+
+/// Removes the whitespace between the tags of an XML document, and its
+/// comments with `strip_comments`.
+#[wasm_bindgen(js_name = minifyXml)]
+pub fn minify_xml(xml: &str, strip_comments: bool) -> Result<String, JsError> {
+    prettify_xml::minify_xml(xml, strip_comments).map_err(js_error)
+}
+
+/// Canonicalizes an XML document (Exclusive XML Canonicalization).
+#[wasm_bindgen(js_name = canonicalizeXml)]
+pub fn canonicalize_xml(xml: &str, with_comments: bool) -> Result<String, JsError> {
+    canonicalize_xml::canonicalize_xml(xml, with_comments).map_err(js_error)
+}
+
+/// Checks that an XML document is well-formed, throwing the problem
+/// with its line and column otherwise.
+#[wasm_bindgen(js_name = validateXml)]
+pub fn validate_xml(xml: &str) -> Result<(), JsError> {
+    prettify_xml::validate(xml).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Converts an XML document into JSON.
+#[wasm_bindgen(js_name = xmlToJson)]
+pub fn xml_to_json(xml: &str) -> Result<String, JsError> {
+    convert::xml_to_json(xml, &convert::ConvertOptions::new()).map_err(js_error)
+}
+
+/// Converts an XML document into YAML.
+#[wasm_bindgen(js_name = xmlToYaml)]
+pub fn xml_to_yaml(xml: &str) -> Result<String, JsError> {
+    convert::xml_to_yaml(xml, &convert::ConvertOptions::new()).map_err(js_error)
+}
+
+/// Converts a YAML document back into XML.
+#[wasm_bindgen(js_name = yamlToXml)]
+pub fn yaml_to_xml(yaml: &str) -> Result<String, JsError> {
+    convert::yaml_to_xml(yaml, &convert::ConvertOptions::new()).map_err(js_error)
+}
+
+/// Formats a JSON document with two-space indentation.
+#[wasm_bindgen(js_name = prettifyJson)]
+pub fn prettify_json(json: &str) -> Result<String, JsError> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|err| JsError::new(&format!("invalid JSON: {err}")))?;
+    serde_json::to_string_pretty(&value).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Computes the SHA-256 of `data`, as lowercase hex.
+#[wasm_bindgen(js_name = sha256Hex)]
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Generates a random (version 4) UUID in its hyphenated form.
+#[wasm_bindgen(js_name = newUuid)]
+pub fn new_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
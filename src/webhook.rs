@@ -0,0 +1,199 @@
+//! Verification of webhook signatures sent by common providers.
+//!
+//! Every provider signs the request body with HMAC-SHA256 and a shared
+//! secret, but each one builds the signed message and the signature
+//! header differently:
+//!
+//! - Stripe: `Stripe-Signature: t=<timestamp>,v1=<hex>` over `<timestamp>.<body>`
+//! - GitHub: `X-Hub-Signature-256: sha256=<hex>` over `<body>`
+//! - Slack: `X-Slack-Signature: v0=<hex>` over `v0:<timestamp>:<body>`,
+//!   with the timestamp from `X-Slack-Request-Timestamp`
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// A webhook provider with its own signing scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Stripe,
+    Github,
+    Slack,
+}
+
+impl std::str::FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stripe" => Ok(Self::Stripe),
+            "github" => Ok(Self::Github),
+            "slack" => Ok(Self::Slack),
+            _ => Err(format!(
+                "unsupported webhook provider ({s}), expected stripe, github or slack"
+            )),
+        }
+    }
+}
+
+/// The outcome of verifying a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub valid: bool,
+    /// The signature computed from the payload, in the provider's header format.
+    pub computed: String,
+}
+
+/// Verifies the `signature` header value sent by `provider` for `payload`.
+///
+/// `timestamp` is the request timestamp header, required by Slack.
+/// Stripe carries its timestamp inside the signature header.
+///
+/// # Example
+/// ```
+/// use crabyknife::webhook::{verify, Provider};
+///
+/// let signature = "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+/// let payload = b"The quick brown fox jumps over the lazy dog";
+/// assert!(verify(Provider::Github, b"key", payload, signature, None).unwrap().valid);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the signature header is malformed or a required
+/// timestamp is missing.
+pub fn verify(
+    provider: Provider,
+    secret: &[u8],
+    payload: &[u8],
+    signature: &str,
+    timestamp: Option<&str>,
+) -> Result<Verification, Box<dyn std::error::Error>> {
+    let signature = signature.trim();
+
+    match provider {
+        Provider::Stripe => {
+            let mut timestamp = None;
+            let mut candidates = Vec::new();
+            for part in signature.split(',') {
+                match part.trim().split_once('=') {
+                    Some(("t", value)) => timestamp = Some(value),
+                    Some(("v1", value)) => candidates.push(value),
+                    _ => {}
+                }
+            }
+            let timestamp = timestamp.ok_or("the Stripe signature has no t=<timestamp> part")?;
+            if candidates.is_empty() {
+                return Err("the Stripe signature has no v1=<signature> part".into());
+            }
+
+            let expected = hmac_sha256_hex(secret, &[timestamp.as_bytes(), b".", payload]);
+            Ok(Verification {
+                valid: candidates
+                    .iter()
+                    .any(|candidate| constant_time_eq(candidate.as_bytes(), expected.as_bytes())),
+                computed: format!("t={timestamp},v1={expected}"),
+            })
+        }
+        Provider::Github => {
+            let expected = format!("sha256={}", hmac_sha256_hex(secret, &[payload]));
+            Ok(Verification {
+                valid: constant_time_eq(signature.as_bytes(), expected.as_bytes()),
+                computed: expected,
+            })
+        }
+        Provider::Slack => {
+            let timestamp = timestamp.ok_or("Slack signatures require --timestamp")?;
+            let expected = format!(
+                "v0={}",
+                hmac_sha256_hex(secret, &[b"v0:", timestamp.as_bytes(), b":", payload])
+            );
+            Ok(Verification {
+                valid: constant_time_eq(signature.as_bytes(), expected.as_bytes()),
+                computed: expected,
+            })
+        }
+    }
+}
+
+/// Computes the lowercase hex HMAC-SHA256 of the concatenated `parts`.
+fn hmac_sha256_hex(secret: &[u8], parts: &[&[u8]]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Compares two byte strings in time independent of where they differ,
+/// so the comparison does not leak how much of a signature was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"{\"event\":\"ping\"}";
+
+    #[test]
+    fn test_stripe() {
+        let computed = verify(
+            Provider::Stripe,
+            b"whsec_test",
+            PAYLOAD,
+            "t=1700000000,v1=00",
+            None,
+        )
+        .unwrap()
+        .computed;
+        assert!(computed.starts_with("t=1700000000,v1="));
+
+        // Any of several v1 signatures may match, e.g. during secret rotation.
+        let header = format!(
+            "t=1700000000,v1=deadbeef,{}",
+            &computed["t=1700000000,".len()..]
+        );
+        let verification = verify(Provider::Stripe, b"whsec_test", PAYLOAD, &header, None).unwrap();
+        assert!(verification.valid);
+        assert!(verify(Provider::Stripe, b"whsec_test", PAYLOAD, "v1=00", None).is_err());
+    }
+
+    #[test]
+    fn test_github_rejects_wrong_secret() {
+        let first = verify(Provider::Github, b"secret", PAYLOAD, "sha256=00", None).unwrap();
+        assert!(!first.valid);
+        let verification =
+            verify(Provider::Github, b"other", PAYLOAD, &first.computed, None).unwrap();
+        assert!(!verification.valid);
+        let verification =
+            verify(Provider::Github, b"secret", PAYLOAD, &first.computed, None).unwrap();
+        assert!(verification.valid);
+    }
+
+    #[test]
+    fn test_slack_requires_timestamp() {
+        assert!(verify(Provider::Slack, b"secret", PAYLOAD, "v0=00", None).is_err());
+
+        let computed = verify(Provider::Slack, b"secret", PAYLOAD, "v0=00", Some("1"))
+            .unwrap()
+            .computed;
+        let verification =
+            verify(Provider::Slack, b"secret", PAYLOAD, &computed, Some("1")).unwrap();
+        assert!(verification.valid);
+        let verification =
+            verify(Provider::Slack, b"secret", PAYLOAD, &computed, Some("2")).unwrap();
+        assert!(!verification.valid);
+    }
+
+    #[test]
+    fn test_provider_from_str() {
+        assert_eq!("GitHub".parse::<Provider>().unwrap(), Provider::Github);
+        assert!("gitlab".parse::<Provider>().is_err());
+    }
+}
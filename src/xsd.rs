@@ -0,0 +1,1509 @@
+//! Validation of XML documents against an XML Schema (XSD).
+//!
+//! A practical subset of XML Schema 1.0 is supported:
+//!
+//! - global and local `element`s, `element ref`, `minOccurs` / `maxOccurs`,
+//! - named and anonymous `complexType`s with `sequence`, `choice`, `all`,
+//!   `any`, `attribute`, `anyAttribute` and `mixed` content,
+//! - `simpleContent` and `complexContent` extension and restriction,
+//! - named and anonymous `simpleType` restrictions with the `enumeration`,
+//!   `length`, `minLength`, `maxLength`, `minInclusive`, `maxInclusive`,
+//!   `minExclusive` and `maxExclusive` facets,
+//! - the common built-in types (strings, booleans, numbers, dates and times).
+//!
+//! Elements are matched by local name, so target namespaces are not checked,
+//! and `pattern` facets are not checked either. Anything else in the schema,
+//! e.g. `include`, `group` or `union`, is reported as unsupported.
+
+use std::collections::HashMap;
+
+use quick_xml::{events::Event, Reader};
+
+const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+
+/// The built-in types known to the validator.
+const BUILTIN_TYPES: &[&str] = &[
+    "anyType",
+    "anySimpleType",
+    "string",
+    "normalizedString",
+    "token",
+    "language",
+    "Name",
+    "NCName",
+    "NMTOKEN",
+    "ID",
+    "IDREF",
+    "QName",
+    "anyURI",
+    "boolean",
+    "decimal",
+    "float",
+    "double",
+    "integer",
+    "long",
+    "int",
+    "short",
+    "byte",
+    "nonNegativeInteger",
+    "positiveInteger",
+    "nonPositiveInteger",
+    "negativeInteger",
+    "unsignedLong",
+    "unsignedInt",
+    "unsignedShort",
+    "unsignedByte",
+    "date",
+    "time",
+    "dateTime",
+];
+
+/// A way a document does not conform to the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The path of the offending element or attribute, e.g. `/order/item[2]/@qty`,
+    /// where the index is only given when several siblings share the same name.
+    pub path: String,
+    /// A human readable description of the problem.
+    pub reason: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// A parsed XML Schema.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    elements: HashMap<String, ElementDecl>,
+    attributes: HashMap<String, AttributeDecl>,
+    complex_types: HashMap<String, ComplexType>,
+    simple_types: HashMap<String, SimpleType>,
+}
+
+#[derive(Debug, Clone)]
+enum TypeRef {
+    Builtin(String),
+    Named(String),
+    Complex(Box<ComplexType>),
+    Simple(Box<SimpleType>),
+}
+
+#[derive(Debug, Clone)]
+struct ElementDecl {
+    name: String,
+    /// Whether this is an `element ref`, whose type is the global element's.
+    reference: bool,
+    type_ref: TypeRef,
+}
+
+#[derive(Debug, Clone)]
+struct AttributeDecl {
+    name: String,
+    /// Whether this is an `attribute ref`, whose type is the global attribute's.
+    reference: bool,
+    type_ref: TypeRef,
+    required: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    term: Term,
+    min_occurs: usize,
+    /// `None` when unbounded.
+    max_occurs: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Element(ElementDecl),
+    Any,
+    Sequence(Vec<Particle>),
+    Choice(Vec<Particle>),
+    All(Vec<Particle>),
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+    Empty,
+    Elements(Particle),
+    Simple(TypeRef),
+}
+
+#[derive(Debug, Clone)]
+struct ComplexType {
+    /// The type extended by `simpleContent` or `complexContent`.
+    base: Option<TypeRef>,
+    attributes: Vec<AttributeDecl>,
+    any_attribute: bool,
+    content: Content,
+    mixed: bool,
+}
+
+#[derive(Debug, Clone)]
+struct SimpleType {
+    base: TypeRef,
+    facets: Facets,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Facets {
+    enumeration: Vec<String>,
+    length: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    min_inclusive: Option<f64>,
+    max_inclusive: Option<f64>,
+    min_exclusive: Option<f64>,
+    max_exclusive: Option<f64>,
+}
+
+/// A generic element tree, used for both the schema and the document.
+#[derive(Debug, Default)]
+struct Node {
+    /// The local name, without namespace prefix.
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Node>,
+}
+
+/// The resolved type of an element.
+enum Resolved<'a> {
+    Any,
+    Simple(&'a TypeRef),
+    Complex(ComplexType),
+}
+
+impl Schema {
+    /// Parses an XML Schema document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema is not well-formed, uses unsupported
+    /// constructs or refers to types, elements or attributes it does not define.
+    pub fn parse(xsd: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = parse_tree(xsd)?;
+        if root.name != "schema" {
+            return Err(format!("expected a <schema> root element, found <{}>", root.name).into());
+        }
+
+        // The prefix bound to the XML Schema namespace, used to tell built-in
+        // types apart from the schema's own types.
+        let xsd_prefix = root
+            .attributes
+            .iter()
+            .find(|(_, value)| value == XSD_NAMESPACE)
+            .map(|(key, _)| key.strip_prefix("xmlns:").unwrap_or_default().to_string())
+            .unwrap_or_else(|| "xs".to_string());
+        let mut parser = SchemaParser {
+            xsd_prefix,
+            references: Vec::new(),
+        };
+
+        let mut schema = Schema::default();
+        for node in &root.children {
+            match node.name.as_str() {
+                "element" => {
+                    let particle = parser.particle(node)?;
+                    if let Term::Element(decl) = particle.term {
+                        schema.elements.insert(decl.name.clone(), decl);
+                    }
+                }
+                "attribute" => {
+                    let decl = parser.attribute(node)?;
+                    schema.attributes.insert(decl.name.clone(), decl);
+                }
+                "complexType" => {
+                    let name = required_attribute(node, "name")?;
+                    schema
+                        .complex_types
+                        .insert(name.to_string(), parser.complex_type(node)?);
+                }
+                "simpleType" => {
+                    let name = required_attribute(node, "name")?;
+                    schema
+                        .simple_types
+                        .insert(name.to_string(), parser.simple_type(node)?);
+                }
+                "annotation" => {}
+                other => return Err(format!("unsupported schema construct <{other}>").into()),
+            }
+        }
+
+        for reference in parser.references {
+            let found = match &reference {
+                Reference::Type(name) => {
+                    schema.complex_types.contains_key(name)
+                        || schema.simple_types.contains_key(name)
+                }
+                Reference::Element(name) => schema.elements.contains_key(name),
+                Reference::Attribute(name) => schema.attributes.contains_key(name),
+            };
+            if !found {
+                return Err(format!("the schema does not define {reference}").into());
+            }
+        }
+
+        // Types are resolved through their bases, which must end in a
+        // built-in type rather than come back to the type itself.
+        for name in schema
+            .complex_types
+            .keys()
+            .chain(schema.simple_types.keys())
+        {
+            let mut derived = vec![name.as_str()];
+            let mut next = schema.named_base(name);
+            while let Some(base) = next {
+                if derived.contains(&base) {
+                    derived.push(base);
+                    return Err(format!(
+                        "type `{name}` derives from itself ({})",
+                        derived.join(" -> ")
+                    )
+                    .into());
+                }
+                derived.push(base);
+                next = schema.named_base(base);
+            }
+        }
+
+        Ok(schema)
+    }
+
+    /// Validates `xml` against the schema and lists its violations,
+    /// in document order. An empty list means the document is valid.
+    ///
+    /// # Example
+    /// ```
+    /// use crabyknife::xsd::Schema;
+    ///
+    /// let schema = Schema::parse(
+    ///     r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+    ///          <xs:element name="qty" type="xs:positiveInteger"/>
+    ///        </xs:schema>"#,
+    /// )
+    /// .unwrap();
+    /// assert!(schema.validate("<qty>3</qty>").unwrap().is_empty());
+    ///
+    /// let violations = schema.validate("<qty>none</qty>").unwrap();
+    /// assert_eq!(violations[0].to_string(), "/qty: \"none\" is not a valid positiveInteger");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document is not well-formed.
+    pub fn validate(&self, xml: &str) -> Result<Vec<Violation>, Box<dyn std::error::Error>> {
+        let root = parse_tree(xml)?;
+        let path = format!("/{}", root.name);
+
+        let mut violations = Vec::new();
+        match self.elements.get(&root.name) {
+            Some(decl) => self.validate_element(decl, &root, &path, &mut violations),
+            None => violations.push(Violation {
+                path,
+                reason: format!("no global element declaration for <{}>", root.name),
+            }),
+        }
+
+        Ok(violations)
+    }
+
+    fn validate_element(
+        &self,
+        decl: &ElementDecl,
+        node: &Node,
+        path: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let decl = if decl.reference {
+            &self.elements[&decl.name]
+        } else {
+            decl
+        };
+
+        match self.resolve(&decl.type_ref) {
+            Resolved::Any => {}
+            Resolved::Simple(type_ref) => {
+                if let Some(child) = node.children.first() {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason: format!("unexpected element <{}> in simple content", child.name),
+                    });
+                }
+                if let Err(reason) = self.check_simple(type_ref, &node.text) {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason,
+                    });
+                }
+            }
+            Resolved::Complex(complex) => {
+                self.validate_attributes(&complex, node, path, violations);
+                self.validate_content(&complex, node, path, violations);
+            }
+        }
+    }
+
+    fn validate_attributes(
+        &self,
+        complex: &ComplexType,
+        node: &Node,
+        path: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        for decl in &complex.attributes {
+            let decl = if decl.reference {
+                let global = &self.attributes[&decl.name];
+                AttributeDecl {
+                    required: decl.required,
+                    ..global.clone()
+                }
+            } else {
+                decl.clone()
+            };
+
+            let attr_path = format!("{path}/@{}", decl.name);
+            match node.attributes.iter().find(|(key, _)| *key == decl.name) {
+                Some((_, value)) => {
+                    if let Err(reason) = self.check_simple(&decl.type_ref, value) {
+                        violations.push(Violation {
+                            path: attr_path,
+                            reason,
+                        });
+                    }
+                }
+                None if decl.required => violations.push(Violation {
+                    path: attr_path,
+                    reason: "missing required attribute".to_string(),
+                }),
+                None => {}
+            }
+        }
+
+        if complex.any_attribute {
+            return;
+        }
+        for (key, _) in &node.attributes {
+            let special = key == "xmlns"
+                || key.starts_with("xmlns:")
+                || key.starts_with("xml:")
+                || key.starts_with("xsi:");
+            if !special && !complex.attributes.iter().any(|decl| decl.name == *key) {
+                violations.push(Violation {
+                    path: format!("{path}/@{key}"),
+                    reason: "unexpected attribute".to_string(),
+                });
+            }
+        }
+    }
+
+    fn validate_content(
+        &self,
+        complex: &ComplexType,
+        node: &Node,
+        path: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let has_text = !node.text.trim().is_empty();
+
+        match &complex.content {
+            Content::Simple(type_ref) => {
+                if let Some(child) = node.children.first() {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason: format!("unexpected element <{}> in simple content", child.name),
+                    });
+                }
+                if let Err(reason) = self.check_simple(type_ref, &node.text) {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason,
+                    });
+                }
+            }
+            Content::Empty => {
+                if has_text && !complex.mixed {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason: "unexpected text in element-only content".to_string(),
+                    });
+                }
+                if let Some(child) = node.children.first() {
+                    violations.push(Violation {
+                        path: child_path(node, 0, path),
+                        reason: format!("unexpected element <{}>", child.name),
+                    });
+                }
+            }
+            Content::Elements(model) => {
+                if has_text && !complex.mixed {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        reason: "unexpected text in element-only content".to_string(),
+                    });
+                }
+
+                let names: Vec<&str> = node.children.iter().map(|c| c.name.as_str()).collect();
+                let mut furthest = 0;
+                let ends = match_particle(model, &names, 0, &mut furthest);
+                if !ends.contains(&names.len()) {
+                    let expected = expected_names(model, &names[..furthest]);
+                    violations.push(if furthest < names.len() {
+                        let mut reason = format!("unexpected element <{}>", names[furthest]);
+                        if !expected.is_empty() {
+                            reason.push_str(&format!(", expected {}", one_of(&expected)));
+                        }
+                        Violation {
+                            path: child_path(node, furthest, path),
+                            reason,
+                        }
+                    } else {
+                        Violation {
+                            path: path.to_string(),
+                            reason: format!("missing {}", one_of(&expected)),
+                        }
+                    });
+                }
+
+                // Children are validated even when the content model did not
+                // match, so one misplaced element does not hide other problems.
+                for (i, child) in node.children.iter().enumerate() {
+                    if let Some(decl) = find_element(&model.term, &child.name) {
+                        self.validate_element(decl, child, &child_path(node, i, path), violations);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The name of the schema type the named type `name` derives from.
+    fn named_base(&self, name: &str) -> Option<&str> {
+        match (self.complex_types.get(name), self.simple_types.get(name)) {
+            (Some(complex), _) => complex.base.as_ref().and_then(named_type),
+            (None, Some(simple)) => named_type(&simple.base),
+            (None, None) => None,
+        }
+    }
+
+    fn resolve<'a>(&'a self, type_ref: &'a TypeRef) -> Resolved<'a> {
+        match type_ref {
+            TypeRef::Builtin(name) if name == "anyType" => Resolved::Any,
+            TypeRef::Builtin(_) | TypeRef::Simple(_) => Resolved::Simple(type_ref),
+            TypeRef::Complex(complex) => Resolved::Complex(self.flatten(complex)),
+            TypeRef::Named(name) => match self.complex_types.get(name) {
+                Some(complex) => Resolved::Complex(self.flatten(complex)),
+                None => Resolved::Simple(type_ref),
+            },
+        }
+    }
+
+    /// Merges a complex type with the types it extends.
+    fn flatten(&self, complex: &ComplexType) -> ComplexType {
+        let Some(base) = &complex.base else {
+            return complex.clone();
+        };
+
+        match self.resolve(base) {
+            Resolved::Any => ComplexType {
+                base: None,
+                ..complex.clone()
+            },
+            Resolved::Simple(type_ref) => ComplexType {
+                base: None,
+                content: Content::Simple(type_ref.clone()),
+                ..complex.clone()
+            },
+            Resolved::Complex(mut base) => {
+                base.attributes.extend(complex.attributes.iter().cloned());
+                base.any_attribute |= complex.any_attribute;
+                base.mixed |= complex.mixed;
+                base.content = match (base.content, &complex.content) {
+                    (Content::Elements(first), Content::Elements(second)) => {
+                        Content::Elements(Particle {
+                            term: Term::Sequence(vec![first, second.clone()]),
+                            min_occurs: 1,
+                            max_occurs: Some(1),
+                        })
+                    }
+                    (content, Content::Empty) => content,
+                    (_, content) => content.clone(),
+                };
+                base
+            }
+        }
+    }
+
+    /// Checks `value` against a simple type, returning the reason it is invalid.
+    fn check_simple(&self, type_ref: &TypeRef, value: &str) -> Result<(), String> {
+        match type_ref {
+            TypeRef::Builtin(name) => check_builtin(name, value),
+            TypeRef::Named(name) => match self.simple_types.get(name) {
+                Some(simple) => self.check_restriction(simple, value),
+                // Complex types with simple content.
+                None => match self.resolve(type_ref) {
+                    Resolved::Complex(ComplexType {
+                        content: Content::Simple(type_ref),
+                        ..
+                    }) => self.check_simple(&type_ref, value),
+                    _ => Ok(()),
+                },
+            },
+            TypeRef::Simple(simple) => self.check_restriction(simple, value),
+            TypeRef::Complex(_) => Ok(()),
+        }
+    }
+
+    fn check_restriction(&self, simple: &SimpleType, value: &str) -> Result<(), String> {
+        self.check_simple(&simple.base, value)?;
+
+        // Only string types keep their whitespace.
+        let value = if self.preserves_whitespace(&simple.base) {
+            value
+        } else {
+            value.trim()
+        };
+
+        let facets = &simple.facets;
+        if !facets.enumeration.is_empty() && !facets.enumeration.iter().any(|v| v == value) {
+            let allowed: Vec<String> = facets
+                .enumeration
+                .iter()
+                .map(|v| format!("{v:?}"))
+                .collect();
+            return Err(format!("{value:?} is not one of {}", allowed.join(", ")));
+        }
+
+        let length = value.chars().count();
+        if facets.length.is_some_and(|expected| length != expected) {
+            return Err(format!(
+                "{value:?} must be exactly {} characters long",
+                facets.length.unwrap_or_default()
+            ));
+        }
+        if let Some(min) = facets.min_length.filter(|min| length < *min) {
+            return Err(format!("{value:?} is shorter than {min} characters"));
+        }
+        if let Some(max) = facets.max_length.filter(|max| length > *max) {
+            return Err(format!("{value:?} is longer than {max} characters"));
+        }
+
+        if let Ok(number) = value.parse::<f64>() {
+            let bounds = [
+                (
+                    facets.min_inclusive,
+                    number >= facets.min_inclusive.unwrap_or_default(),
+                    "at least",
+                ),
+                (
+                    facets.max_inclusive,
+                    number <= facets.max_inclusive.unwrap_or_default(),
+                    "at most",
+                ),
+                (
+                    facets.min_exclusive,
+                    number > facets.min_exclusive.unwrap_or_default(),
+                    "more than",
+                ),
+                (
+                    facets.max_exclusive,
+                    number < facets.max_exclusive.unwrap_or_default(),
+                    "less than",
+                ),
+            ];
+            for (bound, within, description) in bounds {
+                if let (Some(bound), false) = (bound, within) {
+                    return Err(format!("{value} must be {description} {bound}"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn preserves_whitespace(&self, type_ref: &TypeRef) -> bool {
+        match type_ref {
+            TypeRef::Builtin(name) => matches!(name.as_str(), "string" | "anySimpleType"),
+            TypeRef::Named(name) => self
+                .simple_types
+                .get(name)
+                .is_some_and(|simple| self.preserves_whitespace(&simple.base)),
+            TypeRef::Simple(simple) => self.preserves_whitespace(&simple.base),
+            TypeRef::Complex(_) => true,
+        }
+    }
+}
+
+/// Something a schema refers to by name, checked once the whole schema is read.
+enum Reference {
+    Type(String),
+    Element(String),
+    Attribute(String),
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reference::Type(name) => write!(f, "type `{name}`"),
+            Reference::Element(name) => write!(f, "element `{name}`"),
+            Reference::Attribute(name) => write!(f, "attribute `{name}`"),
+        }
+    }
+}
+
+struct SchemaParser {
+    xsd_prefix: String,
+    references: Vec<Reference>,
+}
+
+impl SchemaParser {
+    /// Parses a qualified type name, e.g. `xs:string` or `tns:Address`.
+    fn type_name(&mut self, qualified: &str) -> Result<TypeRef, Box<dyn std::error::Error>> {
+        let (prefix, local) = qualified.split_once(':').unwrap_or(("", qualified));
+        if prefix == self.xsd_prefix {
+            if !BUILTIN_TYPES.contains(&local) {
+                return Err(format!("unsupported built-in type `{qualified}`").into());
+            }
+            return Ok(TypeRef::Builtin(local.to_string()));
+        }
+
+        self.references.push(Reference::Type(local.to_string()));
+        Ok(TypeRef::Named(local.to_string()))
+    }
+
+    /// Parses the type of an element or attribute, given by a `type`
+    /// attribute or an anonymous type definition.
+    fn declared_type(&mut self, node: &Node) -> Result<TypeRef, Box<dyn std::error::Error>> {
+        if let Some(type_name) = attribute(node, "type") {
+            return self.type_name(type_name);
+        }
+
+        for child in &node.children {
+            match child.name.as_str() {
+                "complexType" => return Ok(TypeRef::Complex(Box::new(self.complex_type(child)?))),
+                "simpleType" => return Ok(TypeRef::Simple(Box::new(self.simple_type(child)?))),
+                _ => {}
+            }
+        }
+
+        Ok(TypeRef::Builtin("anyType".to_string()))
+    }
+
+    fn particle(&mut self, node: &Node) -> Result<Particle, Box<dyn std::error::Error>> {
+        let min_occurs = match attribute(node, "minOccurs") {
+            Some(value) => value
+                .parse()
+                .map_err(|err| format!("invalid minOccurs ({value}): {err}"))?,
+            None => 1,
+        };
+        let max_occurs = match attribute(node, "maxOccurs") {
+            Some("unbounded") => None,
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|err| format!("invalid maxOccurs ({value}): {err}"))?,
+            ),
+            None => Some(1),
+        };
+
+        let term = match node.name.as_str() {
+            "element" => match attribute(node, "ref") {
+                Some(reference) => {
+                    let name = local_name(reference).to_string();
+                    self.references.push(Reference::Element(name.clone()));
+                    Term::Element(ElementDecl {
+                        name,
+                        reference: true,
+                        type_ref: TypeRef::Builtin("anyType".to_string()),
+                    })
+                }
+                None => Term::Element(ElementDecl {
+                    name: required_attribute(node, "name")?.to_string(),
+                    reference: false,
+                    type_ref: self.declared_type(node)?,
+                }),
+            },
+            "any" => Term::Any,
+            "sequence" => Term::Sequence(self.particles(node)?),
+            "choice" => Term::Choice(self.particles(node)?),
+            "all" => Term::All(self.particles(node)?),
+            other => return Err(format!("unsupported schema construct <{other}>").into()),
+        };
+
+        Ok(Particle {
+            term,
+            min_occurs,
+            max_occurs,
+        })
+    }
+
+    fn particles(&mut self, node: &Node) -> Result<Vec<Particle>, Box<dyn std::error::Error>> {
+        node.children
+            .iter()
+            .filter(|child| child.name != "annotation")
+            .map(|child| self.particle(child))
+            .collect()
+    }
+
+    fn attribute(&mut self, node: &Node) -> Result<AttributeDecl, Box<dyn std::error::Error>> {
+        let required = attribute(node, "use") == Some("required");
+        if let Some(reference) = attribute(node, "ref") {
+            let name = local_name(reference).to_string();
+            self.references.push(Reference::Attribute(name.clone()));
+            return Ok(AttributeDecl {
+                name,
+                reference: true,
+                type_ref: TypeRef::Builtin("anySimpleType".to_string()),
+                required,
+            });
+        }
+
+        let type_ref = match self.declared_type(node)? {
+            // Attributes without a type accept any text.
+            TypeRef::Builtin(name) if name == "anyType" => {
+                TypeRef::Builtin("anySimpleType".to_string())
+            }
+            type_ref => type_ref,
+        };
+        Ok(AttributeDecl {
+            name: required_attribute(node, "name")?.to_string(),
+            reference: false,
+            type_ref,
+            required,
+        })
+    }
+
+    fn complex_type(&mut self, node: &Node) -> Result<ComplexType, Box<dyn std::error::Error>> {
+        let mut complex = ComplexType {
+            base: None,
+            attributes: Vec::new(),
+            any_attribute: false,
+            content: Content::Empty,
+            mixed: attribute(node, "mixed") == Some("true"),
+        };
+        self.complex_content(node, &mut complex)?;
+        Ok(complex)
+    }
+
+    /// Reads the particle and attributes of a `complexType`, or of the
+    /// `extension` or `restriction` of its `simpleContent` or `complexContent`.
+    fn complex_content(
+        &mut self,
+        node: &Node,
+        complex: &mut ComplexType,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for child in &node.children {
+            match child.name.as_str() {
+                "sequence" | "choice" | "all" => {
+                    complex.content = Content::Elements(self.particle(child)?)
+                }
+                "attribute" => complex.attributes.push(self.attribute(child)?),
+                "anyAttribute" => complex.any_attribute = true,
+                "simpleContent" | "complexContent" => {
+                    if child.name == "complexContent" && attribute(child, "mixed") == Some("true") {
+                        complex.mixed = true;
+                    }
+                    for derivation in &child.children {
+                        match derivation.name.as_str() {
+                            "extension" | "restriction" => {
+                                // A complex content restriction restates the whole content,
+                                // anything else builds on the base type.
+                                if derivation.name == "extension" || child.name == "simpleContent" {
+                                    let base = required_attribute(derivation, "base")?;
+                                    complex.base = Some(self.type_name(base)?);
+                                }
+                                self.complex_content(derivation, complex)?;
+                            }
+                            "annotation" => {}
+                            other => {
+                                return Err(format!("unsupported schema construct <{other}>").into())
+                            }
+                        }
+                    }
+                }
+                "annotation" => {}
+                other => return Err(format!("unsupported schema construct <{other}>").into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn simple_type(&mut self, node: &Node) -> Result<SimpleType, Box<dyn std::error::Error>> {
+        let restriction = node
+            .children
+            .iter()
+            .find(|child| child.name != "annotation")
+            .ok_or("empty <simpleType>")?;
+        if restriction.name != "restriction" {
+            return Err(format!("unsupported schema construct <{}>", restriction.name).into());
+        }
+
+        let base = match attribute(restriction, "base") {
+            Some(base) => self.type_name(base)?,
+            None => match restriction.children.iter().find(|c| c.name == "simpleType") {
+                Some(inline) => TypeRef::Simple(Box::new(self.simple_type(inline)?)),
+                None => return Err("<restriction> has no base type".into()),
+            },
+        };
+
+        let mut facets = Facets::default();
+        for facet in &restriction.children {
+            let value = attribute(facet, "value").unwrap_or_default();
+            let invalid =
+                |err: &dyn std::fmt::Display| format!("invalid <{}> ({value}): {err}", facet.name);
+            match facet.name.as_str() {
+                "enumeration" => facets.enumeration.push(value.to_string()),
+                "length" => facets.length = Some(value.parse().map_err(|err| invalid(&err))?),
+                "minLength" => {
+                    facets.min_length = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                "maxLength" => {
+                    facets.max_length = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                "minInclusive" => {
+                    facets.min_inclusive = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                "maxInclusive" => {
+                    facets.max_inclusive = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                "minExclusive" => {
+                    facets.min_exclusive = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                "maxExclusive" => {
+                    facets.max_exclusive = Some(value.parse().map_err(|err| invalid(&err))?)
+                }
+                // Not checked, see the module documentation.
+                "pattern" | "whiteSpace" | "totalDigits" | "fractionDigits" => {}
+                "simpleType" | "annotation" => {}
+                other => return Err(format!("unsupported facet <{other}>").into()),
+            }
+        }
+
+        Ok(SimpleType { base, facets })
+    }
+}
+
+fn parse_tree(xml: &str) -> Result<Node, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Node> = Vec::new();
+
+    loop {
+        let (node, closed) = match reader.read_event()? {
+            Event::Start(e) => (new_node(&e)?, false),
+            Event::Empty(e) => (new_node(&e)?, true),
+            Event::End(_) => (stack.pop().ok_or("unexpected close tag")?, true),
+            Event::Text(e) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&e.unescape()?);
+                }
+                continue;
+            }
+            Event::CData(e) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&e.decode()?);
+                }
+                continue;
+            }
+            Event::Eof => return Err("the document has no root element".into()),
+            _ => continue,
+        };
+
+        if !closed {
+            stack.push(node);
+            continue;
+        }
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => return Ok(node),
+        }
+    }
+}
+
+fn new_node(e: &quick_xml::events::BytesStart) -> Result<Node, Box<dyn std::error::Error>> {
+    let mut attributes = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        attributes.push((
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            attr.unescape_value()?.into_owned(),
+        ));
+    }
+
+    Ok(Node {
+        name: String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+        attributes,
+        ..Node::default()
+    })
+}
+
+/// The schema type `type_ref` is, or derives from, when it is not built in.
+fn named_type(type_ref: &TypeRef) -> Option<&str> {
+    match type_ref {
+        TypeRef::Builtin(_) => None,
+        TypeRef::Named(name) => Some(name),
+        TypeRef::Simple(simple) => named_type(&simple.base),
+        TypeRef::Complex(complex) => complex.base.as_ref().and_then(named_type),
+    }
+}
+
+fn attribute<'a>(node: &'a Node, name: &str) -> Option<&'a str> {
+    node.attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn required_attribute<'a>(
+    node: &'a Node,
+    name: &str,
+) -> Result<&'a str, Box<dyn std::error::Error>> {
+    attribute(node, name).ok_or_else(|| format!("<{}> has no {name} attribute", node.name).into())
+}
+
+fn local_name(qualified: &str) -> &str {
+    qualified
+        .split_once(':')
+        .map_or(qualified, |(_, local)| local)
+}
+
+/// The path of the `index`th child of `node`, indexed only when several
+/// siblings share its name.
+fn child_path(node: &Node, index: usize, path: &str) -> String {
+    let name = &node.children[index].name;
+    let siblings = node.children.iter().filter(|c| c.name == *name).count();
+    if siblings > 1 {
+        let position = node.children[..index]
+            .iter()
+            .filter(|c| c.name == *name)
+            .count()
+            + 1;
+        format!("{path}/{name}[{position}]")
+    } else {
+        format!("{path}/{name}")
+    }
+}
+
+/// Returns every position in `names` where a match of `particle`
+/// starting at `start` can end. `furthest` tracks the furthest element
+/// consumed by any attempt, to point at the first unexpected element.
+fn match_particle(
+    particle: &Particle,
+    names: &[&str],
+    start: usize,
+    furthest: &mut usize,
+) -> Vec<usize> {
+    let mut ends = Vec::new();
+    if particle.min_occurs == 0 {
+        ends.push(start);
+    }
+
+    let mut current = vec![start];
+    let mut count = 0;
+    while !current.is_empty() && particle.max_occurs.is_none_or(|max| count < max) {
+        count += 1;
+        let mut next = Vec::new();
+        for &position in &current {
+            for end in match_term(&particle.term, names, position, furthest) {
+                if end == position {
+                    // The term can match nothing, so can all remaining repetitions.
+                    if !ends.contains(&end) {
+                        ends.push(end);
+                    }
+                } else if !next.contains(&end) {
+                    next.push(end);
+                }
+            }
+        }
+
+        if count >= particle.min_occurs {
+            for &end in &next {
+                if !ends.contains(&end) {
+                    ends.push(end);
+                }
+            }
+        }
+        current = next;
+    }
+
+    ends
+}
+
+fn match_term(term: &Term, names: &[&str], start: usize, furthest: &mut usize) -> Vec<usize> {
+    match term {
+        Term::Element(decl) => {
+            if names.get(start) == Some(&decl.name.as_str()) {
+                *furthest = (*furthest).max(start + 1);
+                vec![start + 1]
+            } else {
+                Vec::new()
+            }
+        }
+        Term::Any => {
+            if start < names.len() {
+                *furthest = (*furthest).max(start + 1);
+                vec![start + 1]
+            } else {
+                Vec::new()
+            }
+        }
+        Term::Sequence(particles) => {
+            let mut positions = vec![start];
+            for particle in particles {
+                let mut next = Vec::new();
+                for &position in &positions {
+                    for end in match_particle(particle, names, position, furthest) {
+                        if !next.contains(&end) {
+                            next.push(end);
+                        }
+                    }
+                }
+                positions = next;
+            }
+            positions
+        }
+        Term::Choice(particles) => {
+            let mut ends = Vec::new();
+            for particle in particles {
+                for end in match_particle(particle, names, start, furthest) {
+                    if !ends.contains(&end) {
+                        ends.push(end);
+                    }
+                }
+            }
+            ends
+        }
+        Term::All(particles) => {
+            // Each member at most once, in any order.
+            let mut seen = vec![false; particles.len()];
+            let mut position = start;
+            while let Some(i) = names.get(position).and_then(|name| {
+                particles.iter().enumerate().position(|(i, particle)| {
+                    !seen[i] && matches!(&particle.term, Term::Element(decl) if decl.name == *name)
+                })
+            }) {
+                seen[i] = true;
+                position += 1;
+                *furthest = (*furthest).max(position);
+            }
+
+            let complete = particles
+                .iter()
+                .zip(&seen)
+                .all(|(particle, seen)| *seen || particle.min_occurs == 0);
+            if complete {
+                vec![position]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Lists the element names that may follow `names` in `model`.
+fn expected_names(model: &Particle, names: &[&str]) -> Vec<String> {
+    let mut candidates = Vec::new();
+    collect_names(&model.term, &mut candidates);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            let mut extended = names.to_vec();
+            extended.push(candidate);
+            let mut furthest = 0;
+            match_particle(model, &extended, 0, &mut furthest);
+            furthest == extended.len()
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+fn collect_names<'a>(term: &'a Term, names: &mut Vec<&'a str>) {
+    match term {
+        Term::Element(decl) => {
+            if !names.contains(&decl.name.as_str()) {
+                names.push(&decl.name);
+            }
+        }
+        Term::Any => {}
+        Term::Sequence(particles) | Term::Choice(particles) | Term::All(particles) => {
+            for particle in particles {
+                collect_names(&particle.term, names);
+            }
+        }
+    }
+}
+
+/// Finds the declaration of the child element `name` in a content model.
+fn find_element<'a>(term: &'a Term, name: &str) -> Option<&'a ElementDecl> {
+    match term {
+        Term::Element(decl) if decl.name == name => Some(decl),
+        Term::Element(_) | Term::Any => None,
+        Term::Sequence(particles) | Term::Choice(particles) | Term::All(particles) => particles
+            .iter()
+            .find_map(|particle| find_element(&particle.term, name)),
+    }
+}
+
+fn one_of(names: &[String]) -> String {
+    let names: Vec<String> = names.iter().map(|name| format!("<{name}>")).collect();
+    match names.len() {
+        0 => "content".to_string(),
+        1 => names[0].clone(),
+        _ => format!("one of {}", names.join(", ")),
+    }
+}
+
+/// Checks `value` against a built-in type, returning the reason it is invalid.
+fn check_builtin(name: &str, value: &str) -> Result<(), String> {
+    let trimmed = value.trim();
+    let valid = match name {
+        "anyType" | "anySimpleType" | "string" | "normalizedString" | "token" | "anyURI"
+        | "QName" => true,
+        "language" | "Name" | "NCName" | "NMTOKEN" | "ID" | "IDREF" => {
+            !trimmed.is_empty() && !trimmed.contains(char::is_whitespace)
+        }
+        "boolean" => matches!(trimmed, "true" | "false" | "1" | "0"),
+        "decimal" => is_decimal(trimmed),
+        "float" | "double" => {
+            matches!(trimmed, "INF" | "-INF" | "NaN")
+                || (trimmed
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'))
+                    && trimmed.parse::<f64>().is_ok())
+        }
+        "date" => is_date(strip_timezone(trimmed)),
+        "time" => is_time(strip_timezone(trimmed)),
+        "dateTime" => strip_timezone(trimmed)
+            .split_once('T')
+            .is_some_and(|(date, time)| is_date(date) && is_time(time)),
+        _ => {
+            let (min, max) = integer_bounds(name);
+            is_integer(trimmed)
+                && trimmed.parse::<i128>().is_ok_and(|number| {
+                    min.is_none_or(|min| number >= min) && max.is_none_or(|max| number <= max)
+                })
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{trimmed:?} is not a valid {name}"))
+    }
+}
+
+fn integer_bounds(name: &str) -> (Option<i128>, Option<i128>) {
+    match name {
+        "long" => (Some(i64::MIN.into()), Some(i64::MAX.into())),
+        "int" => (Some(i32::MIN.into()), Some(i32::MAX.into())),
+        "short" => (Some(i16::MIN.into()), Some(i16::MAX.into())),
+        "byte" => (Some(i8::MIN.into()), Some(i8::MAX.into())),
+        "nonNegativeInteger" => (Some(0), None),
+        "positiveInteger" => (Some(1), None),
+        "nonPositiveInteger" => (None, Some(0)),
+        "negativeInteger" => (None, Some(-1)),
+        "unsignedLong" => (Some(0), Some(u64::MAX.into())),
+        "unsignedInt" => (Some(0), Some(u32::MAX.into())),
+        "unsignedShort" => (Some(0), Some(u16::MAX.into())),
+        "unsignedByte" => (Some(0), Some(u8::MAX.into())),
+        _ => (None, None),
+    }
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_decimal(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    !(whole.is_empty() && fraction.is_empty())
+        && whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+}
+
+/// Removes a trailing `Z` or `+hh:mm` / `-hh:mm` timezone.
+fn strip_timezone(value: &str) -> &str {
+    if let Some(stripped) = value.strip_suffix('Z') {
+        return stripped;
+    }
+
+    let bytes = value.as_bytes();
+    let len = bytes.len();
+    if len > 6
+        && matches!(bytes[len - 6], b'+' | b'-')
+        && bytes[len - 3] == b':'
+        && value[len - 5..len - 3].chars().all(|c| c.is_ascii_digit())
+        && value[len - 2..].chars().all(|c| c.is_ascii_digit())
+    {
+        return &value[..len - 6];
+    }
+    value
+}
+
+/// Checks a `YYYY-MM-DD` date.
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() < 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (
+        year.parse::<u32>(),
+        month.parse::<u32>(),
+        day.parse::<u32>(),
+    ) else {
+        return false;
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+/// Checks a `hh:mm:ss` time with optional fractional seconds.
+fn is_time(value: &str) -> bool {
+    let parts: Vec<&str> = value.split(':').collect();
+    let [hours, minutes, seconds] = parts[..] else {
+        return false;
+    };
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+
+    let two_digits = |part: &str, max: u32| {
+        part.len() == 2
+            && part.chars().all(|c| c.is_ascii_digit())
+            && part.parse::<u32>().is_ok_and(|n| n <= max)
+    };
+    two_digits(hours, 23)
+        && two_digits(minutes, 59)
+        && two_digits(seconds, 59)
+        && !fraction.is_empty()
+        && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER_SCHEMA: &str = r#"
+        <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+          <xs:element name="order">
+            <xs:complexType>
+              <xs:sequence>
+                <xs:element name="customer" type="xs:string"/>
+                <xs:element name="item" type="Item" maxOccurs="unbounded"/>
+                <xs:element name="note" type="xs:string" minOccurs="0"/>
+              </xs:sequence>
+              <xs:attribute name="id" type="xs:positiveInteger" use="required"/>
+            </xs:complexType>
+          </xs:element>
+          <xs:complexType name="Item">
+            <xs:simpleContent>
+              <xs:extension base="Sku">
+                <xs:attribute name="qty" type="xs:int"/>
+              </xs:extension>
+            </xs:simpleContent>
+          </xs:complexType>
+          <xs:simpleType name="Sku">
+            <xs:restriction base="xs:string">
+              <xs:minLength value="3"/>
+              <xs:maxLength value="8"/>
+            </xs:restriction>
+          </xs:simpleType>
+        </xs:schema>"#;
+
+    fn violations(schema: &str, xml: &str) -> Vec<String> {
+        Schema::parse(schema)
+            .unwrap()
+            .validate(xml)
+            .unwrap()
+            .iter()
+            .map(Violation::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let xml = r#"<order id="7"><customer>Ann</customer><item qty="2">ABC-1</item><item>XYZ</item></order>"#;
+        assert_eq!(violations(ORDER_SCHEMA, xml), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_attribute_violations() {
+        let xml =
+            r#"<order color="red"><customer>Ann</customer><item qty="two">ABC</item></order>"#;
+        assert_eq!(
+            violations(ORDER_SCHEMA, xml),
+            vec![
+                "/order/@id: missing required attribute",
+                "/order/@color: unexpected attribute",
+                "/order/item/@qty: \"two\" is not a valid int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_content_model_violations() {
+        let xml = r#"<order id="1"><customer>Ann</customer><note/></order>"#;
+        assert_eq!(
+            violations(ORDER_SCHEMA, xml),
+            vec!["/order/note: unexpected element <note>, expected <item>"]
+        );
+
+        let xml = r#"<order id="1"><customer>Ann</customer></order>"#;
+        assert_eq!(
+            violations(ORDER_SCHEMA, xml),
+            vec!["/order: missing <item>"]
+        );
+
+        let xml = r#"<order id="1"><customer>Ann</customer><item>AB</item><item>ABCDEFGHI</item></order>"#;
+        assert_eq!(
+            violations(ORDER_SCHEMA, xml),
+            vec![
+                "/order/item[1]: \"AB\" is shorter than 3 characters",
+                "/order/item[2]: \"ABCDEFGHI\" is longer than 8 characters",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_choice_all_and_references() {
+        let schema = r#"
+            <xsd:schema xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+              <xsd:element name="size">
+                <xsd:simpleType>
+                  <xsd:restriction base="xsd:token">
+                    <xsd:enumeration value="S"/>
+                    <xsd:enumeration value="M"/>
+                  </xsd:restriction>
+                </xsd:simpleType>
+              </xsd:element>
+              <xsd:element name="shirt">
+                <xsd:complexType>
+                  <xsd:choice>
+                    <xsd:element ref="size"/>
+                    <xsd:all>
+                      <xsd:element name="chest" type="xsd:decimal"/>
+                      <xsd:element name="waist" type="xsd:decimal" minOccurs="0"/>
+                    </xsd:all>
+                  </xsd:choice>
+                </xsd:complexType>
+              </xsd:element>
+            </xsd:schema>"#;
+
+        assert!(violations(schema, "<shirt><size> M </size></shirt>").is_empty());
+        assert!(
+            violations(schema, "<shirt><waist>80</waist><chest>1.5</chest></shirt>").is_empty()
+        );
+        assert_eq!(
+            violations(schema, "<shirt><size>XL</size></shirt>"),
+            vec![r#"/shirt/size: "XL" is not one of "S", "M""#]
+        );
+        assert_eq!(
+            violations(schema, "<shirt><waist>80</waist></shirt>"),
+            vec!["/shirt: missing <chest>"]
+        );
+        assert_eq!(
+            violations(schema, "<other/>"),
+            vec!["/other: no global element declaration for <other>"]
+        );
+    }
+
+    #[test]
+    fn test_complex_content_extension() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:complexType name="Base">
+                <xs:sequence><xs:element name="a" type="xs:date"/></xs:sequence>
+              </xs:complexType>
+              <xs:element name="root">
+                <xs:complexType>
+                  <xs:complexContent>
+                    <xs:extension base="Base">
+                      <xs:sequence><xs:element name="b" type="xs:dateTime"/></xs:sequence>
+                    </xs:extension>
+                  </xs:complexContent>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>"#;
+
+        assert!(violations(
+            schema,
+            "<root><a>2024-02-29</a><b>2024-01-01T10:00:00Z</b></root>"
+        )
+        .is_empty());
+        assert_eq!(
+            violations(schema, "<root><a>2023-02-29</a><b>noon</b></root>"),
+            vec![
+                "/root/a: \"2023-02-29\" is not a valid date",
+                "/root/b: \"noon\" is not a valid dateTime",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_and_undefined() {
+        let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="a" type="Missing"/>
+            </xs:schema>"#;
+        assert!(Schema::parse(schema).is_err());
+
+        let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:include schemaLocation="other.xsd"/>
+            </xs:schema>"#;
+        assert!(Schema::parse(schema).is_err());
+    }
+
+    #[test]
+    fn test_cyclic_types() {
+        let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="a" type="A"/>
+              <xs:simpleType name="A"><xs:restriction base="A"/></xs:simpleType>
+            </xs:schema>"#;
+        assert_eq!(
+            Schema::parse(schema).unwrap_err().to_string(),
+            "type `A` derives from itself (A -> A)"
+        );
+
+        let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="t" type="T"/>
+              <xs:complexType name="T">
+                <xs:complexContent><xs:extension base="T"/></xs:complexContent>
+              </xs:complexType>
+            </xs:schema>"#;
+        assert!(Schema::parse(schema).is_err());
+
+        // Through other types, and anonymous ones.
+        let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:simpleType name="A"><xs:restriction base="B"/></xs:simpleType>
+              <xs:simpleType name="B">
+                <xs:restriction><xs:simpleType><xs:restriction base="A"/></xs:simpleType></xs:restriction>
+              </xs:simpleType>
+            </xs:schema>"#;
+        assert!(Schema::parse(schema).is_err());
+    }
+
+    #[test]
+    fn test_builtin_types() {
+        assert!(check_builtin("unsignedByte", "255").is_ok());
+        assert!(check_builtin("unsignedByte", "256").is_err());
+        assert!(check_builtin("boolean", "yes").is_err());
+        assert!(check_builtin("double", "-1.5e3").is_ok());
+        assert!(check_builtin("double", "inf").is_err());
+        assert!(check_builtin("time", "23:59:59.5+02:00").is_ok());
+        assert!(check_builtin("decimal", "1.2.3").is_err());
+    }
+}
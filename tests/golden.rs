@@ -0,0 +1,77 @@
+//! Golden-file tests of the command line. Every `tests/golden/<case>.args`
+//! holds the arguments of a run, one per line, and `<case>.out` what the
+//! run prints: its stdout, then its stderr and exit status if any. Runs
+//! start in `tests/golden`, so fixtures are given by relative paths.
+//!
+//! After an intended change of output, rewrite the golden files with
+//! `UPDATE_SNAPSHOTS=1 cargo test --test golden` and review the diff.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// Renders what a run printed, as stored in the golden files.
+fn render(output: &Output) -> String {
+    let mut rendered = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.stderr.is_empty() {
+        rendered += "[stderr]\n";
+        rendered += &String::from_utf8_lossy(&output.stderr);
+    }
+    if !output.status.success() {
+        let code = output
+            .status
+            .code()
+            .map_or_else(|| "none".to_string(), |code| code.to_string());
+        rendered += &format!("[exit status {code}]\n");
+    }
+    rendered
+}
+
+#[test]
+fn test_golden_outputs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+    let mut cases: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "args")
+        })
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no golden cases in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        let args = fs::read_to_string(case).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_crabyknife"))
+            .args(args.lines())
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        let actual = render(&output);
+
+        let golden = case.with_extension("out");
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if actual != expected {
+            failures.push(format!(
+                "{}\n--- expected\n{expected}--- actual\n{actual}",
+                golden.display()
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} golden outputs differ, rerun with UPDATE_SNAPSHOTS=1 to accept them:\n\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}